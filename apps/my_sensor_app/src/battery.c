@@ -0,0 +1,71 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *  http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+//  Battery voltage for the status bar in `rust/app/src/status.rs`. The battery is connected to P0.31 (AIN7) through
+//  a voltage divider that halves the voltage. The voltage is sampled once with the SAADC, which is then disabled
+//  to save power.
+#include "os/mynewt.h"
+#include "nrf.h"
+
+/// SAADC input for the battery voltage: P0.31
+#define BATTERY_INPUT     SAADC_CH_PSELP_PSELP_AnalogInput7
+
+/// Full scale of the SAADC in millivolts: internal reference of 0.6 V with gain 1/5
+#define FULL_SCALE_MV     3000
+
+/// Full scale of the SAADC at 12-bit resolution
+#define FULL_SCALE_COUNT  4096
+
+/// The voltage divider halves the battery voltage
+#define DIVIDER           2
+
+/// Return the battery voltage in millivolts, or 0 if it can't be read
+int battery_millivolts(void) {
+    volatile int16_t result = 0;
+
+    //  Sample the battery input on channel 0, single-ended
+    NRF_SAADC->RESOLUTION  = SAADC_RESOLUTION_VAL_12bit << SAADC_RESOLUTION_VAL_Pos;
+    NRF_SAADC->CH[0].PSELP = BATTERY_INPUT << SAADC_CH_PSELP_PSELP_Pos;
+    NRF_SAADC->CH[0].PSELN = SAADC_CH_PSELN_PSELN_NC << SAADC_CH_PSELN_PSELN_Pos;
+    NRF_SAADC->CH[0].CONFIG =
+        (SAADC_CH_CONFIG_GAIN_Gain1_5     << SAADC_CH_CONFIG_GAIN_Pos)   |
+        (SAADC_CH_CONFIG_REFSEL_Internal  << SAADC_CH_CONFIG_REFSEL_Pos) |
+        (SAADC_CH_CONFIG_TACQ_10us        << SAADC_CH_CONFIG_TACQ_Pos)   |
+        (SAADC_CH_CONFIG_MODE_SE          << SAADC_CH_CONFIG_MODE_Pos);
+    NRF_SAADC->RESULT.PTR    = (uint32_t) &result;
+    NRF_SAADC->RESULT.MAXCNT = 1;
+    NRF_SAADC->ENABLE = SAADC_ENABLE_ENABLE_Enabled << SAADC_ENABLE_ENABLE_Pos;
+
+    //  Start the SAADC, take 1 sample and wait for the result to be written
+    NRF_SAADC->EVENTS_STARTED = 0;
+    NRF_SAADC->TASKS_START = 1;
+    while (!NRF_SAADC->EVENTS_STARTED) {}
+    NRF_SAADC->EVENTS_END = 0;
+    NRF_SAADC->TASKS_SAMPLE = 1;
+    while (!NRF_SAADC->EVENTS_END) {}
+
+    //  Stop and disable the SAADC
+    NRF_SAADC->EVENTS_STOPPED = 0;
+    NRF_SAADC->TASKS_STOP = 1;
+    while (!NRF_SAADC->EVENTS_STOPPED) {}
+    NRF_SAADC->ENABLE = SAADC_ENABLE_ENABLE_Disabled << SAADC_ENABLE_ENABLE_Pos;
+    NRF_SAADC->CH[0].PSELP = SAADC_CH_PSELP_PSELP_NC << SAADC_CH_PSELP_PSELP_Pos;
+
+    if (result <= 0) { return 0; }  //  Negative results are noise around 0 V
+    return (int) result * FULL_SCALE_MV * DIVIDER / FULL_SCALE_COUNT;
+}
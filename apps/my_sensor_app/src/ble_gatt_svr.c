@@ -54,11 +54,60 @@ static const ble_uuid128_t gatt_svr_chr_sec_test_static_uuid =
 
 static uint8_t gatt_svr_sec_test_static_val;
 
+/**
+ * The boot logo transfer service uploads a boot logo to a slot in External
+ * SPI Flash.  The protocol is handled in Rust, see rust/logo/src/transfer.rs
+ * and rust/app/src/logo_transfer.rs.
+ *     o control: start, commit or abort the transfer.
+ *     o data: chunk offset (4 bytes) followed by the RGB565 pixels.
+ *     o status: transfer state, slot, bytes received and error code.
+ */
+
+/* 6e5a1000-3f2b-4c8a-9d61-7a0b3c5e8f21 */
+static const ble_uuid128_t gatt_svr_svc_logo_uuid =
+    BLE_UUID128_INIT(0x21, 0x8f, 0x5e, 0x3c, 0x0b, 0x7a, 0x61, 0x9d,
+                     0x8a, 0x4c, 0x2b, 0x3f, 0x00, 0x10, 0x5a, 0x6e);
+
+/* 6e5a1001-3f2b-4c8a-9d61-7a0b3c5e8f21 */
+static const ble_uuid128_t gatt_svr_chr_logo_control_uuid =
+    BLE_UUID128_INIT(0x21, 0x8f, 0x5e, 0x3c, 0x0b, 0x7a, 0x61, 0x9d,
+                     0x8a, 0x4c, 0x2b, 0x3f, 0x01, 0x10, 0x5a, 0x6e);
+
+/* 6e5a1002-3f2b-4c8a-9d61-7a0b3c5e8f21 */
+static const ble_uuid128_t gatt_svr_chr_logo_data_uuid =
+    BLE_UUID128_INIT(0x21, 0x8f, 0x5e, 0x3c, 0x0b, 0x7a, 0x61, 0x9d,
+                     0x8a, 0x4c, 0x2b, 0x3f, 0x02, 0x10, 0x5a, 0x6e);
+
+/* 6e5a1003-3f2b-4c8a-9d61-7a0b3c5e8f21 */
+static const ble_uuid128_t gatt_svr_chr_logo_status_uuid =
+    BLE_UUID128_INIT(0x21, 0x8f, 0x5e, 0x3c, 0x0b, 0x7a, 0x61, 0x9d,
+                     0x8a, 0x4c, 0x2b, 0x3f, 0x03, 0x10, 0x5a, 0x6e);
+
+/* Max size of a control command and a data chunk, see rust/logo/src/transfer.rs */
+#define LOGO_CONTROL_MAX_LEN 6
+#define LOGO_DATA_MAX_LEN    244
+#define LOGO_STATUS_LEN      7
+
+/* Error codes returned by Rust, see `logo::Error` */
+#define LOGO_ERR_TRUNCATED   5
+#define LOGO_ERR_BAD_OFFSET  10
+#define LOGO_ERR_BAD_STATE   11
+
+/* Implemented in Rust, see rust/app/src/logo_transfer.rs */
+int logo_transfer_control(const uint8_t *data, uint32_t len);
+int logo_transfer_data(const uint8_t *data, uint32_t len);
+int logo_transfer_status(uint8_t *buf, uint32_t len);
+
 static int
 gatt_svr_chr_access_sec_test(uint16_t conn_handle, uint16_t attr_handle,
                              struct ble_gatt_access_ctxt *ctxt,
                              void *arg);
 
+static int
+gatt_svr_chr_access_logo(uint16_t conn_handle, uint16_t attr_handle,
+                         struct ble_gatt_access_ctxt *ctxt,
+                         void *arg);
+
 static const struct ble_gatt_svc_def gatt_svr_svcs[] = {
     {
         /*** Service: Security test. */
@@ -80,6 +129,30 @@ static const struct ble_gatt_svc_def gatt_svr_svcs[] = {
         } },
     },
 
+    {
+        /*** Service: Boot logo transfer. */
+        .type = BLE_GATT_SVC_TYPE_PRIMARY,
+        .uuid = &gatt_svr_svc_logo_uuid.u,
+        .characteristics = (struct ble_gatt_chr_def[]) { {
+            /*** Characteristic: Control. */
+            .uuid = &gatt_svr_chr_logo_control_uuid.u,
+            .access_cb = gatt_svr_chr_access_logo,
+            .flags = BLE_GATT_CHR_F_WRITE,
+        }, {
+            /*** Characteristic: Data. */
+            .uuid = &gatt_svr_chr_logo_data_uuid.u,
+            .access_cb = gatt_svr_chr_access_logo,
+            .flags = BLE_GATT_CHR_F_WRITE | BLE_GATT_CHR_F_WRITE_NO_RSP,
+        }, {
+            /*** Characteristic: Status. */
+            .uuid = &gatt_svr_chr_logo_status_uuid.u,
+            .access_cb = gatt_svr_chr_access_logo,
+            .flags = BLE_GATT_CHR_F_READ,
+        }, {
+            0, /* No more characteristics in this service. */
+        } },
+    },
+
     {
         0, /* No more services. */
     },
@@ -156,6 +229,75 @@ gatt_svr_chr_access_sec_test(uint16_t conn_handle, uint16_t attr_handle,
     return BLE_ATT_ERR_UNLIKELY;
 }
 
+/* Convert the error code returned by Rust to an ATT error. */
+static int
+gatt_svr_logo_att_err(int rc)
+{
+    switch (rc) {
+    case 0:
+        return 0;
+    case LOGO_ERR_TRUNCATED:
+        return BLE_ATT_ERR_INVALID_ATTR_VALUE_LEN;
+    case LOGO_ERR_BAD_OFFSET:
+        return BLE_ATT_ERR_INVALID_OFFSET;
+    case LOGO_ERR_BAD_STATE:
+        return BLE_ATT_ERR_REQ_NOT_SUPPORTED;
+    default:
+        /* Details of the failure are in the status characteristic. */
+        return BLE_ATT_ERR_UNLIKELY;
+    }
+}
+
+static int
+gatt_svr_chr_access_logo(uint16_t conn_handle, uint16_t attr_handle,
+                         struct ble_gatt_access_ctxt *ctxt,
+                         void *arg)
+{
+    const ble_uuid_t *uuid;
+    uint8_t buf[LOGO_DATA_MAX_LEN];
+    uint16_t len;
+    int rc;
+
+    uuid = ctxt->chr->uuid;
+
+    if (ble_uuid_cmp(uuid, &gatt_svr_chr_logo_status_uuid.u) == 0) {
+        assert(ctxt->op == BLE_GATT_ACCESS_OP_READ_CHR);
+
+        rc = logo_transfer_status(buf, sizeof buf);
+        if (rc != LOGO_STATUS_LEN) {
+            return BLE_ATT_ERR_UNLIKELY;
+        }
+        rc = os_mbuf_append(ctxt->om, buf, LOGO_STATUS_LEN);
+        return rc == 0 ? 0 : BLE_ATT_ERR_INSUFFICIENT_RES;
+    }
+
+    if (ble_uuid_cmp(uuid, &gatt_svr_chr_logo_control_uuid.u) == 0) {
+        assert(ctxt->op == BLE_GATT_ACCESS_OP_WRITE_CHR);
+
+        rc = gatt_svr_chr_write(ctxt->om, 1, LOGO_CONTROL_MAX_LEN, buf, &len);
+        if (rc != 0) {
+            return rc;
+        }
+        return gatt_svr_logo_att_err(logo_transfer_control(buf, len));
+    }
+
+    if (ble_uuid_cmp(uuid, &gatt_svr_chr_logo_data_uuid.u) == 0) {
+        assert(ctxt->op == BLE_GATT_ACCESS_OP_WRITE_CHR);
+
+        rc = gatt_svr_chr_write(ctxt->om, 1, LOGO_DATA_MAX_LEN, buf, &len);
+        if (rc != 0) {
+            return rc;
+        }
+        return gatt_svr_logo_att_err(logo_transfer_data(buf, len));
+    }
+
+    /* Unknown characteristic; the nimble stack should not have called this
+     * function.
+     */
+    assert(0);
+    return BLE_ATT_ERR_UNLIKELY;
+}
+
 void
 gatt_svr_register_cb(struct ble_gatt_register_ctxt *ctxt, void *arg)
 {
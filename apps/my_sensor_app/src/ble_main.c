@@ -48,6 +48,9 @@
 
 static int bleprph_gap_event(struct ble_gap_event *event, void *arg);
 
+/// 1 if a peer is connected, shown in the status bar by `rust/app/src/status.rs`
+static int ble_connected = 0;
+
 //  Print info and error messages to Semihosting Console
 #define MODLOG_DFLT_INFO    console_printf
 #define MODLOG_DFLT_ERROR   console_printf
@@ -190,6 +193,7 @@ bleprph_gap_event(struct ble_gap_event *event, void *arg)
                     event->connect.status == 0 ? "established" : "failed",
                     event->connect.status);
         if (event->connect.status == 0) {
+            ble_connected = 1;
             rc = ble_gap_conn_find(event->connect.conn_handle, &desc);
             assert(rc == 0);
             bleprph_print_conn_desc(&desc);
@@ -207,6 +211,7 @@ bleprph_gap_event(struct ble_gap_event *event, void *arg)
         return 0;
 
     case BLE_GAP_EVENT_DISCONNECT:
+        ble_connected = 0;
         MODLOG_DFLT_INFO("disconnect; reason=%d ", event->disconnect.reason);
         bleprph_print_conn_desc(&event->disconnect.conn);
         MODLOG_DFLT_INFO("\n");
@@ -382,10 +387,22 @@ start_ble(void)
     return 0;
 }
 
+/// Return 1 if a peer is connected, else 0
+int
+ble_is_connected(void)
+{
+    return ble_connected;
+}
+
 #else //  If Bluetooth LE is disabled...
 
 int start_ble(void) {
     //  Bluetooth LE not supported.
     return 0;
 }
+
+int ble_is_connected(void) {
+    //  Bluetooth LE not supported, so never connected.
+    return 0;
+}
 #endif  //  MYNEWT_VAL(BLUETOOTH_LE)
\ No newline at end of file
@@ -1 +1,2 @@
 //  Code has been moved to https://github.com/lupyuen/pinetime-rust-mynewt/blob/ota2/libs/pinetime_boot/src/display.c
+//  To show the boot logo stored in External SPI Flash from the application, see `show_logo()` in rust/app/src/logo_display.rs
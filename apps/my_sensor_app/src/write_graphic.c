@@ -16,47 +16,14 @@
  * specific language governing permissions and limitations
  * under the License.
  */
-//  Display image on ST7789 display controller (240 x 240)
+//  Boot logo to be written to External SPI Flash for ST7789 display controller (240 x 240)
 #include <inttypes.h>
-#include "os/mynewt.h"
-#include <console/console.h>
-#include <hal/hal_bsp.h>
-#include <hal/hal_flash.h>
-#include <hal/hal_flash_int.h>
-#include <hal/hal_gpio.h>
-#include <hal/hal_spi.h>
-#include <stdio.h>
-#include <string.h>
 
-#define BATCH_SIZE  4096  //  Max number of data bytes to be written in a batch
-
-//  Flash Device for Image
-#define FLASH_DEVICE 1  //  0 for Internal Flash ROM, 1 for External SPI Flash
-
-
-//  Converted from PNG file by https://github.com/lupyuen/pinetime-graphic
-static const uint8_t image_data[] = {  //  Should be 115,200 bytes
+//  Compressed boot logo, converted from PNG file by rust/logo_tool.
+//  Expanded and written to External SPI Flash by `write_logo()` in rust/app/src/logo_loader.rs
+const uint8_t logo_data[] = {  //  Header followed by compressed pixels, expands to 115,200 bytes
 #include "write_graphic.inc"
 };
 
-/// Write a converted graphic file to SPI Flash
-int write_graphic(void) {
-    console_printf("Writing graphic to flash...\n"); console_flush();
-    uint32_t offset = 0;
-    for (;;) {
-        if (offset >= sizeof(image_data)) { break; }
-        //  How many bytes we will write.
-        uint16_t len = BATCH_SIZE;
-        if (offset + len >= sizeof(image_data)) {
-            len = sizeof(image_data) - offset;
-        }        
-        //  Erase the bytes.
-        int rc = hal_flash_erase(FLASH_DEVICE, offset, len); assert(rc == 0);
-
-        //  Write the bytes.
-        rc = hal_flash_write(FLASH_DEVICE, offset, (void *) &image_data[offset], len); assert(rc == 0);
-        offset += len;
-    }
-    console_printf("Graphic written to flash\n"); console_flush();
-    return 0;
-}
\ No newline at end of file
+//  Number of bytes in `logo_data`
+const uint32_t logo_data_size = sizeof(logo_data);
@@ -50,6 +50,23 @@
 //  Flash Device for Image
 #define FLASH_DEVICE 1  //  0 for Internal Flash ROM, 1 for External SPI Flash
 
+//  Boot Logo Slots in External SPI Flash. From rust/logo/src/slots.rs
+#define LOGO_AREA_OFFSET   0x387000  //  Slot table, followed by the slots
+#define LOGO_SLOT_SIZE     0x1E000   //  Each slot contains the header sector and the pixels
+#define LOGO_MAX_SLOTS     4         //  Number of slots
+#define SLOT_TABLE_SIZE    12        //  Magic (4), Version (1), Active Slot (1), Reserved (2), CRC32 (4)
+#define SLOT_TABLE_VERSION 1         //  Slot table version
+
+//  Boot Logo Layout in each slot. From rust/logo/src/layout.rs and rust/logo/src/header.rs
+#define LOGO_HEADER_OFFSET 0     //  Header is stored in its own sector
+#define LOGO_PIXELS_OFFSET 4096  //  Uncompressed RGB565 pixels follow the header sector
+#define LOGO_HEADER_SIZE   20    //  Magic (4), Version (1), Format (1), Palette (2), Width (2), Height (2), Payload Length (4), CRC32 (4)
+#define LOGO_VERSION       1     //  Header version
+#define LOGO_FORMAT_RGB565 0     //  Uncompressed RGB565 pixels
+
+//  Colour to be shown when the boot logo is missing or corrupted
+#define DEFAULT_COLOR 0x0000  //  Black
+
 //  ST7789 Commands. From https://github.com/lupyuen/st7735-lcd-batch-rs/blob/master/src/instruction.rs
 #define NOP 0x00
 #define SWRESET 0x01
@@ -103,6 +120,10 @@ static int write_command(uint8_t command, const uint8_t *params, uint16_t len);
 static int write_data(const uint8_t *data, uint16_t len);
 static int transmit_spi(const uint8_t *data, uint16_t len);
 static void delay_ms(uint32_t ms);
+static uint32_t get_slot_offset(void);
+static int check_logo(uint32_t slot_offset);
+static uint32_t get_le32(const uint8_t *bytes);
+static uint32_t crc32_update(uint32_t crc, const uint8_t *data, uint16_t len);
 
 /// Buffer for reading flash and writing to display
 static uint8_t flash_buffer[BATCH_SIZE];
@@ -114,6 +135,17 @@ int pinetime_boot_display_image(void) {
     int rc = init_display();  assert(rc == 0);
     rc = set_orientation(Landscape);  assert(rc == 0);
 
+    //  If the boot logo in the active slot is missing or corrupted, render the default colour
+    uint32_t slot_offset = get_slot_offset();
+    int logo_valid = (check_logo(slot_offset) == 0);
+    if (!logo_valid) {
+        console_printf("Boot logo missing, showing default\n"); console_flush();
+        for (int i = 0; i < BATCH_SIZE; i += BYTES_PER_PIXEL) {
+            flash_buffer[i] = DEFAULT_COLOR >> 8;
+            flash_buffer[i + 1] = DEFAULT_COLOR & 0xff;
+        }
+    }
+
     //  Render each row of pixels.
     for (uint8_t row = 0; row < ROW_COUNT; row++) {
         uint8_t top = row;
@@ -133,8 +165,9 @@ int pinetime_boot_display_image(void) {
             uint16_t len = (right - left + 1) * BYTES_PER_PIXEL;
 
             //  Read the bytes from flash memory.
-            uint32_t offset = ((top * COL_COUNT) + left) * BYTES_PER_PIXEL;
-            int rc = hal_flash_read(FLASH_DEVICE, offset, flash_buffer, len); assert(rc == 0);
+            uint32_t offset = slot_offset + LOGO_PIXELS_OFFSET + ((top * COL_COUNT) + left) * BYTES_PER_PIXEL;
+            int rc = 0;
+            if (logo_valid) { rc = hal_flash_read(FLASH_DEVICE, offset, flash_buffer, len); assert(rc == 0); }
 
             //  console_printf("%lx: ", offset); console_dump(flash_buffer, len); console_printf("\n"); console_flush();
 
@@ -185,6 +218,70 @@ int pinetime_boot_display_image(void) {
     return 0;
 }
 
+/// Read the slot table in SPI Flash and return the offset of the active slot.
+/// Returns the offset of slot 0 if the slot table is missing or corrupted.
+static uint32_t get_slot_offset(void) {
+    uint8_t active = 0;
+    int rc = hal_flash_read(FLASH_DEVICE, LOGO_AREA_OFFSET, flash_buffer, SLOT_TABLE_SIZE);
+    if (rc == 0
+        && memcmp(flash_buffer, "PTLS", 4) == 0
+        && flash_buffer[4] == SLOT_TABLE_VERSION
+        && flash_buffer[5] < LOGO_MAX_SLOTS
+        && ~crc32_update(0xffffffff, flash_buffer, 8) == get_le32(&flash_buffer[8])) {
+        active = flash_buffer[5];
+    }
+    console_printf("Boot logo slot %d\n", active); console_flush();
+    return LOGO_AREA_OFFSET + 4096 + active * LOGO_SLOT_SIZE;
+}
+
+/// Check the boot logo header in the slot and verify the CRC32 of the pixels.
+/// Returns 0 if the boot logo is valid, -1 if missing or corrupted.
+static int check_logo(uint32_t slot_offset) {
+    //  Read the header
+    int rc = hal_flash_read(FLASH_DEVICE, slot_offset + LOGO_HEADER_OFFSET, flash_buffer, LOGO_HEADER_SIZE);
+    if (rc != 0) { return -1; }
+    const uint8_t *header = flash_buffer;
+    if (memcmp(header, "PTLG", 4) != 0) { return -1; }  //  Missing magic number, or flash is erased
+    if (header[4] != LOGO_VERSION || header[5] != LOGO_FORMAT_RGB565) { return -1; }
+    if (header[8] != COL_COUNT || header[9] != 0 || header[10] != ROW_COUNT || header[11] != 0) { return -1; }
+    uint32_t payload_len = get_le32(&header[12]);
+    uint32_t expected_crc = get_le32(&header[16]);
+    if (payload_len != ROW_COUNT * COL_COUNT * BYTES_PER_PIXEL) { return -1; }
+
+    //  Compute the CRC32 of the pixels
+    uint32_t crc = 0xffffffff;
+    for (uint32_t offset = 0; offset < payload_len; offset += BATCH_SIZE) {
+        uint16_t len = BATCH_SIZE;
+        if (offset + len > payload_len) { len = payload_len - offset; }
+        rc = hal_flash_read(FLASH_DEVICE, slot_offset + LOGO_PIXELS_OFFSET + offset, flash_buffer, len);
+        if (rc != 0) { return -1; }
+        crc = crc32_update(crc, flash_buffer, len);
+    }
+    if (~crc != expected_crc) { return -1; }
+    return 0;
+}
+
+/// Return the little-endian 32-bit value
+static uint32_t get_le32(const uint8_t *bytes) {
+    return bytes[0] | (bytes[1] << 8) | (bytes[2] << 16) | ((uint32_t) bytes[3] << 24);
+}
+
+/// Add the bytes to the CRC32 (IEEE 802.3). Same as rust/logo/src/crc32.rs
+static uint32_t crc32_update(uint32_t crc, const uint8_t *data, uint16_t len) {
+    static const uint32_t nibble_table[16] = {
+        0x00000000, 0x1db71064, 0x3b6e20c8, 0x26d930ac,
+        0x76dc4190, 0x6b6b51f4, 0x4db26158, 0x5005713c,
+        0xedb88320, 0xf00f9344, 0xd6d6a3e8, 0xcb61b38c,
+        0x9b64c2b0, 0x86d3d2d4, 0xa00ae278, 0xbdbdf21c,
+    };
+    for (uint16_t i = 0; i < len; i++) {
+        crc ^= data[i];
+        crc = (crc >> 4) ^ nibble_table[crc & 0x0f];
+        crc = (crc >> 4) ^ nibble_table[crc & 0x0f];
+    }
+    return crc;
+}
+
 /// Set the ST7789 display window to the coordinates (left, top), (right, bottom)
 static int set_window(uint8_t left, uint8_t top, uint8_t right, uint8_t bottom) {
     assert(left < COL_COUNT && right < COL_COUNT && top < ROW_COUNT && bottom < ROW_COUNT);
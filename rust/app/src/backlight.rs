@@ -0,0 +1,123 @@
+//! Backlight of the PineTime display, with brightness levels 0 to 7, fades and auto-dim. The levels and fades are
+//! computed by `power::backlight::Backlight` over the three backlight GPIO pins, and stepped by a Mynewt
+//! callout on the Default Event Queue. Call `start_backlight()` after `start_display()`.
+use power::backlight::Backlight;
+use mynewt::{
+    self,
+    result::*,
+    kernel::os::{
+        self,
+        os_event,
+    },
+    fill_zero,
+};
+
+pub use power::backlight::MAX_LEVEL;
+
+/// GPIO pins for the backlight: LCD_BACKLIGHT_{LOW,MID,HIGH} (P0.14, 22, 23)
+const BACKLIGHT_PINS: [i32; 3] = [ 14, 22, 23 ];
+
+/// Backlight driver, set by `start_backlight()`
+static mut BACKLIGHT: Option<Backlight<mynewt::GPIO>> = None;
+
+/// Callout that steps the fades and the auto-dim timeout
+static mut BACKLIGHT_CALLOUT: os::os_callout = fill_zero!(os::os_callout);
+
+/// Milliseconds until the callout fires, passed to `tick()` when it fires
+static mut SCHEDULED_MS: u32 = 0;
+
+/// Switch on the backlight at the brightest level
+pub fn start_backlight() -> MynewtResult<()> {
+    let mut pins = [ mynewt::GPIO::new(), mynewt::GPIO::new(), mynewt::GPIO::new() ];
+    for (pin, gpio) in BACKLIGHT_PINS.iter().zip(pins.iter_mut()) {
+        gpio.init(*pin) ? ;
+    }
+    let mut backlight = Backlight::new(pins) ? ;
+    backlight.set_level(MAX_LEVEL) ? ;
+    let queue = os::eventq_dflt_get() ? ;
+    unsafe {
+        BACKLIGHT = Some(backlight);
+        os::os_callout_init(&mut BACKLIGHT_CALLOUT, queue, Some(backlight_callback), core::ptr::null_mut());
+    }
+    Ok(())
+}
+
+/// Set the brightness to the level (0 to 7) immediately
+pub fn set_brightness(level: u8) -> MynewtResult<()> {
+    let next = backlight() ?.set_level(level) ? ;
+    schedule(next)
+}
+
+/// Fade in to the brightest level over the duration
+pub fn fade_in(duration_ms: u32) -> MynewtResult<()> {
+    let next = backlight() ?.fade_in(duration_ms);
+    schedule(next)
+}
+
+/// Fade out until switched off over the duration
+pub fn fade_out(duration_ms: u32) -> MynewtResult<()> {
+    let next = backlight() ?.fade_out(duration_ms);
+    schedule(next)
+}
+
+/// Dim the backlight to the level after `timeout_ms` milliseconds without activity, or `None` to disable auto-dim
+pub fn set_auto_dim(timeout_ms: Option<u32>, level: u8) -> MynewtResult<()> {
+    let next = backlight() ?.set_auto_dim(timeout_ms, level);
+    schedule(next)
+}
+
+/// Restart the auto-dim timeout after activity, like a touch, and brighten the backlight if dimmed
+pub fn wake() -> MynewtResult<()> {
+    let next = backlight() ?.activity();
+    schedule(next)
+}
+
+/// Return the current level (0 to 7)
+pub fn level() -> MynewtResult<u8> {
+    Ok(backlight() ?.level())
+}
+
+/// Return the level (0 to 7) set by `set_brightness()` or the fades, which is shown when not dimmed
+pub fn brightness() -> MynewtResult<u8> {
+    Ok(backlight() ?.brightness())
+}
+
+/// Call the function with the backlight driver, then reschedule the callout for the fades and the auto-dim timeout.
+/// Used by `power.rs` to switch the backlight off and on with the display.
+pub fn update<T>(f: impl FnOnce(&mut Backlight<mynewt::GPIO>) -> MynewtResult<T>) -> MynewtResult<T> {
+    let backlight = backlight() ? ;
+    let result = f(backlight) ? ;
+    schedule(backlight.next_tick()) ? ;
+    Ok(result)
+}
+
+/// Return the backlight driver. Fails with `SYS_EINVAL` if `start_backlight()` has not been called.
+fn backlight() -> MynewtResult<&'static mut Backlight<mynewt::GPIO>> {
+    unsafe { BACKLIGHT.as_mut() }
+        .ok_or(MynewtError::SYS_EINVAL)
+}
+
+/// Fire the callout after the milliseconds, or stop it if `None`
+fn schedule(delay_ms: Option<u32>) -> MynewtResult<()> {
+    let delay_ms = match delay_ms {
+        Some(delay_ms) => delay_ms,
+        None => { unsafe { os::os_callout_stop(&mut BACKLIGHT_CALLOUT) }; return Ok(()); }
+    };
+    let mut ticks: os::os_time_t = 0;
+    let rc = unsafe { os::os_time_ms_to_ticks(delay_ms, &mut ticks) };
+    if rc != 0 { return Err(MynewtError::from(rc)); }
+    unsafe { SCHEDULED_MS = delay_ms };
+    let rc = unsafe { os::os_callout_reset(&mut BACKLIGHT_CALLOUT, ticks.max(1)) };
+    if rc != 0 { return Err(MynewtError::from(rc)); }
+    Ok(())
+}
+
+/// Called by the callout to step the fade and the auto-dim timeout
+extern "C" fn backlight_callback(_event: *mut os_event) {
+    let elapsed_ms = unsafe { SCHEDULED_MS };
+    let next = backlight()
+        .and_then(|backlight| backlight.tick(elapsed_ms))
+        .expect("backlight fail");
+    schedule(next)
+        .expect("backlight fail");
+}
@@ -0,0 +1,144 @@
+//! Touch calibration screen. Shows a crosshair on each target in turn and fits the affine transform from the touch
+//! controller to the display panel with `touch::calibration`. The calibration is stored in Internal Flash ROM, in
+//! the sector after the MCUBoot scratch area, and loaded at startup. Touches are calibrated before they are mapped
+//! to the rotated drawing with `screen::to_drawing()`, so the calibration stays valid when the display is rotated.
+//! Call `start_calibration()` after the touch sensor has started, then run the events until `is_calibrating()`
+//! returns false.
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::Rgb565,
+    primitives::{ Circle, Line, Rectangle },
+};
+use mynewt::{
+    result::*,
+    hw::hal,
+    sys::console,
+};
+use touch::{
+    calibration::{ Calibration, Session, CALIBRATION_LEN, TARGETS_3, TARGETS_5 },
+    event::TouchEvent,
+};
+use crate::screen::{ self, Screen };
+
+/// Flash Device for the touch calibration: 0 for Internal Flash ROM
+const FLASH_DEVICE: u8 = 0;
+
+/// Offset of the touch calibration in Internal Flash ROM. Must sync with `FLASH_AREA_TOUCH_CALIBRATION` in
+/// hw/bsp/nrf52/bsp.yml
+const FLASH_OFFSET: u32 = 0x7d000;
+
+/// Size of the flash sector erased before writing the calibration
+const SECTOR_SIZE: u32 = 4096;
+
+/// Half the length of the crosshair lines, in pixels
+const CROSSHAIR_SIZE: i32 = 12;
+
+/// Calibration applied to every touch
+static mut CALIBRATION: Calibration = Calibration::IDENTITY;
+
+/// Calibration session while the calibration screen is shown
+static mut SESSION: Option<Session> = None;
+
+/// Load the touch calibration from Internal Flash ROM. Returns false if the touch panel has not been calibrated,
+/// so the touches are passed through unchanged. If the flash can't be read, the identity calibration is kept.
+pub fn load_calibration() -> MynewtResult<bool> {
+    let mut bytes = [0; CALIBRATION_LEN];
+    let rc = unsafe {
+        hal::hal_flash_read(FLASH_DEVICE, FLASH_OFFSET, bytes.as_mut_ptr() as *mut _, bytes.len() as u32)
+    };
+    if rc != 0 { return Err(MynewtError::from(rc)); }
+    match Calibration::from_bytes(&bytes) {
+        Ok(calibration) => { unsafe { CALIBRATION = calibration }; Ok(true) }
+        Err(_) => { console::print("touch not calibrated\n"); Ok(false) }
+    }
+}
+
+/// Show the calibration screen with 3 or 5 targets. The touches are passed to the calibration session instead of
+/// the subscribers until all targets have been touched.
+pub fn start_calibration(points: usize) -> MynewtResult<()> {
+    let targets: &'static [(u16, u16)] = match points {
+        3 => &TARGETS_3,
+        5 => &TARGETS_5,
+        _ => return Err(MynewtError::SYS_EINVAL),
+    };
+    console::print("touch calibration\n"); console::flush();
+    let session = Session::new(targets);
+    let target = session.target();
+    unsafe { SESSION = Some(session) };
+    draw_target(target);
+    Ok(())
+}
+
+/// Return true if the calibration screen is shown
+pub fn is_calibrating() -> bool {
+    unsafe { SESSION.is_some() }
+}
+
+/// Map the touch coordinates to the display panel with the calibration
+pub fn calibrate(x: u16, y: u16) -> (u16, u16) {
+    unsafe { CALIBRATION.apply(x, y) }
+}
+
+/// Handle the uncalibrated touch event while the calibration screen is shown. When the finger leaves the last
+/// target, fit and store the calibration. If the touches don't fit, start again from the first target.
+pub fn handle_touch(event: &TouchEvent) {
+    let session = match unsafe { SESSION.as_mut() } {
+        Some(session) => session,
+        None => return,
+    };
+    if !session.event(event) { return; }
+    if let Some(target) = session.target() {
+        draw_target(Some(target));
+        return;
+    }
+    match session.finish() {
+        Ok(calibration) => {
+            unsafe { CALIBRATION = calibration; SESSION = None; }
+            save_calibration(&calibration)
+                .expect("CAL save fail");
+            console::print("touch calibrated\n"); console::flush();
+            draw_target(None);
+        }
+        Err(_) => {
+            console::print("touch calibration fail, try again\n"); console::flush();
+            session.restart();
+            draw_target(session.target());
+        }
+    }
+}
+
+/// Store the calibration in Internal Flash ROM
+fn save_calibration(calibration: &Calibration) -> MynewtResult<()> {
+    let bytes = calibration.to_bytes();
+    let rc = unsafe { hal::hal_flash_erase(FLASH_DEVICE, FLASH_OFFSET, SECTOR_SIZE) };
+    if rc != 0 { return Err(MynewtError::from(rc)); }
+    let rc = unsafe {
+        hal::hal_flash_write(FLASH_DEVICE, FLASH_OFFSET, bytes.as_ptr() as *const _, bytes.len() as u32)
+    };
+    if rc != 0 { return Err(MynewtError::from(rc)); }
+    Ok(())
+}
+
+/// Clear the screen and draw a crosshair on the target on the panel, if any. The target is mapped to the drawing,
+/// which may be rotated.
+fn draw_target(target: Option<(u16, u16)>) {
+    let mut display = Screen;
+    display.draw(Rectangle::<Rgb565>
+        ::new(Coord::new(0, 0), Coord::new(239, 239))
+        .fill(Some(Rgb565::from((0x00, 0x00, 0x00)))));  //  Black
+    let (x, y) = match target {
+        Some((x, y)) => screen::to_drawing(x, y),
+        None => return,
+    };
+    let (x, y) = (x as i32, y as i32);
+    let white = Some(Rgb565::from((0xff, 0xff, 0xff)));
+    display.draw(Line::<Rgb565>
+        ::new(Coord::new(x - CROSSHAIR_SIZE, y), Coord::new(x + CROSSHAIR_SIZE, y))
+        .stroke(white));
+    display.draw(Line::<Rgb565>
+        ::new(Coord::new(x, y - CROSSHAIR_SIZE), Coord::new(x, y + CROSSHAIR_SIZE))
+        .stroke(white));
+    display.draw(Circle::<Rgb565>
+        ::new(Coord::new(x, y), CROSSHAIR_SIZE as u32 / 2)
+        .stroke(Some(Rgb565::from((0xff, 0x00, 0x00)))));  //  Red
+}
@@ -33,11 +33,26 @@ extern crate macros as mynewt_macros;   //  Declare the Mynewt Procedural Macros
 mod app_network;    //  Declare `app_network.rs` as Rust module `app_network` for Application Network functions
 mod app_sensor;     //  Declare `app_sensor.rs` as Rust module `app_sensor` for Application Sensor functions
 mod touch_sensor;   //  Declare `touch_sensor.rs` as Rust module `touch_sensor` for Touch Sensor functions
+mod logo_display;   //  Declare `logo_display.rs` as Rust module `logo_display` for showing the boot logo from flash
+mod logo_loader;    //  Declare `logo_loader.rs` as Rust module `logo_loader` for writing boot logos to flash
+mod logo_transfer;  //  Declare `logo_transfer.rs` as Rust module `logo_transfer` for uploading boot logos over Bluetooth LE
+mod screen;         //  Declare `screen.rs` as Rust module `screen` for drawing screens onto the display
+mod backlight;      //  Declare `backlight.rs` as Rust module `backlight` for the display backlight
+mod power;          //  Declare `power.rs` as Rust module `power` for putting the display to sleep and waking it
+mod status;         //  Declare `status.rs` as Rust module `status` for updating the status bar
+mod status_bar;     //  Declare `status_bar.rs` as Rust module `status_bar` for drawing the status bar
+mod calibration;    //  Declare `calibration.rs` as Rust module `calibration` for the touch calibration screen
 
 //  Declare the optional modules depending on the options in `../Cargo.toml`
 #[cfg(feature = "display_app")]  //  If graphics display app is enabled...
 mod display;                     //  Include the graphics display app
 
+#[cfg(feature = "display_app")]  //  If graphics display app is enabled...
+mod test_screen;                 //  Include the test screen drawn by the graphics display app
+
+#[cfg(feature = "play_animation")]  //  If animated boot logo is enabled...
+mod logo_animation;                 //  Include the player for the animated boot logo
+
 #[cfg(feature = "ui_app")]       //  If druid UI app is enabled...
 mod ui;                          //  Include the druid UI app
 
@@ -48,6 +63,12 @@ mod visual;                      //  Include the Visual Rust app
 #[cfg(feature = "chip8_app")]    //  If CHIP8 Emulator app is enabled...
 mod chip8;                       //  Include the CHIP8 Emulator app
 
+#[cfg(feature = "chip8_app")]    //  If CHIP8 Emulator app is enabled...
+mod chip8_render;                //  Include the renderer for the CHIP8 Emulator app
+
+#[cfg(feature = "record_touch")]  //  If touch recording is enabled...
+mod touch_recorder;               //  Include the recorder for touch reports
+
 #[cfg(feature = "use_float")]    //  If floating-point is enabled...
 mod gps_sensor;                  //  Include the GPS Sensor functions
 
@@ -59,18 +80,33 @@ use mynewt::{
     sys::console,           //  Import Mynewt Console API
 };
 
-//  Select the touch handler depending on the options in `../Cargo.toml`
+//  Select the druid touch handler depending on the options in `../Cargo.toml`. The CHIP8 Emulator app subscribes
+//  to touch events by itself.
 #[cfg(feature = "ui_app")]      //  If druid UI app is enabled...
 use ui::handle_touch;           //  Use the touch handler from druid UI app
 
 #[cfg(feature = "visual_app")]  //  If Visual Rust app is enabled...
 use visual::handle_touch;       //  Use the touch handler from the Visual Rust app
 
-#[cfg(feature = "chip8_app")]   //  If CHIP8 Emulator app is enabled...
-use chip8::handle_touch;        //  Use the touch handler from the CHIP8 Emulator app
-
-#[cfg(not(any(feature = "ui_app", feature = "visual_app", feature = "chip8_app")))]  //  If neither druid UI app nor Visual Rust app are enabled...
-pub fn handle_touch(_x: u16, _y: u16) { console::print("touch not handled\n"); console::flush(); }  //  Define a touch handler that does nothing
+/// Pass the touch down and contact events to the druid touch handler, not touch up. druid redraws the whole screen,
+/// so draw the status bar over it again with the values shown, once when the finger touches and once when it's
+/// lifted, not for every contact event.
+#[cfg(any(feature = "ui_app", feature = "visual_app"))]  //  If druid UI app or Visual Rust app is enabled...
+fn handle_druid_touch(event: &touch::event::TouchEvent) {
+    use touch::event::TouchEvent;
+    match event {
+        TouchEvent::Down(point) => {
+            handle_touch(point.x, point.y);
+            status::redraw()
+                .expect("status fail");
+        }
+        TouchEvent::Move(point) => handle_touch(point.x, point.y),
+        TouchEvent::Up(_) => {
+            status::redraw()
+                .expect("status fail");
+        }
+    }
+}
 
 ///  Main program that initialises the sensor, network driver and starts reading and sending sensor data in the background.
 ///  main() will be called at Mynewt startup. It replaces the C version of the main() function.
@@ -87,45 +123,92 @@ extern "C" fn main() -> ! {  //  Declare extern "C" because it will be called by
     //  let rc = unsafe { write_image() };
     //  assert!(rc == 0, "IMG fail");
     
-    //  Display image from SPI Flash. Must run before testing the display, to avoid contention for SPI port.
-    /*
-    extern { fn display_image() -> i32; }
-    let rc = unsafe { display_image() };
-    assert!(rc == 0, "IMG fail");
-    */
-    
     //  Test External SPI Flash. Must run before testing the display, to avoid contention for SPI port.
     extern { fn test_flash() -> i32; }
     let rc = unsafe { test_flash() };
     assert!(rc == 0, "FLASH fail");
 
+    //  Create the lock for the Boot Logo Loader, which is shared with the logo transfer over Bluetooth LE
+    logo_loader::start_loader()
+        .expect("LOGO start fail");
+
     //  Start Bluetooth LE, including over-the-air firmware upgrade.  TODO: Create a safe wrapper for starting Bluetooth LE.
     extern { fn start_ble() -> i32; }
     let rc = unsafe { start_ble() };
     assert!(rc == 0, "BLE fail");
 
-    //  Write the boot graphic
-    extern { fn write_graphic() -> i32; }  //  Defined in apps/my_sensor_app/src/write_graphic.c
-    #[cfg(feature = "write_graphic")]      //  If writing of boot graphic is enabled...
-    unsafe { write_graphic() };
-
     //  Start the display
     druid::start_display()
         .expect("DSP fail");
+    screen::set_orientation(screen::DEFAULT_ORIENTATION)
+        .expect("DSP orientation fail");
+    backlight::start_backlight()
+        .expect("BKL fail");
 
-    //  Test the display
-    #[cfg(feature = "display_app")]  //  If graphics display app is enabled...
-    display::test_display()
-        .expect("DSP test fail");
+    //  Show the old boot graphic from SPI Flash, write the new boot graphic, then show what the bootloader will show
+    #[cfg(feature = "write_graphic")]  //  If writing of boot graphic is enabled...
+    {
+        logo_display::show_logo()
+            .expect("LOGO show fail");
+        logo_loader::write_logo()
+            .expect("LOGO fail");
+        logo_display::show_logo()
+            .expect("LOGO show fail");
+    }
+
+    //  Play the animated boot logo from SPI Flash, if any
+    #[cfg(feature = "play_animation")]  //  If animated boot logo is enabled...
+    {
+        if logo_animation::play_animation(logo_animation::ANIMATION_SLOT).is_err() {
+            console::print("No animation\n"); console::flush();
+        }
+    }
+
+    //  Dump the previous touch recording to the console and start recording the touch reports
+    #[cfg(feature = "record_touch")]  //  If touch recording is enabled...
+    touch_recorder::start_recorder()
+        .expect("TCH record fail");
 
     //  Start the touch sensor
     touch_sensor::start_touch_sensor()
         .expect("TCH fail");
 
+    //  Put the display to sleep after a period without touches, and wake it on touch
+    power::start_power_manager()
+        .expect("PWR fail");
+
+    //  Load the touch calibration from Internal Flash ROM, if the touch panel has been calibrated. If the flash
+    //  can't be read, the touches are passed through with the identity calibration.
+    if calibration::load_calibration().is_err() {
+        console::print("CAL read fail, using identity\n"); console::flush();
+    }
+
+    //  Show the touch calibration screen with 5 targets (or 3), and process the touch events until all targets have
+    //  been touched. The calibration is stored in Internal Flash ROM.
+    #[cfg(feature = "calibrate_touch")]  //  If touch calibration is enabled...
+    {
+        calibration::start_calibration(5)
+            .expect("CAL start fail");
+        while calibration::is_calibrating() {
+            os::eventq_run(os::eventq_dflt_get().expect("GET fail"))
+                .expect("RUN fail");
+        }
+    }
+
+    //  Test the display
+    #[cfg(feature = "display_app")]  //  If graphics display app is enabled...
+    display::test_display()
+        .expect("DSP test fail");
+
     //  Test the touch sensor
     //  touch_sensor::test()
     //      .expect("TCH test fail");
 
+    //  Pass the touch events to the druid app
+    #[cfg(any(feature = "ui_app", feature = "visual_app"))]  //  If druid UI app or Visual Rust app is enabled...
+    touch_sensor::subscribe(handle_druid_touch)
+        .expect("TCH subscribe fail");
+
     //  Launch the druid UI app
     #[cfg(feature = "ui_app")]  //  If druid UI app is enabled...
     ui::launch();
@@ -140,6 +223,10 @@ extern "C" fn main() -> ! {  //  Declare extern "C" because it will be called by
     chip8::on_start()
         .expect("CHIP8 fail");
 
+    //  Show the time, battery and Bluetooth LE status at the top of the screen
+    status::start_status_bar()
+        .expect("STS fail");
+
     //  Main event loop
     loop {                            //  Loop forever...
         os::eventq_run(               //  Processing events...
@@ -0,0 +1,57 @@
+//! Play the animated boot logo (see `logo::animation`) stored in a slot of External SPI Flash. Each frame is read
+//! from flash by `logo::animation::Player`, which decodes the changed rectangles in bands of rows. The bands are sent
+//! to the display with `druid::set_display_pixels()`, then the player waits for the frame's delay.
+//! The display shares SPI port 0 with External SPI Flash, so the flash is read through `logo_loader::SharedFlash`,
+//! which locks the SPI port and drains the non-blocking SPI queue before every access.
+//! `start_display()` must have been called earlier. Build the animation with `logo_tool animate`.
+use logo::{
+    animation::{ Player, Rect },
+    LogoResult,
+};
+use mynewt::{
+    kernel::os,
+    result::*,
+    spi,
+    sys::console,
+};
+use crate::logo_loader::{ self, MynewtFlash, SharedFlash };
+
+/// Slot that contains the animation. Slot 0 is used for the still boot logo compiled into `write_graphic.c`.
+pub const ANIMATION_SLOT: u8 = 3;
+
+/// Player for the animation in External SPI Flash. Contains the buffers for loading and decoding the rectangles.
+static mut PLAYER: Player<SharedFlash> = Player::new(SharedFlash(MynewtFlash::new(logo_loader::FLASH_DEVICE)));
+
+/// Play the animation in the slot as many times as its header says. Returns `SYS_ENOENT` if the slot doesn't
+/// contain a valid animation.
+pub fn play_animation(slot: u8) -> MynewtResult<()> {
+    let player = unsafe { &mut PLAYER };
+    let animation = player.open(slot)
+        .map_err(|err| match err {
+            logo::Error::FlashFailed | logo::Error::BadSlot => logo_loader::to_mynewt_error(err),
+            _ => MynewtError::SYS_ENOENT,
+        }) ? ;
+    console::print("Playing animation in slot "); console::printint(slot as i32);
+    console::print(", frames: "); console::printint(animation.header.frame_count as i32); console::print("\n"); console::flush();
+    for _ in 0..animation.header.loops {
+        let mut offset = animation.first;
+        for _ in 0..animation.header.frame_count {
+            let frame = player.draw_frame(&animation, offset, draw_band)
+                .map_err(logo_loader::to_mynewt_error) ? ;
+            unsafe { os::os_time_delay(frame.delay_ms as u32 * os::OS_TICKS_PER_SEC / 1000) };
+            offset = frame.next;
+        }
+    }
+    //  Leave the SPI port idle for External SPI Flash
+    spi::spi_noblock_wait()
+}
+
+/// Send the band of big-endian RGB565 pixels to the display
+fn draw_band(rect: &Rect, pixels: &[u8]) -> LogoResult<()> {
+    let mut colors = pixels.chunks(logo::BYTES_PER_PIXEL)
+        .map(|pixel| u16::from_be_bytes([pixel[0], pixel[1]]));
+    druid::set_display_pixels(rect.left, rect.top, rect.left + rect.width - 1, rect.top + rect.height - 1,
+        &mut colors
+    ).expect("set pixels failed");
+    Ok(())
+}
@@ -0,0 +1,83 @@
+//! Show the boot logo stored in External SPI Flash, the same way the bootloader (`libs/pinetime_boot`) will show
+//! it: the active slot if its header and CRC32 are valid, else a black screen. The pixels are streamed from flash
+//! to the ST7789 display controller one band of rows at a time, without a framebuffer.
+//! The display shares SPI port 0 with External SPI Flash, so the non-blocking SPI queue is drained before every
+//! flash read. `start_display()` must have been called earlier.
+use logo::{
+    layout::PIXELS_OFFSET,
+    slots,
+};
+use mynewt::{
+    result::*,
+    spi,
+    sys::console,
+};
+use crate::logo_loader;
+
+/// Number of rows sent in each SPI request: 240 * 4 * 2 = 1,920 bytes
+const BAND_ROWS: u16 = 4;
+
+/// Number of bytes in each band
+const BAND_SIZE: usize = logo::WIDTH as usize * BAND_ROWS as usize * logo::BYTES_PER_PIXEL;
+
+/// Colour shown by the bootloader when there is no valid logo: black
+const DEFAULT_COLOR: u16 = 0x0000;
+
+/// ST7789 Column Address Set
+const CASET: u8 = 0x2A;
+/// ST7789 Row Address Set
+const RASET: u8 = 0x2B;
+/// ST7789 Memory Write
+const RAMWR: u8 = 0x2C;
+
+/// Band of pixels read from flash
+static mut BAND: [u8; BAND_SIZE] = [0; BAND_SIZE];
+
+/// Show the boot logo that the bootloader will show
+pub fn show_logo() -> MynewtResult<()> {
+    let slot = logo_loader::active_slot() ? ;
+    console::print("Showing logo slot "); console::printint(slot as i32); console::print("\n"); console::flush();
+    show_slot(slot)
+}
+
+/// Show the boot logo in the slot. Shows a black screen if the slot doesn't contain a valid logo.
+pub fn show_slot(slot: u8) -> MynewtResult<()> {
+    let slot_offset = slots::slot_offset(slot)
+        .map_err(logo_loader::to_mynewt_error) ? ;
+    spi::spi_noblock_wait() ? ;
+    let valid = logo_loader::is_valid(slot) ? ;
+    if !valid {
+        console::print("No valid logo in slot\n"); console::flush();
+    }
+    let band = unsafe { &mut BAND };
+    for top in (0..logo::HEIGHT).step_by(BAND_ROWS as usize) {
+        let rows = core::cmp::min(BAND_ROWS, logo::HEIGHT - top);
+        let len = logo::WIDTH as usize * rows as usize * logo::BYTES_PER_PIXEL;
+        let band = &mut band[..len];
+
+        //  Read the band from flash after the previous band has been sent, or fill with the default colour
+        if valid {
+            spi::spi_noblock_wait() ? ;
+            let offset = slot_offset + PIXELS_OFFSET + top as u32 * logo::WIDTH as u32 * logo::BYTES_PER_PIXEL as u32;
+            logo_loader::read_flash(offset, band) ? ;
+        } else {
+            for pixel in band.chunks_mut(logo::BYTES_PER_PIXEL) {
+                pixel.copy_from_slice(&DEFAULT_COLOR.to_be_bytes());
+            }
+        }
+        //  Send the band to the display
+        set_window(0, top, logo::WIDTH - 1, top + rows - 1) ? ;
+        spi::spi_noblock_write_command(RAMWR) ? ;
+        spi::spi_noblock_write_data(band) ? ;
+    }
+    //  Leave the SPI port idle for External SPI Flash
+    spi::spi_noblock_wait()
+}
+
+/// Set the display window for the next Memory Write
+fn set_window(left: u16, top: u16, right: u16, bottom: u16) -> MynewtResult<()> {
+    spi::spi_noblock_write_command(CASET) ? ;
+    spi::spi_noblock_write_data(&[(left >> 8) as u8, left as u8, (right >> 8) as u8, right as u8]) ? ;
+    spi::spi_noblock_write_command(RASET) ? ;
+    spi::spi_noblock_write_data(&[(top >> 8) as u8, top as u8, (bottom >> 8) as u8, bottom as u8])
+}
@@ -0,0 +1,286 @@
+//! Boot Logo Loader. Expands the compressed boot logo from `write_graphic.c` into RGB565 pixels and writes
+//! them to a slot in External SPI Flash, with `logo::loader` over Mynewt's `hal_flash`. Every sector is read back
+//! and compared after writing, failed sectors are retried, and the whole slot is verified with CRC32 at the end.
+//! The header is written last (see `logo::layout`), so the bootloader will ignore a partially-written logo.
+//! The slot table (see `logo::slots`) selects the slot that will be shown by the bootloader.
+//! The loader is shared by the main task (boot logo at startup) and the Bluetooth LE task (`logo_transfer.rs`),
+//! so every access is serialised by a mutex. The display shares SPI port 0 with External SPI Flash, so every flash
+//! access locks the SPI port, which stops other tasks from queueing display requests, and drains the non-blocking SPI
+//! queue before accessing the flash. Call `start_loader()` before starting Bluetooth LE.
+use logo::{
+    flash::Flash,
+    header::Header,
+    loader::{ Loader, SlotDiff, SlotInfo },
+    slots::MAX_SLOTS,
+    LogoResult,
+};
+use mynewt::{
+    result::*,
+    hw::hal,
+    kernel::os,
+    spi,
+    sys::console,
+    fill_zero,
+};
+
+/// Flash Device for the boot logo: 0 for Internal Flash ROM, 1 for External SPI Flash
+pub const FLASH_DEVICE: u8 = 1;
+
+/// Slot for the boot logo compiled into `write_graphic.c`
+#[cfg(feature = "write_graphic")]  //  If writing of boot graphic is enabled...
+const WRITE_SLOT: u8 = 0;
+
+/// Loader for the boot logo slots in External SPI Flash. Contains the buffers for decoding and verifying.
+/// Accessed only through `with_loader()`.
+static mut LOADER: Loader<SharedFlash> = Loader::new(SharedFlash(MynewtFlash::new(FLASH_DEVICE)));
+
+/// Mutex that serialises access to `LOADER` by the main task and the Bluetooth LE task
+static mut LOADER_MUTEX: os::os_mutex = fill_zero!(os::os_mutex);
+
+/// Flash device accessed through Mynewt's `hal_flash`
+pub struct MynewtFlash {
+    /// Flash Device: 0 for Internal Flash ROM, 1 for External SPI Flash
+    device: u8,
+}
+
+impl MynewtFlash {
+    /// Create the flash device for the Flash Device number
+    pub const fn new(device: u8) -> MynewtFlash {
+        MynewtFlash { device }
+    }
+}
+
+impl Flash for MynewtFlash {
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> LogoResult<()> {
+        let rc = unsafe { hal::hal_flash_read(self.device, offset, buf.as_mut_ptr() as *mut _, buf.len() as u32) };
+        if rc != 0 { return Err(logo::Error::FlashFailed); }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> LogoResult<()> {
+        let rc = unsafe { hal::hal_flash_write(self.device, offset, data.as_ptr() as *const _, data.len() as u32) };
+        if rc != 0 { return Err(logo::Error::FlashFailed); }
+        Ok(())
+    }
+
+    fn erase(&mut self, offset: u32, len: u32) -> LogoResult<()> {
+        let rc = unsafe { hal::hal_flash_erase(self.device, offset, len) };
+        if rc != 0 { return Err(logo::Error::FlashFailed); }
+        Ok(())
+    }
+}
+
+/// External SPI Flash that locks SPI port 0 against the display for every access
+pub struct SharedFlash(pub MynewtFlash);
+
+impl Flash for SharedFlash {
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> LogoResult<()> {
+        let flash = &mut self.0;
+        with_spi_port(|| flash.read(offset, buf))
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> LogoResult<()> {
+        let flash = &mut self.0;
+        with_spi_port(|| flash.write(offset, data))
+    }
+
+    fn erase(&mut self, offset: u32, len: u32) -> LogoResult<()> {
+        let flash = &mut self.0;
+        with_spi_port(|| flash.erase(offset, len))
+    }
+}
+
+/// Create the mutex for the loader. Must be called before starting Bluetooth LE.
+pub fn start_loader() -> MynewtResult<()> {
+    let rc = unsafe { os::os_mutex_init(&mut LOADER_MUTEX) };
+    if rc != 0 { return Err(MynewtError::from(rc as i32)); }
+    Ok(())
+}
+
+/// Lock the loader for External SPI Flash and call the function with it. Blocks while another task is using the
+/// loader. Also called by `logo_transfer.rs` for the Bluetooth LE task.
+pub fn with_loader<T>(f: impl FnOnce(&mut Loader<SharedFlash>) -> LogoResult<T>) -> LogoResult<T> {
+    lock_loader() ? ;
+    let result = f(unsafe { &mut LOADER });
+    unlock_loader();
+    result
+}
+
+/// Wait for the mutex of the loader. The mutex may be locked again by the same task, e.g. `write_logo()`.
+fn lock_loader() -> LogoResult<()> {
+    let rc = unsafe { os::os_mutex_pend(&mut LOADER_MUTEX, os::OS_TIMEOUT_NEVER) };
+    if rc != 0 { return Err(logo::Error::BadState); }
+    Ok(())
+}
+
+/// Release the mutex of the loader
+fn unlock_loader() {
+    unsafe { os::os_mutex_release(&mut LOADER_MUTEX) };
+}
+
+/// Lock SPI port 0, wait for the display to finish sending the queued requests, then call the function to access
+/// External SPI Flash. Other tasks can't queue display requests until the function returns.
+fn with_spi_port<T>(f: impl FnOnce() -> LogoResult<T>) -> LogoResult<T> {
+    spi::spi_lock()
+        .map_err(|_| logo::Error::FlashFailed) ? ;
+    let result = spi::spi_noblock_wait()
+        .map_err(|_| logo::Error::FlashFailed)
+        .and_then(|_| f());
+    spi::spi_unlock();
+    result
+}
+
+/// Write the compiled-in boot logo to slot 0 and mark the slot active. With the `diff_graphic` feature, only
+/// report the sectors that differ from the logo in flash. With the `update_graphic` feature, rewrite only those sectors.
+#[cfg(feature = "write_graphic")]  //  If writing of boot graphic is enabled...
+pub fn write_logo() -> MynewtResult<()> {
+    //  Keep the loader locked until the slot is active, so that a logo uploaded over Bluetooth LE can't interleave
+    lock_loader()
+        .map_err(to_mynewt_error) ? ;
+    let result = write_active_logo();
+    unlock_loader();
+    result
+}
+
+/// Write the compiled-in boot logo to slot 0 according to the features, then list the slots
+#[cfg(feature = "write_graphic")]  //  If writing of boot graphic is enabled...
+fn write_active_logo() -> MynewtResult<()> {
+    #[cfg(feature = "diff_graphic")]  //  If dry run is enabled...
+    {
+        let diff = diff_slot(WRITE_SLOT, logo_data()) ? ;
+        print_diff(&diff, " differ, nothing written\n");
+    }
+    #[cfg(all(feature = "update_graphic", not(feature = "diff_graphic")))]  //  If rewriting changed sectors only...
+    {
+        let diff = update_slot(WRITE_SLOT, logo_data()) ? ;
+        print_diff(&diff, " rewritten\n");
+        if active_slot() ? != WRITE_SLOT { mark_active(WRITE_SLOT) ? ; }
+    }
+    #[cfg(not(any(feature = "update_graphic", feature = "diff_graphic")))]  //  If rewriting all sectors...
+    {
+        write_slot(WRITE_SLOT, logo_data()) ? ;
+        mark_active(WRITE_SLOT) ? ;
+    }
+    for info in list_slots() ? .iter() {
+        console::print("Logo slot "); console::printint(info.slot as i32);
+        console::print(if info.header.is_some() { " valid" } else { " empty" });
+        console::print(if info.active { " active\n" } else { "\n" }); console::flush();
+    }
+    Ok(())
+}
+
+/// Expand the boot logo (header and optionally compressed pixels) and write to the slot in External SPI Flash.
+/// Returns `SYS_EIO` if a sector can't be written or the CRC32 of the written pixels doesn't match the logo.
+pub fn write_slot(slot: u8, logo: &[u8]) -> MynewtResult<()> {
+    console::print("Writing logo to slot "); console::printint(slot as i32); console::print("...\n"); console::flush();
+    let result = with_loader(|loader| loader.write_slot(slot, logo));
+    print_retries();
+    result.map_err(to_mynewt_error) ? ;
+    console::print("Logo written to flash\n"); console::flush();
+    Ok(())
+}
+
+/// Like `write_slot()`, but erase and write only the sectors that differ from the logo
+pub fn update_slot(slot: u8, logo: &[u8]) -> MynewtResult<SlotDiff> {
+    console::print("Updating logo in slot "); console::printint(slot as i32); console::print("...\n"); console::flush();
+    let result = with_loader(|loader| loader.update_slot(slot, logo));
+    print_retries();
+    result.map_err(to_mynewt_error)
+}
+
+/// Compare the boot logo with the slot in External SPI Flash without writing
+pub fn diff_slot(slot: u8, logo: &[u8]) -> MynewtResult<SlotDiff> {
+    console::print("Comparing logo with slot "); console::printint(slot as i32); console::print("...\n"); console::flush();
+    with_loader(|loader| loader.diff_slot(slot, logo))
+        .map_err(to_mynewt_error)
+}
+
+/// Erase the entire slot. If the slot is active, the bootloader will show the default boot screen.
+pub fn erase_slot(slot: u8) -> MynewtResult<()> {
+    with_loader(|loader| loader.erase_slot(slot))
+        .map_err(to_mynewt_error)
+}
+
+/// Mark the slot as active, so that the bootloader will show it. Returns `SYS_ENOENT` if the slot doesn't contain a valid logo.
+pub fn mark_active(slot: u8) -> MynewtResult<()> {
+    read_header(slot) ? ;
+    with_loader(|loader| loader.mark_active(slot))
+        .map_err(to_mynewt_error)
+}
+
+/// Return the slot that will be shown by the bootloader. Returns slot 0 if the slot table is missing or invalid.
+pub fn active_slot() -> MynewtResult<u8> {
+    with_loader(|loader| loader.active_slot())
+        .map_err(to_mynewt_error)
+}
+
+/// Return the status of every slot
+pub fn list_slots() -> MynewtResult<[SlotInfo; MAX_SLOTS as usize]> {
+    with_loader(|loader| loader.list_slots())
+        .map_err(to_mynewt_error)
+}
+
+/// Read and validate the boot logo header in the slot. Returns `SYS_ENOENT` if the header is missing or invalid.
+pub fn read_header(slot: u8) -> MynewtResult<Header> {
+    with_loader(|loader| loader.read_header(slot))
+        .map_err(|err| match err {
+            logo::Error::FlashFailed | logo::Error::BadSlot => to_mynewt_error(err),
+            _ => MynewtError::SYS_ENOENT,
+        })
+}
+
+/// Return true if the slot contains a logo that the bootloader will show: valid header and matching CRC32
+pub fn is_valid(slot: u8) -> MynewtResult<bool> {
+    with_loader(|loader| loader.is_valid(slot))
+        .map_err(to_mynewt_error)
+}
+
+/// Read the flash at the offset into the buffer
+pub fn read_flash(offset: u32, buf: &mut [u8]) -> MynewtResult<()> {
+    with_loader(|loader| loader.read(offset, buf))
+        .map_err(to_mynewt_error)
+}
+
+/// Print the number of sectors that differ, followed by the message
+#[cfg(any(feature = "update_graphic", feature = "diff_graphic"))]  //  If comparing the boot graphic...
+fn print_diff(diff: &SlotDiff, msg: &str) {
+    console::print("Logo sectors: "); console::printint(diff.changed as i32);
+    console::print(" of "); console::printint(diff.sectors as i32);
+    console::print(msg); console::flush();
+}
+
+/// Print the number of sector writes that have been retried, if any
+fn print_retries() {
+    let retries = with_loader(|loader| Ok(loader.retries())).unwrap_or(0);
+    if retries == 0 { return; }
+    console::print("Logo retries: "); console::printint(retries as i32); console::print("\n"); console::flush();
+}
+
+/// Return the compressed boot logo, defined in `write_graphic.c`
+#[cfg(feature = "write_graphic")]  //  If writing of boot graphic is enabled...
+fn logo_data() -> &'static [u8] {
+    extern "C" {
+        static logo_data: [u8; 0];  //  Header followed by compressed pixels
+        static logo_data_size: u32; //  Number of bytes in `logo_data`
+    }
+    unsafe { core::slice::from_raw_parts(logo_data.as_ptr(), logo_data_size as usize) }
+}
+
+/// Convert the boot logo error to a Mynewt error code
+pub fn to_mynewt_error(err: logo::Error) -> MynewtError {
+    match err {
+        logo::Error::BadMagic
+        | logo::Error::BadVersion
+        | logo::Error::BadFormat
+        | logo::Error::BadSize
+        | logo::Error::Truncated
+        | logo::Error::BadSlot
+        | logo::Error::BadOffset
+        | logo::Error::BadState      => MynewtError::SYS_EINVAL,
+        logo::Error::Corrupted
+        | logo::Error::BadChecksum
+        | logo::Error::FlashFailed
+        | logo::Error::NotErased     => MynewtError::SYS_EIO,
+        logo::Error::TooManyColors   => MynewtError::SYS_ENOTSUP,
+    }
+}
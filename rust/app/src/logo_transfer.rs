@@ -0,0 +1,103 @@
+//! Boot Logo Transfer over Bluetooth LE. The Logo Transfer GATT Service in `ble_gatt_svr.c` forwards the
+//! Control, Data and Status characteristics to the functions below. The protocol is handled by
+//! `logo::transfer::Receiver`, and the pixels are written to the slot in External SPI Flash by the Boot Logo
+//! Loader (`logo_loader.rs`), which is locked against the main task and locks SPI port 0 against the display for
+//! every flash access. A progress bar is drawn at the bottom of the display after each write, while the loader is
+//! still locked, so that the progress bar is drawn before the next flash access by any task.
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::Rgb565,
+    primitives::Rectangle,
+};
+use logo::{
+    header::Header,
+    transfer::{ Receiver, TransferTarget, STATUS_SIZE },
+    LogoResult,
+};
+use mynewt::sys::console;
+use crate::logo_loader;
+
+/// Top row of the progress bar, which fills the rows from here to the bottom of the display
+const PROGRESS_TOP: i32 = 230;
+
+/// Receiver for the transfer in progress. Only accessed by the Bluetooth LE task.
+static mut RECEIVER: Receiver = Receiver::new();
+
+/// Writes the uploaded pixels to the boot logo slots in External SPI Flash with the Boot Logo Loader,
+/// and shows the progress
+struct FlashTarget;
+
+impl TransferTarget for FlashTarget {
+    fn begin(&mut self, slot: u8) -> LogoResult<()> {
+        console::print("Receiving logo for slot "); console::printint(slot as i32); console::print("\n"); console::flush();
+        logo_loader::with_loader(|loader| {
+            loader.begin(slot) ? ;
+            show_progress(0);
+            Ok(())
+        })
+    }
+
+    fn write(&mut self, slot: u8, offset: u32, data: &[u8]) -> LogoResult<()> {
+        logo_loader::with_loader(|loader| {
+            loader.write(slot, offset, data) ? ;
+            show_progress(offset + data.len() as u32);
+            Ok(())
+        })
+    }
+
+    fn commit(&mut self, slot: u8, header: &Header, activate: bool) -> LogoResult<()> {
+        logo_loader::with_loader(|loader| loader.commit(slot, header, activate)) ? ;
+        console::print("Logo received\n"); console::flush();
+        Ok(())
+    }
+}
+
+/// Handle a write to the Control characteristic. Returns 0 if successful, else the `logo::Error` code.
+#[no_mangle]
+extern "C" fn logo_transfer_control(data: *const u8, len: u32) -> i32 {
+    let data = unsafe { core::slice::from_raw_parts(data, len as usize) };
+    let receiver = unsafe { &mut RECEIVER };
+    to_result_code(receiver.control(&mut FlashTarget, data))
+}
+
+/// Handle a write to the Data characteristic. Returns 0 if successful, else the `logo::Error` code.
+#[no_mangle]
+extern "C" fn logo_transfer_data(data: *const u8, len: u32) -> i32 {
+    let data = unsafe { core::slice::from_raw_parts(data, len as usize) };
+    let receiver = unsafe { &mut RECEIVER };
+    to_result_code(receiver.data(&mut FlashTarget, data))
+}
+
+/// Handle a read of the Status characteristic. Copies the status into the buffer and returns the number of bytes.
+#[no_mangle]
+extern "C" fn logo_transfer_status(buf: *mut u8, len: u32) -> i32 {
+    if (len as usize) < STATUS_SIZE { return -1; }
+    let buf = unsafe { core::slice::from_raw_parts_mut(buf, STATUS_SIZE) };
+    let receiver = unsafe { &RECEIVER };
+    buf.copy_from_slice(&receiver.status().to_bytes());
+    STATUS_SIZE as i32
+}
+
+/// Convert the transfer result to the code returned to `ble_gatt_svr.c`
+fn to_result_code(result: LogoResult<()>) -> i32 {
+    match result {
+        Ok(())   => 0,
+        Err(err) => err.code() as i32,
+    }
+}
+
+/// Draw the progress bar for the number of bytes of pixels written. Drawn in green, with the rest in grey.
+fn show_progress(written: u32) {
+    let total = Header::rgb565(logo::WIDTH, logo::HEIGHT, 0).image_size();
+    let right = (written as u64 * (logo::WIDTH as u64 - 1) / total as u64) as i32;
+    let done = Rectangle::<Rgb565>
+        ::new( Coord::new( 0, PROGRESS_TOP ), Coord::new( right, logo::HEIGHT as i32 - 1 ) )
+        .fill( Some( Rgb565::from(( 0x00, 0xff, 0x00 )) ) );  //  Green
+    druid::draw_to_display(done);
+    if right < logo::WIDTH as i32 - 1 {
+        let rest = Rectangle::<Rgb565>
+            ::new( Coord::new( right + 1, PROGRESS_TOP ), Coord::new( logo::WIDTH as i32 - 1, logo::HEIGHT as i32 - 1 ) )
+            .fill( Some( Rgb565::from(( 0x40, 0x40, 0x40 )) ) );  //  Grey
+        druid::draw_to_display(rest);
+    }
+}
@@ -0,0 +1,110 @@
+//! Power manager for the display. After a period without touches, the backlight is switched off and the ST7789
+//! display controller is put to sleep (DISPOFF, SLPIN). The next touch wakes the display (SLPOUT, DISPON) and
+//! restores the backlight. The ST7789 keeps its frame memory while sleeping, and screens drawn while asleep are
+//! written to frame memory, so the last frame is shown again on wakeup without redrawing.
+//! The sleep and wake states are computed by `power::display::Power`, and the inactivity timeout is stepped by a
+//! Mynewt callout on the Default Event Queue.
+//! The CPU sleeps through Mynewt's tickless idle whenever no task is running. `libs/low_power` is only for the
+//! STM32 Blue Pill, so it's not used here. Call `start_power_manager()` after `start_backlight()`.
+use ::power::display::{ Panel, Power };
+use mynewt::{
+    self,
+    result::*,
+    kernel::os::{
+        self,
+        os_event,
+    },
+    spi,
+    sys::console,
+    fill_zero,
+};
+use crate::backlight;
+
+/// Milliseconds without touches before the backlight is dimmed
+const DIM_TIMEOUT_MS: u32 = 10_000;
+
+/// Backlight level when dimmed
+const DIM_LEVEL: u8 = 1;
+
+/// Milliseconds without touches before the display sleeps
+const SLEEP_TIMEOUT_MS: u32 = 30_000;
+
+/// Sleep and wake state of the display
+static mut POWER: Power = Power::new(SLEEP_TIMEOUT_MS);
+
+/// Callout that steps the inactivity timeout
+static mut SLEEP_CALLOUT: os::os_callout = fill_zero!(os::os_callout);
+
+/// Milliseconds until the callout fires, passed to `tick()` when it fires
+static mut SCHEDULED_MS: u32 = 0;
+
+/// ST7789 display controller on SPI port 0
+struct SpiPanel;
+
+impl Panel for SpiPanel {
+    type Error = MynewtError;
+
+    /// Send the command and wait until it has been sent
+    fn command(&mut self, command: u8) -> MynewtResult<()> {
+        spi::spi_noblock_write_command(command) ? ;
+        spi::spi_noblock_wait()
+    }
+}
+
+/// Start the inactivity timeout and auto-dim the backlight
+pub fn start_power_manager() -> MynewtResult<()> {
+    let queue = os::eventq_dflt_get() ? ;
+    unsafe { os::os_callout_init(&mut SLEEP_CALLOUT, queue, Some(sleep_callback), core::ptr::null_mut()) };
+    backlight::set_auto_dim(Some(DIM_TIMEOUT_MS), DIM_LEVEL) ? ;
+    schedule(unsafe { POWER.next_tick() })
+}
+
+/// Called on touch. Restarts the inactivity timeout, and wakes the display if asleep. Returns true if the display
+/// was asleep, so that the touch that woke the display is not passed to the app.
+pub fn wake() -> MynewtResult<bool> {
+    if is_asleep() { console::print("Wake display\n"); console::flush(); }
+    let power = unsafe { &mut POWER };
+    let asleep = backlight::update(|backlight| power.wake(backlight, &mut SpiPanel, &mut mynewt::Delay::new())) ? ;
+    schedule(power.next_tick()) ? ;
+    Ok(asleep)
+}
+
+/// Switch off the backlight and put the display to sleep
+pub fn sleep() -> MynewtResult<()> {
+    if is_asleep() { return Ok(()); }
+    console::print("Sleep display\n"); console::flush();
+    let power = unsafe { &mut POWER };
+    backlight::update(|backlight| power.sleep(backlight, &mut SpiPanel)) ? ;
+    schedule(power.next_tick())
+}
+
+/// Return true if the display is asleep
+pub fn is_asleep() -> bool {
+    unsafe { POWER.is_asleep() }
+}
+
+/// Fire the callout after the milliseconds, or stop it if `None`
+fn schedule(delay_ms: Option<u32>) -> MynewtResult<()> {
+    let delay_ms = match delay_ms {
+        Some(delay_ms) => delay_ms,
+        None => { unsafe { os::os_callout_stop(&mut SLEEP_CALLOUT) }; return Ok(()); }
+    };
+    let mut ticks: os::os_time_t = 0;
+    let rc = unsafe { os::os_time_ms_to_ticks(delay_ms, &mut ticks) };
+    if rc != 0 { return Err(MynewtError::from(rc)); }
+    unsafe { SCHEDULED_MS = delay_ms };
+    let rc = unsafe { os::os_callout_reset(&mut SLEEP_CALLOUT, ticks.max(1)) };
+    if rc != 0 { return Err(MynewtError::from(rc)); }
+    Ok(())
+}
+
+/// Called by the callout to step the inactivity timeout. Puts the display to sleep when the timeout expires.
+extern "C" fn sleep_callback(_event: *mut os_event) {
+    let elapsed_ms = unsafe { SCHEDULED_MS };
+    let power = unsafe { &mut POWER };
+    let next = backlight::update(|backlight| power.tick(elapsed_ms, backlight, &mut SpiPanel))
+        .expect("sleep fail");
+    if power.is_asleep() { console::print("Sleep display\n"); console::flush(); }
+    schedule(next)
+        .expect("sleep fail");
+}
@@ -0,0 +1,112 @@
+//! The PineTime display (ST7789) as a `framebuffer::Display`, so that screens may be drawn onto the display or,
+//! on the host computer, onto the in-memory `framebuffer::Framebuffer`. Pixels are sent to the display through the
+//! druid display driver. `start_display()` must have been called earlier.
+//! The display may be rotated, mirrored and scrolled with the ST7789 commands from `framebuffer::orientation`,
+//! sent through the non-blocking SPI queue. Touches are mapped back to the drawing with `to_drawing()`.
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::Rgb565,
+};
+use framebuffer::{
+    Display,
+    orientation::{
+        MADCTL, MADCTL_BGR, VSCRDEF, VSCRSADD,
+        Orientation, Rotation, ScrollArea,
+    },
+};
+use mynewt::{
+    result::*,
+    spi,
+};
+
+/// The PineTime display
+pub struct Screen;
+
+impl Drawing<Rgb565> for Screen {
+    /// Draw the `embedded-graphics` pixels to the display
+    fn draw<T>(&mut self, item: T)
+    where
+        T: IntoIterator<Item = Pixel<Rgb565>>,
+    {
+        druid::draw_to_display(item);
+    }
+}
+
+impl Display for Screen {
+    /// Send the window of RGB565 pixels to the display
+    fn set_pixels<T>(&mut self, left: u16, top: u16, right: u16, bottom: u16, colors: &mut T)
+    where
+        T: Iterator<Item = u16>,
+    {
+        druid::set_display_pixels(left, top, right, bottom, colors)
+            .expect("set pixels failed");
+    }
+}
+
+/// Colour order of the display, set by the druid display driver: blue-green-red
+const COLOR_ORDER: u8 = MADCTL_BGR;
+
+/// Orientation of the display after `start_display()`
+pub const DEFAULT_ORIENTATION: Orientation = Orientation::new(Rotation::Deg0, false);
+
+/// Current orientation of the display
+static mut ORIENTATION: Orientation = DEFAULT_ORIENTATION;
+
+/// Current scroll area and the number of rows scrolled up, or `None` if not scrolling
+static mut SCROLL: Option<(ScrollArea, u16)> = None;
+
+/// Rotate and mirror the display with the MADCTL command. Stops any scrolling. Screens drawn after this will be
+/// shown in the orientation, and touches will be mapped to the orientation.
+pub fn set_orientation(orientation: Orientation) -> MynewtResult<()> {
+    write_command(MADCTL, &[orientation.madctl() | COLOR_ORDER]) ? ;
+    write_command(VSCRDEF, &orientation.vscrdef()) ? ;
+    write_command(VSCRSADD, &orientation.vscrsadd()) ? ;
+    spi::spi_noblock_write_flush() ? ;
+    unsafe {
+        ORIENTATION = orientation;
+        SCROLL = None;
+    }
+    Ok(())
+}
+
+/// Return the current orientation of the display
+pub fn orientation() -> Orientation {
+    unsafe { ORIENTATION }
+}
+
+/// Define the rows of the panel that will be scrolled by `scroll()`, with the VSCRDEF command. Fails with
+/// `SYS_EINVAL` if the current orientation can't scroll.
+pub fn set_scroll_area(area: ScrollArea) -> MynewtResult<()> {
+    if !orientation().can_scroll() { return Err(MynewtError::SYS_EINVAL); }
+    write_command(VSCRDEF, &area.vscrdef()) ? ;
+    write_command(VSCRSADD, &area.vscrsadd(0)) ? ;
+    spi::spi_noblock_write_flush() ? ;
+    unsafe { SCROLL = Some((area, 0)) };
+    Ok(())
+}
+
+/// Scroll the drawing in the scroll area up by `lines` rows, with the VSCRSADD command. Rows that scroll off the
+/// top reappear at the bottom. Fails with `SYS_EINVAL` if `set_scroll_area()` has not been called.
+pub fn scroll(lines: u16) -> MynewtResult<()> {
+    let (area, _) = unsafe { SCROLL }
+        .ok_or(MynewtError::SYS_EINVAL) ? ;
+    write_command(VSCRSADD, &area.vscrsadd(lines)) ? ;
+    spi::spi_noblock_write_flush() ? ;
+    unsafe { SCROLL = Some((area, lines)) };
+    Ok(())
+}
+
+/// Map the point (`x`, `y`) on the panel, e.g. a touch, to the drawing, by undoing the scrolling and orientation
+pub fn to_drawing(x: u16, y: u16) -> (u16, u16) {
+    let y = match unsafe { SCROLL } {
+        Some((area, lines)) => area.to_drawing_row(lines, y),
+        None => y,
+    };
+    orientation().to_drawing(x, y)
+}
+
+/// Send the ST7789 command and its parameters through the non-blocking SPI queue
+fn write_command(command: u8, data: &[u8]) -> MynewtResult<()> {
+    spi::spi_noblock_write_command(command) ? ;
+    spi::spi_noblock_write_data(data)
+}
@@ -0,0 +1,149 @@
+//! Status bar at the top of the screen for all apps. Every second, a Mynewt callout reads the time from
+//! `os_gettimeofday()`, the battery voltage and charging state, and whether the Bluetooth LE peer is connected, then
+//! redraws only the fields that have changed (see `status_bar.rs`). The fields are drawn into a back buffer, and only
+//! the redrawn windows are flushed to the display. Nothing is drawn while the display is asleep.
+//! Apps draw below `status_bar::HEIGHT`. Apps that redraw the whole screen, like druid, call `redraw()` afterwards.
+//! The CHIP8 Emulator draws from its own task, so the status bar is drawn by that task with `update_pending()` instead
+//! of the callout, to keep the display commands of the two tasks apart.
+//! Call `start_status_bar()` after the app has drawn its first screen.
+use framebuffer::{
+    dirty::{ BackBuffer, DirtyRects, Rect },
+    WIDTH,
+};
+use mynewt::{
+    result::*,
+    hw::hal,
+    kernel::os::{
+        self,
+        os_event,
+    },
+    fill_zero,
+};
+use crate::{
+    power,
+    screen::Screen,
+    status_bar::{ self, Status, StatusBar },
+};
+
+/// Milliseconds between updates of the status bar
+const UPDATE_MS: u32 = 1000;
+
+/// GPIO pin that is low while the battery is charging: CHARGE INDICATION (P0.12)
+const CHARGE_INDICATION_PIN: i32 = 12;
+
+/// Status bar, with the values shown
+static mut STATUS_BAR: StatusBar = StatusBar::new();
+
+/// Number of pixels in the status bar
+const PIXEL_COUNT: usize = WIDTH as usize * status_bar::HEIGHT as usize;
+
+/// Back buffer with the pixels of the status bar, row by row: 240 * 24 * 2 = 11,520 bytes
+static mut PIXELS: [u16; PIXEL_COUNT] = [0; PIXEL_COUNT];
+
+/// Windows redrawn in `PIXELS` that have not been flushed to the display
+static mut DIRTY: DirtyRects = DirtyRects::new();
+
+/// Callout that updates the status bar
+static mut STATUS_CALLOUT: os::os_callout = fill_zero!(os::os_callout);
+
+/// True if the callout has fired but the status bar has not been updated by the app task
+static mut UPDATE_PENDING: bool = false;
+
+/// Draw the status bar and update it every second
+pub fn start_status_bar() -> MynewtResult<()> {
+    let rc = unsafe { hal::hal_gpio_init_in(CHARGE_INDICATION_PIN, hal::hal_gpio_pull_HAL_GPIO_PULL_NONE) };
+    if rc != 0 { return Err(MynewtError::from(rc)); }
+    let queue = os::eventq_dflt_get() ? ;
+    unsafe { os::os_callout_init(&mut STATUS_CALLOUT, queue, Some(status_callback), core::ptr::null_mut()) };
+    redraw() ? ;
+    schedule()
+}
+
+/// Draw the whole status bar again with the values shown, e.g. after the app has drawn over it. The values are
+/// read again only if the status bar has not been drawn yet.
+pub fn redraw() -> MynewtResult<()> {
+    let shown = unsafe { STATUS_BAR.shown() };
+    unsafe { STATUS_BAR.invalidate() };
+    match shown {
+        Some(status) => { draw(&status); Ok(()) }
+        None => update(),
+    }
+}
+
+/// Update the status bar if the callout has fired since the last call. Called by apps that draw from their own task.
+pub fn update_pending() -> MynewtResult<()> {
+    if !unsafe { UPDATE_PENDING } { return Ok(()); }
+    unsafe { UPDATE_PENDING = false };
+    update()
+}
+
+/// Redraw the fields of the status bar that have changed, unless the display is asleep. The ST7789 keeps its frame
+/// memory while asleep, so the status bar is brought up to date by the first update after wakeup.
+fn update() -> MynewtResult<()> {
+    if power::is_asleep() { return Ok(()); }
+    let status = read_status() ? ;
+    draw(&status);
+    Ok(())
+}
+
+/// Redraw the fields that have changed into the back buffer, then flush the redrawn windows to the display
+fn draw(status: &Status) {
+    let window = Rect::new(0, 0, WIDTH - 1, status_bar::HEIGHT - 1);
+    let mut back = BackBuffer::new(window, unsafe { &mut PIXELS });
+    unsafe {
+        STATUS_BAR.draw(&mut back, status, &mut DIRTY);
+        back.flush(&mut DIRTY, &mut Screen);
+    }
+}
+
+/// Return the time, battery and Bluetooth LE values to be shown
+fn read_status() -> MynewtResult<Status> {
+    //  Get the local time, if the time has been set, e.g. by the Current Time Service
+    let mut time = None;
+    if unsafe { os::os_time_is_set() } {
+        let mut utctime = os::os_timeval::default();
+        let mut tz      = os::os_timezone::default();
+        let rc = unsafe { os::os_gettimeofday(&mut utctime, &mut tz) };
+        if rc != 0 { return Err(MynewtError::from(rc)); }
+        time = Some(status_bar::local_time(utctime.tv_sec, tz.tz_minuteswest));
+    }
+    //  Get the battery level from the voltage, 0 if the voltage can't be read
+    let millivolts = unsafe { battery_millivolts() };
+    let battery = if millivolts > 0 { Some(status_bar::battery_level(millivolts as u16)) } else { None };
+    Ok(Status {
+        time,
+        battery,
+        charging:  unsafe { hal::hal_gpio_read(CHARGE_INDICATION_PIN) } == 0,
+        connected: unsafe { ble_is_connected() } != 0,
+    })
+}
+
+/// Fire the callout after `UPDATE_MS`
+fn schedule() -> MynewtResult<()> {
+    let mut ticks: os::os_time_t = 0;
+    let rc = unsafe { os::os_time_ms_to_ticks(UPDATE_MS, &mut ticks) };
+    if rc != 0 { return Err(MynewtError::from(rc)); }
+    let rc = unsafe { os::os_callout_reset(&mut STATUS_CALLOUT, ticks) };
+    if rc != 0 { return Err(MynewtError::from(rc)); }
+    Ok(())
+}
+
+/// Called by the callout every `UPDATE_MS`
+extern "C" fn status_callback(_event: *mut os_event) {
+    if cfg!(feature = "chip8_app") {
+        unsafe { UPDATE_PENDING = true };  //  Drawn by the CHIP8 task
+    } else {
+        update()
+            .expect("status fail");
+    }
+    schedule()
+        .expect("status schedule fail");
+}
+
+extern "C" {
+    /// Return the battery voltage in millivolts, or 0 if it can't be read. Defined in
+    /// `apps/my_sensor_app/src/battery.c`
+    fn battery_millivolts() -> i32;
+    /// Return 1 if a Bluetooth LE peer is connected, else 0. Defined in `apps/my_sensor_app/src/ble_main.c`
+    fn ble_is_connected() -> i32;
+}
@@ -0,0 +1,215 @@
+//! Status bar at the top of the screen, shared by all apps: the time, whether the Bluetooth LE peer is connected and
+//! the battery level. Only the fields whose values have changed are redrawn, and the redrawn windows are marked in
+//! `DirtyRects` for partial refresh. Drawn with `embedded-graphics` onto any `framebuffer::Display`: the PineTime
+//! display, or the in-memory framebuffer for testing on the host computer. The values are read by `status.rs`.
+use embedded_graphics::{
+    prelude::*,
+    fonts,
+    pixelcolor::Rgb565,
+    primitives::{
+        Line,
+        Rectangle,
+    },
+};
+use framebuffer::{
+    dirty::{ DirtyRects, Rect, Tracker },
+    Display, WIDTH,
+};
+
+/// Height of the status bar in pixels. Apps should draw below this row.
+pub const HEIGHT: u16 = 24;
+
+/// Values shown in the status bar
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Status {
+    /// Local time as (hour, minute), or `None` if the time has not been set
+    pub time:      Option<(u8, u8)>,
+    /// Battery level from 0 to 100 percent, or `None` if unknown
+    pub battery:   Option<u8>,
+    /// True if the battery is charging
+    pub charging:  bool,
+    /// True if the Bluetooth LE peer is connected
+    pub connected: bool,
+}
+
+/// Status bar that remembers the values shown, so that only the changed fields are redrawn
+pub struct StatusBar {
+    /// Values shown, or `None` if the status bar must be drawn in full
+    shown: Option<Status>,
+}
+
+/// Window of the time: 5 characters of `Font12x16`
+const CLOCK_RECT: Rect = Rect { left: 4, top: 4, right: 63, bottom: 19 };
+
+/// Window of the Bluetooth LE indicator: 3 characters of `Font8x16`
+const BLE_RECT: Rect = Rect { left: 132, top: 4, right: 155, bottom: 19 };
+
+/// Window of the battery level and icon
+const BATTERY_RECT: Rect = Rect { left: 164, top: 4, right: 235, bottom: 19 };
+
+/// Battery icon: outline from `BATTERY_ICON_LEFT` to the right of `BATTERY_RECT`, with the terminal nub at the right
+const BATTERY_ICON_LEFT: u16 = 210;
+
+/// Battery level at or below which the icon is red
+const BATTERY_LOW: u8 = 20;
+
+/// RGB565 colours
+const BACKGROUND:    u16 = 0x0000;  //  Black
+const FOREGROUND:    u16 = 0xffff;  //  White
+const SEPARATOR:     u16 = 0x4208;  //  Dark grey
+const CONNECTED:     u16 = 0x041f;  //  Blue
+const DISCONNECTED:  u16 = 0x4208;  //  Dark grey
+const BATTERY_FULL:  u16 = 0x07e0;  //  Green
+const BATTERY_EMPTY: u16 = 0xf800;  //  Red
+const CHARGING:      u16 = 0xffe0;  //  Yellow
+
+/// Battery voltage in millivolts for each battery level in percent, for a LiPo battery discharged at low current
+const DISCHARGE_CURVE: [(u16, u8); 10] = [
+    (3300, 0), (3500, 5), (3600, 10), (3700, 25), (3750, 40),
+    (3800, 55), (3900, 70), (4000, 82), (4100, 92), (4200, 100),
+];
+
+impl StatusBar {
+    /// Return a status bar that will be drawn in full on the next `draw()`
+    pub const fn new() -> StatusBar {
+        StatusBar { shown: None }
+    }
+
+    /// Return the values shown, or `None` if the status bar will be drawn in full on the next `draw()`
+    pub fn shown(&self) -> Option<Status> {
+        self.shown
+    }
+
+    /// Draw the status bar in full on the next `draw()`, e.g. after the app has drawn over it
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+
+    /// Redraw the fields whose values have changed since the last call, and mark the redrawn windows in `dirty`.
+    /// Returns true if anything was drawn.
+    pub fn draw<D: Display>(&mut self, display: &mut D, status: &Status, dirty: &mut DirtyRects) -> bool {
+        if self.shown.as_ref() == Some(status) { return false; }
+        let mut display = Tracker::new(display, dirty);
+        let full = self.shown.is_none();
+        let shown = self.shown.unwrap_or(*status);
+        if full {
+            fill(&mut display, Rect::new(0, 0, WIDTH - 1, HEIGHT - 2), BACKGROUND);
+            display.draw(Line::<Rgb565>
+                ::new(Coord::new(0, HEIGHT as i32 - 1), Coord::new(WIDTH as i32 - 1, HEIGHT as i32 - 1))
+                .stroke(Some(Rgb565(SEPARATOR))));
+        }
+        if full || shown.time != status.time {
+            draw_clock(&mut display, status.time);
+        }
+        if full || shown.connected != status.connected {
+            draw_ble(&mut display, status.connected);
+        }
+        if full || shown.battery != status.battery || shown.charging != status.charging {
+            draw_battery(&mut display, status.battery, status.charging);
+        }
+        self.shown = Some(*status);
+        true
+    }
+}
+
+impl Default for StatusBar {
+    fn default() -> StatusBar {
+        StatusBar::new()
+    }
+}
+
+/// Return the local time as (hour, minute) for the seconds since 1970, like `os_gettimeofday()`. `minutes_west` is
+/// the time zone in minutes west of UTC.
+pub fn local_time(seconds: i64, minutes_west: i16) -> (u8, u8) {
+    let seconds_of_day = (seconds - minutes_west as i64 * 60).rem_euclid(24 * 60 * 60);
+    ((seconds_of_day / 3600) as u8, (seconds_of_day / 60 % 60) as u8)
+}
+
+/// Return the battery level in percent for the battery voltage in millivolts, interpolated on the discharge curve
+pub fn battery_level(millivolts: u16) -> u8 {
+    let (first_mv, first_level) = DISCHARGE_CURVE[0];
+    if millivolts <= first_mv { return first_level; }
+    let mut previous = (first_mv, first_level);
+    for &(mv, level) in DISCHARGE_CURVE.iter().skip(1) {
+        if millivolts <= mv {
+            let (previous_mv, previous_level) = previous;
+            let range = (level - previous_level) as u32 * (millivolts - previous_mv) as u32;
+            return previous_level + (range / (mv - previous_mv) as u32) as u8;
+        }
+        previous = (mv, level);
+    }
+    100
+}
+
+/// Draw the time as "HH:MM", or "--:--" if the time has not been set
+fn draw_clock<D: Display>(display: &mut D, time: Option<(u8, u8)>) {
+    let mut text = *b"--:--";
+    if let Some((hour, minute)) = time {
+        text = [b'0' + hour / 10, b'0' + hour % 10, b':', b'0' + minute / 10, b'0' + minute % 10];
+    }
+    let text = core::str::from_utf8(&text).unwrap_or("--:--");
+    display.draw(fonts::Font12x16::<Rgb565>
+        ::render_str(text)
+        .stroke(Some(Rgb565(FOREGROUND)))
+        .fill(Some(Rgb565(BACKGROUND)))
+        .translate(Coord::new(CLOCK_RECT.left as i32, CLOCK_RECT.top as i32)));
+}
+
+/// Draw "BLE" in blue if the peer is connected, else in grey
+fn draw_ble<D: Display>(display: &mut D, connected: bool) {
+    let color = if connected { CONNECTED } else { DISCONNECTED };
+    display.draw(fonts::Font8x16::<Rgb565>
+        ::render_str("BLE")
+        .stroke(Some(Rgb565(color)))
+        .fill(Some(Rgb565(BACKGROUND)))
+        .translate(Coord::new(BLE_RECT.left as i32, BLE_RECT.top as i32)));
+}
+
+/// Draw the battery level as text right-aligned before the icon, and the icon filled to the level. The icon is
+/// yellow when charging, red when low, else green.
+fn draw_battery<D: Display>(display: &mut D, battery: Option<u8>, charging: bool) {
+    fill(display, BATTERY_RECT, BACKGROUND);
+
+    //  Draw the level as "100%", or "?" if unknown
+    let mut buffer = [0u8; 4];
+    let text = match battery {
+        Some(level) => {
+            let level = level.min(100);
+            let mut len = 0;
+            if level >= 100 { buffer[len] = b'1'; len += 1; }
+            if level >= 10  { buffer[len] = b'0' + level / 10 % 10; len += 1; }
+            buffer[len] = b'0' + level % 10;
+            buffer[len + 1] = b'%';
+            core::str::from_utf8(&buffer[..len + 2]).unwrap_or("?")
+        }
+        None => "?",
+    };
+    let text_left = BATTERY_ICON_LEFT as i32 - 4 - text.len() as i32 * 8;
+    display.draw(fonts::Font8x16::<Rgb565>
+        ::render_str(text)
+        .stroke(Some(Rgb565(FOREGROUND)))
+        .fill(Some(Rgb565(BACKGROUND)))
+        .translate(Coord::new(text_left, BATTERY_RECT.top as i32)));
+
+    //  Draw the outline and the nub of the icon
+    let (left, right) = (BATTERY_ICON_LEFT, BATTERY_RECT.right - 2);
+    let (top, bottom) = (BATTERY_RECT.top + 2, BATTERY_RECT.bottom - 2);
+    display.draw(Rectangle::<Rgb565>
+        ::new(Coord::new(left as i32, top as i32), Coord::new(right as i32, bottom as i32))
+        .stroke(Some(Rgb565(FOREGROUND))));
+    fill(display, Rect::new(right + 1, top + 3, BATTERY_RECT.right, bottom - 3), FOREGROUND);
+
+    //  Fill the inside of the icon to the level, leaving a gap of 1 pixel inside the outline
+    let level = match battery { Some(level) => level.min(100) as u16, None => return };
+    let inside_width = right - left - 3;
+    let width = (inside_width * level + 50) / 100;
+    if width == 0 { return; }
+    let color = if charging { CHARGING } else if level <= BATTERY_LOW as u16 { BATTERY_EMPTY } else { BATTERY_FULL };
+    fill(display, Rect::new(left + 2, top + 2, left + 1 + width, bottom - 2), color);
+}
+
+/// Fill the window with the colour
+fn fill<D: Display>(display: &mut D, rect: Rect, color: u16) {
+    let count = (rect.right - rect.left + 1) as usize * (rect.bottom - rect.top + 1) as usize;
+    display.set_pixels(rect.left, rect.top, rect.right, rect.bottom, &mut (0..count).map(|_| color));
+}
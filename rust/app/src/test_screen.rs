@@ -0,0 +1,48 @@
+//! Test screen for the graphics display app, drawn with `embedded-graphics` primitives onto any `framebuffer::Display`:
+//! the PineTime display, or the in-memory framebuffer for testing on the host computer.
+use embedded_graphics::{
+    prelude::*,
+    fonts,
+    pixelcolor::Rgb565,
+    primitives::{
+        Circle,
+        Rectangle,
+    },
+};
+use framebuffer::Display;
+
+/// Draw the black background, magenta circle and blue square
+pub fn draw_shapes<D: Display>(display: &mut D) {
+    //  Create black background
+    let background = Rectangle::<Rgb565>
+        ::new( Coord::new( 0, 0 ), Coord::new( 239, 239 ) )   //  Rectangle coordinates
+        .fill( Some( Rgb565::from(( 0x00, 0x00, 0x00 )) ) );  //  Black
+
+    //  Create circle
+    let circle = Circle::<Rgb565>
+        ::new( Coord::new( 40, 40 ), 40 )                     //  Circle centre and radius
+        .fill( Some( Rgb565::from(( 0xff, 0x00, 0xff )) ) );  //  Magenta
+
+    //  Create square
+    let square = Rectangle::<Rgb565>
+        ::new( Coord::new( 60, 60 ), Coord::new( 150, 150 ) ) //  Square coordinates
+        .fill( Some( Rgb565::from(( 0x00, 0x00, 0xff )) ) );  //  Blue
+
+    //  Render background, circle and square to display
+    display.draw(background);
+    display.draw(circle);
+    display.draw(square);
+}
+
+/// Draw the "Boot Logo Updated" text
+pub fn draw_text<D: Display>(display: &mut D) {
+    //  Create text
+    let text = fonts::Font12x16::<Rgb565>
+        ::render_str(" Boot Logo Updated ")                    //  Text to be rendered
+        .stroke( Some( Rgb565::from(( 0x00, 0x00, 0x00 )) ) )  //  Black text
+        .fill(   Some( Rgb565::from(( 0xff, 0xff, 0x00 )) ) )  //  Yellow background
+        .translate( Coord::new( 20, 32 ));                     //  Shift the text below the status bar
+
+    //  Render text to display
+    display.draw(text);
+}
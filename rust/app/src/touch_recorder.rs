@@ -0,0 +1,86 @@
+//! Touch recorder. Records the raw touch reports read from the touch controller, with their times, in Internal Flash
+//! ROM in the compact format of `touch::record`. Writing to the console while touching would change the timing of
+//! the touch events, so the recording is dumped to the console at the next startup, one record per line:
+//! `rec 1a7b0100...`. Copy the lines into `rust/touch/tests/recordings` to replay them with `cargo test`.
+use mynewt::{
+    result::*,
+    hw::hal,
+    sys::console,
+};
+use touch::{
+    cst816s::REPORT_LEN,
+    record::{ self, Recorder, MAX_RECORD_LEN },
+};
+
+/// Flash Device for the recording: 0 for Internal Flash ROM
+const FLASH_DEVICE: u8 = 0;
+
+/// Offset of the recording in Internal Flash ROM. Must sync with `FLASH_AREA_TOUCH_RECORDING` in
+/// hw/bsp/nrf52/bsp.yml
+const FLASH_OFFSET: u32 = 0x7e000;
+
+/// Size of the recording area, up to the end of Internal Flash ROM
+const FLASH_SIZE: u32 = 8 * 1024;
+
+/// Encodes the touch reports into records
+static mut RECORDER: Recorder = Recorder::new();
+
+/// Offset of the next record from the start of the recording, or `None` if the recording area is full
+static mut NEXT_OFFSET: Option<u32> = None;
+
+/// Dump the previous recording to the console, then erase it and start recording
+pub fn start_recorder() -> MynewtResult<()> {
+    dump_recording() ? ;
+    let rc = unsafe { hal::hal_flash_erase(FLASH_DEVICE, FLASH_OFFSET, FLASH_SIZE) };
+    if rc != 0 { return Err(MynewtError::from(rc)); }
+    unsafe { NEXT_OFFSET = Some(0) };
+    console::print("touch recording\n"); console::flush();
+    Ok(())
+}
+
+/// Record the report read at the time in milliseconds, or `None` if the touch controller didn't respond. Recording
+/// stops when the recording area is full.
+pub fn record(time: u32, report: Option<&[u8; REPORT_LEN]>) -> MynewtResult<()> {
+    let offset = match unsafe { NEXT_OFFSET } {
+        Some(offset) => offset,
+        None => return Ok(()),
+    };
+    let mut buf = [0; MAX_RECORD_LEN];
+    let bytes = unsafe { RECORDER.record(time, report, &mut buf) };
+    //  Leave the last byte erased to end the recording
+    if offset + bytes.len() as u32 >= FLASH_SIZE {
+        unsafe { NEXT_OFFSET = None };
+        console::print("touch recording full\n"); console::flush();
+        return Ok(());
+    }
+    let rc = unsafe {
+        hal::hal_flash_write(FLASH_DEVICE, FLASH_OFFSET + offset, bytes.as_ptr() as *const _, bytes.len() as u32)
+    };
+    if rc != 0 { return Err(MynewtError::from(rc)); }
+    unsafe { NEXT_OFFSET = Some(offset + bytes.len() as u32) };
+    Ok(())
+}
+
+/// Dump the recording in flash to the console, one record per line in hex
+fn dump_recording() -> MynewtResult<()> {
+    let mut offset = 0;
+    let mut buf = [0; MAX_RECORD_LEN];
+    while offset < FLASH_SIZE {
+        let len = core::cmp::min(MAX_RECORD_LEN as u32, FLASH_SIZE - offset) as usize;
+        let rc = unsafe {
+            hal::hal_flash_read(FLASH_DEVICE, FLASH_OFFSET + offset, buf.as_mut_ptr() as *mut _, len as u32)
+        };
+        if rc != 0 { return Err(MynewtError::from(rc)); }
+        let len = match record::record_len(&buf[..len]) {
+            Ok(Some(len)) => len,
+            Ok(None) => break,
+            Err(_) => { console::print("touch recording corrupted\n"); break; }
+        };
+        console::print("rec ");
+        for byte in &buf[..len] { console::printhex(*byte); }
+        console::print("\n"); console::flush();
+        offset += len as u32;
+    }
+    console::flush();
+    Ok(())
+}
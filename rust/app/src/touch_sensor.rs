@@ -1,6 +1,9 @@
 use embedded_hal::{
     self,
-    blocking::delay::DelayMs,
+    blocking::{
+        delay::DelayMs,
+        i2c::WriteRead,
+    },
     digital::v2::OutputPin,
 };
 use mynewt::{
@@ -14,6 +17,12 @@ use mynewt::{
     sys::console,
     fill_zero,
 };
+use touch::{
+    cst816s::{ Cst816s, REPORT_LEN },
+    event::{ Subscriber, SubscriberId, Subscribers, TouchEvent, Tracker },
+    gesture::{ Config, Direction, Gesture, Recognizer },
+};
+use crate::calibration;
 
 /// Reset Pin for touch controller. Note: NFC antenna pins must be reassigned as GPIO pins for this to work.
 const TOUCH_RESET_PIN: i32 = 10;  //  P0.10/NFC2: TP_RESET
@@ -21,11 +30,21 @@ const TOUCH_RESET_PIN: i32 = 10;  //  P0.10/NFC2: TP_RESET
 /// Interrupt Pin for touch controller. We listen for the touch controller interrupt and trigger an event.
 const TOUCH_INTERRUPT_PIN: i32 = 28;  //  P0.28/AIN4: TP_INT
 
+/// I2C port of the touch controller
+const TOUCH_I2C_PORT: u8 = 1;
+
+/// Timeout for I2C operations in ticks
+const I2C_TIMEOUT: u32 = 1000;
+
 /// Reset GPIO Pin
 static mut TOUCH_RESET: MynewtGPIO =  fill_zero!(MynewtGPIO);
 static mut TOUCH_DELAY: MynewtDelay = fill_zero!(MynewtDelay);
 type MynewtGPIO = mynewt::GPIO;
 type MynewtDelay = mynewt::Delay;
+type MynewtI2C = mynewt::I2C;
+
+/// CST816S touch controller on the I2C port, which has been configured and enabled by the BSP
+static mut TOUCH_CONTROLLER: Cst816s<MynewtI2C> = Cst816s::new(MynewtI2C::from_port(TOUCH_I2C_PORT, I2C_TIMEOUT));
 
 /// Initialise the touch controller. NFC antenna pins must already be reassigned as GPIO pins:
 /// Set `NFC_PINS_AS_GPIO: 1` in hw/bsp/nrf52/syscfg.yml.  To check whether whether NFC antenna 
@@ -49,9 +68,23 @@ pub fn start_touch_sensor() -> MynewtResult<()> {
         TOUCH_DELAY.delay_ms(200); TOUCH_DELAY.delay_ms(200);    
     };
 
+    //  Show the chip ID and firmware version. The touch controller may not respond until the screen is touched.
+    match unsafe { (TOUCH_CONTROLLER.chip_id(), TOUCH_CONTROLLER.firmware_version()) } {
+        (Ok(chip_id), Ok(version)) => {
+            console::print("touch chip 0x"); console::printhex(chip_id);
+            console::print(", fw 0x"); console::printhex(version); console::print("\n");
+        }
+        _ => console::print("touch chip not responding\n"),
+    }
+
     //  Initialise the touch event with the callback function
     unsafe { TOUCH_EVENT.ev_cb = Some( touch_event_callback ) };
 
+    //  Initialise the callout that completes gestures after their timeouts, e.g. long press
+    let queue = os::eventq_dflt_get() ? ;
+    unsafe { os::os_callout_init(&mut GESTURE_CALLOUT, queue, Some(gesture_callback), core::ptr::null_mut()) };
+    subscribe(track_gesture) ? ;
+
     //  Configure the touch controller interrupt (active when low) to trigger a touch event
     let rc = unsafe { hal::hal_gpio_irq_init(
         TOUCH_INTERRUPT_PIN,              //  GPIO pin to be configured
@@ -82,45 +115,40 @@ extern "C" fn touch_interrupt_handler(arg: *mut core::ffi::c_void) {
 extern "C" fn touch_event_callback(_event: *mut os_event) {
     //  console::printhex(unsafe { os::os_time_get() } as u8); console::print(" touch\n");
     unsafe { 
-        //  Fetch the touch data from the touch controller
-        read_touchdata(&mut TOUCH_DATA)
-            .expect("touchdata fail");
-        //  Handle each touch data info
-        for i in 0..TOUCH_DATA.count as usize {
-            let TouchInfo{ x, y, action, .. } = TOUCH_DATA.touches[i];
-            //  Skip invalid responses (see note below)
-            if x == 0 && y == 0 { continue; }
-            //  Handle only touch down and contact actions, not touch up (see note below)
-            if action != 0 && action != 2 { continue; }
-            //  Handle the touch data in the UI        
-            super::handle_touch(x, y);
-
-            //  Display the touch data
-            //  druid::show_touch(x, y).expect("show touch fail");
-
-            /* Usually we get responses like:
-            touch
-            count: 1, pt: 0
-            act: 0, fin 0, x: 124, y: 190
-
-            Need to handle unusual responses like: 
-            touch
-            count: 5, pt: 1
-            act: 2, fin 0, x: 166, y: 62
-            act: 0, fin 0, x: 0, y: 0
-            act: 0, fin 0, x: 0, y: 0
-            act: 0, fin 0, x: 0, y: 0
-            act: 0, fin 0, x: 0, y: 0 */
-        }    
+        //  Fetch the touch report from the touch controller
+        let mut buf = [0; REPORT_LEN];
+        let report = TOUCH_CONTROLLER.read_report(&mut buf);
+        let time = now_ms();
+        //  Record the report, to be replayed by the tests on the host computer
+        #[cfg(feature = "record_touch")]  //  If touch recording is enabled...
+        crate::touch_recorder::record(time, report.as_ref().ok().map(|_| &buf))
+            .expect("touch record fail");
+        //  Restart the inactivity timeout. If the display was asleep, the touch only wakes the display.
+        let was_asleep = crate::power::wake()
+            .expect("wake fail");
+        if was_asleep { TRACKER = Tracker::new(); GESTURES.reset(); return; }
+        //  Skip the report if the touch controller didn't respond
+        let report = match report {
+            Ok(report) => report,
+            Err(_) => { console::print("touch read fail\n"); console::flush(); return; }
+        };
+        //  Pass the Down, Move or Up event for each touch point to the subscribers. Fingers that are no longer
+        //  reported have left the screen. The touch points are calibrated to the panel and mapped to the rotated,
+        //  mirrored and scrolled drawing, except for the calibration screen, which needs the touches as reported.
+        //  Empty touch points at (0, 0) are skipped, e.g. in a "count: 5, pt: 1" report: see the synthetic fixture
+        //  rust/touch/tests/recordings/count5_pt1.txt
+        TRACKER.report(&report, time, map_touch, &mut |event| publish(&event));
     }
+    schedule_gesture_tick()
+        .expect("gesture schedule fail");
     /* unsafe {
         //  Disable the console output because it may interfere with touch events
-        console::print("count: "); console::printint(TOUCH_DATA.count as i32);
-        console::print(", pt: "); console::printint(TOUCH_DATA.point_num as i32); console::print("\n");
-        for i in 0..TOUCH_DATA.count as usize {
-            let TouchInfo{ x, y, action, finger, .. } = TOUCH_DATA.touches[i];
+        console::print("count: "); console::printint(report.count as i32);
+        console::print(", pt: "); console::printint(report.point_num as i32); console::print("\n");
+        for point in report.points() {
+            let ReportPoint{ x, y, action, id, .. } = *point;
             console::print("act: "); console::printint(action as i32); 
-            console::print(", fin "); console::printint(finger as i32); 
+            console::print(", fin "); console::printint(id as i32); 
             console::print(", x: "); console::printint(x as i32); 
             console::print(", y: "); console::printint(y as i32); console::print("\n");
         }
@@ -128,195 +156,119 @@ extern "C" fn touch_event_callback(_event: *mut os_event) {
     } */
 }
 
-/// Touch data will be populated here
-static mut TOUCH_DATA: TouchEventInfo = fill_zero!(TouchEventInfo);
-
-/// Read touch controller data. This only works when the screen has been tapped and the touch controller wakes up.
-/// Ported from https://github.com/lupyuen/hynitron_i2c_cst0xxse/blob/master/cst0xx_core.c#L407-L466
-fn read_touchdata(data: &mut TouchEventInfo) -> MynewtResult<()> {
-    read_register_range(           //  Read the range of I2C registers...
-        TOUCH_CONTROLLER_ADDRESS,  //  From the touch controller
-        0,                         //  Starting from register 0
-        POINT_READ_BUF as u8,      //  Number of registers to read
-        unsafe { &mut BUF }        //  Save the read data into `buf`
-    ).expect("read touchdata fail");
-    *data = fill_zero!(TouchEventInfo);
-    data.point_num = unsafe { BUF[FT_TOUCH_POINT_NUM] & 0x0F };
-    data.count     = 0;
-
-    //  Populate the first 5 touch points
-    for i in 0..CFG_MAX_TOUCH_POINTS {
-        let pointid = unsafe { BUF[HYN_TOUCH_ID_POS + HYN_TOUCH_STEP * i] } >> 4;
-        if pointid >= HYN_MAX_ID { break; }
-
-        //  Compute X and Y coordinates
-        data.count += 1;
-        let x_high = unsafe { BUF[HYN_TOUCH_X_H_POS + HYN_TOUCH_STEP * i] & 0x0F } as u16;
-        let x_low  = unsafe { BUF[HYN_TOUCH_X_L_POS + HYN_TOUCH_STEP * i] } as u16;
-        data.touches[i].x  = (x_high << 8) | x_low;
-
-        let y_high = unsafe { BUF[HYN_TOUCH_Y_H_POS + HYN_TOUCH_STEP * i] & 0x0F } as u16;
-        let y_low  = unsafe { BUF[HYN_TOUCH_Y_L_POS + HYN_TOUCH_STEP * i] } as u16;
-        data.touches[i].y  = (y_high << 8) | y_low;
-
-        //  Compute touch action (0 = down, 1 = up, 2 = contact) and finger ID
-        data.touches[i].action =
-            unsafe { BUF[HYN_TOUCH_EVENT_POS + HYN_TOUCH_STEP * i] } >> 6;
-        data.touches[i].finger =
-            unsafe { BUF[HYN_TOUCH_ID_POS    + HYN_TOUCH_STEP * i] } >> 4;
-
-        //  Compute touch pressure and area
-        data.touches[i].pressure =
-            unsafe { BUF[HYN_TOUCH_XY_POS + HYN_TOUCH_STEP * i] };  //  Can't be constant value
-        data.touches[i].area =
-            unsafe { BUF[HYN_TOUCH_MISC   + HYN_TOUCH_STEP * i] } >> 4;
-
-        //  If no more touch points, stop
-        if (data.touches[i].action == 0 || data.touches[i].action == 2)  //  If touch is down or contact
-            && (data.point_num == 0) {
-            break;
-        }
-    }
-    Ok(())
-}
+/// Fingers on the screen, for turning the touch data into touch events
+static mut TRACKER: Tracker = Tracker::new();
 
-/// Buffer for raw touch data
-static mut BUF: [u8; POINT_READ_BUF] = [0; POINT_READ_BUF];
+/// Components that receive the touch events
+static mut SUBSCRIBERS: Subscribers = Subscribers::new();
 
-/// Touch Controller I2C Address: https://github.com/lupyuen/hynitron_i2c_cst0xxse
-const TOUCH_CONTROLLER_ADDRESS: u8 = 0x15;
+/// Call the function for every touch event, e.g. the touch handler of an app. The events are passed to all the
+/// functions subscribed, in the Default Event Queue.
+pub fn subscribe(subscriber: Subscriber) -> MynewtResult<SubscriberId> {
+    unsafe { SUBSCRIBERS.subscribe(subscriber) }
+        .map_err(|_| MynewtError::SYS_ENOMEM)
+}
 
-/// Touch Event Info for multiple touches. Based on https://github.com/lupyuen/hynitron_i2c_cst0xxse/blob/master/cst0xx_core.h#L104-L115
-struct TouchEventInfo {
-    /// Array of touch points
-    touches:   [TouchInfo; HYN_MAX_POINTS],
-    /// How many touch points
-    count:     u8,
-    point_num: u8,
+/// Map the touch on the panel to the drawing, with the calibration, unless the calibration screen is shown
+fn map_touch(x: u16, y: u16) -> (u16, u16) {
+    if calibration::is_calibrating() { return (x, y); }
+    let (x, y) = calibration::calibrate(x, y);
+    crate::screen::to_drawing(x, y)
 }
 
-/// Touch Info for a single touch. Based on https://github.com/lupyuen/hynitron_i2c_cst0xxse/blob/master/cst0xx_core.h#L104-L115
-struct TouchInfo {
-    /// X coordinate
-    x:          u16,
-    /// Y coordinate
-    y:          u16,
-    /// Action: 0 = down, 1 = up, 2 = contact
-    action:     u8,
-    /// Which finger touched
-    finger:     u8,         
-    /// Pressure of touch
-    pressure:   u8,
-    /// Area touched
-    area:       u8,
+/// Pass the touch event to the subscribers, or to the calibration screen while it's shown
+fn publish(event: &TouchEvent) {
+    if calibration::is_calibrating() { calibration::handle_touch(event); }
+    else { unsafe { SUBSCRIBERS.publish(event) }; }
 }
 
-//  Touch Controller Constants. Based on https://github.com/lupyuen/hynitron_i2c_cst0xxse/blob/master/cst0xx_core.h
+/// Stop calling the function subscribed with the handle
+#[allow(dead_code)]
+pub fn unsubscribe(id: SubscriberId) {
+    unsafe { SUBSCRIBERS.unsubscribe(id) };
+}
 
-/// Max touch points for the touch controller
-const CFG_MAX_TOUCH_POINTS: usize = 5;
+/// Gesture recogniser for the first finger
+static mut GESTURES: Recognizer = Recognizer::new(Config::DEFAULT);
 
-/// Max touch channels for the touch controller
-const HYN_MAX_POINTS: usize = 10;
+/// Subscriber that tracks the first finger for gestures
+fn track_gesture(event: &TouchEvent) {
+    unsafe { GESTURES.event(event, &mut handle_gesture) };
+}
 
-const HYN_MAX_ID: u8             = 0x0F;
-const HYN_TOUCH_STEP: usize      = 6;
-// const HYN_FACE_DETECT_POS: usize = 1;
-const HYN_TOUCH_X_H_POS: usize   = 3;
-const HYN_TOUCH_X_L_POS: usize   = 4;
-const HYN_TOUCH_Y_H_POS: usize   = 5;
-const HYN_TOUCH_Y_L_POS: usize   = 6;
-const HYN_TOUCH_EVENT_POS: usize = 3;
-const HYN_TOUCH_ID_POS: usize    = 5;
-const FT_TOUCH_POINT_NUM: usize  = 2;
-const HYN_TOUCH_XY_POS: usize    = 7;
-const HYN_TOUCH_MISC: usize      = 8;
-const POINT_READ_BUF: usize      = 3 + ( HYN_TOUCH_STEP * HYN_MAX_POINTS );
+/// Callout that calls the gesture recogniser at its next deadline
+static mut GESTURE_CALLOUT: os::os_callout = fill_zero!(os::os_callout);
 
-/// Event that will be forwarded to the Event Queue when a touch interrupt is triggered
-static mut TOUCH_EVENT: os_event = fill_zero!(os_event);  //  Init all fields to 0 or NULL
+/// Called by the callout at the deadline of the gesture recogniser, e.g. to recognise a long press
+extern "C" fn gesture_callback(_event: *mut os_event) {
+    unsafe { GESTURES.tick(now_ms(), &mut handle_gesture) };
+    schedule_gesture_tick()
+        .expect("gesture schedule fail");
+}
 
-/// Read a range of I2C registers from the I2C address `addr` (7-bit address), starting at `start_register` for count `num_registers`. Save into `buffer`.
-fn read_register_range(addr: u8, start_register: u8, num_registers: u8, buffer: &mut[u8]) -> MynewtResult<()> {
-    assert!(buffer.len() >= num_registers as usize, "i2c buf");  //  Buffer too small
-    assert!(start_register + num_registers < 128, "i2c addr");   //  Not 7-bit address
-    //  Step 1: Prepare to read I2C Device Registers:
-    //    System sends Clock Signal on SCL to sync Microcontroller with I2C Device
-    //  Step 2: Transmit the I2C Address and the starting Register Number:
-    //    Send the Start Condition (High to Low SDA Transition)...
-    //    Followed by I2C Address (7 bits)...
-    //    Followed by Write Mode (1 bit, value 0)...
-    //    Followed by starting Register Number (8 bits)
-    unsafe { 
-        I2C_BUFFER[0] = start_register;  //  I2C Packet buffer contains starting Register Number (1 byte)
-        I2C_DATA.address = addr;         //  I2C Packet address (7 bits)
-        I2C_DATA.len = I2C_BUFFER.len() as u16;     //  I2C Packet data size is 1 byte
-        I2C_DATA.buffer = I2C_BUFFER.as_mut_ptr();  //  I2C Packet data points to packet buffer
-    };
-    let _rc1 = unsafe { hal::hal_i2c_master_write(1, &mut I2C_DATA, 1000, 0) };  //  No stop yet, must continue even if we hit an error
-    //  Step 3: Prepare to receive the stream of I2C Device Register values...
-    //    Send the Start Condition (High to Low SDA Transition)...
-    //    Followed by I2C Address (7 bits)...
-    //    Followed by Read Mode (1 bit, value 1)
-    unsafe { 
-        I2C_BUFFER[0] = 0x00;     //  I2C Packet buffer should be empty (provided by caller)
-        I2C_DATA.address = addr;  //  I2C Packet address (7 bits)
-        I2C_DATA.len = num_registers as u16;    //  I2C Packet data size is number of Registers to read
-        I2C_DATA.buffer = buffer.as_mut_ptr();  //  I2C Packet data points to packet buffer
+/// Fire the callout at the next deadline of the gesture recogniser, if any
+fn schedule_gesture_tick() -> MynewtResult<()> {
+    let deadline = match unsafe { GESTURES.deadline() } {
+        Some(deadline) => deadline,
+        None => { unsafe { os::os_callout_stop(&mut GESTURE_CALLOUT) }; return Ok(()); }
     };
-    //  Step 4: Receive the requested number of Register values from I2C Device (1 byte per register)
-    //  Step 5: Send the Stop Condition (Low to High SDA Transition)
-    let rc2 = unsafe { hal::hal_i2c_master_read(1, &mut I2C_DATA, 1000, 1) };
-    if rc2 == hal::HAL_I2C_ERR_ADDR_NACK as i32 {
-        ////assert!(false, "i2c fail");  //  I2C read failed
-        console::print("i2c fail\n"); ////
-        return Ok(());               //  TODO: Return an error
-    }
+    //  Deadline may have passed already, comparing the times relative to now
+    let delay_ms = deadline.wrapping_sub(now_ms());
+    let delay_ms = if delay_ms > u32::max_value() / 2 { 0 } else { delay_ms };
+    let mut ticks: os::os_time_t = 0;
+    let rc = unsafe { os::os_time_ms_to_ticks(delay_ms, &mut ticks) };
+    if rc != 0 { return Err(MynewtError::from(rc)); }
+    let rc = unsafe { os::os_callout_reset(&mut GESTURE_CALLOUT, ticks) };
+    if rc != 0 { return Err(MynewtError::from(rc)); }
     Ok(())
 }
 
+/// Handle a gesture recognised from the touches. For now the gesture is only shown on the console.
+fn handle_gesture(gesture: Gesture) {
+    let (name, x, y) = match gesture {
+        Gesture::Tap { x, y }       => ("tap", x, y),
+        Gesture::DoubleTap { x, y } => ("double tap", x, y),
+        Gesture::LongPress { x, y } => ("long press", x, y),
+        Gesture::Swipe { direction, x, y, .. } => (
+            match direction {
+                Direction::Up    => "swipe up",
+                Direction::Down  => "swipe down",
+                Direction::Left  => "swipe left",
+                Direction::Right => "swipe right",
+            }, x, y
+        ),
+    };
+    console::print(name);
+    console::print(" at "); console::printint(x as i32);
+    console::print(", "); console::printint(y as i32); console::print("\n");
+    console::flush();
+}
+
+/// Return the milliseconds since startup, wrapping around after 49 days, for the gesture recogniser
+fn now_ms() -> u32 {
+    (unsafe { os::os_get_uptime_usec() } / 1000) as u32
+}
+
+/// Event that will be forwarded to the Event Queue when a touch interrupt is triggered
+static mut TOUCH_EVENT: os_event = fill_zero!(os_event);  //  Init all fields to 0 or NULL
+
 /// Read the I2C register for the specified I2C address (7-bit address)
 #[allow(dead_code)]
 fn read_register(addr: u8, register: u8) -> MynewtResult<()> {
     assert!(register < 128, "i2c addr");  //  Not 7-bit address
-    //  First the register number must be sent in write mode (I2C address xxxxxxx0). 
-    unsafe { 
-        I2C_BUFFER[0] = register;
-        I2C_DATA.address = addr;
-        I2C_DATA.len = I2C_BUFFER.len() as u16;
-        I2C_DATA.buffer = I2C_BUFFER.as_mut_ptr();
-    };
-    //  Then either a stop or a repeated start condition must be generated. 
-    let _rc1 = unsafe { hal::hal_i2c_master_write(1, &mut I2C_DATA, 1000, 0) };  //  No stop yet, must continue even if we hit an error
-    //  After this the slave is addressed in read mode (RW = ‘1’) at I2C address xxxxxxx1, 
-    unsafe { 
-        I2C_BUFFER[0] = 0x00;
-        I2C_DATA.address = addr;
-        I2C_DATA.len = I2C_BUFFER.len() as u16;
-        I2C_DATA.buffer = I2C_BUFFER.as_mut_ptr();
-    };
-    //  After which the slave sends out data from auto-incremented register addresses until a NOACKM and stop condition occurs.
-    let rc2 = unsafe { hal::hal_i2c_master_read(1, &mut I2C_DATA, 1000, 1) };
-    if rc2 == hal::HAL_I2C_ERR_ADDR_NACK as i32 {
+    //  Send the register number in write mode, then read the value in read mode
+    let mut i2c = MynewtI2C::from_port(TOUCH_I2C_PORT, I2C_TIMEOUT);
+    let mut value = [0];
+    if i2c.write_read(addr, &[register], &mut value).is_err() {
         return Ok(());
     }
     console::print("addr: 0x"); console::printhex(addr); 
     console::print(", reg: 0x"); console::printhex(register); 
-    console::print(" = 0x"); console::printhex(unsafe { I2C_BUFFER[0] }); 
+    console::print(" = 0x"); console::printhex(value[0]); 
     console::print("\n"); console::flush();
     Ok(())
 }
 
-/// I2C packet to be sent
-static mut I2C_DATA: hal::hal_i2c_master_data = hal::hal_i2c_master_data {
-    address: 0,
-    len:     0,
-    buffer:  core::ptr::null_mut(),
-};
-
-/// Buffer containing I2C read/write data
-static mut I2C_BUFFER: [u8; 1] =  [ 0 ];
-
 /// Probe the I2C bus to discover I2C devices
 #[allow(dead_code)]
 pub fn probe() -> MynewtResult<()> {
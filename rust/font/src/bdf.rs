@@ -0,0 +1,152 @@
+//! Converter from fonts in the Glyph Bitmap Distribution Format (BDF) of X11, e.g. the GNU Unifont or the fonts
+//! exported by FontForge. Only the properties needed to draw the glyphs are read: `FONT_ASCENT`, `FONT_DESCENT`,
+//! and `ENCODING`, `DWIDTH`, `BBX` and `BITMAP` of each glyph. Glyphs without a Unicode encoding are skipped.
+//! BDF fonts are 1 bit per pixel. For anti-aliased glyphs, convert a font that is a few times larger than needed
+//! and downsample it. Runs on the host computer (`std` feature).
+use core::convert::TryFrom;
+use std::{
+    vec,
+    vec::Vec,
+};
+use crate::{
+    encoder::{ self, GlyphImage },
+    Error, FontResult,
+};
+
+/// Font loaded from a BDF file
+#[derive(Clone, Debug, PartialEq)]
+pub struct BdfFont {
+    /// Pixels from the top of the line to the baseline
+    pub ascent:  u32,
+    /// Pixels from the baseline to the bottom of the line
+    pub descent: u32,
+    /// Glyphs of the font, with coverage 0 or 255 for each pixel
+    pub glyphs:  Vec<GlyphImage>,
+}
+
+impl BdfFont {
+    /// Parse the text of the BDF file. Fail with the line number if the file can't be parsed.
+    pub fn parse(text: &str) -> FontResult<BdfFont> {
+        let mut font = BdfFont { ascent: 0, descent: 0, glyphs: Vec::new() };
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line.trim()));
+        while let Some((number, line)) = lines.next() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("FONT_ASCENT")  => font.ascent  = number_at(words.next(), number) ? ,
+                Some("FONT_DESCENT") => font.descent = number_at(words.next(), number) ? ,
+                Some("STARTCHAR") => {
+                    if let Some(glyph) = parse_glyph(&mut lines, number) ? { font.glyphs.push(glyph); }
+                }
+                _ => {}
+            }
+        }
+        if font.ascent + font.descent == 0 { return Err(Error::BadBdf { line: 0 }); }
+        Ok(font)
+    }
+
+    /// Return the font scaled down by the factor. Each pixel covers a square of `factor` by `factor` pixels, and
+    /// gets the fraction of the square that is set as coverage. The advance is rounded to the nearest pixel.
+    pub fn downsample(&self, factor: u32) -> BdfFont {
+        assert!(factor > 0, "bad factor");
+        let f = factor as i32;
+        let glyphs = self.glyphs.iter().map(|glyph| {
+            //  Bounds of the glyph in pixels from the pen position, with y pointing down from the baseline
+            let (left, top) = (glyph.left, -glyph.top);
+            let (right, bottom) = (left + glyph.width as i32, top + glyph.height as i32);
+            let (new_left, new_top) = (left.div_euclid(f), top.div_euclid(f));
+            let (new_right, new_bottom) = ((right + f - 1).div_euclid(f), (bottom + f - 1).div_euclid(f));
+            let (width, height) =
+                if glyph.width == 0 || glyph.height == 0 { (0, 0) }
+                else { ((new_right - new_left) as u32, (new_bottom - new_top) as u32) };
+
+            //  Count the pixels that are set in each square
+            let mut counts = vec![0u32; (width * height) as usize];
+            for y in 0..glyph.height as i32 {
+                for x in 0..glyph.width as i32 {
+                    if glyph.alpha[(y * glyph.width as i32 + x) as usize] < 128 { continue; }
+                    let cell_x = (left + x).div_euclid(f) - new_left;
+                    let cell_y = (top + y).div_euclid(f) - new_top;
+                    counts[(cell_y * width as i32 + cell_x) as usize] += 1;
+                }
+            }
+            let area = factor * factor;
+            GlyphImage {
+                codepoint: glyph.codepoint,
+                width,
+                height,
+                left:      new_left,
+                top:       -new_top,
+                advance:   (glyph.advance + f / 2).div_euclid(f),
+                alpha:     counts.iter().map(|count| ((count * 255 + area / 2) / area) as u8).collect(),
+            }
+        }).collect();
+        BdfFont {
+            ascent:  self.ascent.div_ceil(factor),
+            descent: self.descent.div_ceil(factor),
+            glyphs,
+        }
+    }
+
+    /// Encode the font into the font format (see `format.rs`) with the bits per pixel. Missing characters are
+    /// shown with the `replacement` glyph, if given.
+    pub fn encode(&self, bits_per_pixel: u8, replacement: Option<char>) -> FontResult<Vec<u8>> {
+        encoder::encode(&self.glyphs, bits_per_pixel, self.ascent + self.descent, self.ascent, replacement)
+    }
+}
+
+/// Parse the glyph after `STARTCHAR` at the line number, up to `ENDCHAR`. Return `None` if the glyph has no Unicode
+/// encoding.
+fn parse_glyph<'a, I>(lines: &mut I, start_line: usize) -> FontResult<Option<GlyphImage>>
+where
+    I: Iterator<Item = (usize, &'a str)>,
+{
+    let mut encoding = None;
+    let mut advance  = 0;
+    let mut bbx      = (0, 0, 0, 0);
+    let mut alpha    = Vec::new();
+    let mut in_bitmap = false;
+    let mut last_line = start_line;
+    for (number, line) in lines {
+        last_line = number;
+        let mut words = line.split_whitespace();
+        let keyword = words.next();
+        if keyword == Some("ENDCHAR") {
+            let (width, height, left, bottom) = bbx;
+            if alpha.len() != (width * height) as usize { return Err(Error::BadBdf { line: number }); }
+            return Ok(encoding.map(|codepoint| GlyphImage {
+                codepoint, width, height, left, top: bottom + height as i32, advance, alpha,
+            }));
+        }
+        if in_bitmap {
+            //  Each row is padded to whole bytes, most significant bit first
+            let (width, _, _, _) = bbx;
+            let row = line.as_bytes().chunks(2)
+                .map(|hex| std::str::from_utf8(hex).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()))
+                .collect::<Option<Vec<u8>>>()
+                .filter(|row| row.len() * 8 >= width as usize)
+                .ok_or(Error::BadBdf { line: number }) ? ;
+            alpha.extend((0..width as usize).map(|x| if row[x / 8] & (0x80 >> (x % 8)) != 0 { 255 } else { 0 }));
+            continue;
+        }
+        match keyword {
+            Some("ENCODING") => {
+                let code: i64 = number_at(words.next(), number) ? ;
+                encoding = u32::try_from(code).ok().and_then(char::from_u32);
+            }
+            Some("DWIDTH") => advance = number_at(words.next(), number) ? ,
+            Some("BBX") => bbx = (
+                number_at(words.next(), number) ? , number_at(words.next(), number) ? ,
+                number_at(words.next(), number) ? , number_at(words.next(), number) ? ,
+            ),
+            Some("BITMAP") => in_bitmap = true,
+            _ => {}
+        }
+    }
+    Err(Error::BadBdf { line: last_line })
+}
+
+/// Parse the word as a number. Fail with the line number if missing or invalid.
+fn number_at<T: core::str::FromStr>(word: Option<&str>, line: usize) -> FontResult<T> {
+    word.and_then(|word| word.parse().ok())
+        .ok_or(Error::BadBdf { line })
+}
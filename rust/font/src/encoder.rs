@@ -0,0 +1,102 @@
+//! Encoder that packs glyph images into the font format (see `format.rs`). The coverage of each pixel is quantised
+//! to the bits per pixel of the font. Runs on the host computer (`std` feature).
+use std::{
+    vec,
+    vec::Vec,
+};
+use crate::{
+    format::{ Header, Metrics, GLYPH_SIZE, HEADER_SIZE, NO_REPLACEMENT },
+    Error, FontResult,
+};
+
+/// Glyph to be encoded, with the coverage of each pixel
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlyphImage {
+    /// Character of the glyph
+    pub codepoint: char,
+    /// Width of the image in pixels
+    pub width:     u32,
+    /// Height of the image in pixels
+    pub height:    u32,
+    /// Distance from the pen position to the left column of the image
+    pub left:      i32,
+    /// Distance from the baseline up to the top row of the image
+    pub top:       i32,
+    /// Distance to move the pen to the next glyph
+    pub advance:   i32,
+    /// Coverage of each pixel, row by row, from 0 (transparent) to 255 (opaque)
+    pub alpha:     Vec<u8>,
+}
+
+/// Encode the glyphs into a font with the bits per pixel (1, 2, 4 or 8), line height and ascent. Missing characters
+/// are shown with the `replacement` glyph, if given. The glyphs may be in any order, but each character only once.
+pub fn encode(glyphs: &[GlyphImage], bits_per_pixel: u8, line_height: u32, ascent: u32, replacement: Option<char>)
+    -> FontResult<Vec<u8>> {
+    if ![1, 2, 4, 8].contains(&bits_per_pixel) { return Err(Error::BadBitsPerPixel); }
+    if line_height > u8::MAX as u32 || ascent > line_height || glyphs.len() >= NO_REPLACEMENT as usize {
+        return Err(Error::BadSize);
+    }
+    let mut glyphs: Vec<&GlyphImage> = glyphs.iter().collect();
+    glyphs.sort_by_key(|glyph| glyph.codepoint);
+    if glyphs.windows(2).any(|pair| pair[0].codepoint == pair[1].codepoint) { return Err(Error::Corrupted); }
+
+    //  Pack the bitmaps and fill the glyph table
+    let mut table   = Vec::with_capacity(glyphs.len() * GLYPH_SIZE);
+    let mut bitmaps = Vec::new();
+    for glyph in &glyphs {
+        let metrics = metrics(glyph) ? ;
+        table.extend_from_slice(&(glyph.codepoint as u32).to_le_bytes());
+        table.extend_from_slice(&(bitmaps.len() as u32).to_le_bytes());
+        table.extend_from_slice(&[
+            metrics.width, metrics.height, metrics.left as u8, metrics.top as u8, metrics.advance, 0,
+        ]);
+        bitmaps.extend(pack(&glyph.alpha, bits_per_pixel));
+    }
+    let replacement = match replacement {
+        Some(c) => glyphs.iter().position(|glyph| glyph.codepoint == c).ok_or(Error::Corrupted) ? as u16,
+        None => NO_REPLACEMENT,
+    };
+    let header = Header {
+        bits_per_pixel,
+        line_height: line_height as u8,
+        ascent:      ascent as u8,
+        glyph_count: glyphs.len() as u16,
+        replacement,
+        bitmap_len:  bitmaps.len() as u32,
+    };
+    let mut font = Vec::with_capacity(HEADER_SIZE + table.len() + bitmaps.len());
+    font.extend_from_slice(&header.to_bytes());
+    font.extend(table);
+    font.extend(bitmaps);
+    Ok(font)
+}
+
+/// Return the metrics of the glyph image. Fail if they don't fit in the font format.
+fn metrics(glyph: &GlyphImage) -> FontResult<Metrics> {
+    let fits_u8 = |value: i32| (0..=u8::MAX as i32).contains(&value);
+    let fits_i8 = |value: i32| (i8::MIN as i32..=i8::MAX as i32).contains(&value);
+    if !fits_u8(glyph.width as i32) || !fits_u8(glyph.height as i32) || !fits_i8(glyph.left) || !fits_i8(glyph.top)
+        || !fits_u8(glyph.advance) || glyph.alpha.len() != (glyph.width * glyph.height) as usize {
+        return Err(Error::BadSize);
+    }
+    Ok(Metrics {
+        width:   glyph.width as u8,
+        height:  glyph.height as u8,
+        left:    glyph.left as i8,
+        top:     glyph.top as i8,
+        advance: glyph.advance as u8,
+    })
+}
+
+/// Quantise the coverage of each pixel to the bits per pixel and pack the pixels from the most significant bit
+fn pack(alpha: &[u8], bits_per_pixel: u8) -> Vec<u8> {
+    let bits = bits_per_pixel as usize;
+    let max  = (1u32 << bits) - 1;
+    let mut packed = vec![0u8; (alpha.len() * bits).div_ceil(8)];
+    for (i, a) in alpha.iter().enumerate() {
+        let level = (*a as u32 * max + 127) / 255;
+        let bit = i * bits;
+        packed[bit / 8] |= (level << (8 - bits - bit % 8)) as u8;
+    }
+    packed
+}
@@ -0,0 +1,250 @@
+//! Binary format of a proportional bitmap font, read in place without copying. The header is followed by the glyph
+//! table, sorted by code point for binary search, and the bitmaps. All fields are little-endian:
+//! ```text
+//! Offset  Size  Field
+//!      0     4  Magic number "PTFN"
+//!      4     1  Version (1)
+//!      5     1  Bits per pixel of the bitmaps: 1, 2, 4 or 8. More bits give smoother anti-aliased edges.
+//!      6     1  Line height in pixels
+//!      7     1  Ascent in pixels, from the top of the line to the baseline
+//!      8     2  Number of glyphs
+//!     10     2  Index of the glyph shown for missing characters, or 0xffff if none
+//!     12     4  Length of the bitmaps in bytes
+//! ```
+//! Each glyph in the table takes 14 bytes:
+//! ```text
+//! Offset  Size  Field
+//!      0     4  Unicode code point
+//!      4     4  Offset of the bitmap from the start of the bitmaps
+//!      8     1  Width of the bitmap in pixels
+//!      9     1  Height of the bitmap in pixels
+//!     10     1  Left: signed distance from the pen position to the left column of the bitmap
+//!     11     1  Top: signed distance from the baseline up to the top row of the bitmap
+//!     12     1  Advance: distance to move the pen to the next glyph
+//!     13     1  Reserved (0)
+//! ```
+//! Each bitmap contains the coverage (alpha) of every pixel, row by row, packed from the most significant bit.
+//! Bitmaps start on a byte boundary.
+use crate::{
+    Error, FontResult,
+};
+
+/// Magic number at the start of the header
+pub const MAGIC: [u8; 4] = *b"PTFN";
+
+/// Current version of the header
+pub const VERSION: u8 = 1;
+
+/// Size of the header in bytes
+pub const HEADER_SIZE: usize = 16;
+
+/// Size of each glyph in the glyph table in bytes
+pub const GLYPH_SIZE: usize = 14;
+
+/// Replacement glyph index when there is no glyph for missing characters
+pub const NO_REPLACEMENT: u16 = 0xffff;
+
+/// Header of the font
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Header {
+    /// Bits per pixel of the bitmaps: 1, 2, 4 or 8
+    pub bits_per_pixel: u8,
+    /// Line height in pixels
+    pub line_height:    u8,
+    /// Ascent in pixels, from the top of the line to the baseline
+    pub ascent:         u8,
+    /// Number of glyphs
+    pub glyph_count:    u16,
+    /// Index of the glyph shown for missing characters, or `NO_REPLACEMENT`
+    pub replacement:    u16,
+    /// Length of the bitmaps in bytes
+    pub bitmap_len:     u32,
+}
+
+/// Size and position of a glyph bitmap relative to the pen position on the baseline
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Metrics {
+    /// Width of the bitmap in pixels
+    pub width:   u8,
+    /// Height of the bitmap in pixels
+    pub height:  u8,
+    /// Distance from the pen position to the left column of the bitmap
+    pub left:    i8,
+    /// Distance from the baseline up to the top row of the bitmap
+    pub top:     i8,
+    /// Distance to move the pen to the next glyph
+    pub advance: u8,
+}
+
+/// Glyph in the font, with its bitmap
+#[derive(Clone, Copy, Debug)]
+pub struct Glyph<'a> {
+    /// Unicode code point
+    pub codepoint:  u32,
+    /// Size and position of the bitmap
+    pub metrics:    Metrics,
+    /// Bits per pixel of the bitmap
+    bits_per_pixel: u8,
+    /// Packed bitmap
+    bitmap:         &'a [u8],
+}
+
+/// Font read in place from its binary format
+#[derive(Clone, Copy, Debug)]
+pub struct Font<'a> {
+    /// Header of the font
+    header:  Header,
+    /// Glyph table
+    glyphs:  &'a [u8],
+    /// Bitmaps of the glyphs
+    bitmaps: &'a [u8],
+}
+
+impl Header {
+    /// Parse the header at the start of the bytes. Fail if the header is missing or invalid.
+    pub fn parse(bytes: &[u8]) -> FontResult<Header> {
+        if bytes.len() < HEADER_SIZE { return Err(Error::Truncated); }
+        if bytes[0..4] != MAGIC      { return Err(Error::BadMagic); }
+        if bytes[4] != VERSION       { return Err(Error::BadVersion); }
+        let header = Header {
+            bits_per_pixel: bytes[5],
+            line_height:    bytes[6],
+            ascent:         bytes[7],
+            glyph_count:    u16::from_le_bytes([bytes[8], bytes[9]]),
+            replacement:    u16::from_le_bytes([bytes[10], bytes[11]]),
+            bitmap_len:     u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+        };
+        if ![1, 2, 4, 8].contains(&header.bits_per_pixel) { return Err(Error::BadBitsPerPixel); }
+        if header.replacement != NO_REPLACEMENT && header.replacement >= header.glyph_count {
+            return Err(Error::Corrupted);
+        }
+        Ok(header)
+    }
+
+    /// Return the header as bytes
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4] = VERSION;
+        bytes[5] = self.bits_per_pixel;
+        bytes[6] = self.line_height;
+        bytes[7] = self.ascent;
+        bytes[8..10].copy_from_slice(&self.glyph_count.to_le_bytes());
+        bytes[10..12].copy_from_slice(&self.replacement.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.bitmap_len.to_le_bytes());
+        bytes
+    }
+}
+
+impl Metrics {
+    /// Return the size of the packed bitmap in bytes
+    pub fn bitmap_size(&self, bits_per_pixel: u8) -> usize {
+        (self.width as usize * self.height as usize * bits_per_pixel as usize).div_ceil(8)
+    }
+}
+
+impl<'a> Glyph<'a> {
+    /// Return the coverage of the pixel at (`x`, `y`) in the bitmap, from 0 (transparent) to 255 (opaque)
+    pub fn alpha(&self, x: u8, y: u8) -> u8 {
+        assert!(x < self.metrics.width && y < self.metrics.height, "bad pixel");
+        let bits = self.bits_per_pixel as usize;
+        let bit  = (y as usize * self.metrics.width as usize + x as usize) * bits;
+        let max  = (1u16 << bits) - 1;
+        let level = (self.bitmap[bit / 8] as u16 >> (8 - bits - bit % 8)) & max;
+        (level * 255 / max) as u8
+    }
+}
+
+impl<'a> Font<'a> {
+    /// Parse the font in the bytes. Fail if the header, glyph table or bitmaps are invalid.
+    pub fn parse(bytes: &'a [u8]) -> FontResult<Font<'a>> {
+        let header = Header::parse(bytes) ? ;
+        let table_end  = HEADER_SIZE + header.glyph_count as usize * GLYPH_SIZE;
+        let bitmap_end = table_end + header.bitmap_len as usize;
+        if bytes.len() < bitmap_end { return Err(Error::Truncated); }
+        let font = Font { header, glyphs: &bytes[HEADER_SIZE..table_end], bitmaps: &bytes[table_end..bitmap_end] };
+
+        //  Check that the code points are sorted and the bitmaps are inside the font
+        let mut previous = None;
+        for index in 0..header.glyph_count {
+            let (codepoint, offset, metrics) = font.entry(index);
+            if matches!(previous, Some(previous) if codepoint <= previous) { return Err(Error::Corrupted); }
+            if offset + metrics.bitmap_size(header.bits_per_pixel) > font.bitmaps.len() {
+                return Err(Error::Corrupted);
+            }
+            previous = Some(codepoint);
+        }
+        Ok(font)
+    }
+
+    /// Return the header of the font
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// Return the line height in pixels
+    pub fn line_height(&self) -> u8 {
+        self.header.line_height
+    }
+
+    /// Return the ascent in pixels, from the top of the line to the baseline
+    pub fn ascent(&self) -> u8 {
+        self.header.ascent
+    }
+
+    /// Return the glyph for the character, or `None` if the font doesn't have it
+    pub fn glyph(&self, c: char) -> Option<Glyph<'a>> {
+        let codepoint = c as u32;
+        let (mut low, mut high) = (0, self.header.glyph_count);
+        while low < high {
+            let middle = (low + high) / 2;
+            let (found, _, _) = self.entry(middle);
+            if found == codepoint { return Some(self.glyph_at(middle)); }
+            if found < codepoint { low = middle + 1; } else { high = middle; }
+        }
+        None
+    }
+
+    /// Return the glyph for the character, or the replacement glyph if the font doesn't have it
+    pub fn glyph_or_replacement(&self, c: char) -> Option<Glyph<'a>> {
+        self.glyph(c).or_else(|| {
+            if self.header.replacement == NO_REPLACEMENT { None }
+            else { Some(self.glyph_at(self.header.replacement)) }
+        })
+    }
+
+    /// Return the distance to move the pen after the character, 0 if the font doesn't have it
+    pub fn advance(&self, c: char) -> u32 {
+        self.glyph_or_replacement(c).map_or(0, |glyph| glyph.metrics.advance as u32)
+    }
+
+    /// Return the width of the text on a single line in pixels: the sum of the advances
+    pub fn text_width(&self, text: &str) -> u32 {
+        text.chars().map(|c| self.advance(c)).sum()
+    }
+
+    /// Return the glyph at the index of the glyph table
+    fn glyph_at(&self, index: u16) -> Glyph<'a> {
+        let (codepoint, offset, metrics) = self.entry(index);
+        let size = metrics.bitmap_size(self.header.bits_per_pixel);
+        Glyph {
+            codepoint,
+            metrics,
+            bits_per_pixel: self.header.bits_per_pixel,
+            bitmap: &self.bitmaps[offset..offset + size],
+        }
+    }
+
+    /// Return the code point, bitmap offset and metrics at the index of the glyph table
+    fn entry(&self, index: u16) -> (u32, usize, Metrics) {
+        let e = &self.glyphs[index as usize * GLYPH_SIZE..(index as usize + 1) * GLYPH_SIZE];
+        let metrics = Metrics {
+            width:   e[8],
+            height:  e[9],
+            left:    e[10] as i8,
+            top:     e[11] as i8,
+            advance: e[12],
+        };
+        (u32::from_le_bytes([e[0], e[1], e[2], e[3]]), u32::from_le_bytes([e[4], e[5], e[6], e[7]]) as usize, metrics)
+    }
+}
@@ -0,0 +1,83 @@
+//! Lay out UTF-8 text inside a rectangle: lines are wrapped at spaces, or inside a word that is wider than the
+//! rectangle, and at `\n`. Each line is aligned left, centred or aligned right. Lines that don't fit below the
+//! rectangle are not laid out, and the returned `Layout` tells where the text stopped, e.g. to show the next page.
+//! Runs without the standard Rust library: glyphs are passed to a callback instead of being collected.
+use framebuffer::dirty::Rect;
+use crate::format::{ Font, Glyph };
+
+/// Horizontal alignment of each line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Result of laying out the text
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    /// Number of lines laid out
+    pub lines: u16,
+    /// Byte offset in the text after the last line laid out. Equals the length of the text if all the text fits.
+    pub end:   usize,
+}
+
+/// Lay out the text inside the area. For every character that has a glyph (or a replacement glyph), calls `place`
+/// with the glyph and the pen position: `x` and the `y` of the baseline.
+pub fn layout<'a, F>(font: &Font<'a>, text: &str, area: Rect, align: Align, mut place: F) -> Layout
+where
+    F: FnMut(Glyph<'a>, i32, i32),
+{
+    let max_width   = (area.right - area.left + 1) as u32;
+    let line_height = font.line_height() as i32;
+    let mut top  = area.top as i32;
+    let mut rest = 0;
+    let mut lines = 0;
+    while rest < text.len() && top + line_height - 1 <= area.bottom as i32 {
+        let (line_end, next) = break_line(font, &text[rest..], max_width);
+        let line  = text[rest..rest + line_end].trim_end_matches(' ');
+        let width = font.text_width(line);
+        let mut x = area.left as i32 + match align {
+            Align::Left   => 0,
+            Align::Center => (max_width as i32 - width as i32) / 2,
+            Align::Right  => max_width as i32 - width as i32,
+        };
+        let baseline = top + font.ascent() as i32;
+        for c in line.chars() {
+            if let Some(glyph) = font.glyph_or_replacement(c) {
+                place(glyph, x, baseline);
+                x += glyph.metrics.advance as i32;
+            }
+        }
+        lines += 1;
+        rest  += next;
+        top   += line_height;
+    }
+    Layout { lines, end: rest }
+}
+
+/// Find the end of the first line of the text that fits in `max_width` pixels. Returns the byte offset of the end
+/// of the line, and the byte offset where the next line starts. Breaks after `\n`, else at the last run of spaces
+/// that fits (the spaces are skipped), else before the first character that doesn't fit. At least 1 character is
+/// taken, so that the layout always moves forward.
+pub fn break_line(font: &Font, text: &str, max_width: u32) -> (usize, usize) {
+    let mut width = 0;
+    let mut last_space = None;
+    for (i, c) in text.char_indices() {
+        if c == '\n' { return (i, i + 1); }
+        if c == ' ' { last_space = Some(i); }
+        width += font.advance(c);
+        if width > max_width && c != ' ' && i > 0 {
+            return match last_space {
+                Some(space) => (text[..space].trim_end_matches(' ').len(), skip_spaces(text, space)),
+                None => (i, i),
+            };
+        }
+    }
+    (text.len(), text.len())
+}
+
+/// Return the byte offset of the first character at or after the offset that is not a space
+fn skip_spaces(text: &str, offset: usize) -> usize {
+    offset + text[offset..].len() - text[offset..].trim_start_matches(' ').len()
+}
@@ -0,0 +1,58 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *  http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+//!  Proportional bitmap fonts for PineTime. Fonts are stored in a compact binary format (see `format.rs`) that is
+//!  read in place, e.g. from `include_bytes!`. UTF-8 text is laid out with word wrap and alignment inside a rectangle,
+//!  and the glyphs are drawn onto a `framebuffer::Display` with anti-aliasing blended in RGB565. Shared by the Rust
+//!  Application on PineTime and by `logo_tool` on the host computer, which converts BDF fonts (`std` feature).
+#![no_std]  //  Don't link with standard Rust library, which is not compatible with embedded systems
+
+#[cfg(feature = "std")]  //  If the converter is enabled...
+extern crate std;        //  Use the standard Rust library for `Vec` and `String`
+
+pub mod format;  //  Export `format.rs` as Rust module `font::format`
+pub mod layout;  //  Export `layout.rs` as Rust module `font::layout`
+pub mod render;  //  Export `render.rs` as Rust module `font::render`
+
+#[cfg(feature = "std")]  //  If the converter is enabled...
+pub mod bdf;             //  Export `bdf.rs` as Rust module `font::bdf`
+
+#[cfg(feature = "std")]  //  If the converter is enabled...
+pub mod encoder;         //  Export `encoder.rs` as Rust module `font::encoder`
+
+/// Errors while loading, encoding and converting fonts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// Header doesn't start with the magic number
+    BadMagic,
+    /// Header version is not supported
+    BadVersion,
+    /// Bits per pixel is not 1, 2, 4 or 8
+    BadBitsPerPixel,
+    /// Font data ended before the glyph table or bitmaps
+    Truncated,
+    /// Glyph table is not sorted, or a bitmap is outside the font data
+    Corrupted,
+    /// Glyph metrics don't fit in the font format
+    BadSize,
+    /// BDF file can't be parsed at the line number
+    BadBdf { line: usize },
+}
+
+/// Common return type for the Font API
+pub type FontResult<T> = ::core::result::Result<T, Error>;
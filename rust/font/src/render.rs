@@ -0,0 +1,67 @@
+//! Draw glyphs and laid out text onto a `framebuffer::Display`. The display can't be read back, so anti-aliased
+//! edges are blended in RGB565 with a known background colour, and each glyph is sent as one window of pixels.
+//! Without a background colour, only the pixels that are at least half covered are drawn, without anti-aliasing.
+//! Glyphs are clipped to the area of the text and to the display.
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::Rgb565,
+};
+use framebuffer::{
+    dirty::Rect,
+    Display, HEIGHT, WIDTH,
+};
+use crate::{
+    format::{ Font, Glyph },
+    layout::{ self, Align, Layout },
+};
+
+/// Blend the RGB565 colours: `alpha` 255 returns `fg`, 0 returns `bg`. Each channel is blended separately.
+pub fn blend(fg: u16, bg: u16, alpha: u8) -> u16 {
+    let alpha = alpha as u32;
+    let channel = |shift: u32, mask: u32| {
+        let (f, b) = ((fg as u32 >> shift) & mask, (bg as u32 >> shift) & mask);
+        ((f * alpha + b * (255 - alpha) + 127) / 255) << shift
+    };
+    (channel(11, 0x1f) | channel(5, 0x3f) | channel(0, 0x1f)) as u16
+}
+
+/// Draw the text inside the area, wrapped and aligned like `layout::layout()`, in the RGB565 colour. If `background`
+/// is given, the glyphs are anti-aliased against the background, which should be the colour already in the area.
+pub fn draw_text<D: Display>(display: &mut D, font: &Font, text: &str, area: Rect, align: Align, color: u16,
+    background: Option<u16>) -> Layout {
+    layout::layout(font, text, area, align, |glyph, x, baseline| {
+        draw_glyph(display, &glyph, x, baseline, area, color, background);
+    })
+}
+
+/// Draw the glyph with the pen position at `x` and the baseline at `y`, clipped to the area
+pub fn draw_glyph<D: Display>(display: &mut D, glyph: &Glyph, x: i32, y: i32, clip: Rect, color: u16,
+    background: Option<u16>) {
+    let metrics = glyph.metrics;
+    if metrics.width == 0 || metrics.height == 0 { return; }
+
+    //  Window of the bitmap on the display, clipped to the area and the display
+    let left   = x + metrics.left as i32;
+    let top    = y - metrics.top as i32;
+    let right  = left + metrics.width as i32 - 1;
+    let bottom = top + metrics.height as i32 - 1;
+    let clip_left   = left.max(clip.left as i32).max(0);
+    let clip_top    = top.max(clip.top as i32).max(0);
+    let clip_right  = right.min(clip.right as i32).min(WIDTH as i32 - 1);
+    let clip_bottom = bottom.min(clip.bottom as i32).min(HEIGHT as i32 - 1);
+    if clip_left > clip_right || clip_top > clip_bottom { return; }
+
+    //  Pixels of the window, with the position in the bitmap
+    let window = (clip_top..=clip_bottom)
+        .flat_map(move |py| (clip_left..=clip_right).map(move |px| (px, py)))
+        .map(move |(px, py)| (px, py, glyph.alpha((px - left) as u8, (py - top) as u8)));
+    match background {
+        Some(background) => {
+            let mut colors = window.map(|(_, _, alpha)| blend(color, background, alpha));
+            display.set_pixels(clip_left as u16, clip_top as u16, clip_right as u16, clip_bottom as u16, &mut colors);
+        }
+        None => display.draw(window
+            .filter(|(_, _, alpha)| *alpha >= 128)
+            .map(|(px, py, _)| Pixel(UnsignedCoord::new(px as u32, py as u32), Rgb565(color)))),
+    }
+}
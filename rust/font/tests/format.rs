@@ -0,0 +1,130 @@
+//! Check the encoding and parsing of the font format, and the conversion of BDF fonts. Run on the host computer:
+//! `cargo test -p font --features std --target x86_64-unknown-linux-gnu`
+use font::{
+    bdf::BdfFont,
+    encoder::{ encode, GlyphImage },
+    format::{ Font, Header, Metrics, HEADER_SIZE, NO_REPLACEMENT },
+    Error,
+};
+
+/// Text of the BDF test font, 12x16 pixels
+const BDF: &str = include_str!("data/font12x16.bdf");
+
+/// Return a glyph image with the coverage of each pixel
+fn glyph(codepoint: char, width: u32, height: u32, alpha: &[u8]) -> GlyphImage {
+    GlyphImage {
+        codepoint, width, height, left: 1, top: height as i32, advance: width as i32 + 2, alpha: alpha.to_vec(),
+    }
+}
+
+#[test]
+fn encoded_glyphs_are_parsed_back() {
+    let glyphs = [
+        glyph('b', 3, 2, &[255, 0, 255, 0, 255, 0]),
+        glyph('a', 2, 2, &[0, 255, 255, 0]),
+        glyph('é', 1, 1, &[255]),
+    ];
+    let bytes = encode(&glyphs, 1, 16, 12, Some('a')).unwrap();
+    let font  = Font::parse(&bytes).unwrap();
+    assert_eq!(font.header(), Header {
+        bits_per_pixel: 1, line_height: 16, ascent: 12, glyph_count: 3, replacement: 0, bitmap_len: 3,
+    });
+
+    //  Glyphs are sorted by code point and found by binary search
+    let b = font.glyph('b').unwrap();
+    assert_eq!(b.codepoint, 'b' as u32);
+    assert_eq!(b.metrics, Metrics { width: 3, height: 2, left: 1, top: 2, advance: 5 });
+    assert_eq!((b.alpha(0, 0), b.alpha(1, 0), b.alpha(2, 0)), (255, 0, 255));
+    assert_eq!((b.alpha(0, 1), b.alpha(1, 1), b.alpha(2, 1)), (0, 255, 0));
+    assert_eq!(font.glyph('é').unwrap().alpha(0, 0), 255);
+    assert!(font.glyph('c').is_none());
+
+    //  Missing characters get the replacement glyph
+    assert_eq!(font.glyph_or_replacement('c').unwrap().codepoint, 'a' as u32);
+    assert_eq!(font.text_width("ab€"), 4 + 5 + 4);
+}
+
+#[test]
+fn coverage_is_quantised_to_bits_per_pixel() {
+    let alpha = [0, 40, 100, 128, 200, 255];
+    let glyphs = [glyph('a', 6, 1, &alpha)];
+    let levels = |bits_per_pixel| {
+        let bytes = encode(&glyphs, bits_per_pixel, 8, 6, None).unwrap();
+        let glyph = Font::parse(&bytes).unwrap().glyph('a').unwrap();
+        (0..6).map(|x| glyph.alpha(x, 0)).collect::<Vec<u8>>()
+    };
+    assert_eq!(levels(1), [0, 0, 0, 255, 255, 255]);
+    assert_eq!(levels(2), [0, 0, 85, 170, 170, 255]);
+    assert_eq!(levels(4), [0, 34, 102, 136, 204, 255]);
+    assert_eq!(levels(8), alpha);
+}
+
+#[test]
+fn bad_fonts_are_rejected() {
+    let bytes = encode(&[glyph('a', 2, 2, &[0; 4])], 2, 8, 6, None).unwrap();
+    assert_eq!(Font::parse(&bytes).unwrap().header().replacement, NO_REPLACEMENT);
+    assert_eq!(Font::parse(&bytes[..HEADER_SIZE - 1]).unwrap_err(), Error::Truncated);
+    assert_eq!(Font::parse(&bytes[..bytes.len() - 1]).unwrap_err(), Error::Truncated);
+
+    let mut bad = bytes.clone();
+    bad[0] = b'X';
+    assert_eq!(Font::parse(&bad).unwrap_err(), Error::BadMagic);
+    let mut bad = bytes.clone();
+    bad[4] = 2;
+    assert_eq!(Font::parse(&bad).unwrap_err(), Error::BadVersion);
+    let mut bad = bytes.clone();
+    bad[5] = 3;
+    assert_eq!(Font::parse(&bad).unwrap_err(), Error::BadBitsPerPixel);
+    let mut bad = bytes;
+    bad[HEADER_SIZE + 8] = 9;  //  Bitmap is wider than the font data
+    assert_eq!(Font::parse(&bad).unwrap_err(), Error::Corrupted);
+}
+
+#[test]
+fn bad_glyphs_are_not_encoded() {
+    assert_eq!(encode(&[glyph('a', 1, 1, &[0])], 3, 8, 6, None).unwrap_err(), Error::BadBitsPerPixel);
+    assert_eq!(encode(&[glyph('a', 1, 1, &[0, 0])], 1, 8, 6, None).unwrap_err(), Error::BadSize);
+    assert_eq!(encode(&[glyph('a', 300, 1, &[0; 300])], 1, 8, 6, None).unwrap_err(), Error::BadSize);
+    let twice = [glyph('a', 1, 1, &[0]), glyph('a', 1, 1, &[0])];
+    assert_eq!(encode(&twice, 1, 8, 6, None).unwrap_err(), Error::Corrupted);
+    assert_eq!(encode(&[glyph('a', 1, 1, &[0])], 1, 8, 6, Some('b')).unwrap_err(), Error::Corrupted);
+}
+
+#[test]
+fn bdf_glyphs_are_converted() {
+    let bdf = BdfFont::parse(BDF).unwrap();
+    assert_eq!((bdf.ascent, bdf.descent, bdf.glyphs.len()), (14, 2, 97));
+
+    //  "!" is 2 pixels wide, 1 pixel right of the pen, with 2 rows missing above the bottom dot
+    let bytes = bdf.encode(1, Some('?')).unwrap();
+    let font  = Font::parse(&bytes).unwrap();
+    assert_eq!((font.line_height(), font.ascent()), (16, 14));
+    let exclaim = font.glyph('!').unwrap();
+    assert_eq!(exclaim.metrics, Metrics { width: 2, height: 14, left: 1, top: 14, advance: 4 });
+    assert_eq!((exclaim.alpha(0, 0), exclaim.alpha(1, 9)), (255, 255));
+    assert_eq!((exclaim.alpha(0, 10), exclaim.alpha(1, 11)), (0, 0));
+    assert_eq!(font.glyph(' ').unwrap().metrics.advance, 6);
+    assert!(font.glyph('é').is_some());
+    assert_eq!(font.glyph_or_replacement('中').unwrap().codepoint, '?' as u32);
+}
+
+#[test]
+fn bdf_glyphs_are_downsampled_with_coverage() {
+    let bdf = BdfFont::parse(BDF).unwrap().downsample(2);
+    assert_eq!((bdf.ascent, bdf.descent), (7, 1));
+
+    //  Each pixel of "!" covers 2x2 pixels: the left column is half covered
+    let exclaim = bdf.glyphs.iter().find(|glyph| glyph.codepoint == '!').unwrap();
+    assert_eq!((exclaim.width, exclaim.height, exclaim.left, exclaim.top, exclaim.advance), (2, 7, 0, 7, 2));
+    assert_eq!(&exclaim.alpha[0..2], [128, 128]);
+    assert_eq!(&exclaim.alpha[10..12], [0, 0]);
+}
+
+#[test]
+fn bad_bdf_reports_line() {
+    let bdf = "STARTFONT 2.1\nFONT_ASCENT 8\nFONT_DESCENT 2\nSTARTCHAR A\nENCODING 65\nDWIDTH 6 0\n\
+        BBX 8 2 0 0\nBITMAP\nFF\nZZ\nENDCHAR\n";
+    assert_eq!(BdfFont::parse(bdf).unwrap_err(), Error::BadBdf { line: 10 });
+    assert_eq!(BdfFont::parse("FONT_ASCENT x\n").unwrap_err(), Error::BadBdf { line: 1 });
+    assert_eq!(BdfFont::parse("FONT_ASCENT 8\nSTARTCHAR A\n").unwrap_err(), Error::BadBdf { line: 2 });
+}
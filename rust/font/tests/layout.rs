@@ -0,0 +1,81 @@
+//! Check the line breaking and alignment of UTF-8 text inside a rectangle. Run on the host computer:
+//! `cargo test -p font --features std --target x86_64-unknown-linux-gnu`
+use font::{
+    encoder::{ encode, GlyphImage },
+    format::Font,
+    layout::{ break_line, layout, Align, Layout },
+};
+use framebuffer::dirty::Rect;
+
+/// Return a font where every character in the text, and the replacement `?`, is 10 pixels wide, with a line
+/// height of 20
+fn font(text: &str) -> Vec<u8> {
+    let mut chars: Vec<char> = text.chars().chain(Some('?')).collect();
+    chars.sort();
+    chars.dedup();
+    let glyphs: Vec<GlyphImage> = chars.iter()
+        .map(|c| GlyphImage {
+            codepoint: *c, width: 8, height: 12, left: 1, top: 12, advance: 10, alpha: vec![255; 96],
+        })
+        .collect();
+    encode(&glyphs, 1, 20, 15, Some('?')).unwrap()
+}
+
+/// Lay out the text and return the code point and pen position of each glyph
+fn place(font: &Font, text: &str, area: Rect, align: Align) -> (Layout, Vec<(char, i32, i32)>) {
+    let mut glyphs = Vec::new();
+    let result = layout(font, text, area, align, |glyph, x, y| {
+        glyphs.push((std::char::from_u32(glyph.codepoint).unwrap(), x, y));
+    });
+    (result, glyphs)
+}
+
+#[test]
+fn lines_break_at_spaces_newlines_and_long_words() {
+    let bytes = font("abcdefghé ");
+    let font  = Font::parse(&bytes).unwrap();
+    assert_eq!(break_line(&font, "ab cd ef", 60), ("ab cd".len(), "ab cd ".len()));
+    assert_eq!(break_line(&font, "ab   cd", 30), ("ab".len(), "ab   ".len()));
+    assert_eq!(break_line(&font, "abcdefgh", 50), (5, 5));
+    assert_eq!(break_line(&font, "ab\ncd", 100), (2, 3));
+    assert_eq!(break_line(&font, "abc", 5), (1, 1));
+    assert_eq!(break_line(&font, "", 5), (0, 0));
+
+    //  Multi-byte characters are measured and broken as one character
+    assert_eq!(break_line(&font, "éé éé", 40), ("éé".len(), "éé ".len()));
+    assert_eq!(break_line(&font, "ééé", 20), ("éé".len(), "éé".len()));
+}
+
+#[test]
+fn lines_are_aligned_inside_area() {
+    let bytes = font("ab cd");
+    let font  = Font::parse(&bytes).unwrap();
+    let area  = Rect::new(20, 30, 79, 99);
+    let (result, glyphs) = place(&font, "ab  cd", area, Align::Left);
+    assert_eq!(result, Layout { lines: 1, end: 6 });
+    assert_eq!(glyphs.iter().map(|g| g.1).collect::<Vec<i32>>(), [20, 30, 40, 50, 60, 70]);
+    assert!(glyphs.iter().all(|g| g.2 == 30 + 15));
+
+    //  Trailing spaces are not counted when centring or aligning right
+    let (_, glyphs) = place(&font, "ab cd ef", area, Align::Center);
+    assert_eq!(glyphs.iter().map(|g| (g.0, g.1, g.2)).collect::<Vec<_>>(), [
+        ('a', 25, 45), ('b', 35, 45), (' ', 45, 45), ('c', 55, 45), ('d', 65, 45), ('?', 40, 65), ('?', 50, 65),
+    ]);
+    let (_, glyphs) = place(&font, "ab ", area, Align::Right);
+    assert_eq!(glyphs.iter().map(|g| g.1).collect::<Vec<i32>>(), [60, 70]);
+}
+
+#[test]
+fn layout_stops_at_bottom_of_area() {
+    let bytes = font("abc ");
+    let font  = Font::parse(&bytes).unwrap();
+    let area  = Rect::new(0, 0, 29, 49);
+    let (result, glyphs) = place(&font, "abc abc abc", area, Align::Left);
+    assert_eq!(result, Layout { lines: 2, end: "abc abc ".len() });
+    assert_eq!(glyphs.len(), 6);
+    assert_eq!(glyphs.last(), Some(&('c', 20, 20 + 15)));
+
+    //  The rest of the text is laid out on the next page
+    let (result, _) = place(&font, &"abc abc abc"[result.end..], area, Align::Left);
+    assert_eq!(result, Layout { lines: 1, end: 3 });
+}
@@ -0,0 +1,83 @@
+//! Render UTF-8 text with the BDF test font into the framebuffer and compare with the golden images in
+//! `tests/golden`. Run on the host computer:
+//! `cargo test -p font --features std --target x86_64-unknown-linux-gnu`
+//! After an intended change to the rendering, check the rendered text and update the golden images:
+//! `UPDATE_GOLDEN=1 cargo test -p font --features std --target x86_64-unknown-linux-gnu`
+use font::{
+    bdf::BdfFont,
+    format::Font,
+    layout::Align,
+    render::{ blend, draw_text },
+};
+use framebuffer::{
+    dirty::Rect,
+    golden, Framebuffer,
+};
+use std::path::{ Path, PathBuf };
+
+/// Text of the BDF test font, 12x16 pixels
+const BDF: &str = include_str!("data/font12x16.bdf");
+
+/// Text to be rendered, with characters that are missing in the font
+const TEXT: &str = "Proportional fonts for PineTime: 25°C, café.\nMissing: 中文";
+
+/// RGB565 colours
+const BLACK:  u16 = 0x0000;
+const WHITE:  u16 = 0xffff;
+const YELLOW: u16 = 0xffe0;
+const NAVY:   u16 = 0x0010;
+
+/// Return the path of the golden image
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name)
+}
+
+/// Draw the text with the three alignments into the framebuffer, and compare with the golden image
+fn render(font: &Font, background: Option<u16>, golden: &str) {
+    let mut framebuffer = Box::new(Framebuffer::new());
+    framebuffer.fill(background.unwrap_or(BLACK));
+    for (i, align) in [Align::Left, Align::Center, Align::Right].iter().enumerate() {
+        let top = 4 + i as u16 * 78;
+        let result = draw_text(&mut *framebuffer, font, TEXT, Rect::new(4, top, 235, top + 73), *align, WHITE,
+            background);
+        assert_eq!(result.end, TEXT.len());
+    }
+    golden::compare(&framebuffer, &golden_path(golden)).unwrap();
+}
+
+#[test]
+fn blend_mixes_each_channel() {
+    assert_eq!(blend(YELLOW, NAVY, 255), YELLOW);
+    assert_eq!(blend(YELLOW, NAVY, 0), NAVY);
+    assert_eq!(blend(WHITE, BLACK, 128), (16 << 11) | (32 << 5) | 16);
+    assert_eq!(blend(YELLOW, NAVY, 64), (8 << 11) | (16 << 5) | 12);
+}
+
+#[test]
+fn bitmap_text_matches_golden() {
+    let bytes = BdfFont::parse(BDF).unwrap().encode(1, Some('?')).unwrap();
+    render(&Font::parse(&bytes).unwrap(), None, "text_1bpp.png");
+}
+
+#[test]
+fn antialiased_text_matches_golden() {
+    let bytes = BdfFont::parse(BDF).unwrap().downsample(2).encode(4, Some('?')).unwrap();
+    render(&Font::parse(&bytes).unwrap(), Some(NAVY), "text_aa4bpp.png");
+}
+
+#[test]
+fn text_is_clipped_to_area() {
+    let bytes = BdfFont::parse(BDF).unwrap().encode(1, None).unwrap();
+    let font  = Font::parse(&bytes).unwrap();
+    let mut framebuffer = Box::new(Framebuffer::new());
+    let area  = Rect::new(10, 10, 15, 40);
+    let result = draw_text(&mut *framebuffer, &font, "MW", area, Align::Left, WHITE, Some(NAVY));
+    assert_eq!(result.lines, 1);
+    for y in 0..60 {
+        for x in 0..60 {
+            let inside = (10..=15).contains(&x) && (10..=40).contains(&y);
+            assert!(inside || framebuffer.pixel(x, y) == 0, "pixel ({}, {}) outside area", x, y);
+        }
+    }
+    assert!(framebuffer.pixels().contains(&WHITE));
+}
@@ -0,0 +1,279 @@
+//! Dirty rectangles for partial refresh of the display. Drawing calls mark the windows they change, nearby windows are
+//! merged, and `flush()` sends only the changed windows to the display with `Display::set_pixels()`, which sets the
+//! ST7789 window with CASET and RASET before writing the pixels. Apps get partial refresh without keeping their own
+//! bounding boxes: draw through `Tracker` onto a `BackBuffer` for a window of the display, then flush the dirty
+//! rectangles from the back buffer. A back buffer for the whole display doesn't fit in the RAM of PineTime, so apps
+//! keep back buffers only for the windows they redraw often, like the status bar. Runs without the standard Rust
+//! library, so the rectangles are kept in a fixed-size array.
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::Rgb565,
+};
+use crate::{ Display, HEIGHT, WIDTH };
+
+/// Max number of dirty rectangles. When full, the next rectangle is merged into the nearest one.
+pub const MAX_RECTS: usize = 8;
+
+/// Two rectangles are merged if their union has at most this many more pixels than the two rectangles. Sending a
+/// few extra pixels is cheaper than another CASET, RASET and RAMWR.
+const MERGE_SLACK: u32 = 64;
+
+/// Max number of pixels sent in each `set_pixels()` call by `flush()`: 2,400 * 2 = 4,800 bytes, without overflowing
+/// the SPI buffer (`PendingDataSize` is 8,192 bytes)
+pub const FLUSH_PIXELS: u32 = 2400;
+
+/// Window of pixels from (`left`, `top`) to (`right`, `bottom`) inclusive
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub left:   u16,
+    pub top:    u16,
+    pub right:  u16,
+    pub bottom: u16,
+}
+
+/// Dirty rectangles that have not been flushed to the display. The rectangles don't overlap after merging, except
+/// when merged because the array was full.
+#[derive(Clone, Debug)]
+pub struct DirtyRects {
+    /// Dirty rectangles, `count` of them
+    rects: [Rect; MAX_RECTS],
+    /// Number of dirty rectangles
+    count: usize,
+}
+
+/// Drawing that marks the windows changed by every drawing call as dirty, e.g. a back buffer that will be flushed
+/// to the display
+pub struct Tracker<'a, D> {
+    /// Drawing that receives the pixels
+    drawing: &'a mut D,
+    /// Dirty rectangles to be marked
+    dirty:   &'a mut DirtyRects,
+}
+
+/// Back buffer for a window of the display, with the RGB565 pixels of the window row by row in a slice, e.g. a
+/// `static` array. Pixels drawn outside the window are dropped.
+pub struct BackBuffer<'a> {
+    /// Window of the display covered by the back buffer
+    window: Rect,
+    /// RGB565 pixels of the window, row by row
+    pixels: &'a mut [u16],
+}
+
+impl Rect {
+    /// Return the window from (`left`, `top`) to (`right`, `bottom`) inclusive
+    pub fn new(left: u16, top: u16, right: u16, bottom: u16) -> Rect {
+        assert!(left <= right && top <= bottom, "bad rect");
+        Rect { left, top, right, bottom }
+    }
+
+    /// Return the window of the whole display
+    pub const fn full() -> Rect {
+        Rect { left: 0, top: 0, right: WIDTH - 1, bottom: HEIGHT - 1 }
+    }
+
+    /// Return the number of pixels in the window
+    pub fn area(self) -> u32 {
+        (self.right - self.left + 1) as u32 * (self.bottom - self.top + 1) as u32
+    }
+
+    /// Return the smallest window that contains both windows
+    pub fn union(self, other: Rect) -> Rect {
+        Rect {
+            left:   self.left.min(other.left),
+            top:    self.top.min(other.top),
+            right:  self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    /// Return true if the window contains the other window
+    pub fn contains(self, other: Rect) -> bool {
+        self.left <= other.left && self.top <= other.top && self.right >= other.right && self.bottom >= other.bottom
+    }
+
+    /// Return the number of extra pixels in the union of the windows, compared with sending both windows
+    fn merge_cost(self, other: Rect) -> u32 {
+        self.union(other).area().saturating_sub(self.area() + other.area())
+    }
+}
+
+impl DirtyRects {
+    /// Return no dirty rectangles
+    pub const fn new() -> DirtyRects {
+        DirtyRects { rects: [Rect { left: 0, top: 0, right: 0, bottom: 0 }; MAX_RECTS], count: 0 }
+    }
+
+    /// Mark the window as dirty. The window is clipped to the display and merged with nearby dirty rectangles.
+    pub fn add(&mut self, rect: Rect) {
+        if rect.left >= WIDTH || rect.top >= HEIGHT { return; }
+        let mut rect = Rect::new(rect.left, rect.top, rect.right.min(WIDTH - 1), rect.bottom.min(HEIGHT - 1));
+        //  Merge with any nearby rectangle, then check the merged rectangle again
+        while let Some(i) = (0..self.count).find(|i| self.rects[*i].merge_cost(rect) <= MERGE_SLACK) {
+            rect = rect.union(self.remove(i));
+        }
+        //  If full, merge with the rectangle that adds the fewest pixels
+        if self.count == MAX_RECTS {
+            let i = (0..self.count)
+                .min_by_key(|i| self.rects[*i].merge_cost(rect))
+                .expect("no rects");
+            rect = rect.union(self.remove(i));
+        }
+        self.rects[self.count] = rect;
+        self.count += 1;
+    }
+
+    /// Mark the pixel at (`x`, `y`) as dirty
+    pub fn add_pixel(&mut self, x: u16, y: u16) {
+        self.add(Rect::new(x, y, x, y));
+    }
+
+    /// Return the dirty rectangles
+    pub fn rects(&self) -> &[Rect] {
+        &self.rects[..self.count]
+    }
+
+    /// Return true if there are no dirty rectangles
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Forget the dirty rectangles, after they have been refreshed
+    pub fn clear(&mut self) {
+        self.count = 0;
+    }
+
+    /// Send the pixels of the dirty rectangles to the display, then forget the rectangles. `pixel` returns the RGB565
+    /// colour at (`x`, `y`), e.g. from a back buffer. Large rectangles are sent in bands of rows of at most
+    /// `FLUSH_PIXELS` pixels.
+    pub fn flush<D, F>(&mut self, display: &mut D, mut pixel: F)
+    where
+        D: Display,
+        F: FnMut(u16, u16) -> u16,
+    {
+        for rect in self.rects() {
+            let width = rect.right - rect.left + 1;
+            let band_rows = (FLUSH_PIXELS / width as u32).max(1) as u16;
+            let mut top = rect.top;
+            loop {
+                let bottom = rect.bottom.min(top + band_rows - 1);
+                let (left, right) = (rect.left, rect.right);
+                let mut colors = (top..=bottom)
+                    .flat_map(|y| (left..=right).map(move |x| (x, y)))
+                    .map(|(x, y)| pixel(x, y));
+                display.set_pixels(left, top, right, bottom, &mut colors);
+                if bottom == rect.bottom { break; }
+                top = bottom + 1;
+            }
+        }
+        self.clear();
+    }
+
+    /// Remove and return the dirty rectangle at the index
+    fn remove(&mut self, index: usize) -> Rect {
+        let rect = self.rects[index];
+        self.count -= 1;
+        self.rects[index] = self.rects[self.count];
+        rect
+    }
+}
+
+impl Default for DirtyRects {
+    fn default() -> DirtyRects {
+        DirtyRects::new()
+    }
+}
+
+impl<'a> BackBuffer<'a> {
+    /// Return the back buffer for the window of the display, with the pixels of the window row by row
+    pub fn new(window: Rect, pixels: &'a mut [u16]) -> BackBuffer<'a> {
+        assert_eq!(pixels.len() as u32, window.area(), "bad size");
+        BackBuffer { window, pixels }
+    }
+
+    /// Return the window of the display covered by the back buffer
+    pub fn window(&self) -> Rect {
+        self.window
+    }
+
+    /// Return the RGB565 pixel at (`x`, `y`) on the display, which must be inside the window
+    pub fn pixel(&self, x: u16, y: u16) -> u16 {
+        self.pixels[self.index(x, y).expect("bad pixel")]
+    }
+
+    /// Send the pixels of the dirty rectangles to the display, then forget the rectangles. The dirty rectangles must
+    /// be inside the window.
+    pub fn flush<D: Display>(&self, dirty: &mut DirtyRects, display: &mut D) {
+        dirty.flush(display, |x, y| self.pixel(x, y));
+    }
+
+    /// Return the index of the pixel at (`x`, `y`) on the display, or `None` if outside the window
+    fn index(&self, x: u16, y: u16) -> Option<usize> {
+        let window = self.window;
+        if x < window.left || x > window.right || y < window.top || y > window.bottom { return None; }
+        let width = (window.right - window.left + 1) as usize;
+        Some((y - window.top) as usize * width + (x - window.left) as usize)
+    }
+}
+
+impl<'a> Drawing<Rgb565> for BackBuffer<'a> {
+    /// Set the pixels that fall inside the window
+    fn draw<T>(&mut self, item: T)
+    where
+        T: IntoIterator<Item = Pixel<Rgb565>>,
+    {
+        for Pixel(coord, color) in item {
+            if coord[0] >= WIDTH as u32 || coord[1] >= HEIGHT as u32 { continue; }
+            if let Some(i) = self.index(coord[0] as u16, coord[1] as u16) { self.pixels[i] = color.0; }
+        }
+    }
+}
+
+impl<'a> Display for BackBuffer<'a> {
+    /// Set the pixels in the window from (`left`, `top`) to (`right`, `bottom`) that fall inside the back buffer
+    fn set_pixels<T>(&mut self, left: u16, top: u16, right: u16, bottom: u16, colors: &mut T)
+    where
+        T: Iterator<Item = u16>,
+    {
+        let window = (top..=bottom)
+            .flat_map(|y| (left..=right).map(move |x| (x, y)));
+        for ((x, y), color) in window.zip(colors) {
+            if let Some(i) = self.index(x, y) { self.pixels[i] = color; }
+        }
+    }
+}
+
+impl<'a, D> Tracker<'a, D> {
+    /// Return a drawing that draws onto `drawing` and marks the changed windows in `dirty`
+    pub fn new(drawing: &'a mut D, dirty: &'a mut DirtyRects) -> Tracker<'a, D> {
+        Tracker { drawing, dirty }
+    }
+}
+
+impl<'a, D: Drawing<Rgb565>> Drawing<Rgb565> for Tracker<'a, D> {
+    /// Draw the pixels and mark their bounding box as dirty
+    fn draw<T>(&mut self, item: T)
+    where
+        T: IntoIterator<Item = Pixel<Rgb565>>,
+    {
+        let mut bounds: Option<Rect> = None;
+        let pixels = item.into_iter()
+            .inspect(|Pixel(coord, _)| {
+                if coord[0] >= WIDTH as u32 || coord[1] >= HEIGHT as u32 { return; }
+                let pixel = Rect::new(coord[0] as u16, coord[1] as u16, coord[0] as u16, coord[1] as u16);
+                bounds = Some(bounds.map_or(pixel, |bounds| bounds.union(pixel)));
+            });
+        self.drawing.draw(pixels);
+        if let Some(bounds) = bounds { self.dirty.add(bounds); }
+    }
+}
+
+impl<'a, D: Display> Display for Tracker<'a, D> {
+    /// Set the pixels in the window and mark the window as dirty
+    fn set_pixels<T>(&mut self, left: u16, top: u16, right: u16, bottom: u16, colors: &mut T)
+    where
+        T: Iterator<Item = u16>,
+    {
+        self.drawing.set_pixels(left, top, right, bottom, colors);
+        self.dirty.add(Rect::new(left, top, right, bottom));
+    }
+}
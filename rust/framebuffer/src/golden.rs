@@ -0,0 +1,76 @@
+//! Dump the framebuffer to a PNG file, and compare it with a golden image for testing screens on the host computer.
+//! RGB565 pixels are written as 24-bit RGB by replicating the upper bits of each channel into the lower bits, so
+//! loading the PNG gives back the same pixels. Requires the `std` feature.
+use std::{
+    env,
+    format,
+    fs::File,
+    io::{ self, BufWriter },
+    path::Path,
+    string::String,
+    vec,
+    vec::Vec,
+};
+use crate::{ Framebuffer, HEIGHT, WIDTH };
+
+/// Write the framebuffer to a 24-bit RGB PNG file
+pub fn write_png(framebuffer: &Framebuffer, path: &Path) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path) ?), WIDTH as u32, HEIGHT as u32);
+    encoder.set_color(png::ColorType::RGB);
+    encoder.set_depth(png::BitDepth::Eight);
+    let data: Vec<u8> = framebuffer.pixels().iter()
+        .flat_map(|pixel| to_rgb888(*pixel).to_vec())
+        .collect();
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(|err| err.into())
+}
+
+/// Load a 240x240 24-bit RGB PNG file into a framebuffer. The lower bits of each channel are truncated.
+pub fn load_png(path: &Path) -> io::Result<Framebuffer> {
+    let decoder = png::Decoder::new(File::open(path) ?);
+    let (info, mut reader) = decoder.read_info() ?;
+    if info.width != WIDTH as u32 || info.height != HEIGHT as u32
+        || info.color_type != png::ColorType::RGB || info.bit_depth != png::BitDepth::Eight {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "PNG must be 240x240 24-bit RGB"));
+    }
+    let mut data = vec![0; info.buffer_size()];
+    reader.next_frame(&mut data) ? ;
+    let pixels: Vec<u16> = data.chunks(3)
+        .map(|p| ((p[0] as u16 & 0xf8) << 8) | ((p[1] as u16 & 0xfc) << 3) | (p[2] as u16 >> 3))
+        .collect();
+    Ok(Framebuffer::from_pixels(&pixels))
+}
+
+/// Return the number of pixels that differ between the framebuffers
+pub fn diff(a: &Framebuffer, b: &Framebuffer) -> usize {
+    a.pixels().iter()
+        .zip(b.pixels())
+        .filter(|(a, b)| a != b)
+        .count()
+}
+
+/// Compare the framebuffer with the golden PNG file. If the environment variable `UPDATE_GOLDEN` is set, the golden
+/// file is replaced instead. If they differ, the framebuffer is written to the temporary directory for inspection.
+pub fn compare(framebuffer: &Framebuffer, golden: &Path) -> Result<(), String> {
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        return write_png(framebuffer, golden)
+            .map_err(|err| format!("{}: {}", golden.display(), err));
+    }
+    let expected = load_png(golden)
+        .map_err(|err| format!("{}: {}", golden.display(), err)) ? ;
+    let count = diff(framebuffer, &expected);
+    if count == 0 { return Ok(()); }
+
+    //  Save the rendered screen next to the golden file name, in the temporary directory
+    let actual = env::temp_dir().join(golden.file_name().unwrap_or_default());
+    write_png(framebuffer, &actual)
+        .map_err(|err| format!("{}: {}", actual.display(), err)) ? ;
+    Err(format!("{}: {} pixels differ, rendered screen written to {}", golden.display(), count, actual.display()))
+}
+
+/// Expand the RGB565 pixel to 8-bit red, green and blue, by replicating the upper bits into the lower bits
+fn to_rgb888(pixel: u16) -> [u8; 3] {
+    let (r, g, b) = ((pixel >> 11) as u8, ((pixel >> 5) & 0x3f) as u8, (pixel & 0x1f) as u8);
+    [ (r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2) ]
+}
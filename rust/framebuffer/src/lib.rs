@@ -0,0 +1,128 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *  http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+//!  In-memory framebuffer for the PineTime display. Screens that are drawn onto the `Display` trait with
+//!  `embedded-graphics` primitives or windows of RGB565 pixels may be rendered on PineTime (ST7789 display) or into
+//!  the 240x240 RGB565 `Framebuffer`, which may be dumped to PNG on the host computer (`std` feature).
+//!  Also tracks the dirty rectangles for partial refresh, and computes the ST7789 commands for rotating, mirroring
+//!  and scrolling the display.
+#![no_std]  //  Don't link with standard Rust library, which is not compatible with embedded systems
+
+pub mod dirty;           //  Export `dirty.rs` as Rust module `framebuffer::dirty`
+pub mod orientation;     //  Export `orientation.rs` as Rust module `framebuffer::orientation`
+
+#[cfg(feature = "std")]  //  If PNG dumps are enabled...
+extern crate std;        //  Use the standard Rust library for files
+
+#[cfg(feature = "std")]  //  If PNG dumps are enabled...
+pub mod golden;          //  Export `golden.rs` as Rust module `framebuffer::golden`
+
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::Rgb565,
+};
+
+/// Width of the PineTime display, in pixels
+pub const WIDTH: u16 = 240;
+
+/// Height of the PineTime display, in pixels
+pub const HEIGHT: u16 = 240;
+
+/// Number of pixels in the framebuffer
+const PIXEL_COUNT: usize = WIDTH as usize * HEIGHT as usize;
+
+/// Display that screens are drawn onto: `embedded-graphics` primitives through `Drawing`, or windows of RGB565
+/// pixels through `set_pixels()`. Implemented by `Framebuffer` and by the ST7789 display on PineTime.
+pub trait Display: Drawing<Rgb565> {
+    /// Set the pixels in the window from (`left`, `top`) to (`right`, `bottom`) inclusive, row by row, like
+    /// `druid::set_display_pixels()`. Stops when the window is full or there are no more colours.
+    fn set_pixels<T>(&mut self, left: u16, top: u16, right: u16, bottom: u16, colors: &mut T)
+    where
+        T: Iterator<Item = u16>;
+}
+
+/// 240x240 RGB565 pixels, row by row. Pixels drawn outside the framebuffer are dropped.
+pub struct Framebuffer {
+    /// RGB565 pixels, row by row
+    pixels: [u16; PIXEL_COUNT],
+}
+
+impl Framebuffer {
+    /// Return a new framebuffer with black pixels
+    pub const fn new() -> Framebuffer {
+        Framebuffer { pixels: [0; PIXEL_COUNT] }
+    }
+
+    /// Return a new framebuffer with the 240x240 RGB565 pixels, row by row
+    pub fn from_pixels(pixels: &[u16]) -> Framebuffer {
+        assert_eq!(pixels.len(), PIXEL_COUNT, "bad size");
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.pixels.copy_from_slice(pixels);
+        framebuffer
+    }
+
+    /// Return the RGB565 pixels, row by row
+    pub fn pixels(&self) -> &[u16] {
+        &self.pixels
+    }
+
+    /// Return the RGB565 pixel at (`x`, `y`)
+    pub fn pixel(&self, x: u16, y: u16) -> u16 {
+        assert!(x < WIDTH && y < HEIGHT, "bad pixel");
+        self.pixels[y as usize * WIDTH as usize + x as usize]
+    }
+
+    /// Set every pixel to the RGB565 colour
+    pub fn fill(&mut self, color: u16) {
+        self.pixels.iter_mut().for_each(|pixel| *pixel = color);
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Framebuffer {
+        Framebuffer::new()
+    }
+}
+
+impl Drawing<Rgb565> for Framebuffer {
+    /// Set the pixels that fall inside the framebuffer
+    fn draw<T>(&mut self, item: T)
+    where
+        T: IntoIterator<Item = Pixel<Rgb565>>,
+    {
+        for Pixel(coord, color) in item {
+            if coord[0] < WIDTH as u32 && coord[1] < HEIGHT as u32 {
+                self.pixels[coord[1] as usize * WIDTH as usize + coord[0] as usize] = color.0;
+            }
+        }
+    }
+}
+
+impl Display for Framebuffer {
+    fn set_pixels<T>(&mut self, left: u16, top: u16, right: u16, bottom: u16, colors: &mut T)
+    where
+        T: Iterator<Item = u16>,
+    {
+        assert!(left <= right && top <= bottom && right < WIDTH && bottom < HEIGHT, "bad window");
+        let window = (top..=bottom)
+            .flat_map(|y| (left..=right).map(move |x| y as usize * WIDTH as usize + x as usize));
+        for (i, color) in window.zip(colors) {
+            self.pixels[i] = color;
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Rotation, mirroring and vertical scrolling of the ST7789 display, set with the MADCTL, VSCRDEF and VSCRSADD
+//! commands. The ST7789 has 320 rows of frame memory but the PineTime panel shows only 240 of them, so orientations
+//! that reverse the row order (MY) are shifted back onto the panel with the scroll start address. Hardware scrolling
+//! is only possible in the other orientations. Also maps touch coordinates on the panel back to the coordinates
+//! that were drawn, so that apps see the same orientation for drawing and touch.
+use crate::{ HEIGHT, WIDTH };
+
+/// ST7789 command: Memory Data Access Control, followed by 1 byte
+pub const MADCTL: u8 = 0x36;
+
+/// ST7789 command: Vertical Scrolling Definition, followed by the fixed top rows, scrolled rows and fixed bottom rows
+/// (2 bytes each, big-endian)
+pub const VSCRDEF: u8 = 0x33;
+
+/// ST7789 command: Vertical Scroll Start Address of RAM, followed by the frame memory row (2 bytes, big-endian)
+pub const VSCRSADD: u8 = 0x37;
+
+/// MADCTL bit: reverse the row order (page address order)
+pub const MADCTL_MY: u8 = 0x80;
+
+/// MADCTL bit: reverse the column order (column address order)
+pub const MADCTL_MX: u8 = 0x40;
+
+/// MADCTL bit: exchange rows and columns
+pub const MADCTL_MV: u8 = 0x20;
+
+/// MADCTL bit: blue-green-red colour order instead of red-green-blue
+pub const MADCTL_BGR: u8 = 0x08;
+
+/// Number of rows of ST7789 frame memory
+pub const FRAME_ROWS: u16 = 320;
+
+/// Rotation of the drawing on the panel, clockwise
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rotation {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+/// How the drawing is shown on the panel: mirrored left to right, then rotated
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Orientation {
+    /// Rotation, clockwise
+    pub rotation: Rotation,
+    /// True if mirrored left to right before rotating
+    pub mirror:   bool,
+}
+
+/// Rows of the panel that are scrolled by hardware, between the fixed rows at the top and bottom
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScrollArea {
+    /// Number of fixed rows at the top of the panel
+    pub top_fixed:    u16,
+    /// Number of fixed rows at the bottom of the panel
+    pub bottom_fixed: u16,
+}
+
+impl Rotation {
+    /// Return the rotation for the angle in degrees: 0, 90, 180 or 270
+    pub fn from_degrees(degrees: u16) -> Option<Rotation> {
+        match degrees {
+            0   => Some(Rotation::Deg0),
+            90  => Some(Rotation::Deg90),
+            180 => Some(Rotation::Deg180),
+            270 => Some(Rotation::Deg270),
+            _ => None,
+        }
+    }
+}
+
+impl Orientation {
+    /// Return the orientation with the rotation and mirroring
+    pub const fn new(rotation: Rotation, mirror: bool) -> Orientation {
+        Orientation { rotation, mirror }
+    }
+
+    /// Return the MADCTL bits for the orientation, without the colour order
+    pub fn madctl(self) -> u8 {
+        let madctl = match self.rotation {
+            Rotation::Deg0   => 0,
+            Rotation::Deg90  => MADCTL_MV | MADCTL_MX,
+            Rotation::Deg180 => MADCTL_MY | MADCTL_MX,
+            Rotation::Deg270 => MADCTL_MV | MADCTL_MY,
+        };
+        if !self.mirror { return madctl; }
+        //  Columns of the drawing go to frame memory columns, or to frame memory rows if exchanged
+        if madctl & MADCTL_MV == 0 { madctl ^ MADCTL_MX } else { madctl ^ MADCTL_MY }
+    }
+
+    /// Return the frame memory row shown on the top row of the panel. When the row order is reversed, the drawing
+    /// is written to the last 240 of the 320 rows.
+    pub fn row_offset(self) -> u16 {
+        if self.madctl() & MADCTL_MY == 0 { 0 } else { FRAME_ROWS - HEIGHT }
+    }
+
+    /// Return true if hardware scrolling may be used. Orientations with a row offset use the whole frame memory
+    /// as the scroll area, so the rows past the drawing would scroll into view.
+    pub fn can_scroll(self) -> bool {
+        self.row_offset() == 0
+    }
+
+    /// Return the parameters of VSCRDEF when not scrolling. With a row offset, the whole frame memory is the
+    /// scroll area, so that the scroll start address may skip the rows before the drawing.
+    pub fn vscrdef(self) -> [u8; 6] {
+        if self.can_scroll() { return ScrollArea::full().vscrdef(); }
+        let [h0, h1] = FRAME_ROWS.to_be_bytes();
+        [ 0, 0, h0, h1, 0, 0 ]
+    }
+
+    /// Return the parameters of VSCRSADD when not scrolling
+    pub fn vscrsadd(self) -> [u8; 2] {
+        self.row_offset().to_be_bytes()
+    }
+
+    /// Map the point (`x`, `y`) of the drawing to the panel
+    pub fn to_panel(self, x: u16, y: u16) -> (u16, u16) {
+        let (max_x, max_y) = (WIDTH - 1, HEIGHT - 1);
+        let x = if self.mirror { max_x - x } else { x };
+        match self.rotation {
+            Rotation::Deg0   => (x, y),
+            Rotation::Deg90  => (max_y - y, x),
+            Rotation::Deg180 => (max_x - x, max_y - y),
+            Rotation::Deg270 => (y, max_x - x),
+        }
+    }
+
+    /// Map the point (`x`, `y`) of the panel, e.g. a touch, to the drawing. Points outside the panel are clamped.
+    pub fn to_drawing(self, x: u16, y: u16) -> (u16, u16) {
+        let (max_x, max_y) = (WIDTH - 1, HEIGHT - 1);
+        let (x, y) = (x.min(max_x), y.min(max_y));
+        let (x, y) = match self.rotation {
+            Rotation::Deg0   => (x, y),
+            Rotation::Deg90  => (y, max_x - x),
+            Rotation::Deg180 => (max_x - x, max_y - y),
+            Rotation::Deg270 => (max_y - y, x),
+        };
+        (if self.mirror { max_x - x } else { x }, y)
+    }
+}
+
+impl Default for Orientation {
+    /// Not rotated or mirrored
+    fn default() -> Orientation {
+        Orientation::new(Rotation::Deg0, false)
+    }
+}
+
+impl ScrollArea {
+    /// Return the scroll area between the fixed rows at the top and bottom of the panel. At least 1 row must scroll.
+    pub fn new(top_fixed: u16, bottom_fixed: u16) -> Option<ScrollArea> {
+        if top_fixed as u32 + bottom_fixed as u32 >= HEIGHT as u32 { return None; }
+        Some(ScrollArea { top_fixed, bottom_fixed })
+    }
+
+    /// Return the scroll area for the whole panel
+    pub const fn full() -> ScrollArea {
+        ScrollArea { top_fixed: 0, bottom_fixed: 0 }
+    }
+
+    /// Return the number of rows that scroll
+    pub fn height(self) -> u16 {
+        HEIGHT - self.top_fixed - self.bottom_fixed
+    }
+
+    /// Return the parameters of VSCRDEF. The frame memory rows below the panel belong to the fixed bottom rows.
+    pub fn vscrdef(self) -> [u8; 6] {
+        let bottom = self.bottom_fixed + FRAME_ROWS - HEIGHT;
+        let [t0, t1] = self.top_fixed.to_be_bytes();
+        let [h0, h1] = self.height().to_be_bytes();
+        let [b0, b1] = bottom.to_be_bytes();
+        [ t0, t1, h0, h1, b0, b1 ]
+    }
+
+    /// Return the parameters of VSCRSADD to scroll the drawing up by `lines` rows. Rows that scroll off the top of
+    /// the scroll area reappear at the bottom.
+    pub fn vscrsadd(self, lines: u16) -> [u8; 2] {
+        (self.top_fixed + lines % self.height()).to_be_bytes()
+    }
+
+    /// Return the row of the drawing that is shown on the row of the panel after scrolling up by `lines` rows
+    pub fn to_drawing_row(self, lines: u16, row: u16) -> u16 {
+        if row < self.top_fixed || row >= self.top_fixed + self.height() { return row; }
+        self.top_fixed + (row - self.top_fixed + lines % self.height()) % self.height()
+    }
+}
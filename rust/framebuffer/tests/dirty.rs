@@ -0,0 +1,153 @@
+//! Check the merging of dirty rectangles, and that flushing them refreshes only the changed windows. Run on the host
+//! computer: `cargo test -p framebuffer --target x86_64-unknown-linux-gnu`
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::Rgb565,
+    primitives::{ Circle, Rectangle },
+};
+use framebuffer::{
+    dirty::{ BackBuffer, DirtyRects, Rect, Tracker, FLUSH_PIXELS, MAX_RECTS },
+    Display, Framebuffer, HEIGHT, WIDTH,
+};
+
+/// Display that records the windows set by `set_pixels()`, and the number of colours in each window
+#[derive(Default)]
+struct Recorder {
+    windows: Vec<(Rect, usize)>,
+}
+
+impl Drawing<Rgb565> for Recorder {
+    fn draw<T>(&mut self, _item: T)
+    where
+        T: IntoIterator<Item = Pixel<Rgb565>>,
+    {
+    }
+}
+
+impl Display for Recorder {
+    fn set_pixels<T>(&mut self, left: u16, top: u16, right: u16, bottom: u16, colors: &mut T)
+    where
+        T: Iterator<Item = u16>,
+    {
+        self.windows.push((Rect::new(left, top, right, bottom), colors.count()));
+    }
+}
+
+#[test]
+fn nearby_rects_are_merged() {
+    let mut dirty = DirtyRects::new();
+    assert!(dirty.is_empty());
+    dirty.add(Rect::new(10, 10, 19, 19));
+    dirty.add(Rect::new(15, 15, 24, 24));  //  Overlapping
+    dirty.add(Rect::new(25, 10, 30, 19));  //  Adjacent
+    dirty.add_pixel(12, 12);                //  Inside
+    assert_eq!(dirty.rects(), &[ Rect::new(10, 10, 30, 24) ]);
+
+    //  Far away rects are kept apart
+    dirty.add(Rect::new(200, 200, 209, 209));
+    assert_eq!(dirty.rects().len(), 2);
+
+    //  Rects are clipped to the display
+    dirty.add(Rect::new(230, 0, 300, 5));
+    dirty.add(Rect::new(240, 240, 250, 250));
+    assert_eq!(dirty.rects().len(), 3);
+    assert!(dirty.rects().contains(&Rect::new(230, 0, 239, 5)));
+
+    dirty.clear();
+    assert!(dirty.is_empty());
+}
+
+#[test]
+fn full_rects_merge_into_nearest() {
+    let mut dirty = DirtyRects::new();
+    let mut added = Vec::new();
+    for i in 0..30 {
+        let (x, y) = ((i * 37 % 23) * 10, (i * 11 % 23) * 10);
+        let rect = Rect::new(x, y, x + 2, y + 2);
+        dirty.add(rect);
+        added.push(rect);
+        assert!(dirty.rects().len() <= MAX_RECTS);
+    }
+    //  Every rect that was added is covered by a dirty rect
+    for rect in added {
+        assert!(dirty.rects().iter().any(|dirty| dirty.contains(rect)), "{:?} not dirty", rect);
+    }
+}
+
+#[test]
+fn flush_sends_only_dirty_windows() {
+    //  Back buffer that is drawn, and front buffer that shows what has been flushed
+    let mut back  = Box::new(Framebuffer::new());
+    let mut front = Box::new(Framebuffer::new());
+    let mut dirty = DirtyRects::new();
+    {
+        let mut tracker = Tracker::new(&mut *back, &mut dirty);
+        tracker.draw(Rectangle::<Rgb565>::new(Coord::new(10, 10), Coord::new(49, 29))
+            .fill(Some(Rgb565(0xf800))));
+        tracker.draw(Circle::<Rgb565>::new(Coord::new(180, 180), 20)
+            .stroke(Some(Rgb565(0x07e0))));
+        tracker.set_pixels(100, 0, 101, 1, &mut [0x001f; 4].iter().cloned());
+    }
+    assert_eq!(dirty.rects().len(), 3);
+    let pixels: u32 = dirty.rects().iter().map(|rect| rect.area()).sum();
+    assert!(pixels < 4000, "{} pixels dirty", pixels);
+
+    dirty.flush(&mut *front, |x, y| back.pixel(x, y));
+    assert!(dirty.is_empty());
+    assert_eq!(front.pixels(), back.pixels());
+}
+
+#[test]
+fn flush_splits_large_windows() {
+    let mut dirty = DirtyRects::new();
+    dirty.add(Rect::full());
+    let mut recorder = Recorder::default();
+    dirty.flush(&mut recorder, |x, y| x ^ y);
+    let mut sent = 0;
+    for (window, count) in &recorder.windows {
+        assert!(*count as u32 <= FLUSH_PIXELS);
+        assert_eq!(*count as u32, window.area());
+        assert_eq!((window.left, window.right), (0, WIDTH - 1));
+        sent += count;
+    }
+    assert_eq!(sent, WIDTH as usize * HEIGHT as usize);
+}
+
+#[test]
+fn back_buffer_flushes_changed_windows_only() {
+    //  Back buffer for 20 rows in the middle of the display
+    let window = Rect::new(0, 100, WIDTH - 1, 119);
+    let mut pixels = vec![0; window.area() as usize];
+    let mut back = BackBuffer::new(window, &mut pixels);
+    let mut dirty = DirtyRects::new();
+    {
+        let mut tracker = Tracker::new(&mut back, &mut dirty);
+        tracker.draw(Rectangle::<Rgb565>::new(Coord::new(10, 105), Coord::new(29, 114))
+            .fill(Some(Rgb565(0xf800))));
+        tracker.set_pixels(200, 110, 201, 111, &mut [0x001f; 4].iter().cloned());
+        //  Pixels outside the window are dropped
+        tracker.draw(Rectangle::<Rgb565>::new(Coord::new(10, 90), Coord::new(29, 99))
+            .fill(Some(Rgb565(0x07e0))));
+    }
+    assert_eq!(back.pixel(10, 105), 0xf800);
+    assert_eq!(back.pixel(201, 111), 0x001f);
+    assert_eq!(back.pixel(100, 110), 0);
+
+    //  Only the changed windows inside the back buffer are sent
+    dirty = DirtyRects::new();
+    let mut tracker = Tracker::new(&mut back, &mut dirty);
+    tracker.draw(Rectangle::<Rgb565>::new(Coord::new(10, 105), Coord::new(29, 114))
+        .fill(Some(Rgb565(0xffff))));
+    let mut recorder = Recorder::default();
+    back.flush(&mut dirty, &mut recorder);
+    assert_eq!(recorder.windows, vec![ (Rect::new(10, 105, 29, 114), 200) ]);
+    assert!(dirty.is_empty());
+
+    //  Flushed pixels match the back buffer
+    let mut front = Box::new(Framebuffer::new());
+    dirty.add(window);
+    back.flush(&mut dirty, &mut *front);
+    assert_eq!(front.pixel(10, 105), 0xffff);
+    assert_eq!(front.pixel(200, 110), 0x001f);
+    assert_eq!(front.pixel(10, 95), 0);
+}
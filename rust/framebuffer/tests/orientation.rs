@@ -0,0 +1,132 @@
+//! Check the ST7789 commands for rotating, mirroring and scrolling the display, by writing a drawing into a simulated
+//! ST7789 frame memory with the MADCTL bits and reading back what the panel shows with the VSCRDEF and VSCRSADD
+//! parameters. Run on the host computer:
+//! `cargo test -p framebuffer --target x86_64-unknown-linux-gnu`
+use framebuffer::{
+    orientation::{
+        FRAME_ROWS, MADCTL_MV, MADCTL_MX, MADCTL_MY,
+        Orientation, Rotation, ScrollArea,
+    },
+    HEIGHT, WIDTH,
+};
+
+/// Every rotation
+const ROTATIONS: [Rotation; 4] = [ Rotation::Deg0, Rotation::Deg90, Rotation::Deg180, Rotation::Deg270 ];
+
+/// ST7789 frame memory of 240 columns and 320 rows, with the vertical scroll settings
+struct St7789 {
+    /// Pixel of each frame memory row and column
+    memory:   Vec<u16>,
+    /// Number of fixed rows at the top, set by VSCRDEF
+    top:      u16,
+    /// Number of scrolled rows, set by VSCRDEF
+    height:   u16,
+    /// Frame memory row shown on the first row of the scroll area, set by VSCRSADD
+    start:    u16,
+}
+
+impl St7789 {
+    /// Write the 240x240 drawing into frame memory, window by window like the display driver, with the MADCTL bits.
+    /// Each pixel of the drawing is its position, `y * 240 + x`.
+    fn new(madctl: u8, vscrdef: [u8; 6], vscrsadd: [u8; 2]) -> St7789 {
+        let mut memory = vec![0xffff; WIDTH as usize * FRAME_ROWS as usize];
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                //  Exchange the column and row addresses, then reverse the order of frame memory columns and rows
+                let (col, row) = if madctl & MADCTL_MV == 0 { (x, y) } else { (y, x) };
+                let col = if madctl & MADCTL_MX == 0 { col } else { WIDTH - 1 - col };
+                let row = if madctl & MADCTL_MY == 0 { row } else { FRAME_ROWS - 1 - row };
+                memory[row as usize * WIDTH as usize + col as usize] = y * WIDTH + x;
+            }
+        }
+        let top    = u16::from_be_bytes([vscrdef[0], vscrdef[1]]);
+        let height = u16::from_be_bytes([vscrdef[2], vscrdef[3]]);
+        let bottom = u16::from_be_bytes([vscrdef[4], vscrdef[5]]);
+        assert_eq!(top + height + bottom, FRAME_ROWS, "VSCRDEF must cover the frame memory");
+        St7789 { memory, top, height, start: u16::from_be_bytes(vscrsadd) }
+    }
+
+    /// Return the pixel shown on the panel at (`x`, `y`)
+    fn panel(&self, x: u16, y: u16) -> u16 {
+        let row = if y < self.top || y >= self.top + self.height { y }
+            else { self.top + (self.start - self.top + y - self.top) % self.height };
+        self.memory[row as usize * WIDTH as usize + x as usize]
+    }
+}
+
+/// Return every orientation
+fn orientations() -> Vec<Orientation> {
+    ROTATIONS.iter()
+        .flat_map(|rotation| vec![ Orientation::new(*rotation, false), Orientation::new(*rotation, true) ])
+        .collect()
+}
+
+#[test]
+fn panel_shows_drawing_in_orientation() {
+    for orientation in orientations() {
+        let display = St7789::new(orientation.madctl(), orientation.vscrdef(), orientation.vscrsadd());
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let (px, py) = orientation.to_panel(x, y);
+                assert_eq!(display.panel(px, py), y * WIDTH + x, "{:?} at ({}, {})", orientation, x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn rotations_turn_clockwise() {
+    //  Top left corner of the drawing goes to the top right corner of the panel after rotating 90 degrees
+    let corner = |rotation, mirror| Orientation::new(rotation, mirror).to_panel(0, 0);
+    assert_eq!(corner(Rotation::Deg0,   false), (0,   0));
+    assert_eq!(corner(Rotation::Deg90,  false), (239, 0));
+    assert_eq!(corner(Rotation::Deg180, false), (239, 239));
+    assert_eq!(corner(Rotation::Deg270, false), (0,   239));
+    assert_eq!(corner(Rotation::Deg0,   true),  (239, 0));
+    assert_eq!(Rotation::from_degrees(270), Some(Rotation::Deg270));
+    assert_eq!(Rotation::from_degrees(45), None);
+}
+
+#[test]
+fn touch_maps_back_to_drawing() {
+    for orientation in orientations() {
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let (px, py) = orientation.to_panel(x, y);
+                assert_eq!(orientation.to_drawing(px, py), (x, y), "{:?} at ({}, {})", orientation, x, y);
+            }
+        }
+        //  Touches outside the panel are clamped
+        assert_eq!(orientation.to_drawing(500, 500), orientation.to_drawing(239, 239));
+    }
+}
+
+#[test]
+fn only_orientations_without_row_offset_scroll() {
+    for orientation in orientations() {
+        let reversed = orientation.madctl() & MADCTL_MY != 0;
+        assert_eq!(orientation.can_scroll(), !reversed, "{:?}", orientation);
+        assert_eq!(orientation.row_offset(), if reversed { 80 } else { 0 }, "{:?}", orientation);
+    }
+}
+
+#[test]
+fn scroll_area_scrolls_drawing_up() {
+    assert_eq!(ScrollArea::new(120, 120), None);
+    let area = ScrollArea::new(20, 30).unwrap();
+    assert_eq!(area.height(), 190);
+    for orientation in orientations().into_iter().filter(|o| o.can_scroll()) {
+        for lines in &[0, 1, 100, 189, 190, 500] {
+            let display = St7789::new(orientation.madctl(), area.vscrdef(), area.vscrsadd(*lines));
+            for py in 0..HEIGHT {
+                for px in 0..WIDTH {
+                    //  Undo the scrolling, then the orientation
+                    let row = area.to_drawing_row(*lines, py);
+                    let (x, y) = orientation.to_drawing(px, row);
+                    assert_eq!(display.panel(px, py), y * WIDTH + x,
+                        "{:?} scrolled {} at ({}, {})", orientation, lines, px, py);
+                }
+            }
+        }
+    }
+}
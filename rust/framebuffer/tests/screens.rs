@@ -0,0 +1,61 @@
+//! Render the screens of the Rust Application into the framebuffer and compare with the golden images in
+//! `tests/golden`. The screens are included from `rust/app/src` with `#[path]`, so they must draw only onto
+//! `framebuffer::Display` and must not call Mynewt. The druid UI widgets are not covered: druid builds only for Mynewt.
+//! Run on the host computer:
+//! `cargo test -p framebuffer --features std --target x86_64-unknown-linux-gnu`
+//! After an intended change to a screen, check the rendered screens and update the golden images:
+//! `UPDATE_GOLDEN=1 cargo test -p framebuffer --features std --target x86_64-unknown-linux-gnu`
+use framebuffer::{
+    golden,
+    Display, Framebuffer,
+};
+use std::path::{ Path, PathBuf };
+
+#[path = "../../app/src/test_screen.rs"]
+mod test_screen;   //  Test screen of the graphics display app
+
+#[path = "../../app/src/chip8_render.rs"]
+#[allow(dead_code, clippy::all)]  //  Code of the app, not all of it is used here
+mod chip8_render;  //  Renderer for the CHIP8 Emulator app
+
+/// Return the path of the golden image
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name)
+}
+
+#[test]
+fn set_pixels_fills_window_row_by_row() {
+    let mut framebuffer = Box::new(Framebuffer::new());
+    framebuffer.set_pixels(10, 20, 12, 21, &mut (1..100));
+    assert_eq!(framebuffer.pixel(10, 20), 1);
+    assert_eq!(framebuffer.pixel(12, 20), 3);
+    assert_eq!(framebuffer.pixel(10, 21), 4);
+    assert_eq!(framebuffer.pixel(12, 21), 6);
+    assert_eq!(framebuffer.pixel(13, 21), 0);
+    assert_eq!(framebuffer.pixels().iter().filter(|pixel| **pixel != 0).count(), 6);
+}
+
+#[test]
+fn test_screen_matches_golden() {
+    let mut framebuffer = Box::new(Framebuffer::new());
+    test_screen::draw_shapes(&mut *framebuffer);
+    test_screen::draw_text(&mut *framebuffer);
+    golden::compare(&framebuffer, &golden_path("test_screen.png")).unwrap();
+}
+
+#[test]
+fn chip8_screen_matches_golden() {
+    use chip8_render::{ SCREEN_BUFFER, SCREEN_HEIGHT, SCREEN_WIDTH };
+
+    //  Fill the Virtual Screen with blocks of white, greenish, dark blue and black Virtual Pixels
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            let grey = [255, 200, 100, 0][(x / 8 + y / 8) % 4];
+            unsafe { SCREEN_BUFFER[y * SCREEN_WIDTH + x] = grey; }
+        }
+    }
+    let mut framebuffer = Box::new(Framebuffer::new());
+    chip8_render::render_region(&mut *framebuffer, 0, 0, SCREEN_WIDTH as u8 - 1, SCREEN_HEIGHT as u8 - 1);
+    let name = if cfg!(feature = "chip8_curve") { "chip8_curve.png" } else { "chip8.png" };
+    golden::compare(&framebuffer, &golden_path(name)).unwrap();
+}
@@ -0,0 +1,115 @@
+//! Draw the status bar of the Rust Application into the framebuffer and compare with the golden image in
+//! `tests/golden`. Check that only the changed fields are redrawn. The status bar is included from `rust/app/src`.
+//! Run on the host computer: `cargo test -p framebuffer --features std --target x86_64-unknown-linux-gnu`
+//! After an intended change to the status bar, check the rendered image and update the golden image:
+//! `UPDATE_GOLDEN=1 cargo test -p framebuffer --features std --target x86_64-unknown-linux-gnu`
+use framebuffer::{
+    dirty::{ BackBuffer, DirtyRects, Rect },
+    golden, Framebuffer, WIDTH,
+};
+use std::path::Path;
+
+#[path = "../../app/src/status_bar.rs"]
+mod status_bar;  //  Status bar shared by all apps
+
+use status_bar::{ battery_level, local_time, Status, StatusBar, HEIGHT };
+
+/// Status shown at the start of each test
+const STATUS: Status = Status { time: Some((9, 41)), battery: Some(76), charging: false, connected: true };
+
+#[test]
+fn local_time_applies_time_zone() {
+    assert_eq!(local_time(0, 0), (0, 0));
+    assert_eq!(local_time(1_600_000_000, 0), (12, 26));     //  2020-09-13 12:26:40 UTC
+    assert_eq!(local_time(1_600_000_000, -480), (20, 26));  //  UTC+8
+    assert_eq!(local_time(1_600_000_000, 780), (23, 26));   //  UTC-13, the day before
+}
+
+#[test]
+fn battery_level_follows_discharge_curve() {
+    assert_eq!(battery_level(0), 0);
+    assert_eq!(battery_level(3300), 0);
+    assert_eq!(battery_level(3400), 2);
+    assert_eq!(battery_level(3650), 17);
+    assert_eq!(battery_level(3800), 55);
+    assert_eq!(battery_level(4150), 96);
+    assert_eq!(battery_level(4200), 100);
+    assert_eq!(battery_level(4400), 100);
+}
+
+#[test]
+fn status_bar_matches_golden() {
+    let mut framebuffer = Box::new(Framebuffer::new());
+    let mut dirty = DirtyRects::new();
+    let mut status_bar = StatusBar::new();
+    assert!(status_bar.draw(&mut *framebuffer, &STATUS, &mut dirty));
+    assert!(dirty.rects().iter().all(|rect| rect.bottom < HEIGHT));
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/status_bar.png");
+    golden::compare(&framebuffer, &path).unwrap();
+}
+
+#[test]
+fn only_changed_fields_are_redrawn() {
+    let mut framebuffer = Box::new(Framebuffer::new());
+    let mut dirty = DirtyRects::new();
+    let mut status_bar = StatusBar::new();
+    status_bar.draw(&mut *framebuffer, &STATUS, &mut dirty);
+
+    //  Nothing is drawn if nothing has changed
+    dirty.clear();
+    assert!(!status_bar.draw(&mut *framebuffer, &STATUS, &mut dirty));
+    assert!(dirty.is_empty());
+
+    //  Only the clock is redrawn when the time changes
+    let clock = Rect::new(4, 4, 63, 19);
+    let status = Status { time: Some((9, 42)), ..STATUS };
+    assert!(status_bar.draw(&mut *framebuffer, &status, &mut dirty));
+    assert!(!dirty.is_empty());
+    assert!(dirty.rects().iter().all(|rect| clock.contains(*rect)));
+
+    //  Only the battery is redrawn when charging starts
+    let battery = Rect::new(164, 4, 235, 19);
+    dirty.clear();
+    let status = Status { charging: true, ..status };
+    assert!(status_bar.draw(&mut *framebuffer, &status, &mut dirty));
+    assert!(dirty.rects().iter().all(|rect| battery.contains(*rect)));
+
+    //  Everything is redrawn after invalidating
+    dirty.clear();
+    assert_eq!(status_bar.shown(), Some(status));
+    status_bar.invalidate();
+    assert_eq!(status_bar.shown(), None);
+    assert!(status_bar.draw(&mut *framebuffer, &status, &mut dirty));
+    assert!(dirty.rects().iter().any(|rect| rect.left == 0 && rect.top == 0));
+}
+
+#[test]
+fn back_buffer_flushes_redrawn_fields() {
+    //  Draw into the back buffer for the status bar and flush to the display, like `status.rs`
+    let window = Rect::new(0, 0, WIDTH - 1, HEIGHT - 1);
+    let mut pixels = vec![0; window.area() as usize];
+    let mut back = BackBuffer::new(window, &mut pixels);
+    let mut display = Box::new(Framebuffer::new());
+    let mut dirty = DirtyRects::new();
+    let mut status_bar = StatusBar::new();
+    status_bar.draw(&mut back, &STATUS, &mut dirty);
+    back.flush(&mut dirty, &mut *display);
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/status_bar.png");
+    golden::compare(&display, &path).unwrap();
+
+    //  Only the clock is flushed when the time changes
+    let clock = Rect::new(4, 4, 63, 19);
+    let status = Status { time: Some((9, 42)), ..STATUS };
+    status_bar.draw(&mut back, &status, &mut dirty);
+    assert!(dirty.rects().iter().all(|rect| clock.contains(*rect)));
+    let before = display.pixels().to_vec();
+    back.flush(&mut dirty, &mut *display);
+    let changed = (0..before.len())
+        .filter(|i| before[*i] != display.pixels()[*i])
+        .map(|i| ((i % WIDTH as usize) as u16, (i / WIDTH as usize) as u16))
+        .collect::<Vec<_>>();
+    assert!(!changed.is_empty());
+    for (x, y) in changed {
+        assert!(clock.contains(Rect::new(x, y, x, y)), "({}, {}) changed outside the clock", x, y);
+    }
+}
@@ -0,0 +1,284 @@
+//! Animated boot logo: a sequence of frames stored in a slot in External SPI Flash (see `slots.rs`).
+//! The first frame fills the animation, each following frame contains only the rectangles that changed since the
+//! previous frame. Every rectangle is a compressed boot logo (see `header.rs` and `decoder.rs`) of the rectangle's
+//! size, at most `MAX_RECT_SIZE` bytes, so that the player can load it into a small buffer.
+//! Like `layout.rs`, the header has its own sector and is written last. Offsets are relative to the start of the slot:
+//! ```text
+//! Offset  Size  Contents
+//!      0  4096  Animation header, padded with 0xFF
+//!   4096     n  Frames
+//! ```
+//! The animation header is 24 bytes, all fields little-endian:
+//! ```text
+//! Offset  Size  Field
+//!      0     4  Magic number "PTLA"
+//!      4     1  Version (1)
+//!      5     1  Reserved (0)
+//!      6     2  Number of frames
+//!      8     2  Width
+//!     10     2  Height
+//!     12     2  Number of times to play the frames
+//!     14     2  Reserved (0)
+//!     16     4  Number of bytes of frames
+//!     20     4  CRC32 of the frames
+//! ```
+//! Each frame is a frame header followed by its rectangles:
+//! ```text
+//! Frame header (8 bytes)         Rectangle
+//!      0     2  Delay (ms)            0     2  Left
+//!      2     2  Number of rects       2     2  Top
+//!      4     4  Bytes of rects        4     n  Compressed boot logo (header and payload)
+//! ```
+//! The bootloader doesn't show animations: `parse_flash_header()` rejects the magic number.
+use crate::{
+    crc32::Crc32,
+    decoder::Decoder,
+    flash::Flash,
+    header::{ Header, HEADER_SIZE },
+    layout::{ SECTOR_SIZE, SLOT_SIZE },
+    slots,
+    Error, LogoResult,
+};
+
+/// Magic number at the start of the animation header
+pub const MAGIC: [u8; 4] = *b"PTLA";
+
+/// Version of the animation format
+pub const VERSION: u8 = 1;
+
+/// Size of the animation header
+pub const ANIMATION_HEADER_SIZE: usize = 24;
+
+/// Size of the frame header
+pub const FRAME_HEADER_SIZE: usize = 8;
+
+/// Size of the rectangle position that comes before the compressed boot logo
+pub const RECT_HEADER_SIZE: usize = 4;
+
+/// Max size of a rectangle: position, header and payload
+pub const MAX_RECT_SIZE: usize = 4096;
+
+/// Offset of the frames, relative to the start of the slot
+pub const FRAMES_OFFSET: u32 = SECTOR_SIZE;
+
+/// Max number of bytes of frames in a slot
+pub const MAX_FRAMES_SIZE: u32 = SLOT_SIZE - FRAMES_OFFSET;
+
+/// Max number of bytes of pixels passed to the draw function: 8 rows of 240 pixels
+pub const BAND_SIZE: usize = crate::WIDTH as usize * 8 * crate::BYTES_PER_PIXEL;
+
+/// Header of the animation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnimationHeader {
+    /// Number of frames
+    pub frame_count: u16,
+    /// Width of the animation, in pixels
+    pub width:       u16,
+    /// Height of the animation, in pixels
+    pub height:      u16,
+    /// Number of times to play the frames, at least 1
+    pub loops:       u16,
+    /// Number of bytes of frames
+    pub frames_len:  u32,
+    /// CRC32 of the frames
+    pub crc:         u32,
+}
+
+/// Header of a frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameHeader {
+    /// Number of milliseconds to show the frame
+    pub delay_ms:   u16,
+    /// Number of rectangles that changed
+    pub rect_count: u16,
+    /// Number of bytes of rectangles
+    pub rects_len:  u32,
+}
+
+/// Rectangle of pixels on the display
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    /// Left column
+    pub left:   u16,
+    /// Top row
+    pub top:    u16,
+    /// Width in pixels
+    pub width:  u16,
+    /// Height in pixels
+    pub height: u16,
+}
+
+/// Frame drawn by `Player::draw_frame()`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    /// Number of milliseconds to show the frame
+    pub delay_ms: u16,
+    /// Offset of the next frame in flash
+    pub next:     u32,
+}
+
+/// Animation opened by `Player::open()`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Animation {
+    /// Header of the animation
+    pub header: AnimationHeader,
+    /// Offset of the first frame in flash
+    pub first:  u32,
+}
+
+impl AnimationHeader {
+    /// Parse the animation header. Fail if the header is invalid or the animation doesn't fit the display.
+    pub fn parse(bytes: &[u8]) -> LogoResult<AnimationHeader> {
+        if bytes.len() < ANIMATION_HEADER_SIZE { return Err(Error::Truncated); }
+        if bytes[0..4] != MAGIC   { return Err(Error::BadMagic); }
+        if bytes[4] != VERSION    { return Err(Error::BadVersion); }
+        let header = AnimationHeader {
+            frame_count: u16::from_le_bytes([bytes[6], bytes[7]]),
+            width:       u16::from_le_bytes([bytes[8], bytes[9]]),
+            height:      u16::from_le_bytes([bytes[10], bytes[11]]),
+            loops:       u16::from_le_bytes([bytes[12], bytes[13]]),
+            frames_len:  u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]),
+            crc:         u32::from_le_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]),
+        };
+        if header.width == 0 || header.height == 0
+            || header.width > crate::WIDTH || header.height > crate::HEIGHT { return Err(Error::BadSize); }
+        if header.frame_count == 0 || header.loops == 0 { return Err(Error::Corrupted); }
+        if header.frames_len > MAX_FRAMES_SIZE { return Err(Error::BadSize); }
+        Ok(header)
+    }
+
+    /// Return the header as bytes
+    pub fn to_bytes(&self) -> [u8; ANIMATION_HEADER_SIZE] {
+        let mut bytes = [0; ANIMATION_HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4] = VERSION;
+        bytes[6..8].copy_from_slice(&self.frame_count.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.width.to_le_bytes());
+        bytes[10..12].copy_from_slice(&self.height.to_le_bytes());
+        bytes[12..14].copy_from_slice(&self.loops.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.frames_len.to_le_bytes());
+        bytes[20..24].copy_from_slice(&self.crc.to_le_bytes());
+        bytes
+    }
+}
+
+impl FrameHeader {
+    /// Parse the frame header
+    pub fn parse(bytes: &[u8]) -> LogoResult<FrameHeader> {
+        if bytes.len() < FRAME_HEADER_SIZE { return Err(Error::Truncated); }
+        Ok(FrameHeader {
+            delay_ms:   u16::from_le_bytes([bytes[0], bytes[1]]),
+            rect_count: u16::from_le_bytes([bytes[2], bytes[3]]),
+            rects_len:  u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        })
+    }
+
+    /// Return the header as bytes
+    pub fn to_bytes(&self) -> [u8; FRAME_HEADER_SIZE] {
+        let mut bytes = [0; FRAME_HEADER_SIZE];
+        bytes[0..2].copy_from_slice(&self.delay_ms.to_le_bytes());
+        bytes[2..4].copy_from_slice(&self.rect_count.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.rects_len.to_le_bytes());
+        bytes
+    }
+}
+
+/// Plays the animation stored in a slot of the flash device, one frame at a time. Each rectangle is loaded into
+/// a buffer and decoded in bands of rows, which are passed to the draw function.
+pub struct Player<F> {
+    /// Flash device that contains the slots
+    flash: F,
+    /// Rectangle loaded from flash: position, header and payload
+    rect:  [u8; MAX_RECT_SIZE],
+    /// Band of decoded big-endian RGB565 pixels
+    band:  [u8; BAND_SIZE],
+}
+
+impl<F> Player<F> {
+    /// Create a player for the flash device
+    pub const fn new(flash: F) -> Player<F> {
+        Player {
+            flash,
+            rect: [0; MAX_RECT_SIZE],
+            band: [0; BAND_SIZE],
+        }
+    }
+
+    /// Return the flash device
+    pub fn flash(&self) -> &F {
+        &self.flash
+    }
+}
+
+impl<F: Flash> Player<F> {
+    /// Open the animation in the slot. Fail if the header is invalid or the CRC32 of the frames doesn't match.
+    pub fn open(&mut self, slot: u8) -> LogoResult<Animation> {
+        let slot_offset = slots::slot_offset(slot) ? ;
+        let bytes = &mut self.band[..ANIMATION_HEADER_SIZE];
+        self.flash.read(slot_offset, bytes) ? ;
+        let header = AnimationHeader::parse(bytes) ? ;
+
+        //  Verify the frames
+        let first = slot_offset + FRAMES_OFFSET;
+        let mut crc = Crc32::new();
+        let mut pos = 0;
+        while pos < header.frames_len {
+            let size = core::cmp::min(BAND_SIZE as u32, header.frames_len - pos) as usize;
+            self.flash.read(first + pos, &mut self.band[..size]) ? ;
+            crc.update(&self.band[..size]);
+            pos += size as u32;
+        }
+        if crc.finish() != header.crc { return Err(Error::BadChecksum); }
+        Ok(Animation { header, first })
+    }
+
+    /// Draw the frame at the offset, starting with `Animation::first`. `draw` is called with each band of decoded
+    /// pixels and its position. Returns the delay and the offset of the next frame.
+    pub fn draw_frame<D>(&mut self, animation: &Animation, offset: u32, mut draw: D) -> LogoResult<Frame>
+    where D: FnMut(&Rect, &[u8]) -> LogoResult<()> {
+        let end = animation.first + animation.header.frames_len;
+        if offset < animation.first || offset + FRAME_HEADER_SIZE as u32 > end { return Err(Error::BadOffset); }
+        let bytes = &mut self.band[..FRAME_HEADER_SIZE];
+        self.flash.read(offset, bytes) ? ;
+        let frame = FrameHeader::parse(bytes) ? ;
+        let next = offset + FRAME_HEADER_SIZE as u32 + frame.rects_len;
+        if next > end { return Err(Error::Corrupted); }
+
+        let Player { flash, rect, band } = self;
+        let mut pos = offset + FRAME_HEADER_SIZE as u32;
+        for _ in 0..frame.rect_count {
+            //  Load the rectangle position and header, then the payload
+            let head = RECT_HEADER_SIZE + HEADER_SIZE;
+            if pos + head as u32 > next { return Err(Error::Corrupted); }
+            flash.read(pos, &mut rect[..head]) ? ;
+            let header = Header::parse(&rect[RECT_HEADER_SIZE..head]) ? ;
+            let len = head + header.payload_len as usize;
+            if len > MAX_RECT_SIZE || pos + len as u32 > next { return Err(Error::Corrupted); }
+            flash.read(pos + head as u32, &mut rect[head..len]) ? ;
+            let area = Rect {
+                left:   u16::from_le_bytes([rect[0], rect[1]]),
+                top:    u16::from_le_bytes([rect[2], rect[3]]),
+                width:  header.width,
+                height: header.height,
+            };
+            if area.left as u32 + area.width as u32 > animation.header.width as u32
+                || area.top as u32 + area.height as u32 > animation.header.height as u32 { return Err(Error::BadSize); }
+
+            //  Decode the rectangle in bands of whole rows
+            let row_size = area.width as usize * crate::BYTES_PER_PIXEL;
+            let band_rows = BAND_SIZE / row_size;
+            let mut decoder = Decoder::new(&rect[RECT_HEADER_SIZE..len]) ? ;
+            let mut top = area.top;
+            loop {
+                let size = decoder.read(&mut band[..band_rows * row_size]) ? ;
+                if size == 0 { break; }
+                let rows = (size / row_size) as u16;
+                draw(&Rect { left: area.left, top, width: area.width, height: rows }, &band[..size]) ? ;
+                top += rows;
+            }
+            pos += len as u32;
+        }
+        if pos != next { return Err(Error::Corrupted); }
+        Ok(Frame { delay_ms: frame.delay_ms, next })
+    }
+}
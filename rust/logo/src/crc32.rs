@@ -0,0 +1,54 @@
+//! CRC32 checksum (IEEE 802.3, same as zlib) for verifying the boot logo.
+//! Uses a 16-entry table to keep the Flash ROM usage small.
+
+/// CRC32 for each 4-bit nibble, with reversed polynomial `0xEDB88320`
+const NIBBLE_TABLE: [u32; 16] = [
+    0x0000_0000, 0x1db7_1064, 0x3b6e_20c8, 0x26d9_30ac,
+    0x76dc_4190, 0x6b6b_51f4, 0x4db2_6158, 0x5005_713c,
+    0xedb8_8320, 0xf00f_9344, 0xd6d6_a3e8, 0xcb61_b38c,
+    0x9b64_c2b0, 0x86d3_d2d4, 0xa00a_e278, 0xbdbd_f21c,
+];
+
+/// CRC32 that may be computed incrementally, one batch of bytes at a time
+#[derive(Clone, Copy)]
+pub struct Crc32 {
+    /// Current CRC value, before the final inversion
+    value: u32,
+}
+
+impl Crc32 {
+    /// Start a new CRC32
+    pub const fn new() -> Self {
+        Crc32 { value: 0xffff_ffff }
+    }
+
+    /// Add the bytes to the CRC32
+    pub fn update(&mut self, data: &[u8]) {
+        let mut crc = self.value;
+        for byte in data {
+            crc ^= *byte as u32;
+            crc = (crc >> 4) ^ NIBBLE_TABLE[(crc & 0x0f) as usize];
+            crc = (crc >> 4) ^ NIBBLE_TABLE[(crc & 0x0f) as usize];
+        }
+        self.value = crc;
+    }
+
+    /// Return the CRC32 of all bytes added so far
+    pub fn finish(&self) -> u32 {
+        !self.value
+    }
+}
+
+/// Start a new CRC32
+impl Default for Crc32 {
+    fn default() -> Self {
+        Crc32::new()
+    }
+}
+
+/// Return the CRC32 of the bytes
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
@@ -0,0 +1,150 @@
+//! Streaming decoder for the compressed boot logo. Expands the payload into big-endian RGB565 pixels,
+//! one batch at a time, so that the caller needs only a small buffer (e.g. 4096 bytes for `hal_flash_write`).
+//!
+//! For `Rgb565`, the payload contains the uncompressed pixels, which are copied as is.
+//! Otherwise the payload is run-length encoded in units: a unit is a RGB565 pixel (2 bytes) for `Rgb565Rle`,
+//! or a palette index (1 byte) for `Indexed8Rle`. Each run starts with a control byte `c`...
+//! ```text
+//! 0x00..=0x7F  Literal: c + 1 units follow
+//! 0x80..=0xFF  Repeat:  next byte n, then 1 unit that is repeated ((c & 0x7F) << 8 | n) + 1 times
+//! ```
+use crate::{
+    crc32::Crc32,
+    header::{ Header, PixelFormat, HEADER_SIZE },
+    Error, LogoResult,
+};
+
+/// Decoder that expands the compressed boot logo into big-endian RGB565 pixels
+pub struct Decoder<'a> {
+    /// Header of the compressed boot logo
+    header:    Header,
+    /// Palette of big-endian RGB565 colours, 2 bytes per colour. Empty if no palette.
+    palette:   &'a [u8],
+    /// Compressed runs that have not been decoded
+    data:      &'a [u8],
+    /// Number of units left in the current run
+    run:       u32,
+    /// True if the current run is a literal run, false if repeated
+    literal:   bool,
+    /// Pixel that is repeated in the current run
+    pixel:     u16,
+    /// Second byte of a pixel that didn't fit into the last batch
+    pending:   Option<u8>,
+    /// Number of pixels that have not been decoded
+    remaining: u32,
+    /// CRC32 of the decoded pixels so far
+    crc:       Crc32,
+}
+
+impl<'a> Decoder<'a> {
+    /// Create a decoder for the compressed boot logo, which starts with the header. Fail if the header is invalid.
+    pub fn new(logo: &'a [u8]) -> LogoResult<Decoder<'a>> {
+        let header = Header::parse(logo) ? ;
+        let payload_end = HEADER_SIZE + header.payload_len as usize;
+        if logo.len() < payload_end { return Err(Error::Truncated); }
+
+        //  Palette comes before the compressed runs
+        let palette_end = HEADER_SIZE + header.palette_len as usize * crate::BYTES_PER_PIXEL;
+        if palette_end > payload_end { return Err(Error::Truncated); }
+        Ok(Decoder {
+            header,
+            palette:   &logo[HEADER_SIZE..palette_end],
+            data:      &logo[palette_end..payload_end],
+            run:       0,
+            literal:   false,
+            pixel:     0,
+            pending:   None,
+            remaining: header.pixel_count(),
+            crc:       Crc32::new(),
+        })
+    }
+
+    /// Return the header of the compressed boot logo
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Decode the next batch of big-endian RGB565 bytes into the buffer. Returns the number of bytes decoded,
+    /// which is less than the buffer size only for the last batch. Returns 0 when all pixels have been decoded.
+    /// Fails if the compressed data is corrupted, or if the checksum doesn't match when the last pixel is decoded.
+    pub fn read(&mut self, buf: &mut [u8]) -> LogoResult<usize> {
+        let mut len = 0;
+        //  Write the second byte of the pixel that didn't fit into the last batch
+        if let Some(byte) = self.pending {
+            if buf.is_empty() { return Ok(0); }
+            buf[0] = byte;
+            self.pending = None;
+            len = 1;
+        }
+        //  Decode one pixel at a time until the buffer is full
+        while len < buf.len() && self.remaining > 0 {
+            let bytes = self.next_pixel() ? .to_be_bytes();
+            self.crc.update(&bytes);
+            self.remaining -= 1;
+            buf[len] = bytes[0];
+            len += 1;
+            if len < buf.len() {
+                buf[len] = bytes[1];
+                len += 1;
+            } else {
+                self.pending = Some(bytes[1]);
+            }
+        }
+        //  After decoding all pixels, verify the checksum
+        if self.remaining == 0 && self.pending.is_none() {
+            if self.run > 0 || !self.data.is_empty() { return Err(Error::Corrupted); }
+            if self.crc.finish() != self.header.crc  { return Err(Error::BadChecksum); }
+        }
+        Ok(len)
+    }
+
+    /// Return the next decoded pixel
+    fn next_pixel(&mut self) -> LogoResult<u16> {
+        //  Uncompressed pixels have no runs
+        if self.header.format == PixelFormat::Rgb565 { return self.next_unit(); }
+
+        //  Start a new run if the current run has ended
+        if self.run == 0 {
+            let control = self.next_byte() ? ;
+            if control & 0x80 == 0 {
+                //  Literal run: `control + 1` units follow
+                self.literal = true;
+                self.run = control as u32 + 1;
+            } else {
+                //  Repeated run: count follows, then the unit to be repeated
+                let low = self.next_byte() ? ;
+                self.literal = false;
+                self.run = ((((control & 0x7f) as u32) << 8) | low as u32) + 1;
+                self.pixel = self.next_unit() ? ;
+            }
+            if self.run > self.remaining { return Err(Error::Corrupted); }
+        }
+        self.run -= 1;
+        if self.literal { self.next_unit() }
+        else { Ok(self.pixel) }
+    }
+
+    /// Return the next unit in the compressed data as a RGB565 pixel
+    fn next_unit(&mut self) -> LogoResult<u16> {
+        match self.header.format {
+            PixelFormat::Rgb565 | PixelFormat::Rgb565Rle => {
+                let high = self.next_byte() ? ;
+                let low  = self.next_byte() ? ;
+                Ok(u16::from_be_bytes([high, low]))
+            }
+            PixelFormat::Indexed8Rle => {
+                let index = self.next_byte() ? as usize * crate::BYTES_PER_PIXEL;
+                if index + 1 >= self.palette.len() { return Err(Error::Corrupted); }
+                Ok(u16::from_be_bytes([self.palette[index], self.palette[index + 1]]))
+            }
+        }
+    }
+
+    /// Return the next byte in the compressed data
+    fn next_byte(&mut self) -> LogoResult<u8> {
+        let (byte, rest) = self.data.split_first()
+            .ok_or(Error::Truncated) ? ;
+        self.data = rest;
+        Ok(*byte)
+    }
+}
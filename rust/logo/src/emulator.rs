@@ -0,0 +1,114 @@
+//! In-memory emulator of External SPI Flash, for testing the Boot Logo Loader on the host computer.
+//! Models the sector erase granularity of NOR flash: writes fail unless the bytes have been erased since the
+//! last write. Counts the erases and writes of every sector. Requires the `std` feature.
+use std::{
+    vec,
+    vec::Vec,
+};
+use crate::{
+    flash::Flash,
+    layout::{ ERASED, SECTOR_SIZE },
+    Error, LogoResult,
+};
+
+/// Size of External SPI Flash on PineTime: 4 MB
+pub const FLASH_SIZE: u32 = 4 * 1024 * 1024;
+
+/// In-memory flash device
+pub struct FlashEmulator {
+    /// Contents of the flash
+    data:   Vec<u8>,
+    /// Number of times each sector has been erased
+    erases: Vec<u32>,
+    /// Number of times each sector has been written
+    writes: Vec<u32>,
+}
+
+impl FlashEmulator {
+    /// Create an erased flash device of the size, which must be a multiple of the sector size
+    pub fn new(size: u32) -> FlashEmulator {
+        assert_eq!(size % SECTOR_SIZE, 0, "bad flash size");
+        let sectors = (size / SECTOR_SIZE) as usize;
+        FlashEmulator {
+            data:   vec![ERASED; size as usize],
+            erases: vec![0; sectors],
+            writes: vec![0; sectors],
+        }
+    }
+
+    /// Return the contents of the flash
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Return the number of times the sector at the offset has been erased
+    pub fn erase_count(&self, offset: u32) -> u32 {
+        self.erases[(offset / SECTOR_SIZE) as usize]
+    }
+
+    /// Return the number of times the sector at the offset has been written
+    pub fn write_count(&self, offset: u32) -> u32 {
+        self.writes[(offset / SECTOR_SIZE) as usize]
+    }
+
+    /// Return the total number of sector erases
+    pub fn total_erases(&self) -> u32 {
+        self.erases.iter().sum()
+    }
+
+    /// Return the total number of sector writes
+    pub fn total_writes(&self) -> u32 {
+        self.writes.iter().sum()
+    }
+
+    /// Reset the erase and write counts
+    pub fn reset_counts(&mut self) {
+        self.erases.iter_mut().for_each(|count| *count = 0);
+        self.writes.iter_mut().for_each(|count| *count = 0);
+    }
+
+    /// Return the range of bytes, or `BadOffset` if the range is outside the flash
+    fn range(&self, offset: u32, len: usize) -> LogoResult<std::ops::Range<usize>> {
+        let start = offset as usize;
+        match start.checked_add(len) {
+            Some(end) if end <= self.data.len() => Ok(start..end),
+            _ => Err(Error::BadOffset),
+        }
+    }
+
+    /// Return the range of sectors that contain the range of bytes
+    fn sectors(range: &std::ops::Range<usize>) -> std::ops::Range<usize> {
+        let sector = SECTOR_SIZE as usize;
+        if range.start == range.end { return 0..0; }
+        range.start / sector..(range.end - 1) / sector + 1
+    }
+}
+
+impl Flash for FlashEmulator {
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> LogoResult<()> {
+        let range = self.range(offset, buf.len()) ? ;
+        buf.copy_from_slice(&self.data[range]);
+        Ok(())
+    }
+
+    /// Fail with `NotErased` if any byte hasn't been erased, without writing anything
+    fn write(&mut self, offset: u32, data: &[u8]) -> LogoResult<()> {
+        let range = self.range(offset, data.len()) ? ;
+        if self.data[range.clone()].iter().any(|byte| *byte != ERASED) { return Err(Error::NotErased); }
+        self.data[range.clone()].copy_from_slice(data);
+        for sector in Self::sectors(&range) {
+            self.writes[sector] += 1;
+        }
+        Ok(())
+    }
+
+    fn erase(&mut self, offset: u32, len: u32) -> LogoResult<()> {
+        let range = self.range(offset, len as usize) ? ;
+        let sector = SECTOR_SIZE as usize;
+        for index in Self::sectors(&range) {
+            self.data[index * sector..(index + 1) * sector].iter_mut().for_each(|byte| *byte = ERASED);
+            self.erases[index] += 1;
+        }
+        Ok(())
+    }
+}
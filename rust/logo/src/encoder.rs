@@ -0,0 +1,273 @@
+//! Encoder that compresses RGB565 pixels into the boot logo format read by `decoder.rs`.
+//! Runs on the host computer, requires the `std` feature.
+use std::{
+    vec,
+    vec::Vec,
+};
+use crate::{
+    animation::{ AnimationHeader, FrameHeader, Rect, FRAMES_OFFSET, MAX_FRAMES_SIZE, MAX_RECT_SIZE, RECT_HEADER_SIZE },
+    crc32::crc32,
+    header::{ Header, PixelFormat, HEADER_SIZE, MAX_PALETTE_COLORS },
+    layout::{ ERASED, PIXELS_OFFSET },
+    slots::{ self, SlotTable, LOGO_AREA_OFFSET, LOGO_AREA_SIZE, MAX_SLOTS, SLOT_TABLE_OFFSET },
+    Error, LogoResult,
+};
+
+/// Max number of units in a literal run
+const MAX_LITERAL: usize = 0x80;
+
+/// Max number of units in a repeated run
+const MAX_REPEAT: usize = 0x8000;
+
+/// Shortest repeated run. Shorter runs are stored as literals, because they don't save any space.
+const MIN_REPEAT: usize = 3;
+
+/// Compress the RGB565 pixels (row by row from the top left) into a boot logo with header, in the pixel format
+pub fn encode(pixels: &[u16], width: u16, height: u16, format: PixelFormat) -> LogoResult<Vec<u8>> {
+    if width == 0 || height == 0 || pixels.len() != width as usize * height as usize {
+        return Err(Error::BadSize);
+    }
+    //  Compute the palette and the runs
+    let mut palette = Vec::new();
+    let payload = match format {
+        PixelFormat::Rgb565 => to_bytes(pixels),
+        PixelFormat::Rgb565Rle => encode_runs(pixels, |pixel, out| out.extend_from_slice(&pixel.to_be_bytes())),
+        PixelFormat::Indexed8Rle => {
+            let indexes = to_indexes(pixels, &mut palette) ? ;
+            encode_runs(&indexes, |index, out| out.push(*index))
+        }
+    };
+    //  Checksum is computed over the decoded big-endian pixels
+    let image = to_bytes(pixels);
+    let header = Header {
+        format,
+        palette_len: palette.len() as u16,
+        width,
+        height,
+        payload_len: (palette.len() * crate::BYTES_PER_PIXEL + payload.len()) as u32,
+        crc:         crc32(&image),
+    };
+    //  Concatenate the header, palette and runs
+    let mut logo = Vec::with_capacity(HEADER_SIZE + header.payload_len as usize);
+    logo.extend_from_slice(&header.to_bytes());
+    for color in &palette {
+        logo.extend_from_slice(&color.to_be_bytes());
+    }
+    logo.extend_from_slice(&payload);
+    Ok(logo)
+}
+
+/// Compress the RGB565 pixels with every pixel format and return the smallest boot logo
+pub fn encode_smallest(pixels: &[u16], width: u16, height: u16) -> LogoResult<Vec<u8>> {
+    let rle = encode(pixels, width, height, PixelFormat::Rgb565Rle) ? ;
+    match encode(pixels, width, height, PixelFormat::Indexed8Rle) {
+        Ok(indexed) if indexed.len() < rle.len() => Ok(indexed),
+        Ok(_) | Err(Error::TooManyColors) => Ok(rle),
+        Err(err) => Err(err),
+    }
+}
+
+/// Create the image of a slot to be written to External SPI Flash (see `layout.rs`): the header sector followed by
+/// the uncompressed RGB565 pixels. Unused bytes in the header sector are set to 0xFF, like erased flash.
+pub fn encode_flash_image(pixels: &[u16], width: u16, height: u16) -> LogoResult<Vec<u8>> {
+    if width == 0 || height == 0 || pixels.len() != width as usize * height as usize {
+        return Err(Error::BadSize);
+    }
+    let data = to_bytes(pixels);
+    let header = Header::rgb565(width, height, crc32(&data));
+    let mut image = Vec::with_capacity(PIXELS_OFFSET as usize + data.len());
+    image.extend_from_slice(&header.to_bytes());
+    image.resize(PIXELS_OFFSET as usize, ERASED);
+    image.extend_from_slice(&data);
+    Ok(image)
+}
+
+/// Create the image of the logo area to be written to External SPI Flash at `LOGO_AREA_OFFSET` (see `slots.rs`):
+/// the slot table followed by the slots. `slots[i]` contains the RGB565 pixels for slot `i`, or `None` to leave
+/// the slot erased. Fail if there are too many slots, or if the active slot is empty.
+pub fn encode_logo_area(slots: &[Option<Vec<u16>>], width: u16, height: u16, active: u8) -> LogoResult<Vec<u8>> {
+    if slots.len() > MAX_SLOTS as usize { return Err(Error::BadSlot); }
+    match slots.get(active as usize) {
+        Some(Some(_)) => {}
+        _ => return Err(Error::BadSlot),
+    }
+    let mut area = vec![ERASED; LOGO_AREA_SIZE as usize];
+
+    //  Write the slot table
+    let table = SlotTable::new(active) ?.to_bytes();
+    let table_offset = (SLOT_TABLE_OFFSET - LOGO_AREA_OFFSET) as usize;
+    area[table_offset..table_offset + table.len()].copy_from_slice(&table);
+
+    //  Write each slot
+    for (slot, pixels) in slots.iter().enumerate() {
+        if let Some(pixels) = pixels {
+            let image = encode_flash_image(pixels, width, height) ? ;
+            let offset = (slots::slot_offset(slot as u8) ? - LOGO_AREA_OFFSET) as usize;
+            area[offset..offset + image.len()].copy_from_slice(&image);
+        }
+    }
+    Ok(area)
+}
+
+/// Frame of an animation, for `encode_animation()`
+pub struct AnimationFrame {
+    /// RGB565 pixels, row by row from the top left
+    pub pixels:   Vec<u16>,
+    /// Number of milliseconds to show the frame
+    pub delay_ms: u16,
+}
+
+/// Create the image of a slot that contains the animation (see `animation.rs`): the header sector followed by the
+/// frames. The first frame is stored whole. Each following frame stores only the rectangle that bounds the pixels
+/// changed since the previous frame, split into bands of rows that fit into `MAX_RECT_SIZE`.
+/// Fail if the frames don't fit into a slot.
+pub fn encode_animation(frames: &[AnimationFrame], width: u16, height: u16, loops: u16) -> LogoResult<Vec<u8>> {
+    if frames.is_empty() || frames.len() > u16::MAX as usize || loops == 0 { return Err(Error::Corrupted); }
+    let mut data = Vec::new();
+    let mut previous: Option<&[u16]> = None;
+    for frame in frames {
+        if width == 0 || height == 0 || frame.pixels.len() != width as usize * height as usize {
+            return Err(Error::BadSize);
+        }
+        let mut rects = Vec::new();
+        let rect_count = match changed_area(previous, &frame.pixels, width, height) {
+            Some(area) => encode_rects(&frame.pixels, width, &area, &mut rects) ?,
+            None => 0,  //  Nothing changed, only the delay
+        };
+        let header = FrameHeader { delay_ms: frame.delay_ms, rect_count, rects_len: rects.len() as u32 };
+        data.extend_from_slice(&header.to_bytes());
+        data.extend_from_slice(&rects);
+        previous = Some(&frame.pixels);
+    }
+    if data.len() > MAX_FRAMES_SIZE as usize { return Err(Error::BadSize); }
+
+    //  Header sector comes before the frames
+    let header = AnimationHeader {
+        frame_count: frames.len() as u16,
+        width,
+        height,
+        loops,
+        frames_len:  data.len() as u32,
+        crc:         crc32(&data),
+    };
+    let mut image = Vec::with_capacity(FRAMES_OFFSET as usize + data.len());
+    image.extend_from_slice(&header.to_bytes());
+    image.resize(FRAMES_OFFSET as usize, ERASED);
+    image.extend_from_slice(&data);
+    Ok(image)
+}
+
+/// Return the rectangle that bounds the pixels that differ from the previous frame, or the entire frame if there is
+/// no previous frame. Returns `None` if no pixels changed.
+fn changed_area(previous: Option<&[u16]>, pixels: &[u16], width: u16, height: u16) -> Option<Rect> {
+    let previous = match previous {
+        Some(previous) => previous,
+        None => return Some(Rect { left: 0, top: 0, width, height }),
+    };
+    let (mut left, mut top, mut right, mut bottom) = (width, height, 0, 0);
+    for (i, (old, new)) in previous.iter().zip(pixels).enumerate() {
+        if old == new { continue; }
+        let (x, y) = ((i % width as usize) as u16, (i / width as usize) as u16);
+        left = left.min(x);
+        top = top.min(y);
+        right = right.max(x);
+        bottom = bottom.max(y);
+    }
+    if left > right { return None; }
+    Some(Rect { left, top, width: right - left + 1, height: bottom - top + 1 })
+}
+
+/// Compress the area of the frame as bands of rows and append each band with its position. Each band is made as tall
+/// as possible while fitting into `MAX_RECT_SIZE`. Returns the number of bands.
+fn encode_rects(pixels: &[u16], width: u16, area: &Rect, out: &mut Vec<u8>) -> LogoResult<u16> {
+    let bottom = area.top + area.height;
+    let mut top = area.top;
+    let mut count = 0;
+    while top < bottom {
+        let mut rows = bottom - top;
+        let logo = loop {
+            let band: Vec<u16> = (top..top + rows)
+                .flat_map(|y| {
+                    let start = y as usize * width as usize + area.left as usize;
+                    pixels[start..start + area.width as usize].iter().cloned()
+                })
+                .collect();
+            let logo = encode_smallest(&band, area.width, rows) ? ;
+            let size = RECT_HEADER_SIZE + logo.len();
+            if size <= MAX_RECT_SIZE { break logo; }
+            if rows == 1 { return Err(Error::BadSize); }
+            //  Shrink the band in proportion to the excess
+            rows = (rows as usize * MAX_RECT_SIZE / size).max(1).min(rows as usize - 1) as u16;
+        };
+        out.extend_from_slice(&area.left.to_le_bytes());
+        out.extend_from_slice(&top.to_le_bytes());
+        out.extend_from_slice(&logo);
+        top += rows;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Convert the pixels to big-endian bytes
+fn to_bytes(pixels: &[u16]) -> Vec<u8> {
+    pixels.iter()
+        .flat_map(|pixel| pixel.to_be_bytes().to_vec())
+        .collect()
+}
+
+/// Convert the pixels to palette indexes. Colours are added to the palette in order of appearance.
+fn to_indexes(pixels: &[u16], palette: &mut Vec<u16>) -> LogoResult<Vec<u8>> {
+    let mut indexes = Vec::with_capacity(pixels.len());
+    for pixel in pixels {
+        let index = match palette.iter().position(|color| color == pixel) {
+            Some(index) => index,
+            None => {
+                if palette.len() >= MAX_PALETTE_COLORS { return Err(Error::TooManyColors); }
+                palette.push(*pixel);
+                palette.len() - 1
+            }
+        };
+        indexes.push(index as u8);
+    }
+    Ok(indexes)
+}
+
+/// Run-length encode the units. `write_unit` appends a unit to the output.
+fn encode_runs<T, F>(units: &[T], write_unit: F) -> Vec<u8>
+where T: PartialEq, F: Fn(&T, &mut Vec<u8>) {
+    let mut out = Vec::new();
+    let mut literal_start = 0;  //  Start of the pending literal run
+    let mut i = 0;
+    while i < units.len() {
+        //  Count the repeated units at this position
+        let mut repeat = 1;
+        while i + repeat < units.len() && repeat < MAX_REPEAT && units[i + repeat] == units[i] {
+            repeat += 1;
+        }
+        if repeat < MIN_REPEAT {
+            i += 1;
+            continue;
+        }
+        //  Flush the pending literal run, then write the repeated run
+        write_literals(&units[literal_start..i], &write_unit, &mut out);
+        let count = repeat - 1;
+        out.push(0x80 | (count >> 8) as u8);
+        out.push(count as u8);
+        write_unit(&units[i], &mut out);
+        i += repeat;
+        literal_start = i;
+    }
+    write_literals(&units[literal_start..], &write_unit, &mut out);
+    out
+}
+
+/// Write the units as literal runs of up to 128 units each
+fn write_literals<T, F>(units: &[T], write_unit: &F, out: &mut Vec<u8>)
+where F: Fn(&T, &mut Vec<u8>) {
+    for chunk in units.chunks(MAX_LITERAL) {
+        out.push((chunk.len() - 1) as u8);
+        for unit in chunk {
+            write_unit(unit, out);
+        }
+    }
+}
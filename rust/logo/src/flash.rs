@@ -0,0 +1,14 @@
+//! Flash device that stores the boot logo slots. Implemented by Mynewt's `hal_flash` on PineTime (see
+//! `rust/app/src/logo_loader.rs`) and by `FlashEmulator` on the host computer, so that the Boot Logo Loader
+//! (`loader.rs`) may be tested without PineTime.
+use crate::LogoResult;
+
+/// Flash device with the erase and write semantics of `hal_flash`. Offsets are absolute, e.g. `LOGO_AREA_OFFSET`.
+pub trait Flash {
+    /// Read the flash at the offset into the buffer
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> LogoResult<()>;
+    /// Write the data to the flash at the offset. The flash must have been erased.
+    fn write(&mut self, offset: u32, data: &[u8]) -> LogoResult<()>;
+    /// Erase every sector that contains part of the range
+    fn erase(&mut self, offset: u32, len: u32) -> LogoResult<()>;
+}
@@ -0,0 +1,142 @@
+//! Header of the boot logo. The header is followed by the payload: the optional palette and the pixels, which may be compressed.
+//! The same header is stored in External SPI Flash in front of the pixels (see `layout.rs`), so that the bootloader can tell
+//! a valid boot logo from erased or stale flash. All fields are little-endian:
+//! ```text
+//! Offset  Size  Field
+//!      0     4  Magic number "PTLG"
+//!      4     1  Version (1)
+//!      5     1  Pixel format, see `PixelFormat`
+//!      6     2  Number of palette colours (0 if no palette)
+//!      8     2  Width in pixels
+//!     10     2  Height in pixels
+//!     12     4  Payload length in bytes, excluding the header
+//!     16     4  CRC32 of the decoded big-endian RGB565 pixels
+//! ```
+use crate::{
+    Error, LogoResult,
+};
+
+/// Magic number at the start of the header
+pub const MAGIC: [u8; 4] = *b"PTLG";
+
+/// Current version of the header
+pub const VERSION: u8 = 1;
+
+/// Size of the header in bytes
+pub const HEADER_SIZE: usize = 20;
+
+/// Max number of colours in the palette
+pub const MAX_PALETTE_COLORS: usize = 256;
+
+/// How the pixels are stored in the payload
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PixelFormat {
+    /// Uncompressed big-endian RGB565 pixels, 2 bytes per pixel. Used in External SPI Flash.
+    Rgb565 = 0,
+    /// Run-length encoded RGB565 pixels. Each unit is a big-endian RGB565 pixel (2 bytes).
+    Rgb565Rle = 1,
+    /// Palette of big-endian RGB565 colours, followed by run-length encoded palette indexes. Each unit is a palette index (1 byte).
+    Indexed8Rle = 2,
+}
+
+/// Header of the boot logo
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Header {
+    /// How the pixels are stored in the payload
+    pub format:        PixelFormat,
+    /// Number of palette colours (0 if no palette)
+    pub palette_len:   u16,
+    /// Width in pixels
+    pub width:         u16,
+    /// Height in pixels
+    pub height:        u16,
+    /// Payload length in bytes, excluding the header
+    pub payload_len:   u32,
+    /// CRC32 of the decoded big-endian RGB565 pixels
+    pub crc:           u32,
+}
+
+impl Header {
+    /// Parse the header at the start of the bytes. Fail if the header is missing or invalid.
+    pub fn parse(bytes: &[u8]) -> LogoResult<Header> {
+        if bytes.len() < HEADER_SIZE       { return Err(Error::Truncated); }
+        if bytes[0..4] != MAGIC            { return Err(Error::BadMagic); }
+        if bytes[4] != VERSION             { return Err(Error::BadVersion); }
+        let format = PixelFormat::from_u8(bytes[5]) ? ;
+        let header = Header {
+            format,
+            palette_len: u16::from_le_bytes([bytes[6], bytes[7]]),
+            width:       u16::from_le_bytes([bytes[8], bytes[9]]),
+            height:      u16::from_le_bytes([bytes[10], bytes[11]]),
+            payload_len: u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+            crc:         u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]),
+        };
+        if header.width == 0 || header.height == 0 { return Err(Error::BadSize); }
+        match header.format {
+            PixelFormat::Indexed8Rle => {
+                if header.palette_len == 0 || header.palette_len as usize > MAX_PALETTE_COLORS {
+                    return Err(Error::Corrupted);
+                }
+            }
+            PixelFormat::Rgb565 => {
+                if header.palette_len != 0 || header.payload_len != header.image_size() {
+                    return Err(Error::Corrupted);
+                }
+            }
+            PixelFormat::Rgb565Rle => {
+                if header.palette_len != 0 { return Err(Error::Corrupted); }
+            }
+        }
+        Ok(header)
+    }
+
+    /// Create the header for uncompressed RGB565 pixels with the CRC32 of the pixels
+    pub fn rgb565(width: u16, height: u16, crc: u32) -> Header {
+        let mut header = Header {
+            format:      PixelFormat::Rgb565,
+            palette_len: 0,
+            width,
+            height,
+            payload_len: 0,
+            crc,
+        };
+        header.payload_len = header.image_size();
+        header
+    }
+
+    /// Return the header as bytes
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4] = VERSION;
+        bytes[5] = self.format as u8;
+        bytes[6..8].copy_from_slice(&self.palette_len.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.width.to_le_bytes());
+        bytes[10..12].copy_from_slice(&self.height.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.payload_len.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.crc.to_le_bytes());
+        bytes
+    }
+
+    /// Return the number of pixels in the image
+    pub fn pixel_count(&self) -> u32 {
+        self.width as u32 * self.height as u32
+    }
+
+    /// Return the size of the decoded image in bytes
+    pub fn image_size(&self) -> u32 {
+        self.pixel_count() * crate::BYTES_PER_PIXEL as u32
+    }
+}
+
+impl PixelFormat {
+    /// Convert the pixel format stored in the header
+    pub fn from_u8(format: u8) -> LogoResult<PixelFormat> {
+        match format {
+            0 => Ok(PixelFormat::Rgb565),
+            1 => Ok(PixelFormat::Rgb565Rle),
+            2 => Ok(PixelFormat::Indexed8Rle),
+            _ => Err(Error::BadFormat),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! Layout of a boot logo slot in External SPI Flash (see `slots.rs` for the location of each slot).
+//! The header is in its own sector, so that it can be written after the pixels have been written and verified.
+//! If the write is interrupted, the header will be missing and the bootloader will show the default boot screen
+//! instead of a partial logo. Offsets are relative to the start of the slot:
+//! ```text
+//! Offset  Size     Contents
+//!      0  4096     Header (see `header.rs`), padded with 0xFF
+//!   4096  115,200  Uncompressed big-endian RGB565 pixels
+//! ```
+use crate::{
+    header::{ Header, PixelFormat, HEADER_SIZE },
+    Error, LogoResult,
+};
+
+/// Size of an erase sector in External SPI Flash
+pub const SECTOR_SIZE: u32 = 4096;
+
+/// Offset of the header, relative to the start of the slot
+pub const HEADER_OFFSET: u32 = 0;
+
+/// Offset of the pixels, relative to the start of the slot
+pub const PIXELS_OFFSET: u32 = HEADER_OFFSET + SECTOR_SIZE;
+
+/// Size of a slot: the header sector plus 115,200 bytes of pixels, rounded up to whole sectors (1 + 29 sectors)
+pub const SLOT_SIZE: u32 = 30 * SECTOR_SIZE;
+
+/// Value of erased flash
+pub const ERASED: u8 = 0xff;
+
+/// Parse the header read from `HEADER_OFFSET` of a slot in External SPI Flash. Fail if the flash is erased,
+/// or if the header is invalid or doesn't describe uncompressed pixels that fill the display.
+pub fn parse_flash_header(bytes: &[u8]) -> LogoResult<Header> {
+    if bytes.len() >= HEADER_SIZE && bytes[..HEADER_SIZE].iter().all(|b| *b == ERASED) {
+        return Err(Error::BadMagic);  //  Flash is erased
+    }
+    let header = Header::parse(bytes) ? ;
+    if header.format != PixelFormat::Rgb565 { return Err(Error::BadFormat); }
+    if header.width != crate::WIDTH || header.height != crate::HEIGHT { return Err(Error::BadSize); }
+    Ok(header)
+}
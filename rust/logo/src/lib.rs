@@ -0,0 +1,110 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *  http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+//!  Boot Logo formats for PineTime. Shared by the Rust Application on PineTime (decoder) and by
+//!  `logo_tool` on the host computer (encoder and flash emulator, with the `std` feature).
+#![no_std]  //  Don't link with standard Rust library, which is not compatible with embedded systems
+
+#[cfg(feature = "std")]  //  If the encoder is enabled...
+extern crate std;        //  Use the standard Rust library for `Vec`
+
+pub mod animation; //  Export `animation.rs` as Rust module `logo::animation`
+pub mod crc32;     //  Export `crc32.rs` as Rust module `logo::crc32`
+pub mod decoder;   //  Export `decoder.rs` as Rust module `logo::decoder`
+pub mod flash;     //  Export `flash.rs` as Rust module `logo::flash`
+pub mod header;    //  Export `header.rs` as Rust module `logo::header`
+pub mod layout;    //  Export `layout.rs` as Rust module `logo::layout`
+pub mod loader;    //  Export `loader.rs` as Rust module `logo::loader`
+pub mod slots;     //  Export `slots.rs` as Rust module `logo::slots`
+pub mod transfer;  //  Export `transfer.rs` as Rust module `logo::transfer`
+
+#[cfg(feature = "std")]  //  If the encoder is enabled...
+pub mod encoder;         //  Export `encoder.rs` as Rust module `logo::encoder`
+
+#[cfg(feature = "std")]  //  If the encoder is enabled...
+pub mod emulator;        //  Export `emulator.rs` as Rust module `logo::emulator`
+
+/// Width of the PineTime display, in pixels
+pub const WIDTH: u16 = 240;
+
+/// Height of the PineTime display, in pixels
+pub const HEIGHT: u16 = 240;
+
+/// Each RGB565 pixel takes 2 bytes
+pub const BYTES_PER_PIXEL: usize = 2;
+
+/// Errors while encoding, decoding, transferring and writing the boot logo
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// Header doesn't start with the magic number
+    BadMagic = 1,
+    /// Header version is not supported
+    BadVersion = 2,
+    /// Pixel format is not supported
+    BadFormat = 3,
+    /// Width or height is zero, or doesn't match the pixels
+    BadSize = 4,
+    /// Compressed data ended before all pixels were decoded
+    Truncated = 5,
+    /// Compressed data contains an invalid run or palette index
+    Corrupted = 6,
+    /// Checksum of the decoded pixels doesn't match the header
+    BadChecksum = 7,
+    /// Image has too many colours for a palette
+    TooManyColors = 8,
+    /// Slot number doesn't exist
+    BadSlot = 9,
+    /// Transferred chunk doesn't start at the expected offset, or flash offset is out of range
+    BadOffset = 10,
+    /// Transfer command is not allowed in the current state
+    BadState = 11,
+    /// Flash device failed to erase, write or read
+    FlashFailed = 12,
+    /// Flash was written without erasing
+    NotErased = 13,
+}
+
+impl Error {
+    /// Return the error code, for sending over Bluetooth LE. 0 means no error.
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// Convert the error code to an error. Returns `None` if the code is 0 or unknown.
+    pub fn from_code(code: u8) -> Option<Error> {
+        match code {
+            1  => Some(Error::BadMagic),
+            2  => Some(Error::BadVersion),
+            3  => Some(Error::BadFormat),
+            4  => Some(Error::BadSize),
+            5  => Some(Error::Truncated),
+            6  => Some(Error::Corrupted),
+            7  => Some(Error::BadChecksum),
+            8  => Some(Error::TooManyColors),
+            9  => Some(Error::BadSlot),
+            10 => Some(Error::BadOffset),
+            11 => Some(Error::BadState),
+            12 => Some(Error::FlashFailed),
+            13 => Some(Error::NotErased),
+            _  => None,
+        }
+    }
+}
+
+/// Common return type for the Boot Logo API
+pub type LogoResult<T> = ::core::result::Result<T, Error>;
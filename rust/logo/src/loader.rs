@@ -0,0 +1,331 @@
+//! Boot Logo Loader. Expands a compressed boot logo into RGB565 pixels and writes them to a slot in a flash device.
+//! Every sector is read back and compared after writing, failed sectors are retried, and the whole slot is
+//! verified with CRC32 at the end. The header is written last (see `layout.rs`), so the bootloader will ignore a
+//! partially-written logo. The slot table (see `slots.rs`) selects the slot that will be shown by the bootloader.
+//! Runs on PineTime with Mynewt's `hal_flash`, and on the host computer with `FlashEmulator`.
+use crate::{
+    animation::{ AnimationHeader, ANIMATION_HEADER_SIZE, FRAMES_OFFSET },
+    crc32::Crc32,
+    decoder::Decoder,
+    flash::Flash,
+    header::{ Header, HEADER_SIZE },
+    layout::{ self, HEADER_OFFSET, PIXELS_OFFSET, SECTOR_SIZE, SLOT_SIZE },
+    slots::{ self, SlotTable, MAX_SLOTS, SLOT_TABLE_OFFSET, SLOT_TABLE_SIZE },
+    transfer::TransferTarget,
+    Error, LogoResult,
+};
+
+/// Max number of bytes to be written in a batch. Must be a multiple of the erase sector size.
+const BATCH_SIZE: usize = SECTOR_SIZE as usize;
+
+/// Number of bytes to read back at a time when verifying
+const READ_SIZE: usize = 256;
+
+/// Number of times to erase and write a sector before giving up
+const MAX_ATTEMPTS: u8 = 3;
+
+/// Status of a slot, returned by `list_slots()`
+#[derive(Clone, Copy, Debug)]
+pub struct SlotInfo {
+    /// Slot number
+    pub slot:   u8,
+    /// Header of the boot logo in the slot, or `None` if the slot is empty or invalid
+    pub header: Option<Header>,
+    /// True if the bootloader will show this slot
+    pub active: bool,
+}
+
+/// Number of sectors compared and changed, returned by `diff_slot()` and `update_slot()`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SlotDiff {
+    /// Number of sectors in the slot that hold the logo, including the header sector
+    pub sectors: u32,
+    /// Number of sectors that differ from the logo
+    pub changed: u32,
+}
+
+/// How `write_pixels()` writes the sectors of the slot
+#[derive(Clone, Copy, PartialEq)]
+enum WriteMode {
+    /// Erase and write every sector
+    All,
+    /// Erase and write only the sectors that differ from the logo
+    Changed,
+    /// Count the sectors that differ, without writing
+    DryRun,
+}
+
+/// Writes boot logos to the slots in the flash device
+pub struct Loader<F> {
+    /// Flash device that contains the slots
+    flash:    F,
+    /// Batch of decoded pixels to be written
+    batch:    [u8; BATCH_SIZE],
+    /// Buffer for reading back the flash
+    read_buf: [u8; READ_SIZE],
+    /// Number of sector writes that have been retried
+    retries:  u32,
+}
+
+impl<F> Loader<F> {
+    /// Create a loader for the flash device
+    pub const fn new(flash: F) -> Loader<F> {
+        Loader {
+            flash,
+            batch:    [0; BATCH_SIZE],
+            read_buf: [0; READ_SIZE],
+            retries:  0,
+        }
+    }
+
+    /// Return the flash device
+    pub fn flash(&self) -> &F {
+        &self.flash
+    }
+
+    /// Return the flash device, consuming the loader
+    pub fn into_flash(self) -> F {
+        self.flash
+    }
+
+    /// Return the number of sector writes that have been retried
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+}
+
+impl<F: Flash> Loader<F> {
+    /// Expand the boot logo (header and optionally compressed pixels) and write to the slot. Each batch is erased,
+    /// written and read back, and retried up to `MAX_ATTEMPTS` times. Returns `BadChecksum` if the CRC32 of the
+    /// written pixels doesn't match the logo, or `BadSize` if the logo is not `WIDTH` x `HEIGHT`.
+    pub fn write_slot(&mut self, slot: u8, logo: &[u8]) -> LogoResult<()> {
+        self.write_pixels(slot, logo, WriteMode::All)
+            .map(|_| ())
+    }
+
+    /// Like `write_slot()`, but erase and write only the sectors that differ from the logo. If nothing differs, the
+    /// flash is not written at all. Returns the number of sectors compared and rewritten, including the header sector.
+    pub fn update_slot(&mut self, slot: u8, logo: &[u8]) -> LogoResult<SlotDiff> {
+        self.write_pixels(slot, logo, WriteMode::Changed)
+    }
+
+    /// Compare the boot logo with the slot without writing. Returns the number of sectors compared and the number
+    /// that differ, including the header sector.
+    pub fn diff_slot(&mut self, slot: u8, logo: &[u8]) -> LogoResult<SlotDiff> {
+        self.write_pixels(slot, logo, WriteMode::DryRun)
+    }
+
+    /// Erase the entire slot. If the slot is active, the bootloader will show the default boot screen.
+    pub fn erase_slot(&mut self, slot: u8) -> LogoResult<()> {
+        let slot_offset = slots::slot_offset(slot) ? ;
+        self.flash.erase(slot_offset, SLOT_SIZE)
+    }
+
+    /// Mark the slot as active, so that the bootloader will show it. Fails if the slot doesn't contain a valid logo.
+    pub fn mark_active(&mut self, slot: u8) -> LogoResult<()> {
+        self.read_header(slot) ? ;
+        let table = SlotTable::new(slot) ? ;
+        self.write_sector(SLOT_TABLE_OFFSET, &table.to_bytes())
+    }
+
+    /// Return the slot that will be shown by the bootloader. Returns slot 0 if the slot table is missing or invalid.
+    pub fn active_slot(&mut self) -> LogoResult<u8> {
+        let mut buf = [0; SLOT_TABLE_SIZE];
+        self.flash.read(SLOT_TABLE_OFFSET, &mut buf) ? ;
+        Ok(SlotTable::active_slot(&buf))
+    }
+
+    /// Return the status of every slot
+    pub fn list_slots(&mut self) -> LogoResult<[SlotInfo; MAX_SLOTS as usize]> {
+        let active = self.active_slot() ? ;
+        let mut list = [SlotInfo { slot: 0, header: None, active: false }; MAX_SLOTS as usize];
+        for (slot, info) in list.iter_mut().enumerate() {
+            let slot = slot as u8;
+            *info = SlotInfo {
+                slot,
+                header: self.read_header(slot).ok(),
+                active: slot == active,
+            };
+        }
+        Ok(list)
+    }
+
+    /// Read and validate the boot logo header in the slot. Fails if the header is missing or invalid.
+    pub fn read_header(&mut self, slot: u8) -> LogoResult<Header> {
+        let slot_offset = slots::slot_offset(slot) ? ;
+        let mut buf = [0; HEADER_SIZE];
+        self.flash.read(slot_offset + HEADER_OFFSET, &mut buf) ? ;
+        layout::parse_flash_header(&buf)
+    }
+
+    /// Return true if the slot has a valid header and the CRC32 of the pixels matches, like the bootloader checks
+    pub fn is_valid(&mut self, slot: u8) -> LogoResult<bool> {
+        let header = match self.read_header(slot) {
+            Ok(header) => header,
+            Err(Error::FlashFailed) => return Err(Error::FlashFailed),
+            Err(_) => return Ok(false),
+        };
+        let slot_offset = slots::slot_offset(slot) ? ;
+        let crc = self.crc_region(slot_offset + PIXELS_OFFSET, header.image_size()) ? ;
+        Ok(crc == header.crc)
+    }
+
+    /// Write the animation (see `animation.rs`) to the slot: the header sector followed by the frames. The header
+    /// is erased first and written after the CRC32 of the frames has been verified, like `write_slot()`.
+    pub fn write_animation(&mut self, slot: u8, image: &[u8]) -> LogoResult<()> {
+        let slot_offset = slots::slot_offset(slot) ? ;
+        let header = AnimationHeader::parse(image) ? ;
+        if image.len() != FRAMES_OFFSET as usize + header.frames_len as usize { return Err(Error::BadSize); }
+        let frames = &image[FRAMES_OFFSET as usize..];
+        self.flash.erase(slot_offset + HEADER_OFFSET, SECTOR_SIZE) ? ;
+        for (i, sector) in frames.chunks(SECTOR_SIZE as usize).enumerate() {
+            self.write_sector(slot_offset + FRAMES_OFFSET + i as u32 * SECTOR_SIZE, sector) ? ;
+        }
+        let crc = self.crc_region(slot_offset + FRAMES_OFFSET, header.frames_len) ? ;
+        if crc != header.crc { return Err(Error::BadChecksum); }
+        self.write_sector(slot_offset + HEADER_OFFSET, &image[..ANIMATION_HEADER_SIZE])
+    }
+
+    /// Read the flash at the offset into the buffer
+    pub fn read(&mut self, offset: u32, buf: &mut [u8]) -> LogoResult<()> {
+        self.flash.read(offset, buf)
+    }
+
+    /// Erase the sector at the offset, write the data and read back to verify. Retry if the sector fails.
+    pub fn write_sector(&mut self, offset: u32, data: &[u8]) -> LogoResult<()> {
+        write_sector(&mut self.flash, &mut self.read_buf, &mut self.retries, offset, data)
+    }
+
+    /// Return the CRC32 of the flash region at the offset
+    pub fn crc_region(&mut self, offset: u32, len: u32) -> LogoResult<u32> {
+        let mut crc = Crc32::new();
+        let mut pos = 0;
+        while pos < len {
+            let size = core::cmp::min(READ_SIZE as u32, len - pos) as usize;
+            self.flash.read(offset + pos, &mut self.read_buf[..size]) ? ;
+            crc.update(&self.read_buf[..size]);
+            pos += size as u32;
+        }
+        Ok(crc.finish())
+    }
+
+    /// Expand the boot logo and write the sectors of the slot according to the mode. The header is erased before the
+    /// first pixel sector is written and rewritten after the CRC32 of the pixels has been verified.
+    fn write_pixels(&mut self, slot: u8, logo: &[u8], mode: WriteMode) -> LogoResult<SlotDiff> {
+        let slot_offset = slots::slot_offset(slot) ? ;
+        let mut decoder = Decoder::new(logo) ? ;
+        let logo_header = *decoder.header();
+        //  Logo must fill the display, or it would overflow into the next slot
+        if logo_header.width != crate::WIDTH || logo_header.height != crate::HEIGHT { return Err(Error::BadSize); }
+        let header = Header::rgb565(logo_header.width, logo_header.height, logo_header.crc);
+        let header_offset = slot_offset + HEADER_OFFSET;
+
+        //  Compare the header. When writing all sectors, the header is always rewritten.
+        let mut diff = SlotDiff { sectors: 1, changed: 0 };
+        let header_changed = mode == WriteMode::All
+            || !compare(&mut self.flash, &mut self.read_buf, header_offset, &header.to_bytes()) ? ;
+
+        //  Erase the old header, so that the slot is invalid until the new header is written
+        let mut header_erased = false;
+        if mode == WriteMode::All {
+            self.flash.erase(header_offset, SECTOR_SIZE) ? ;
+            header_erased = true;
+        }
+        let pixels_offset = slot_offset + PIXELS_OFFSET;
+        let mut offset = pixels_offset;
+        let Loader { flash, batch, read_buf, retries } = self;
+        loop {
+            //  Decode the next batch of pixels. Returns 0 after the last batch.
+            let len = decoder.read(batch) ? ;
+            if len == 0 { break; }
+            let batch = &batch[..len];
+            diff.sectors += 1;
+
+            //  Skip the batch if it's already in flash
+            if mode != WriteMode::All && compare(flash, read_buf, offset, batch) ? {
+                offset += len as u32;
+                continue;
+            }
+            diff.changed += 1;
+            if mode != WriteMode::DryRun {
+                if !header_erased {
+                    flash.erase(header_offset, SECTOR_SIZE) ? ;
+                    header_erased = true;
+                }
+                //  Erase, write and verify the batch
+                write_sector(flash, read_buf, retries, offset, batch) ? ;
+            }
+            offset += len as u32;
+        }
+        if mode == WriteMode::DryRun {
+            if header_changed { diff.changed += 1; }
+            return Ok(diff);
+        }
+        if !(header_changed || header_erased) { return Ok(diff); }
+        diff.changed += 1;
+
+        //  Verify the CRC32 of the entire region
+        let len = offset - pixels_offset;
+        let crc = self.crc_region(pixels_offset, len) ? ;
+        if len != logo_header.image_size() || crc != logo_header.crc { return Err(Error::BadChecksum); }
+
+        //  Write the header in front of the pixels and check that it can be parsed
+        self.write_sector(header_offset, &header.to_bytes()) ? ;
+        self.read_header(slot) ? ;
+        Ok(diff)
+    }
+
+}
+
+/// Erase the sector at the offset, write the data and read back to verify. Retry if the sector fails.
+fn write_sector<F: Flash>(flash: &mut F, read_buf: &mut [u8; READ_SIZE], retries: &mut u32, offset: u32, data: &[u8])
+    -> LogoResult<()> {
+    let mut result = Ok(());
+    for attempt in 0..MAX_ATTEMPTS {
+        if attempt > 0 { *retries += 1; }
+        result = flash.erase(offset, data.len() as u32)
+            .and_then(|_| flash.write(offset, data))
+            .and_then(|_| match compare(flash, read_buf, offset, data) {
+                Ok(true)  => Ok(()),
+                Ok(false) => Err(Error::FlashFailed),
+                Err(err)  => Err(err),
+            });
+        if result.is_ok() { return result; }
+    }
+    result
+}
+
+/// Return true if the flash at the offset contains the data
+fn compare<F: Flash>(flash: &mut F, read_buf: &mut [u8; READ_SIZE], offset: u32, data: &[u8]) -> LogoResult<bool> {
+    for (i, chunk) in data.chunks(READ_SIZE).enumerate() {
+        let buf = &mut read_buf[..chunk.len()];
+        flash.read(offset + (i * READ_SIZE) as u32, buf) ? ;
+        if buf[..] != chunk[..] { return Ok(false); }
+    }
+    Ok(true)
+}
+
+/// Write the pixels uploaded over Bluetooth LE (see `transfer.rs`) to the slots
+impl<F: Flash> TransferTarget for Loader<F> {
+    /// Erase the old header, so that the slot is invalid until the new header is written
+    fn begin(&mut self, slot: u8) -> LogoResult<()> {
+        let slot_offset = slots::slot_offset(slot) ? ;
+        self.flash.erase(slot_offset + HEADER_OFFSET, SECTOR_SIZE)
+    }
+
+    /// Erase, write and verify the sector of pixels
+    fn write(&mut self, slot: u8, offset: u32, data: &[u8]) -> LogoResult<()> {
+        let slot_offset = slots::slot_offset(slot) ? ;
+        self.write_sector(slot_offset + PIXELS_OFFSET + offset, data)
+    }
+
+    /// Verify the CRC32 of the pixels in flash, then write the header and activate the slot
+    fn commit(&mut self, slot: u8, header: &Header, activate: bool) -> LogoResult<()> {
+        let slot_offset = slots::slot_offset(slot) ? ;
+        let crc = self.crc_region(slot_offset + PIXELS_OFFSET, header.image_size()) ? ;
+        if crc != header.crc { return Err(Error::BadChecksum); }
+        self.write_sector(slot_offset + HEADER_OFFSET, &header.to_bytes()) ? ;
+        if activate { self.mark_active(slot) ? ; }
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+//! Boot logo slots in External SPI Flash. Several boot logos (e.g. day, night and low battery) may be stored
+//! at the same time, one per slot. The slot table records which slot the bootloader should show.
+//! The logo area is carved from the end of External SPI Flash (see `hw/bsp/nrf52/bsp.yml`):
+//! ```text
+//! Offset    Size     Contents
+//! 0x387000  4096     Slot table, padded with 0xFF
+//! 0x388000  0x1E000  Slot 0 (see `layout.rs`)
+//! 0x3A6000  0x1E000  Slot 1
+//! 0x3C4000  0x1E000  Slot 2
+//! 0x3E2000  0x1E000  Slot 3
+//! ```
+//! The slot table is 12 bytes, all fields little-endian:
+//! ```text
+//! Offset  Size  Field
+//!      0     4  Magic number "PTLS"
+//!      4     1  Version (1)
+//!      5     1  Active slot
+//!      6     2  Reserved (0)
+//!      8     4  CRC32 of bytes 0 to 7
+//! ```
+//! If the slot table is missing or invalid, slot 0 is active.
+use crate::{
+    crc32::crc32,
+    layout::{ SECTOR_SIZE, SLOT_SIZE },
+    Error, LogoResult,
+};
+
+/// Number of slots
+pub const MAX_SLOTS: u8 = 4;
+
+/// Offset of the logo area (slot table and slots) in External SPI Flash
+pub const LOGO_AREA_OFFSET: u32 = 0x387000;
+
+/// Size of the logo area in External SPI Flash, up to the end of the 4 MB flash
+pub const LOGO_AREA_SIZE: u32 = SECTOR_SIZE + MAX_SLOTS as u32 * SLOT_SIZE;
+
+/// Offset of the slot table in External SPI Flash
+pub const SLOT_TABLE_OFFSET: u32 = LOGO_AREA_OFFSET;
+
+/// Size of the slot table in bytes
+pub const SLOT_TABLE_SIZE: usize = 12;
+
+/// Magic number at the start of the slot table
+pub const SLOT_TABLE_MAGIC: [u8; 4] = *b"PTLS";
+
+/// Current version of the slot table
+pub const SLOT_TABLE_VERSION: u8 = 1;
+
+/// Slot table that records the active slot
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SlotTable {
+    /// Slot that will be shown by the bootloader
+    pub active: u8,
+}
+
+impl SlotTable {
+    /// Create a slot table with the active slot. Fail if the slot doesn't exist.
+    pub fn new(active: u8) -> LogoResult<SlotTable> {
+        if active >= MAX_SLOTS { return Err(Error::BadSlot); }
+        Ok(SlotTable { active })
+    }
+
+    /// Parse the slot table read from `SLOT_TABLE_OFFSET`. Fail if the table is missing or invalid.
+    pub fn parse(bytes: &[u8]) -> LogoResult<SlotTable> {
+        if bytes.len() < SLOT_TABLE_SIZE      { return Err(Error::Truncated); }
+        if bytes[0..4] != SLOT_TABLE_MAGIC    { return Err(Error::BadMagic); }
+        if bytes[4] != SLOT_TABLE_VERSION     { return Err(Error::BadVersion); }
+        let crc = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+        if crc != crc32(&bytes[0..8])         { return Err(Error::BadChecksum); }
+        SlotTable::new(bytes[5])
+    }
+
+    /// Parse the slot table read from `SLOT_TABLE_OFFSET` and return the active slot. Returns slot 0 if the table is missing or invalid.
+    pub fn active_slot(bytes: &[u8]) -> u8 {
+        SlotTable::parse(bytes)
+            .map(|table| table.active)
+            .unwrap_or(0)
+    }
+
+    /// Return the slot table as bytes
+    pub fn to_bytes(&self) -> [u8; SLOT_TABLE_SIZE] {
+        let mut bytes = [0; SLOT_TABLE_SIZE];
+        bytes[0..4].copy_from_slice(&SLOT_TABLE_MAGIC);
+        bytes[4] = SLOT_TABLE_VERSION;
+        bytes[5] = self.active;
+        let crc = crc32(&bytes[0..8]);
+        bytes[8..12].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+}
+
+/// Return the offset of the slot in External SPI Flash. Fail if the slot doesn't exist.
+pub fn slot_offset(slot: u8) -> LogoResult<u32> {
+    if slot >= MAX_SLOTS { return Err(Error::BadSlot); }
+    Ok(LOGO_AREA_OFFSET + SECTOR_SIZE + slot as u32 * SLOT_SIZE)
+}
@@ -0,0 +1,279 @@
+//! Boot logo transfer protocol, for uploading a boot logo to a slot over Bluetooth LE. Shared by the Rust
+//! Application on PineTime (`Receiver`) and by `logo_tool` on the host computer (client).
+//!
+//! The Logo Transfer GATT Service has 3 characteristics:
+//! ```text
+//! Control  Write   Command, see `Command`
+//! Data     Write   Offset of the chunk (4 bytes, little-endian), followed by the chunk of big-endian RGB565 pixels
+//! Status   Read    Transfer status, see `Status`
+//! ```
+//! The client sends `Start`, then writes the 115,200 bytes of pixels in order, in chunks that fit the ATT MTU.
+//! A chunk with the wrong offset is rejected with `BadOffset`, and the client resumes from `Status::received`.
+//! Finally the client sends `Commit` with the CRC32 of the pixels. The pixels are written to flash one sector
+//! at a time, and the header is written only after the CRC32 has been verified.
+use crate::{
+    crc32::Crc32,
+    header::Header,
+    layout::SECTOR_SIZE,
+    Error, LogoResult,
+};
+
+/// Size of the Data characteristic header: offset of the chunk
+pub const DATA_HEADER_SIZE: usize = 4;
+
+/// Max size of a Data write: ATT MTU of 247 bytes, minus 3 bytes of ATT header
+pub const MAX_DATA_SIZE: usize = 244;
+
+/// Size of the Status characteristic
+pub const STATUS_SIZE: usize = 7;
+
+/// Command written to the Control characteristic
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// Start uploading `len` bytes of pixels to the slot: `0x01, slot, len (4 bytes)`
+    Start { slot: u8, len: u32 },
+    /// Verify the CRC32 of the uploaded pixels and write the header. Activate the slot if `activate` is true:
+    /// `0x02, crc (4 bytes), activate`
+    Commit { crc: u32, activate: bool },
+    /// Abandon the upload: `0x03`
+    Abort,
+}
+
+/// State of the transfer, returned in `Status`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum State {
+    /// No transfer in progress
+    Idle = 0,
+    /// Receiving pixels after `Start`
+    Receiving = 1,
+    /// Pixels have been written and verified after `Commit`
+    Committed = 2,
+    /// Transfer failed, see `Status::error`
+    Failed = 3,
+}
+
+/// Transfer status, read from the Status characteristic: `state, slot, received (4 bytes), error`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Status {
+    /// State of the transfer
+    pub state:    State,
+    /// Slot being written
+    pub slot:     u8,
+    /// Number of bytes of pixels received so far. The next chunk must start at this offset.
+    pub received: u32,
+    /// Error that caused the transfer to fail, if any
+    pub error:    Option<Error>,
+}
+
+/// Destination of the uploaded pixels, e.g. the boot logo slots in External SPI Flash
+pub trait TransferTarget {
+    /// Prepare the slot for writing. The slot must become invalid until `commit()` is called.
+    fn begin(&mut self, slot: u8) -> LogoResult<()>;
+    /// Write a batch of pixels to the slot, at the offset relative to the start of the pixels.
+    /// The offset is a multiple of the sector size.
+    fn write(&mut self, slot: u8, offset: u32, data: &[u8]) -> LogoResult<()>;
+    /// Verify the pixels written to the slot against the header and write the header. Activate the slot if `activate` is true.
+    fn commit(&mut self, slot: u8, header: &Header, activate: bool) -> LogoResult<()>;
+}
+
+/// Receiver for the boot logo transfer. Collects the chunks into sectors and writes them to the target.
+pub struct Receiver {
+    /// State of the transfer
+    state:    State,
+    /// Slot being written
+    slot:     u8,
+    /// Number of bytes of pixels expected
+    len:      u32,
+    /// Number of bytes of pixels received
+    received: u32,
+    /// Error that caused the transfer to fail
+    error:    Option<Error>,
+    /// CRC32 of the pixels received
+    crc:      Crc32,
+    /// Pixels received for the current sector
+    sector:   [u8; SECTOR_SIZE as usize],
+}
+
+impl Receiver {
+    /// Create an idle receiver
+    pub const fn new() -> Receiver {
+        Receiver {
+            state:    State::Idle,
+            slot:     0,
+            len:      0,
+            received: 0,
+            error:    None,
+            crc:      Crc32::new(),
+            sector:   [0; SECTOR_SIZE as usize],
+        }
+    }
+
+    /// Handle a command written to the Control characteristic
+    pub fn control<T: TransferTarget>(&mut self, target: &mut T, data: &[u8]) -> LogoResult<()> {
+        let command = Command::parse(data) ? ;
+        let result = match command {
+            Command::Start { slot, len } => self.start(target, slot, len),
+            Command::Commit { crc, activate } => self.commit(target, crc, activate),
+            Command::Abort => {
+                self.reset();
+                Ok(())
+            }
+        };
+        self.check(result)
+    }
+
+    /// Handle a chunk written to the Data characteristic
+    pub fn data<T: TransferTarget>(&mut self, target: &mut T, data: &[u8]) -> LogoResult<()> {
+        if data.len() <= DATA_HEADER_SIZE { return Err(Error::Truncated); }
+        if self.state != State::Receiving { return Err(Error::BadState); }
+        //  Chunks must arrive in order. Reject the chunk without failing the transfer, so that the client may resume.
+        let offset = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        if offset != self.received { return Err(Error::BadOffset); }
+        let chunk = &data[DATA_HEADER_SIZE..];
+        if offset + chunk.len() as u32 > self.len { return Err(Error::BadSize); }
+
+        let result = self.receive(target, chunk);
+        self.check(result)
+    }
+
+    /// Return the transfer status
+    pub fn status(&self) -> Status {
+        Status {
+            state:    self.state,
+            slot:     self.slot,
+            received: self.received,
+            error:    self.error,
+        }
+    }
+
+    /// Start the transfer
+    fn start<T: TransferTarget>(&mut self, target: &mut T, slot: u8, len: u32) -> LogoResult<()> {
+        if len != Header::rgb565(crate::WIDTH, crate::HEIGHT, 0).image_size() { return Err(Error::BadSize); }
+        self.reset();
+        self.slot  = slot;
+        self.len   = len;
+        self.state = State::Receiving;
+        target.begin(slot)
+    }
+
+    /// Copy the chunk into the sector buffer, and write the sector when it's full or when all pixels have been received
+    fn receive<T: TransferTarget>(&mut self, target: &mut T, mut chunk: &[u8]) -> LogoResult<()> {
+        self.crc.update(chunk);
+        while !chunk.is_empty() {
+            let pos  = (self.received % SECTOR_SIZE) as usize;
+            let size = core::cmp::min(chunk.len(), self.sector.len() - pos);
+            self.sector[pos..pos + size].copy_from_slice(&chunk[..size]);
+            self.received += size as u32;
+            chunk = &chunk[size..];
+
+            if pos + size == self.sector.len() || self.received == self.len {
+                let offset = self.received - (pos + size) as u32;
+                target.write(self.slot, offset, &self.sector[..pos + size]) ? ;
+            }
+        }
+        Ok(())
+    }
+
+    /// Verify the CRC32 of the received pixels and commit the slot
+    fn commit<T: TransferTarget>(&mut self, target: &mut T, crc: u32, activate: bool) -> LogoResult<()> {
+        if self.state != State::Receiving { return Err(Error::BadState); }
+        if self.received != self.len      { return Err(Error::Truncated); }
+        if self.crc.finish() != crc       { return Err(Error::BadChecksum); }
+        let header = Header::rgb565(crate::WIDTH, crate::HEIGHT, crc);
+        target.commit(self.slot, &header, activate) ? ;
+        self.state = State::Committed;
+        Ok(())
+    }
+
+    /// Return to the idle state. Doesn't clear the sector buffer, to avoid copying it on the stack.
+    fn reset(&mut self) {
+        self.state    = State::Idle;
+        self.slot     = 0;
+        self.len      = 0;
+        self.received = 0;
+        self.error    = None;
+        self.crc      = Crc32::new();
+    }
+
+    /// Fail the transfer if the result is an error, except for chunks that may be resent
+    fn check(&mut self, result: LogoResult<()>) -> LogoResult<()> {
+        if let Err(err) = result {
+            if err != Error::BadOffset && err != Error::BadState {
+                self.state = State::Failed;
+                self.error = Some(err);
+            }
+        }
+        result
+    }
+}
+
+impl Default for Receiver {
+    fn default() -> Self {
+        Receiver::new()
+    }
+}
+
+impl Command {
+    /// Parse the command written to the Control characteristic
+    pub fn parse(data: &[u8]) -> LogoResult<Command> {
+        match data {
+            [0x01, slot, a, b, c, d] => Ok(Command::Start { slot: *slot, len: u32::from_le_bytes([*a, *b, *c, *d]) }),
+            [0x02, a, b, c, d, activate] => Ok(Command::Commit { crc: u32::from_le_bytes([*a, *b, *c, *d]), activate: *activate != 0 }),
+            [0x03] => Ok(Command::Abort),
+            _ => Err(Error::Corrupted),
+        }
+    }
+
+    /// Return the command as bytes, and the number of bytes used
+    pub fn to_bytes(&self) -> ([u8; 6], usize) {
+        let mut bytes = [0; 6];
+        match self {
+            Command::Start { slot, len } => {
+                bytes[0] = 0x01;
+                bytes[1] = *slot;
+                bytes[2..6].copy_from_slice(&len.to_le_bytes());
+                (bytes, 6)
+            }
+            Command::Commit { crc, activate } => {
+                bytes[0] = 0x02;
+                bytes[1..5].copy_from_slice(&crc.to_le_bytes());
+                bytes[5] = *activate as u8;
+                (bytes, 6)
+            }
+            Command::Abort => {
+                bytes[0] = 0x03;
+                (bytes, 1)
+            }
+        }
+    }
+}
+
+impl Status {
+    /// Parse the status read from the Status characteristic
+    pub fn parse(data: &[u8]) -> LogoResult<Status> {
+        if data.len() < STATUS_SIZE { return Err(Error::Truncated); }
+        let state = match data[0] {
+            0 => State::Idle,
+            1 => State::Receiving,
+            2 => State::Committed,
+            3 => State::Failed,
+            _ => return Err(Error::Corrupted),
+        };
+        Ok(Status {
+            state,
+            slot:     data[1],
+            received: u32::from_le_bytes([data[2], data[3], data[4], data[5]]),
+            error:    Error::from_code(data[6]),
+        })
+    }
+
+    /// Return the status as bytes
+    pub fn to_bytes(&self) -> [u8; STATUS_SIZE] {
+        let mut bytes = [0; STATUS_SIZE];
+        bytes[0] = self.state as u8;
+        bytes[1] = self.slot;
+        bytes[2..6].copy_from_slice(&self.received.to_le_bytes());
+        bytes[6] = self.error.map_or(0, Error::code);
+        bytes
+    }
+}
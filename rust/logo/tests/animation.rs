@@ -0,0 +1,95 @@
+//! Test the animated boot logo with emulated External SPI Flash. Run on the host computer:
+//! `cargo test -p logo --features std --target x86_64-unknown-linux-gnu`
+use logo::{
+    animation::{ Animation, FrameHeader, Player, Rect, FRAMES_OFFSET, FRAME_HEADER_SIZE },
+    emulator::{ FlashEmulator, FLASH_SIZE },
+    encoder::{ self, AnimationFrame },
+    flash::Flash,
+    loader::Loader,
+    slots,
+    Error,
+};
+
+/// Number of pixels in a frame
+const PIXEL_COUNT: usize = logo::WIDTH as usize * logo::HEIGHT as usize;
+
+/// Return a frame with a grey gradient background and a red square at the position
+fn square_frame(left: usize, top: usize, delay_ms: u16) -> AnimationFrame {
+    let width = logo::WIDTH as usize;
+    let pixels = (0..PIXEL_COUNT)
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            if x >= left && x < left + 40 && y >= top && y < top + 40 { 0xf800 }
+            else { ((y / 8) as u16) * 0x0841 }
+        })
+        .collect();
+    AnimationFrame { pixels, delay_ms }
+}
+
+/// Write the animation to the slot of the emulated flash and return the player
+fn write_animation(frames: &[AnimationFrame], slot: u8) -> Player<FlashEmulator> {
+    let image = encoder::encode_animation(frames, logo::WIDTH, logo::HEIGHT, 2).unwrap();
+    let mut loader = Loader::new(FlashEmulator::new(FLASH_SIZE));
+    loader.write_animation(slot, &image).unwrap();
+    assert!(!loader.is_valid(slot).unwrap());  //  Bootloader won't show the animation
+    Player::new(loader.into_flash())
+}
+
+/// Play every frame of the animation into the framebuffer. Returns the framebuffer after each frame.
+fn play(player: &mut Player<FlashEmulator>, animation: &Animation) -> Vec<Vec<u16>> {
+    let mut framebuffer = vec![0u16; PIXEL_COUNT];
+    let mut frames = Vec::new();
+    let mut offset = animation.first;
+    for _ in 0..animation.header.frame_count {
+        let frame = player.draw_frame(animation, offset, |rect: &Rect, pixels: &[u8]| {
+            for (i, pixel) in pixels.chunks(2).enumerate() {
+                let x = rect.left as usize + i % rect.width as usize;
+                let y = rect.top as usize + i / rect.width as usize;
+                framebuffer[y * logo::WIDTH as usize + x] = u16::from_be_bytes([pixel[0], pixel[1]]);
+            }
+            Ok(())
+        }).unwrap();
+        frames.push(framebuffer.clone());
+        offset = frame.next;
+    }
+    assert_eq!(offset, animation.first + animation.header.frames_len);
+    frames
+}
+
+#[test]
+fn player_draws_encoded_frames() {
+    let frames = vec![square_frame(0, 0, 100), square_frame(20, 10, 50), square_frame(20, 10, 200), square_frame(200, 200, 100)];
+    let mut player = write_animation(&frames, 3);
+    let animation = player.open(3).unwrap();
+    assert_eq!(animation.header.frame_count, 4);
+    assert_eq!(animation.header.loops, 2);
+    for (played, frame) in play(&mut player, &animation).iter().zip(&frames) {
+        assert!(played == &frame.pixels);
+    }
+}
+
+#[test]
+fn unchanged_frame_has_no_rects() {
+    let frames = vec![square_frame(0, 0, 100), square_frame(0, 0, 300)];
+    let image = encoder::encode_animation(&frames, logo::WIDTH, logo::HEIGHT, 1).unwrap();
+    let last = image.len() - FRAME_HEADER_SIZE;
+    let header = FrameHeader::parse(&image[last..]).unwrap();
+    assert_eq!(header, FrameHeader { delay_ms: 300, rect_count: 0, rects_len: 0 });
+}
+
+#[test]
+fn open_rejects_corrupted_frames() {
+    let mut player = write_animation(&[square_frame(0, 0, 100)], 1);
+    assert_eq!(player.open(0), Err(Error::BadMagic));
+    assert!(player.open(1).is_ok());
+
+    //  Flip a byte in the first frame
+    let offset = slots::slot_offset(1).unwrap() + FRAMES_OFFSET + 100;
+    let mut flash = FlashEmulator::new(FLASH_SIZE);
+    flash.write(0, player.flash().data()).unwrap();
+    flash.erase(offset, 1).unwrap();
+    let mut sector = player.flash().data()[(offset - 100) as usize..(offset - 100) as usize + 4096].to_vec();
+    sector[100] ^= 0xff;
+    flash.write(offset - 100, &sector).unwrap();
+    assert_eq!(Player::new(flash).open(1), Err(Error::BadChecksum));
+}
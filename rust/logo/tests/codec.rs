@@ -0,0 +1,136 @@
+//! Round-trip the boot logo through the encoder and the streaming decoder, with every batch size that splits a
+//! pixel or a run, and check that corrupted logos are rejected. Run on the host computer:
+//! `cargo test -p logo --features std --target x86_64-unknown-linux-gnu`
+use logo::{
+    decoder::Decoder,
+    encoder,
+    header::{ Header, PixelFormat, HEADER_SIZE },
+    Error, LogoResult,
+};
+
+/// Odd size so that the image doesn't end on a batch boundary
+const WIDTH: u16 = 37;
+const HEIGHT: u16 = 11;
+
+/// Compressed pixel formats
+const FORMATS: [PixelFormat; 2] = [PixelFormat::Rgb565Rle, PixelFormat::Indexed8Rle];
+
+/// Return test pixels: a long repeated run, short runs, a literal run of distinct colours, then another long run
+fn test_pixels() -> Vec<u16> {
+    let count = WIDTH as usize * HEIGHT as usize;
+    (0..count).map(|i| match i {
+        0..=149   => 0xf800,
+        150..=199 => if (i / 2) & 1 == 0 { 0x07e0 } else { 0x001f },
+        200..=299 => (i as u16).wrapping_mul(0x0123),
+        _         => 0xffff,
+    }).collect()
+}
+
+/// Return the big-endian RGB565 bytes of the pixels
+fn to_bytes(pixels: &[u16]) -> Vec<u8> {
+    pixels.iter().flat_map(|pixel| pixel.to_be_bytes().to_vec()).collect()
+}
+
+/// Decode the compressed logo in batches of the size. Returns the decoded bytes.
+fn decode(logo: &[u8], batch: usize) -> LogoResult<Vec<u8>> {
+    let mut decoder = Decoder::new(logo) ? ;
+    let mut decoded = Vec::new();
+    let mut buf = vec![0; batch];
+    loop {
+        let len = decoder.read(&mut buf) ? ;
+        if len == 0 { return Ok(decoded); }
+        decoded.extend_from_slice(&buf[..len]);
+    }
+}
+
+/// Replace the payload of the compressed logo and update the payload length in the header
+fn with_payload(logo: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut header = Header::parse(logo).unwrap();
+    header.payload_len = payload.len() as u32;
+    let mut changed = header.to_bytes().to_vec();
+    changed.extend_from_slice(payload);
+    changed
+}
+
+#[test]
+fn round_trip_with_every_batch_size() {
+    let pixels = test_pixels();
+    let expected = to_bytes(&pixels);
+    for format in FORMATS.iter() {
+        let logo = encoder::encode(&pixels, WIDTH, HEIGHT, *format).unwrap();
+        assert!(logo.len() < HEADER_SIZE + expected.len(), "{:?} not compressed", format);
+        //  Odd batch sizes split a pixel across batches. Small batches split the runs.
+        for batch in [1, 2, 3, 5, 7, 64, 255, 4096].iter() {
+            assert_eq!(decode(&logo, *batch).unwrap(), expected, "{:?} in batches of {}", format, batch);
+        }
+    }
+}
+
+#[test]
+fn repeated_run_crosses_batches() {
+    //  Runs longer than the batch and longer than the max literal
+    let pixels = vec![0x1234; WIDTH as usize * HEIGHT as usize];
+    for format in FORMATS.iter() {
+        let logo = encoder::encode(&pixels, WIDTH, HEIGHT, *format).unwrap();
+        let mut decoder = Decoder::new(&logo).unwrap();
+        let mut buf = [0; 99];
+        let mut total = 0;
+        loop {
+            let len = decoder.read(&mut buf).unwrap();
+            if len == 0 { break; }
+            assert!(buf[..len].iter().enumerate().all(|(i, byte)| {
+                *byte == if (total + i) & 1 == 0 { 0x12 } else { 0x34 }
+            }));
+            total += len;
+        }
+        assert_eq!(total, pixels.len() * logo::BYTES_PER_PIXEL);
+    }
+}
+
+#[test]
+fn truncated_payload_is_rejected() {
+    let pixels = test_pixels();
+    for format in FORMATS.iter() {
+        let logo = encoder::encode(&pixels, WIDTH, HEIGHT, *format).unwrap();
+        //  Shorter than the payload length in the header
+        assert!(matches!(Decoder::new(&logo[..logo.len() - 1]), Err(Error::Truncated)));
+        assert!(matches!(Decoder::new(&logo[..HEADER_SIZE - 1]), Err(Error::Truncated)));
+
+        //  Payload ends before the last pixel
+        let payload = &logo[HEADER_SIZE..logo.len() - 1];
+        assert_eq!(decode(&with_payload(&logo, payload), 7), Err(Error::Truncated), "{:?}", format);
+    }
+}
+
+#[test]
+fn trailing_data_is_rejected() {
+    let pixels = test_pixels();
+    for format in FORMATS.iter() {
+        let logo = encoder::encode(&pixels, WIDTH, HEIGHT, *format).unwrap();
+        let mut payload = logo[HEADER_SIZE..].to_vec();
+        payload.extend_from_slice(&[0x00, 0x00, 0x00]);
+        for batch in [3, 4096].iter() {
+            assert_eq!(decode(&with_payload(&logo, &payload), *batch), Err(Error::Corrupted), "{:?}", format);
+        }
+        //  Data after the payload length is ignored
+        let mut longer = logo.clone();
+        longer.extend_from_slice(&[0xff; 8]);
+        assert_eq!(decode(&longer, 64).unwrap(), to_bytes(&pixels));
+    }
+}
+
+#[test]
+fn bad_checksum_is_rejected() {
+    let pixels = test_pixels();
+    for format in FORMATS.iter() {
+        let mut logo = encoder::encode(&pixels, WIDTH, HEIGHT, *format).unwrap();
+        logo[16] ^= 0x01;  //  CRC32 in the header
+        for batch in [5, 4096].iter() {
+            assert_eq!(decode(&logo, *batch), Err(Error::BadChecksum), "{:?}", format);
+        }
+    }
+    //  Corrupted pixel in the uncompressed payload
+    let mut logo = encoder::encode(&pixels, WIDTH, HEIGHT, PixelFormat::Rgb565).unwrap();
+    logo[HEADER_SIZE + 100] ^= 0x80;
+    assert_eq!(decode(&logo, 4096), Err(Error::BadChecksum));
+}
@@ -0,0 +1,204 @@
+//! Test the Boot Logo Loader with emulated External SPI Flash. Run on the host computer:
+//! `cargo test -p logo --features std --target x86_64-unknown-linux-gnu`
+use logo::{
+    emulator::{ FlashEmulator, FLASH_SIZE },
+    encoder,
+    flash::Flash,
+    header::PixelFormat,
+    layout::{ HEADER_OFFSET, PIXELS_OFFSET, SECTOR_SIZE },
+    loader::{ Loader, SlotDiff },
+    slots,
+    Error,
+};
+
+/// Number of sectors in a slot that hold the logo: header sector and 115,200 bytes of pixels
+const LOGO_SECTORS: u32 = 1 + 29;
+
+/// Return the pixels of a test logo: horizontal stripes of the colour
+fn stripes(color: u16) -> Vec<u16> {
+    (0..logo::WIDTH as usize * logo::HEIGHT as usize)
+        .map(|i| if (i / logo::WIDTH as usize / 8) & 1 == 0 { color } else { 0 })
+        .collect()
+}
+
+/// Return the compressed logo for the pixels
+fn compress(pixels: &[u16]) -> Vec<u8> {
+    encoder::encode(pixels, logo::WIDTH, logo::HEIGHT, PixelFormat::Rgb565Rle).unwrap()
+}
+
+/// Return a loader with erased flash
+fn new_loader() -> Loader<FlashEmulator> {
+    Loader::new(FlashEmulator::new(FLASH_SIZE))
+}
+
+/// Return the total number of sector erases and writes
+fn counts(loader: &Loader<FlashEmulator>) -> (u32, u32) {
+    (loader.flash().total_erases(), loader.flash().total_writes())
+}
+
+#[test]
+fn write_slot_matches_flash_image() {
+    let pixels = stripes(0xf800);
+    let mut loader = new_loader();
+    loader.write_slot(2, &compress(&pixels)).unwrap();
+    loader.mark_active(2).unwrap();
+
+    let image = encoder::encode_flash_image(&pixels, logo::WIDTH, logo::HEIGHT).unwrap();
+    let offset = slots::slot_offset(2).unwrap() as usize;
+    assert_eq!(&loader.flash().data()[offset..offset + image.len()], &image[..]);
+    assert_eq!(loader.active_slot().unwrap(), 2);
+    assert!(loader.is_valid(2).unwrap());
+    assert!(!loader.is_valid(0).unwrap());
+}
+
+#[test]
+fn update_slot_skips_unchanged_sectors() {
+    let mut pixels = stripes(0x07e0);
+    let mut loader = new_loader();
+    loader.write_slot(0, &compress(&pixels)).unwrap();
+
+    //  Nothing changed: nothing is erased or written
+    let before = counts(&loader);
+    assert_eq!(loader.update_slot(0, &compress(&pixels)).unwrap(), SlotDiff { sectors: LOGO_SECTORS, changed: 0 });
+    assert_eq!(counts(&loader), before);
+
+    //  One pixel changed: its sector and the header sector are rewritten
+    pixels[5000] = 0x001f;
+    assert_eq!(loader.diff_slot(0, &compress(&pixels)).unwrap(), SlotDiff { sectors: LOGO_SECTORS, changed: 2 });
+    assert_eq!(counts(&loader), before);
+    assert_eq!(loader.update_slot(0, &compress(&pixels)).unwrap(), SlotDiff { sectors: LOGO_SECTORS, changed: 2 });
+    let slot_offset = slots::slot_offset(0).unwrap();
+    assert_eq!(loader.flash().erase_count(slot_offset + PIXELS_OFFSET + SECTOR_SIZE * 2), 2);
+    assert_eq!(loader.flash().erase_count(slot_offset + PIXELS_OFFSET), 1);
+    assert_eq!(loader.flash().write_count(slot_offset + HEADER_OFFSET), 2);
+    assert!(loader.is_valid(0).unwrap());
+}
+
+#[test]
+fn diff_slot_counts_empty_slot() {
+    let mut loader = new_loader();
+    let diff = loader.diff_slot(1, &compress(&stripes(0xffff))).unwrap();
+    assert_eq!(diff, SlotDiff { sectors: LOGO_SECTORS, changed: LOGO_SECTORS });
+    assert_eq!(loader.flash().total_writes(), 0);
+}
+
+#[test]
+fn diff_slot_counts_changed_sectors_without_writing() {
+    let mut pixels = stripes(0xf81f);
+    let mut loader = new_loader();
+    loader.write_slot(1, &compress(&pixels)).unwrap();
+    let before = loader.flash().data().to_vec();
+    let counts_before = counts(&loader);
+
+    //  Unchanged logo: nothing differs
+    assert_eq!(loader.diff_slot(1, &compress(&pixels)).unwrap(), SlotDiff { sectors: LOGO_SECTORS, changed: 0 });
+
+    //  Pixels changed in 3 sectors: the 3 sectors and the header differ, because the CRC32 changes
+    let sector_pixels = SECTOR_SIZE as usize / 2;
+    for &sector in [0, 7, 27].iter() {
+        pixels[sector * sector_pixels + 10] ^= 0xffff;
+    }
+    assert_eq!(loader.diff_slot(1, &compress(&pixels)).unwrap(), SlotDiff { sectors: LOGO_SECTORS, changed: 4 });
+
+    //  Last sector (28) is only partly used by the pixels
+    let last = pixels.len() - 1;
+    pixels[last] ^= 0xffff;
+    assert_eq!(loader.diff_slot(1, &compress(&pixels)).unwrap(), SlotDiff { sectors: LOGO_SECTORS, changed: 5 });
+
+    //  Nothing was erased or written, and the old logo is still valid
+    assert_eq!(counts(&loader), counts_before);
+    assert_eq!(loader.flash().data(), &before[..]);
+    assert!(loader.is_valid(1).unwrap());
+}
+
+#[test]
+fn update_slot_rewrites_only_changed_sectors() {
+    let mut pixels = stripes(0x001f);
+    let mut loader = new_loader();
+    loader.write_slot(1, &compress(&pixels)).unwrap();
+    let slot_offset = slots::slot_offset(1).unwrap();
+    let sector_offset = |sector: u32| slot_offset + PIXELS_OFFSET + sector * SECTOR_SIZE;
+
+    //  Pixels changed in 2 sectors: only those sectors and the header are written. The header is erased before the
+    //  first changed sector, so the slot is invalid while it's updated, and erased again when it's rewritten.
+    let sector_pixels = SECTOR_SIZE as usize / 2;
+    pixels[3 * sector_pixels] = 0x07e0;
+    pixels[20 * sector_pixels + 100] = 0x07e0;
+    let logo = compress(&pixels);
+    assert_eq!(loader.diff_slot(1, &logo).unwrap(), SlotDiff { sectors: LOGO_SECTORS, changed: 3 });
+    let mut flash = loader.into_flash();
+    flash.reset_counts();
+    let mut loader = Loader::new(flash);
+    assert_eq!(loader.update_slot(1, &logo).unwrap(), SlotDiff { sectors: LOGO_SECTORS, changed: 3 });
+    assert_eq!(counts(&loader), (4, 3));
+    for sector in 0..LOGO_SECTORS - 1 {
+        let expected = if sector == 3 || sector == 20 { 1 } else { 0 };
+        assert_eq!(loader.flash().erase_count(sector_offset(sector)), expected, "sector {}", sector);
+    }
+    assert_eq!(loader.flash().erase_count(slot_offset + HEADER_OFFSET), 2);
+
+    //  Slot now matches the logo and the flash image
+    assert_eq!(loader.diff_slot(1, &logo).unwrap(), SlotDiff { sectors: LOGO_SECTORS, changed: 0 });
+    assert!(loader.is_valid(1).unwrap());
+    let image = encoder::encode_flash_image(&pixels, logo::WIDTH, logo::HEIGHT).unwrap();
+    let offset = slot_offset as usize;
+    assert_eq!(&loader.flash().data()[offset..offset + image.len()], &image[..]);
+}
+
+#[test]
+fn update_slot_restores_missing_header() {
+    //  Header erased after the pixels were written, e.g. by an interrupted update: only the header is rewritten
+    let pixels = stripes(0xffe0);
+    let mut loader = new_loader();
+    loader.write_slot(0, &compress(&pixels)).unwrap();
+    let header_offset = slots::slot_offset(0).unwrap() + HEADER_OFFSET;
+    let mut flash = loader.into_flash();
+    flash.erase(header_offset, SECTOR_SIZE).unwrap();
+    flash.reset_counts();
+    let mut loader = Loader::new(flash);
+    assert!(!loader.is_valid(0).unwrap());
+
+    assert_eq!(loader.diff_slot(0, &compress(&pixels)).unwrap(), SlotDiff { sectors: LOGO_SECTORS, changed: 1 });
+    assert_eq!(loader.update_slot(0, &compress(&pixels)).unwrap(), SlotDiff { sectors: LOGO_SECTORS, changed: 1 });
+    assert_eq!(counts(&loader), (1, 1));
+    assert_eq!(loader.flash().write_count(header_offset), 1);
+    assert!(loader.is_valid(0).unwrap());
+}
+
+#[test]
+fn logo_of_wrong_size_is_rejected() {
+    let mut loader = new_loader();
+    loader.write_slot(2, &compress(&stripes(0xf800))).unwrap();
+
+    //  Larger logo would overflow into the next slot. Nothing is erased.
+    let (width, height) = (logo::WIDTH, logo::HEIGHT + 40);
+    let large = encoder::encode(&vec![0xffff; width as usize * height as usize], width, height, PixelFormat::Rgb565Rle)
+        .unwrap();
+    let small = encoder::encode(&[0xffff; 16 * 16], 16, 16, PixelFormat::Rgb565Rle).unwrap();
+    let before = counts(&loader);
+    for slot in [2, 3].iter() {
+        assert_eq!(loader.write_slot(*slot, &large), Err(Error::BadSize));
+        assert_eq!(loader.update_slot(*slot, &large), Err(Error::BadSize));
+        assert_eq!(loader.diff_slot(*slot, &small), Err(Error::BadSize));
+    }
+    assert_eq!(counts(&loader), before);
+    assert!(loader.is_valid(2).unwrap());
+}
+
+#[test]
+fn emulator_rejects_write_without_erase() {
+    let mut flash = FlashEmulator::new(FLASH_SIZE);
+    flash.write(0x1000, &[0x12, 0x34]).unwrap();
+    assert_eq!(flash.write(0x1001, &[0x56]), Err(Error::NotErased));
+    flash.erase(0x1fff, 1).unwrap();
+    assert_eq!(flash.erase_count(0x1000), 1);
+    flash.write(0x1001, &[0x56]).unwrap();
+    assert_eq!(flash.write(FLASH_SIZE - 1, &[0, 0]), Err(Error::BadOffset));
+}
+
+#[test]
+fn mark_active_requires_valid_logo() {
+    let mut loader = new_loader();
+    assert!(loader.mark_active(3).is_err());
+    assert_eq!(loader.active_slot().unwrap(), 0);
+}
@@ -0,0 +1,69 @@
+//! Build an animated boot logo (see `logo::animation`) from a directory of 240x240 PNG files, one frame per file
+//! in order of file name. The animation is played back with the player from the `logo` library on emulated flash,
+//! and every frame is compared with its PNG file.
+use logo::{
+    animation::{ Player, Rect },
+    emulator::{ FlashEmulator, FLASH_SIZE },
+    encoder::AnimationFrame,
+    loader::Loader,
+};
+use std::{
+    fs,
+    path::{ Path, PathBuf },
+};
+use crate::{
+    convert,
+    quantise::{ self, Options },
+};
+
+/// Load the PNG files in the directory as frames, sorted by file name, and quantise with the options.
+/// Every frame is shown for the delay.
+pub fn load_frames(dir: &Path, delay_ms: u16, options: &Options) -> Result<Vec<AnimationFrame>, String> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|err| format!("{}: {}", dir.display(), err)) ?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()).map(str::to_lowercase).as_deref() == Some("png"))
+        .collect();
+    paths.sort();
+    if paths.is_empty() { return Err(format!("{}: no PNG files", dir.display())); }
+    paths.iter()
+        .map(|path| {
+            let image = convert::load_png(path)
+                .map_err(|err| format!("{}: {}", path.display(), err)) ? ;
+            let pixels = quantise::quantise(&image.pixels, logo::WIDTH as usize, options);
+            Ok(AnimationFrame { pixels, delay_ms })
+        })
+        .collect()
+}
+
+/// Write the animation image to a slot of emulated flash, play every frame and compare with the source frames.
+/// Returns the number of bands of pixels drawn.
+pub fn verify(image: &[u8], frames: &[AnimationFrame], slot: u8) -> Result<usize, String> {
+    let mut loader = Loader::new(FlashEmulator::new(FLASH_SIZE));
+    loader.write_animation(slot, image)
+        .map_err(|err| format!("unable to write animation: {:?}", err)) ? ;
+    let mut player = Player::new(loader.into_flash());
+    let animation = player.open(slot)
+        .map_err(|err| format!("unable to open animation: {:?}", err)) ? ;
+
+    let width = animation.header.width as usize;
+    let mut framebuffer = vec![0u16; width * animation.header.height as usize];
+    let mut bands = 0;
+    let mut offset = animation.first;
+    for (index, frame) in frames.iter().enumerate() {
+        let drawn = player.draw_frame(&animation, offset, |rect: &Rect, pixels: &[u8]| {
+            for (i, pixel) in pixels.chunks(logo::BYTES_PER_PIXEL).enumerate() {
+                let x = rect.left as usize + i % rect.width as usize;
+                let y = rect.top as usize + i / rect.width as usize;
+                framebuffer[y * width + x] = u16::from_be_bytes([pixel[0], pixel[1]]);
+            }
+            bands += 1;
+            Ok(())
+        }).map_err(|err| format!("unable to play frame {}: {:?}", index, err)) ? ;
+        if framebuffer != frame.pixels || drawn.delay_ms != frame.delay_ms {
+            return Err(format!("frame {} doesn't match", index));
+        }
+        offset = drawn.next;
+    }
+    Ok(bands)
+}
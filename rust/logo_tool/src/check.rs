@@ -0,0 +1,308 @@
+//! Check a PNG file before it's used as a boot logo. Unlike `convert::load_png()`, any PNG is loaded, so that every
+//! problem can be reported at once: wrong size, alpha channel, 16-bit depth, greyscale or palette colours.
+//! The image is quantised to RGB565 as PineTime will show it, and gradients are checked for banding.
+use std::{
+    fmt,
+    fs::File,
+    io::BufWriter,
+    path::Path,
+};
+use logo::{ HEIGHT, WIDTH };
+use crate::{
+    convert::Error,
+    quantise::{ self, Options },
+};
+
+/// Max difference of each channel between neighbouring pixels in a smooth gradient
+const SMOOTH_STEP: i32 = 3;
+
+/// Min number of pixels in a gradient that is checked for banding
+const MIN_GRADIENT: usize = 24;
+
+/// Gradient is banded if the quantised bands are this many times wider than the steps in the PNG...
+const BAND_RATIO: usize = 4;
+
+/// ...and at least this many pixels wide
+const MIN_BAND_WIDTH: usize = 8;
+
+/// Problem found in the PNG file
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    /// PNG is not 240x240
+    WrongSize { width: u32, height: u32 },
+    /// PNG has 16 bits per channel. Only the upper 8 bits will be used.
+    SixteenBit,
+    /// PNG has less than 8 bits per channel
+    LowBitDepth { bit_depth: png::BitDepth },
+    /// PNG has an alpha channel or a transparent colour. Transparent pixels will be shown over black.
+    Alpha { transparent: usize },
+    /// PNG is greyscale or palette-indexed, not RGB
+    NotRgb { color_type: png::ColorType },
+    /// Gradients will show visible bands in RGB565. `x` and `y` locate the first banded gradient.
+    Banding { rows: usize, columns: usize, x: usize, y: usize },
+}
+
+/// Result of checking a PNG file
+pub struct Report {
+    /// Problems found, errors first
+    pub problems: Vec<Problem>,
+    /// Width of the PNG
+    pub width:    usize,
+    /// Height of the PNG
+    pub height:   usize,
+    /// RGB565 pixels as PineTime will show them
+    pub pixels:   Vec<u16>,
+}
+
+impl Problem {
+    /// Return true if `convert::load_png()` will reject the PNG because of this problem.
+    /// Other problems are warnings.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Problem::Banding { .. })
+    }
+}
+
+impl Report {
+    /// Return true if the PNG may be converted
+    pub fn is_ok(&self) -> bool {
+        !self.problems.iter().any(Problem::is_error)
+    }
+}
+
+/// Load the PNG file in any format and report every problem. The pixels are quantised with the options.
+pub fn check_png(path: &Path, options: &Options) -> Result<Report, Error> {
+    let mut decoder = png::Decoder::new(File::open(path) ?);
+    //  Expand palette and greyscale to 8 bits, and reduce 16-bit channels to their upper 8 bits
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (output, mut reader) = decoder.read_info() ?;
+    let info = reader.info();
+    let mut problems = Vec::new();
+
+    //  Check the format as stored in the file
+    if info.width != WIDTH as u32 || info.height != HEIGHT as u32 {
+        problems.push(Problem::WrongSize { width: info.width, height: info.height });
+    }
+    match info.bit_depth {
+        png::BitDepth::Eight => {}
+        png::BitDepth::Sixteen => problems.push(Problem::SixteenBit),
+        bit_depth => problems.push(Problem::LowBitDepth { bit_depth }),
+    }
+    if info.color_type != png::ColorType::RGB && info.color_type != png::ColorType::RGBA {
+        problems.push(Problem::NotRgb { color_type: info.color_type });
+    }
+    //  Convert to 8-bit RGBA
+    let mut data = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut data) ? ;
+    let rgba: Vec<[u8; 4]> = match output.color_type {
+        png::ColorType::RGB            => data.chunks(3).map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::RGBA           => data.chunks(4).map(|p| [p[0], p[1], p[2], p[3]]).collect(),
+        png::ColorType::Grayscale      => data.iter().map(|g| [*g, *g, *g, 255]).collect(),
+        png::ColorType::GrayscaleAlpha => data.chunks(2).map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Indexed        => unreachable!("palette not expanded"),
+    };
+    if output.color_type == png::ColorType::RGBA || output.color_type == png::ColorType::GrayscaleAlpha {
+        let transparent = rgba.iter().filter(|p| p[3] != 255).count();
+        problems.push(Problem::Alpha { transparent });
+    }
+    //  Show transparent pixels over black, then quantise
+    let rgb: Vec<u8> = rgba.iter()
+        .flat_map(|p| (0..3).map(move |c| (p[c] as u32 * p[3] as u32 / 255) as u8))
+        .collect();
+    let (width, height) = (output.width as usize, output.height as usize);
+    let pixels = quantise::quantise(&rgb, width, options);
+    if let Some(banding) = find_banding(&rgb, &pixels, width, height) {
+        problems.push(banding);
+    }
+    Ok(Report { problems, width, height, pixels })
+}
+
+/// Write the quantised pixels as a 24-bit RGB PNG file, to preview the logo on the host computer
+pub fn write_preview(report: &Report, path: &Path) -> Result<(), Error> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path) ?), report.width as u32, report.height as u32);
+    encoder.set_color(png::ColorType::RGB);
+    encoder.set_depth(png::BitDepth::Eight);
+    let data: Vec<u8> = report.pixels.iter()
+        .flat_map(|pixel| quantise::to_rgb888(*pixel).to_vec())
+        .collect();
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(|err| Error::Io(err.into()))
+}
+
+/// Check every row and column for smooth gradients that are banded after quantising. Returns `None` if no banding.
+fn find_banding(rgb: &[u8], pixels: &[u16], width: usize, height: usize) -> Option<Problem> {
+    let mut first = None;
+    let mut rows = 0;
+    for y in 0..height {
+        let line: Vec<usize> = (0..width).map(|x| y * width + x).collect();
+        if let Some(pos) = banded_gradient(rgb, pixels, &line) {
+            rows += 1;
+            first = first.or(Some((pos, y)));
+        }
+    }
+    let mut columns = 0;
+    for x in 0..width {
+        let line: Vec<usize> = (0..height).map(|y| y * width + x).collect();
+        if let Some(pos) = banded_gradient(rgb, pixels, &line) {
+            columns += 1;
+            first = first.or(Some((x, pos)));
+        }
+    }
+    let (x, y) = first ? ;
+    Some(Problem::Banding { rows, columns, x, y })
+}
+
+/// Return the position of the first banded gradient along the line of pixel indexes, if any
+fn banded_gradient(rgb: &[u8], pixels: &[u16], line: &[usize]) -> Option<usize> {
+    let color = |i: usize| [rgb[i * 3] as i32, rgb[i * 3 + 1] as i32, rgb[i * 3 + 2] as i32];
+    let mut start = 0;
+    while start < line.len() {
+        //  Extend the gradient while neighbouring pixels are close
+        let mut end = start + 1;
+        while end < line.len() {
+            let (a, b) = (color(line[end - 1]), color(line[end]));
+            if (0..3).any(|c| (a[c] - b[c]).abs() > SMOOTH_STEP) { break; }
+            end += 1;
+        }
+        let gradient = &line[start..end];
+        if gradient.len() >= MIN_GRADIENT {
+            //  Count the steps in the PNG and after quantising
+            let source_steps = gradient.windows(2).filter(|w| color(w[0]) != color(w[1])).count();
+            let quantised_steps = gradient.windows(2).filter(|w| pixels[w[0]] != pixels[w[1]]).count();
+            let source_width = gradient.len() / (source_steps + 1);
+            let band_width = gradient.len() / (quantised_steps + 1);
+            if source_steps > 0 && quantised_steps > 0
+                && band_width >= MIN_BAND_WIDTH && band_width >= source_width * BAND_RATIO {
+                return Some(start);
+            }
+        }
+        start = end;
+    }
+    None
+}
+
+/// Display the problem for the command line
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::WrongSize { width, height } =>
+                write!(f, "PNG is {}x{}, must be {}x{}", width, height, WIDTH, HEIGHT),
+            Problem::SixteenBit =>
+                write!(f, "PNG has 16 bits per channel, must be 8 bits (24-bit RGB)"),
+            Problem::LowBitDepth { bit_depth } =>
+                write!(f, "PNG has {} bits per channel, must be 8 bits (24-bit RGB)", *bit_depth as u8),
+            Problem::Alpha { transparent } =>
+                write!(f, "PNG has an alpha channel, must be 24-bit RGB ({} transparent pixels will be shown over black)", transparent),
+            Problem::NotRgb { color_type } =>
+                write!(f, "PNG is {:?}, must be 24-bit RGB", color_type),
+            Problem::Banding { rows, columns, x, y } =>
+                write!(f, "gradients will show bands in RGB565: {} rows and {} columns, first at ({}, {}). Try --dither", rows, columns, x, y),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantise::Dither;
+    use std::{ env, fs, path::PathBuf };
+
+    /// Width and height of the test images
+    const SIZE: usize = WIDTH as usize;
+
+    /// Write a PNG file with the colour type, bit depth and samples to a temporary file and return the path
+    fn write_png(name: &str, width: u32, height: u32, color_type: png::ColorType, bit_depth: png::BitDepth,
+        data: &[u8]) -> PathBuf {
+        let path = env::temp_dir().join(format!("logo_tool_check_{}_{}.png", name, std::process::id()));
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(&path).unwrap()), width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(bit_depth);
+        encoder.write_header().unwrap().write_image_data(data).unwrap();
+        path
+    }
+
+    /// Check the PNG file and delete it
+    fn check(path: PathBuf, options: &Options) -> Report {
+        let report = check_png(&path, options).unwrap();
+        fs::remove_file(&path).unwrap();
+        report
+    }
+
+    /// Return a 240x240 grey gradient from left to right that rises by 1 level every `step` pixels
+    fn gradient(step: usize) -> Vec<u8> {
+        (0..SIZE * SIZE).flat_map(|i| {
+            let level = (i % SIZE / step) as u8;
+            vec![level, level, level]
+        }).collect()
+    }
+
+    #[test]
+    fn shallow_gradient_is_banded() {
+        //  60 grey levels become 8 levels of red and blue in RGB565: bands 32 pixels wide instead of 4
+        let rgb = gradient(4);
+        let path = write_png("banded", SIZE as u32, SIZE as u32, png::ColorType::RGB, png::BitDepth::Eight, &rgb);
+        let report = check(path, &Options::default());
+        assert_eq!(report.problems, [Problem::Banding { rows: SIZE, columns: 0, x: 0, y: 0 }]);
+        assert!(report.is_ok(), "banding is only a warning");
+        assert_eq!(report.pixels.len(), SIZE * SIZE);
+
+        //  Banding is located at the first banded gradient, below 10 black rows
+        let mut pixels = quantise::quantise(&rgb, SIZE, &Options::default());
+        let mut rgb_top = rgb.clone();
+        for i in 0..SIZE * 10 {
+            rgb_top[i * 3..i * 3 + 3].copy_from_slice(&[0, 0, 0]);
+            pixels[i] = 0;
+        }
+        assert_eq!(find_banding(&rgb_top, &pixels, SIZE, SIZE),
+            Some(Problem::Banding { rows: SIZE - 10, columns: 0, x: 0, y: 10 }));
+    }
+
+    #[test]
+    fn dithered_or_steep_gradient_is_clean() {
+        //  Same gradient with ordered dithering: the steps in RGB565 are as fine as in the PNG
+        let rgb = gradient(4);
+        let options = Options { dither: Dither::Ordered, ..Options::default() };
+        let pixels = quantise::quantise(&rgb, SIZE, &options);
+        assert_eq!(find_banding(&rgb, &pixels, SIZE, SIZE), None);
+
+        //  Gradient that steps by 1 level every pixel in green only: every 4 levels are 1 level of RGB565 green
+        let rgb: Vec<u8> = (0..SIZE * SIZE).flat_map(|i| vec![0, (i % SIZE) as u8, 0]).collect();
+        let path = write_png("clean", SIZE as u32, SIZE as u32, png::ColorType::RGB, png::BitDepth::Eight, &rgb);
+        let report = check(path, &Options::default());
+        assert_eq!(report.problems, []);
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn every_format_problem_is_reported() {
+        //  Wrong size, 16-bit greyscale
+        let data = vec![0x12; 10 * 20 * 2];
+        let path = write_png("grey16", 10, 20, png::ColorType::Grayscale, png::BitDepth::Sixteen, &data);
+        let report = check(path, &Options::default());
+        assert_eq!(report.problems, [
+            Problem::WrongSize { width: 10, height: 20 },
+            Problem::SixteenBit,
+            Problem::NotRgb { color_type: png::ColorType::Grayscale },
+        ]);
+        assert!(!report.is_ok());
+        assert_eq!(report.pixels, vec![convert_grey(0x12); 10 * 20]);
+
+        //  Alpha channel: transparent pixels are shown over black
+        let mut data: Vec<u8> = (0..SIZE * SIZE).flat_map(|_| vec![0xff, 0xff, 0xff, 0xff]).collect();
+        for i in 0..100 { data[i * 4 + 3] = 0; }
+        data[100 * 4 + 3] = 0x80;
+        let path = write_png("alpha", SIZE as u32, SIZE as u32, png::ColorType::RGBA, png::BitDepth::Eight, &data);
+        let report = check(path, &Options::default());
+        assert_eq!(report.problems, [Problem::Alpha { transparent: 101 }]);
+        assert!(!report.is_ok());
+        assert_eq!(report.pixels[..101].iter().filter(|pixel| **pixel == 0).count(), 100);
+        assert_eq!(report.pixels[100], convert_grey(0x80));
+        assert_eq!(report.pixels[101], 0xffff);
+        assert!(report.problems[0].to_string().contains("101 transparent pixels"));
+    }
+
+    /// Return the RGB565 pixel for the grey level
+    fn convert_grey(level: u8) -> u16 {
+        crate::convert::rgb565(level, level, level)
+    }
+}
@@ -0,0 +1,273 @@
+//! Client for the Logo Transfer GATT Service (see `logo::transfer`). Uploads the pixels of a boot logo to a slot
+//! through a `Transport`, resuming from the offset reported by PineTime when a chunk is lost or rejected.
+//! `SimTransport` runs the PineTime side of the protocol in memory, for testing the client without Bluetooth LE.
+use logo::{
+    crc32::crc32,
+    emulator::{ FlashEmulator, FLASH_SIZE },
+    layout,
+    loader::Loader,
+    slots::{ LOGO_AREA_OFFSET, LOGO_AREA_SIZE },
+    transfer::{ Command, Receiver, State, Status, DATA_HEADER_SIZE, MAX_DATA_SIZE },
+};
+
+/// Number of times to resume after a failed chunk without making progress
+const MAX_RETRIES: u32 = 5;
+
+/// Connection to the Logo Transfer GATT Service
+pub trait Transport {
+    /// Write the command to the Control characteristic
+    fn write_control(&mut self, data: &[u8]) -> Result<(), String>;
+    /// Write the chunk to the Data characteristic
+    fn write_data(&mut self, data: &[u8]) -> Result<(), String>;
+    /// Read the Status characteristic
+    fn read_status(&mut self) -> Result<Vec<u8>, String>;
+}
+
+/// Upload the big-endian RGB565 pixels to the slot in chunks of `chunk_size` bytes (including the offset), then
+/// commit with the CRC32 of the pixels. Activate the slot if `activate` is true. `progress` is called with the
+/// number of bytes acknowledged by PineTime.
+pub fn upload<T: Transport>(transport: &mut T, pixels: &[u8], slot: u8, activate: bool, chunk_size: usize,
+    mut progress: impl FnMut(u32)) -> Result<(), String> {
+    if chunk_size <= DATA_HEADER_SIZE || chunk_size > MAX_DATA_SIZE {
+        return Err(format!("chunk size must be {} to {}", DATA_HEADER_SIZE + 1, MAX_DATA_SIZE));
+    }
+    send_command(transport, Command::Start { slot, len: pixels.len() as u32 }) ? ;
+    expect_state(&read_status(transport) ?, State::Receiving) ? ;
+
+    //  Send the chunks in order. If a chunk is lost or rejected, resume from the offset that PineTime has received.
+    let sector = layout::SECTOR_SIZE as usize;
+    let mut offset   = 0;
+    let mut received = 0;
+    let mut retries  = 0;
+    while offset < pixels.len() {
+        let end = std::cmp::min(offset + chunk_size - DATA_HEADER_SIZE, pixels.len());
+        let mut chunk = Vec::with_capacity(chunk_size);
+        chunk.extend_from_slice(&(offset as u32).to_le_bytes());
+        chunk.extend_from_slice(&pixels[offset..end]);
+        let failed = transport.write_data(&chunk).is_err();
+        let crossed = offset / sector != end / sector;
+        offset = end;
+
+        //  Chunks may be written without response, so check the status after every sector and at the end
+        if !(failed || crossed || offset == pixels.len()) { continue; }
+        let status = read_status(transport) ? ;
+        expect_state(&status, State::Receiving) ? ;
+        if status.received > received {
+            received = status.received;
+            retries = 0;
+            progress(received);
+        }
+        if received as usize != offset {
+            retries += 1;
+            if retries > MAX_RETRIES { return Err(format!("upload stalled at offset {}", received)); }
+            offset = received as usize;
+        }
+    }
+    send_command(transport, Command::Commit { crc: crc32(pixels), activate }) ? ;
+    expect_state(&read_status(transport) ?, State::Committed)
+}
+
+/// Write the command to the Control characteristic
+fn send_command<T: Transport>(transport: &mut T, command: Command) -> Result<(), String> {
+    let (bytes, len) = command.to_bytes();
+    transport.write_control(&bytes[..len])
+        .map_err(|err| format!("{:?} failed: {}", command, err))
+}
+
+/// Read and parse the Status characteristic
+fn read_status<T: Transport>(transport: &mut T) -> Result<Status, String> {
+    let data = transport.read_status() ? ;
+    Status::parse(&data)
+        .map_err(|err| format!("bad status: {:?}", err))
+}
+
+/// Fail if the transfer is not in the expected state
+fn expect_state(status: &Status, state: State) -> Result<(), String> {
+    if status.state == state { return Ok(()); }
+    match status.error {
+        Some(err) => Err(format!("transfer failed: {:?}", err)),
+        None      => Err(format!("transfer is {:?}, expected {:?}", status.state, state)),
+    }
+}
+
+/// Simulated PineTime that receives the transfer with the Boot Logo Loader into emulated External SPI Flash
+pub struct SimTransport {
+    /// PineTime side of the protocol
+    receiver: Receiver,
+    /// Boot Logo Loader with emulated External SPI Flash
+    loader: Loader<FlashEmulator>,
+    /// Silently drop every `drop_every`-th chunk, like a lost Write Without Response
+    drop_every: Option<usize>,
+    /// Number of chunks written since the last dropped chunk
+    chunks: usize,
+    /// Number of chunks dropped
+    pub dropped: usize,
+}
+
+impl SimTransport {
+    /// Create a simulated PineTime with erased flash. If `drop_every` is set, every n-th chunk is lost.
+    pub fn new(drop_every: Option<usize>) -> SimTransport {
+        SimTransport {
+            receiver:   Receiver::new(),
+            loader:     Loader::new(FlashEmulator::new(FLASH_SIZE)),
+            drop_every,
+            chunks:     0,
+            dropped:    0,
+        }
+    }
+
+    /// Return the image of the logo area after the transfer, to be flashed at `LOGO_AREA_OFFSET`
+    pub fn logo_area(&self) -> &[u8] {
+        let start = LOGO_AREA_OFFSET as usize;
+        &self.loader.flash().data()[start..start + LOGO_AREA_SIZE as usize]
+    }
+}
+
+impl Transport for SimTransport {
+    fn write_control(&mut self, data: &[u8]) -> Result<(), String> {
+        self.receiver.control(&mut self.loader, data)
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<(), String> {
+        self.chunks += 1;
+        if Some(self.chunks) == self.drop_every {
+            self.chunks = 0;
+            self.dropped += 1;
+            return Ok(());
+        }
+        self.receiver.data(&mut self.loader, data)
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    fn read_status(&mut self) -> Result<Vec<u8>, String> {
+        Ok(self.receiver.status().to_bytes().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logo::{ layout::PIXELS_OFFSET, slots };
+
+    /// Size of the uploaded pixels: 240 x 240 x 2 bytes
+    const IMAGE_SIZE: usize = logo::WIDTH as usize * logo::HEIGHT as usize * logo::BYTES_PER_PIXEL;
+
+    /// Simulated PineTime whose connection drops for some chunks: the write fails and the chunk is not received
+    struct DroppingTransport {
+        /// PineTime side of the protocol, with emulated flash
+        sim:     SimTransport,
+        /// Number of each chunk that fails, counting from 1
+        fail:    Vec<usize>,
+        /// Offset of every chunk written, including those that failed
+        offsets: Vec<u32>,
+    }
+
+    impl Transport for DroppingTransport {
+        fn write_control(&mut self, data: &[u8]) -> Result<(), String> {
+            self.sim.write_control(data)
+        }
+
+        fn write_data(&mut self, data: &[u8]) -> Result<(), String> {
+            self.offsets.push(u32::from_le_bytes([data[0], data[1], data[2], data[3]]));
+            if self.fail.contains(&self.offsets.len()) { return Err("disconnected".to_string()); }
+            self.sim.write_data(data)
+        }
+
+        fn read_status(&mut self) -> Result<Vec<u8>, String> {
+            self.sim.read_status()
+        }
+    }
+
+    /// Return test pixels that differ in every sector
+    fn test_pixels() -> Vec<u8> {
+        (0..IMAGE_SIZE).map(|i| (i * 7 + i / 4096) as u8).collect()
+    }
+
+    /// Return the pixels written to the slot of the simulated PineTime
+    fn slot_pixels(sim: &SimTransport, slot: u8) -> &[u8] {
+        let offset = (slots::slot_offset(slot).unwrap() + PIXELS_OFFSET) as usize;
+        &sim.loader.flash().data()[offset..offset + IMAGE_SIZE]
+    }
+
+    #[test]
+    fn pixels_are_uploaded_in_chunks() {
+        let pixels = test_pixels();
+        for chunk_size in [DATA_HEADER_SIZE + 1, 100, MAX_DATA_SIZE].iter() {
+            let mut sim = SimTransport::new(None);
+            let mut acknowledged = Vec::new();
+            upload(&mut sim, &pixels, 1, false, *chunk_size, |received| acknowledged.push(received)).unwrap();
+            assert_eq!(slot_pixels(&sim, 1), &pixels[..], "chunks of {}", chunk_size);
+            assert!(sim.loader.is_valid(1).unwrap());
+
+            //  Status is read after every sector
+            let sectors = IMAGE_SIZE.div_ceil(layout::SECTOR_SIZE as usize);
+            assert_eq!(acknowledged.len(), sectors, "chunks of {}", chunk_size);
+            assert_eq!(acknowledged.last(), Some(&(IMAGE_SIZE as u32)));
+        }
+        assert!(upload(&mut SimTransport::new(None), &pixels, 1, false, DATA_HEADER_SIZE, |_| {}).is_err());
+        assert!(upload(&mut SimTransport::new(None), &pixels, 1, false, MAX_DATA_SIZE + 1, |_| {}).is_err());
+    }
+
+    #[test]
+    fn upload_resumes_after_dropped_connection() {
+        let pixels = test_pixels();
+        let chunk_size = 200;
+        let step = (chunk_size - DATA_HEADER_SIZE) as u32;
+        let mut transport = DroppingTransport { sim: SimTransport::new(None), fail: vec![5, 6, 300], offsets: vec![] };
+        upload(&mut transport, &pixels, 2, false, chunk_size, |_| {}).unwrap();
+        assert_eq!(slot_pixels(&transport.sim, 2), &pixels[..]);
+
+        //  Chunks 5 and 6 are resent from the offset reported by PineTime
+        assert_eq!(transport.offsets[3..8], [3 * step, 4 * step, 4 * step, 4 * step, 5 * step]);
+        assert_eq!(transport.offsets[299], transport.offsets[300]);
+        assert_eq!(transport.offsets.len(), IMAGE_SIZE.div_ceil(step as usize) + 3);
+
+        //  Silently lost chunks are detected by the status after each sector
+        let mut sim = SimTransport::new(Some(7));
+        upload(&mut sim, &pixels, 2, false, chunk_size, |_| {}).unwrap();
+        assert!(sim.dropped > 0);
+        assert_eq!(slot_pixels(&sim, 2), &pixels[..]);
+
+        //  Upload fails if the connection doesn't recover
+        let fail = (10..100).collect();
+        let mut transport = DroppingTransport { sim: SimTransport::new(None), fail, offsets: vec![] };
+        let err = upload(&mut transport, &pixels, 2, false, chunk_size, |_| {}).unwrap_err();
+        assert_eq!(err, format!("upload stalled at offset {}", 9 * step));
+        assert!(!transport.sim.loader.is_valid(2).unwrap());
+    }
+
+    #[test]
+    fn commit_activates_slot() {
+        let pixels = test_pixels();
+        let mut sim = SimTransport::new(None);
+        upload(&mut sim, &pixels, 3, false, MAX_DATA_SIZE, |_| {}).unwrap();
+        assert!(sim.loader.is_valid(3).unwrap());
+        assert_eq!(sim.loader.active_slot().unwrap(), 0);
+
+        upload(&mut sim, &pixels, 2, true, MAX_DATA_SIZE, |_| {}).unwrap();
+        assert_eq!(sim.loader.active_slot().unwrap(), 2);
+        assert_eq!(crc32(slot_pixels(&sim, 2)), crc32(&pixels));
+        assert_eq!(sim.logo_area().len(), LOGO_AREA_SIZE as usize);
+    }
+
+    #[test]
+    fn corrupted_chunk_fails_commit() {
+        //  Chunk corrupted in transit: the CRC32 doesn't match, and the slot stays invalid
+        struct CorruptingTransport(SimTransport);
+        impl Transport for CorruptingTransport {
+            fn write_control(&mut self, data: &[u8]) -> Result<(), String> { self.0.write_control(data) }
+            fn write_data(&mut self, data: &[u8]) -> Result<(), String> {
+                let mut data = data.to_vec();
+                if data[..DATA_HEADER_SIZE] == 1000u32.to_le_bytes() { data[DATA_HEADER_SIZE] ^= 0x01; }
+                self.0.write_data(&data)
+            }
+            fn read_status(&mut self) -> Result<Vec<u8>, String> { self.0.read_status() }
+        }
+        let mut transport = CorruptingTransport(SimTransport::new(None));
+        let err = upload(&mut transport, &test_pixels(), 1, true, 104, |_| {}).unwrap_err();
+        assert!(err.contains("BadChecksum"), "{}", err);
+        assert!(!transport.0.loader.is_valid(1).unwrap());
+        assert_eq!(transport.0.loader.active_slot().unwrap(), 0);
+    }
+}
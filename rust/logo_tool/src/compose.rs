@@ -0,0 +1,190 @@
+//! Stamp text and shapes onto a boot logo before it's encoded, e.g. "If found, call..." or the firmware version.
+//! The overlays are drawn with the same `embedded-graphics` primitives as the test screen on PineTime, into a
+//! `framebuffer::Framebuffer` with the RGB565 pixels of the logo. Overlays are drawn in order, so later overlays cover
+//! earlier ones.
+use embedded_graphics::{
+    prelude::*,
+    fonts,
+    pixelcolor::Rgb565,
+    primitives::{
+        Circle,
+        Rectangle,
+    },
+};
+use framebuffer::Framebuffer;
+use logo::{ HEIGHT, WIDTH };
+
+/// Font for text overlays, from `embedded_graphics::fonts`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextFont {
+    Font6x8,
+    Font6x12,
+    Font8x16,
+    Font12x16,
+}
+
+/// Shape of an overlay, in pixel coordinates of the logo
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    /// Text with its top left corner at (`x`, `y`). If `x` is `None`, the text is centred horizontally.
+    Text { x: Option<i32>, y: i32, text: String, font: TextFont },
+    /// Rectangle from the top left corner to the bottom right corner, inclusive
+    Rect { left: i32, top: i32, right: i32, bottom: i32 },
+    /// Circle with the centre and radius
+    Circle { x: i32, y: i32, radius: u32 },
+}
+
+/// Overlay to be stamped onto the logo
+#[derive(Clone, Debug, PartialEq)]
+pub struct Overlay {
+    /// Text or shape
+    pub shape:  Shape,
+    /// Colour of the text or outline, `None` for transparent
+    pub stroke: Option<Rgb565>,
+    /// Colour of the text background or the inside of the shape, `None` for transparent
+    pub fill:   Option<Rgb565>,
+}
+
+/// Overlays given on the command line, and the style for the next overlay
+#[derive(Debug)]
+pub struct Overlays {
+    /// Overlays in the order they are drawn
+    pub overlays: Vec<Overlay>,
+    /// Font for the next text
+    pub font:     TextFont,
+    /// Colour for the next text or outline
+    pub color:    Option<Rgb565>,
+    /// Background for the next text or fill for the next shape
+    pub fill:     Option<Rgb565>,
+}
+
+impl TextFont {
+    /// Parse the font given on the command line: `6x8`, `6x12`, `8x16` or `12x16`
+    pub fn parse(name: &str) -> Option<TextFont> {
+        match name {
+            "6x8"   => Some(TextFont::Font6x8),
+            "6x12"  => Some(TextFont::Font6x12),
+            "8x16"  => Some(TextFont::Font8x16),
+            "12x16" => Some(TextFont::Font12x16),
+            _ => None,
+        }
+    }
+
+    /// Return the width of each character in pixels
+    pub fn char_width(self) -> u32 {
+        match self {
+            TextFont::Font6x8 | TextFont::Font6x12 => 6,
+            TextFont::Font8x16  => 8,
+            TextFont::Font12x16 => 12,
+        }
+    }
+}
+
+impl Default for Overlays {
+    /// No overlays. Text is white in `Font12x16` over a transparent background.
+    fn default() -> Overlays {
+        Overlays {
+            overlays: Vec::new(),
+            font:     TextFont::Font12x16,
+            color:    Some(Rgb565(0xffff)),
+            fill:     None,
+        }
+    }
+}
+
+impl Overlays {
+    /// Add the text or shape with the current colours
+    pub fn add(&mut self, shape: Shape) {
+        self.overlays.push(Overlay { shape, stroke: self.color, fill: self.fill });
+    }
+
+    /// Draw the overlays onto the 240x240 RGB565 pixels of the logo
+    pub fn draw(&self, pixels: &mut [u16]) {
+        assert_eq!(pixels.len(), WIDTH as usize * HEIGHT as usize, "bad size");
+        let mut framebuffer = Box::new(Framebuffer::from_pixels(pixels));
+        for overlay in &self.overlays {
+            overlay.draw(&mut framebuffer);
+        }
+        pixels.copy_from_slice(framebuffer.pixels());
+    }
+}
+
+impl Overlay {
+    /// Draw the overlay with the `embedded-graphics` primitives
+    fn draw(&self, framebuffer: &mut Framebuffer) {
+        let (stroke, fill) = (self.stroke, self.fill);
+        match &self.shape {
+            Shape::Text { x, y, text, font } => {
+                //  Centre the text if no position given
+                let width = (text.len() as u32 * font.char_width()) as i32;
+                let position = Coord::new(x.unwrap_or((WIDTH as i32 - width) / 2), *y);
+                match font {
+                    TextFont::Font6x8 => framebuffer.draw(
+                        fonts::Font6x8::<Rgb565>::render_str(text)
+                            .stroke(stroke).fill(fill).translate(position)),
+                    TextFont::Font6x12 => framebuffer.draw(
+                        fonts::Font6x12::<Rgb565>::render_str(text)
+                            .stroke(stroke).fill(fill).translate(position)),
+                    TextFont::Font8x16 => framebuffer.draw(
+                        fonts::Font8x16::<Rgb565>::render_str(text)
+                            .stroke(stroke).fill(fill).translate(position)),
+                    TextFont::Font12x16 => framebuffer.draw(
+                        fonts::Font12x16::<Rgb565>::render_str(text)
+                            .stroke(stroke).fill(fill).translate(position)),
+                }
+            }
+            Shape::Rect { left, top, right, bottom } => framebuffer.draw(
+                Rectangle::<Rgb565>::new(Coord::new(*left, *top), Coord::new(*right, *bottom))
+                    .stroke(stroke).fill(fill)),
+            Shape::Circle { x, y, radius } => framebuffer.draw(
+                Circle::<Rgb565>::new(Coord::new(*x, *y), *radius)
+                    .stroke(stroke).fill(fill)),
+        }
+    }
+}
+
+/// Parse the colour given on the command line: `RRGGBB` or `#RRGGBB` in hex, or `none` for transparent
+pub fn parse_color(value: &str) -> Option<Option<Rgb565>> {
+    if value == "none" { return Some(None); }
+    let hex = value.trim_start_matches('#');
+    if hex.len() != 6 { return None; }
+    let rgb = u32::from_str_radix(hex, 16).ok() ? ;
+    Some(Some(Rgb565::from(((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))))
+}
+
+/// Parse the text given on the command line: `<x>,<y>,<text>`, to be drawn in the font. `x` may be `center`.
+/// The text may contain commas but only printable ASCII characters, which are the characters in the fonts.
+pub fn parse_text(value: &str, font: TextFont) -> Option<Shape> {
+    let mut parts = value.splitn(3, ',');
+    let x = match parts.next() ?.trim() {
+        "center" => None,
+        x => Some(x.parse().ok() ?),
+    };
+    let y = parts.next() ?.trim().parse().ok() ? ;
+    let text = parts.next() ? ;
+    if !text.chars().all(|c| (' '..='~').contains(&c)) { return None; }
+    Some(Shape::Text { x, y, text: text.to_string(), font })
+}
+
+/// Parse the rectangle given on the command line: `<left>,<top>,<right>,<bottom>`
+pub fn parse_rect(value: &str) -> Option<Shape> {
+    match parse_numbers(value) ?.as_slice() {
+        [left, top, right, bottom] => Some(Shape::Rect { left: *left, top: *top, right: *right, bottom: *bottom }),
+        _ => None,
+    }
+}
+
+/// Parse the circle given on the command line: `<x>,<y>,<radius>`
+pub fn parse_circle(value: &str) -> Option<Shape> {
+    match parse_numbers(value) ?.as_slice() {
+        [x, y, radius] if *radius >= 0 => Some(Shape::Circle { x: *x, y: *y, radius: *radius as u32 }),
+        _ => None,
+    }
+}
+
+/// Parse the comma-separated numbers
+fn parse_numbers(value: &str) -> Option<Vec<i32>> {
+    value.split(',')
+        .map(|number| number.trim().parse().ok())
+        .collect()
+}
@@ -0,0 +1,170 @@
+//! Convert a PNG boot logo to RGB565 pixels for the ST7789 display controller.
+//! The encoded logo is written as a C array (`.inc`) for `write_graphic.c`, or as a raw binary file (`.bin`).
+use logo::{ BYTES_PER_PIXEL, HEIGHT, WIDTH };
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+/// Size of the converted logo: 240 * 240 * 2 = 115,200 bytes
+pub const IMAGE_SIZE: usize = WIDTH as usize * HEIGHT as usize * BYTES_PER_PIXEL;
+
+/// Bytes per line in the `.inc` C array
+const INC_BYTES_PER_LINE: usize = 16;
+
+/// Decoded 240x240 PNG image with 8-bit RGB pixels, 3 bytes per pixel
+pub struct Image {
+    /// RGB pixels, row by row from the top left
+    pub pixels: Vec<u8>,
+}
+
+/// Errors while loading and converting the logo
+#[derive(Debug)]
+pub enum Error {
+    /// Unable to read or write a file
+    Io(io::Error),
+    /// PNG file is corrupted
+    Decode(png::DecodingError),
+    /// PNG is not 240x240
+    WrongSize { width: u32, height: u32 },
+    /// PNG is not 24-bit RGB
+    WrongFormat { color_type: png::ColorType, bit_depth: png::BitDepth },
+}
+
+/// Load a 240x240 24-bit RGB PNG file. Fail if the PNG has any other size or format.
+pub fn load_png(path: &Path) -> Result<Image, Error> {
+    let mut decoder = png::Decoder::new(File::open(path) ?);
+    //  Keep the pixels as stored in the file, so that we can check the format
+    decoder.set_transformations(png::Transformations::IDENTITY);
+    let (info, mut reader) = decoder.read_info() ?;
+
+    //  Logo must fill the entire display
+    if info.width != WIDTH as u32 || info.height != HEIGHT as u32 {
+        return Err(Error::WrongSize { width: info.width, height: info.height });
+    }
+    //  Logo must be 24-bit RGB, without alpha channel
+    if info.color_type != png::ColorType::RGB || info.bit_depth != png::BitDepth::Eight {
+        return Err(Error::WrongFormat { color_type: info.color_type, bit_depth: info.bit_depth });
+    }
+    let mut pixels = vec![0; info.buffer_size()];
+    reader.next_frame(&mut pixels) ? ;
+    Ok(Image { pixels })
+}
+
+/// Convert the 8-bit red, green and blue values to RGB565 by truncating the lower bits
+pub fn rgb565(r: u8, g: u8, b: u8) -> u16 {
+    ((r as u16 & 0xf8) << 8)
+        | ((g as u16 & 0xfc) << 3)
+        | (b as u16 >> 3)
+}
+
+/// Convert the RGB565 pixels to big-endian bytes, as stored in External SPI Flash and sent to the ST7789
+pub fn to_rgb565(pixels: &[u16]) -> Vec<u8> {
+    pixels.iter()
+        .flat_map(|pixel| pixel.to_be_bytes().to_vec())
+        .collect()
+}
+
+/// Write the converted pixels (or the compressed logo) as a C array that will be included by `write_graphic.c`
+pub fn write_inc<W: Write>(data: &[u8], source: &str, out: &mut W) -> io::Result<()> {
+    writeln!(out, "//  Converted from PNG file {} by rust/logo_tool", source) ? ;
+    for line in data.chunks(INC_BYTES_PER_LINE) {
+        let bytes: Vec<String> = line.iter()
+            .map(|byte| format!("0x{:02x}", byte))
+            .collect();
+        writeln!(out, "{},", bytes.join(", ")) ? ;
+    }
+    Ok(())
+}
+
+/// Write the pixels or the External SPI Flash image as a raw binary file, to be flashed to External SPI Flash
+pub fn write_bin<W: Write>(data: &[u8], out: &mut W) -> io::Result<()> {
+    out.write_all(data)
+}
+
+/// Display the error for the command line
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err)     => write!(f, "{}", err),
+            Error::Decode(err) => write!(f, "invalid PNG: {}", err),
+            Error::WrongSize { width, height } =>
+                write!(f, "PNG is {}x{}, must be {}x{}", width, height, WIDTH, HEIGHT),
+            Error::WrongFormat { color_type, bit_depth } =>
+                write!(f, "PNG is {:?} with {:?} bit depth, must be 24-bit RGB", color_type, bit_depth),
+        }
+    }
+}
+
+/// Cast `io::Error` to `Error`
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// Cast `png::DecodingError` to `Error`
+impl From<png::DecodingError> for Error {
+    fn from(err: png::DecodingError) -> Self {
+        Error::Decode(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantise::{ self, Options };
+    use std::{ env, io::BufWriter, path::PathBuf };
+
+    /// RGB565 pixel at `(x, y)`
+    type KnownPixel = ((usize, usize), u16);
+
+    /// Boot logos in `logos`, with the number of non-black pixels and some known pixels
+    const LOGOS: [(&str, usize, [KnownPixel; 3]); 6] = [
+        ("its-booting.png",           2171, [((60, 120), 0xf467), ((120, 120), 0x0000), ((120, 180), 0x0000)]),
+        ("its-booting-pinecone.png", 23122, [((120, 60), 0x2bf8), ((60, 120), 0xf467), ((120, 180), 0x3af1)]),
+        ("pallette-fun.png",         24935, [((120, 60), 0xf467), ((60, 120), 0xcaa7), ((180, 120), 0x7e7a)]),
+        ("pine64-rainbow.png",       19086, [((120, 60), 0xfb40), ((120, 120), 0x03e1), ((120, 180), 0x500f)]),
+        ("pinecone.png",             24935, [((120, 60), 0x2bf8), ((60, 120), 0x2b56), ((120, 180), 0x3af1)]),
+        ("pinetime-green.png",       11242, [((60, 120), 0x07e5), ((120, 180), 0x02e1), ((120, 120), 0x0000)]),
+    ];
+
+    /// Return the path of the boot logo in `logos`
+    fn logo_path(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../logos").join(name)
+    }
+
+    #[test]
+    fn logos_are_converted() {
+        for (name, non_black, known) in LOGOS.iter() {
+            let image = load_png(&logo_path(name)).unwrap();
+            let pixels = quantise::quantise(&image.pixels, WIDTH as usize, &Options::default());
+            assert_eq!(pixels.len() * BYTES_PER_PIXEL, IMAGE_SIZE, "{}", name);
+            assert_eq!(pixels.iter().filter(|pixel| **pixel != 0).count(), *non_black, "{}", name);
+            for &((x, y), pixel) in known.iter() {
+                assert_eq!(pixels[y * WIDTH as usize + x], pixel, "{} at ({}, {})", name, x, y);
+            }
+        }
+        assert_eq!(rgb565(0xff, 0xff, 0xff), 0xffff);
+        assert_eq!(rgb565(0x12, 0x34, 0x56), 0x11aa);
+        assert_eq!(to_rgb565(&[0x11aa, 0xf467]), [0x11, 0xaa, 0xf4, 0x67]);
+    }
+
+    #[test]
+    fn wrong_size_is_rejected() {
+        let path = env::temp_dir().join(format!("logo_tool_wrong_size_{}.png", std::process::id()));
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(&path).unwrap()), 10, 12);
+        encoder.set_color(png::ColorType::RGB);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(&[0; 10 * 12 * 3]).unwrap();
+        let result = load_png(&path);
+        std::fs::remove_file(&path).unwrap();
+        match result {
+            Err(Error::WrongSize { width: 10, height: 12 }) => {}
+            Err(err) => panic!("wrong error: {}", err),
+            Ok(_) => panic!("10x12 PNG not rejected"),
+        }
+    }
+}
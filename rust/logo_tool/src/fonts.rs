@@ -0,0 +1,53 @@
+//! Convert a BDF font to the proportional bitmap font format of the `font` library, for `include_bytes!` in the Rust
+//! Application. Large BDF fonts may be downsampled into anti-aliased glyphs. The converted font is loaded back and
+//! rendered into a `framebuffer::Framebuffer` for the preview, the same way as on PineTime.
+use font::{
+    bdf::BdfFont,
+    format::Font,
+    layout::Align,
+    render,
+};
+use framebuffer::{
+    dirty::Rect,
+    golden, Framebuffer, HEIGHT, WIDTH,
+};
+use std::{
+    fs,
+    path::Path,
+};
+
+/// Text shown in the preview if no text is given
+pub const PREVIEW_TEXT: &str = "The quick brown fox jumps over the lazy dog. 0123456789 °C café";
+
+/// Background of the preview: black
+const BACKGROUND: u16 = 0x0000;
+
+/// Colour of the text in the preview: white
+const FOREGROUND: u16 = 0xffff;
+
+/// Load the BDF file and convert it with the bits per pixel. If `factor` is more than 1, the glyphs are scaled down
+/// by the factor with anti-aliasing. Missing characters are shown as `replacement`, if the font has it.
+pub fn convert(path: &Path, factor: u32, bits_per_pixel: u8, replacement: char) -> Result<Vec<u8>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("{}: {}", path.display(), err)) ? ;
+    let mut bdf = BdfFont::parse(&text)
+        .map_err(|err| format!("{}: {:?}", path.display(), err)) ? ;
+    if factor > 1 { bdf = bdf.downsample(factor); }
+    let replacement = Some(replacement)
+        .filter(|c| bdf.glyphs.iter().any(|glyph| glyph.codepoint == *c));
+    bdf.encode(bits_per_pixel, replacement)
+        .map_err(|err| format!("{}: unable to encode font: {:?}", path.display(), err))
+}
+
+/// Render the text with the converted font into a 240x240 PNG file, white on black. Returns the number of lines.
+pub fn write_preview(font: &[u8], text: &str, path: &Path) -> Result<u16, String> {
+    let font = Font::parse(font)
+        .map_err(|err| format!("unable to load font: {:?}", err)) ? ;
+    let mut framebuffer = Box::new(Framebuffer::new());
+    framebuffer.fill(BACKGROUND);
+    let area = Rect::new(4, 4, WIDTH - 5, HEIGHT - 5);
+    let layout = render::draw_text(&mut *framebuffer, &font, text, area, Align::Left, FOREGROUND, Some(BACKGROUND));
+    golden::write_png(&framebuffer, path)
+        .map_err(|err| format!("{}: {}", path.display(), err)) ? ;
+    Ok(layout.lines)
+}
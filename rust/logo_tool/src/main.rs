@@ -0,0 +1,518 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *  http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+//!  Boot Logo Tool for PineTime. Runs on the host computer, not on PineTime.
+//!  Converts a 240x240 PNG file to RGB565 for writing to External SPI Flash:
+//!  ```bash
+//!  logo_tool convert pinetime-graphic.png --inc apps/my_sensor_app/src/write_graphic.inc --bin boot-graphic.bin
+//!  ```
+//!  If no output file is given, the C array is written to the console. `--bin` writes the raw RGB565 pixels, like
+//!  `scripts/write-graphic`. `--logo` writes the bytes of the C array (header and compressed pixels) as a binary file.
+//!  `--slot-bin` writes the image of a slot in External SPI Flash: header sector followed by the uncompressed pixels,
+//!  see `logo::layout`.
+//!
+//!  Gradients may show bands after the lower bits are truncated to RGB565. The quantise options `--dither ordered`
+//!  or `--dither floyd` map each pixel to the nearest RGB565 colour with dithering, `--gamma` compares colours in linear
+//!  light, and `--colors <n>` reduces the logo to a palette of n colours. They apply to `convert`, `image`, `upload`,
+//!  `check` and `animate`:
+//!  ```bash
+//!  logo_tool convert logos/pine64-rainbow.png --inc apps/my_sensor_app/src/write_graphic.inc --dither floyd --gamma
+//!  ```
+//!
+//!  To stamp text and shapes onto the logo before it's encoded, e.g. an owner's phone number, add the overlay options.
+//!  They are drawn in order with the `embedded-graphics` fonts and primitives, after quantising:
+//!  ```bash
+//!  logo_tool convert pinetime-graphic.png --logo boot-logo.bin --fill 000000 --rect 0,196,239,239 \
+//!      --font 8x16 --color ffff00 --text center,204,"If found, call 555-0100"
+//!  ```
+//!
+//!  `--format` selects how the C array is stored: a compressed boot logo with header (`rle`, `palette` or `auto`
+//!  for the smaller of the two, the default) that is expanded by `logo::decoder` on PineTime, or `raw` for uncompressed
+//!  RGB565 pixels with header.
+//!
+//!  To store several boot logos in External SPI Flash, build an image of the logo area (slot table and slots)
+//!  for flashing over SWD at `logo::slots::LOGO_AREA_OFFSET`:
+//!  ```bash
+//!  logo_tool image --slot 0 day.png --slot 1 night.png --active 0 --out logos.bin
+//!  ```
+//!
+//!  To test uploading a boot logo over Bluetooth LE (see `logo::transfer`), run the client against a simulated
+//!  PineTime. `--drop` loses every n-th chunk to exercise resuming, `--out` saves the resulting logo area image:
+//!  ```bash
+//!  logo_tool upload pinetime-graphic.png --slot 1 --activate --drop 50 --out logos.bin
+//!  ```
+//!
+//!  To check a PNG file before converting it, and preview how PineTime will show it in RGB565. Every problem is
+//!  reported, including banding in gradients. The preview is quantised with the quantise options:
+//!  ```bash
+//!  logo_tool check pinetime-graphic.png --preview preview.png --dither floyd
+//!  ```
+//!
+//!  To build an animated boot logo (see `logo::animation`) from a directory of PNG files, one frame per file in order
+//!  of file name, for flashing over SWD at the start of the slot. The animation is played back and checked before writing:
+//!  ```bash
+//!  logo_tool animate logos/spinner --delay 100 --loops 3 --slot 3 --out animation.bin
+//!  ```
+//!
+//!  To convert a BDF font to the proportional bitmap font format of the `font` library (see `font::format`),
+//!  anti-aliased by downsampling a font drawn 2 times larger, and preview some text rendered with it:
+//!  ```bash
+//!  logo_tool font unifont-32.bdf --aa 2 --bpp 4 --out unifont-16.font --preview font.png --text "Hello, PineTime"
+//!  ```
+
+mod animate;  //  Declare `animate.rs` as Rust module `animate` for building animated boot logos
+mod check;    //  Declare `check.rs` as Rust module `check` for validating PNG files
+mod client;   //  Declare `client.rs` as Rust module `client` for the Logo Transfer client
+mod compose;  //  Declare `compose.rs` as Rust module `compose` for text and shape overlays
+mod convert;  //  Declare `convert.rs` as Rust module `convert` for PNG conversion
+mod fonts;    //  Declare `fonts.rs` as Rust module `fonts` for converting BDF fonts
+mod quantise; //  Declare `quantise.rs` as Rust module `quantise` for RGB565 quantisation and dithering
+
+use logo::{
+    encoder,
+    header::PixelFormat,
+    slots,
+    transfer::MAX_DATA_SIZE,
+};
+use std::{
+    env,
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+    process,
+};
+use compose::{ Overlays, TextFont };
+use quantise::{ Dither, Options };
+
+/// Usage for the command line
+const USAGE: &str = "\
+Usage:
+    logo_tool convert <input.png> [--inc <output.inc>] [--bin <output.bin>] [--slot-bin <output.bin>] [--logo <output.bin>] [--format <raw|rle|palette|auto>] [<quantise>] [<overlay>]...
+    logo_tool image --slot <n> <input.png> [--slot <n> <input.png>]... [--active <n>] --out <output.bin> [<quantise>]
+    logo_tool upload <input.png> --slot <n> [--activate] [--chunk <bytes>] [--drop <n>] [--out <output.bin>] [<quantise>]
+    logo_tool check <input.png> [--preview <output.png>] [<quantise>] [<overlay>]...
+    logo_tool animate <input_dir> [--delay <ms>] [--loops <n>] [--slot <n>] --out <output.bin> [<quantise>]
+    logo_tool font <input.bdf> --out <output.font> [--aa <factor>] [--bpp <1|2|4|8>] [--replacement <char>] [--preview <output.png>] [--text <text>]
+Quantise options:
+    --dither <none|ordered|floyd>  Dithering, default none
+    --gamma                        Compare colours and diffuse errors in linear light
+    --colors <2-256>               Reduce to a palette of at most this many colours
+Overlay options, drawn in order after quantising:
+    --text <x|center>,<y>,<text>   Draw the text with its top left corner at (x, y)
+    --rect <left>,<top>,<right>,<bottom>
+                                   Draw the rectangle
+    --circle <x>,<y>,<radius>      Draw the circle
+    --font <6x8|6x12|8x16|12x16>   Font for the following text, default 12x16
+    --color <RRGGBB|none>          Colour for the following text and outlines, default ffffff
+    --fill <RRGGBB|none>           Background for the following text and fill for shapes, default none";
+
+/// Run the command given on the command line
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("convert") => convert_command(&args[1..]),
+        Some("image")   => image_command(&args[1..]),
+        Some("upload")  => upload_command(&args[1..]),
+        Some("check")   => check_command(&args[1..]),
+        Some("animate") => animate_command(&args[1..]),
+        Some("font")    => font_command(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+    if let Err(msg) = result {
+        eprintln!("{}", msg);
+        process::exit(1);
+    }
+}
+
+/// Convert a PNG file to `.inc` and `.bin` files:
+/// `convert <input.png> [--inc <output.inc>] [--bin <output.bin>] [--slot-bin <output.bin>] [--logo <output.bin>] [--format <format>] [<quantise>] [<overlay>]...`
+fn convert_command(args: &[String]) -> Result<(), String> {
+    let mut input     = None;
+    let mut inc_path  = None;
+    let mut bin_path  = None;
+    let mut slot_path = None;
+    let mut logo_path = None;
+    let mut format    = "auto".to_string();
+    let mut options   = Options::default();
+    let mut overlays  = Overlays::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--inc" => inc_path = Some(next_value(&mut args, "--inc") ?),
+            "--bin" => bin_path = Some(next_value(&mut args, "--bin") ?),
+            "--slot-bin" => slot_path = Some(next_value(&mut args, "--slot-bin") ?),
+            "--logo" => logo_path = Some(next_value(&mut args, "--logo") ?),
+            "--format" => format = next_value(&mut args, "--format") ?,
+            _ if parse_quantise_option(arg, &mut args, &mut options) ? => {}
+            _ if parse_overlay_option(arg, &mut args, &mut overlays) ? => {}
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}\n{}", arg, USAGE)),
+        }
+    }
+    let input = input.ok_or_else(|| USAGE.to_string()) ? ;
+
+    //  Load the PNG, convert to RGB565 and stamp the overlays
+    let image = convert::load_png(Path::new(&input))
+        .map_err(|err| format!("{}: {}", input, err)) ? ;
+    let mut pixels = quantise::quantise(&image.pixels, logo::WIDTH as usize, &options);
+    overlays.draw(&mut pixels);
+    assert_eq!(pixels.len() * logo::BYTES_PER_PIXEL, convert::IMAGE_SIZE, "bad size");
+
+    //  Compress the C array if required
+    let inc_data = encode(&pixels, &format) ? ;
+    eprintln!("{}: encoded {} bytes to {} bytes", input, convert::IMAGE_SIZE, inc_data.len());
+
+    //  Write the C array, the raw pixels and the flash image
+    if let Some(path) = &inc_path {
+        let mut out = BufWriter::new(create_file(path) ? );
+        convert::write_inc(&inc_data, &input, &mut out)
+            .map_err(|err| format!("{}: {}", path, err)) ? ;
+    }
+    if let Some(path) = &bin_path {
+        let mut out = create_file(path) ? ;
+        convert::write_bin(&convert::to_rgb565(&pixels), &mut out)
+            .map_err(|err| format!("{}: {}", path, err)) ? ;
+    }
+    if let Some(path) = &slot_path {
+        let data = encoder::encode_flash_image(&pixels, logo::WIDTH, logo::HEIGHT)
+            .map_err(|err| format!("unable to create flash image: {:?}", err)) ? ;
+        let mut out = create_file(path) ? ;
+        convert::write_bin(&data, &mut out)
+            .map_err(|err| format!("{}: {}", path, err)) ? ;
+    }
+    if let Some(path) = &logo_path {
+        let mut out = create_file(path) ? ;
+        convert::write_bin(&inc_data, &mut out)
+            .map_err(|err| format!("{}: {}", path, err)) ? ;
+    }
+    //  If no output files, write the C array to the console like `pinetime-graphic`
+    if inc_path.is_none() && bin_path.is_none() && slot_path.is_none() && logo_path.is_none() {
+        let stdout = io::stdout();
+        convert::write_inc(&inc_data, &input, &mut stdout.lock())
+            .map_err(|err| err.to_string()) ? ;
+    }
+    Ok(())
+}
+
+/// Build the logo area image with the slot table and slots:
+/// `image --slot <n> <input.png> [--slot <n> <input.png>]... [--active <n>] --out <output.bin> [<quantise>]`
+fn image_command(args: &[String]) -> Result<(), String> {
+    let mut images: Vec<Option<convert::Image>> = (0..slots::MAX_SLOTS).map(|_| None).collect();
+    let mut active   = 0;
+    let mut out_path = None;
+    let mut options  = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--slot" => {
+                let slot = parse_slot(&next_value(&mut args, "--slot") ?) ? ;
+                let input = next_value(&mut args, "--slot") ? ;
+                let image = convert::load_png(Path::new(&input))
+                    .map_err(|err| format!("{}: {}", input, err)) ? ;
+                images[slot as usize] = Some(image);
+            }
+            "--active" => active = parse_slot(&next_value(&mut args, "--active") ?) ?,
+            "--out"    => out_path = Some(next_value(&mut args, "--out") ?),
+            _ if parse_quantise_option(arg, &mut args, &mut options) ? => {}
+            _ => return Err(format!("unexpected argument {}\n{}", arg, USAGE)),
+        }
+    }
+    let out_path = out_path.ok_or_else(|| USAGE.to_string()) ? ;
+    let slots: Vec<Option<Vec<u16>>> = images.iter()
+        .map(|image| image.as_ref().map(|image| quantise::quantise(&image.pixels, logo::WIDTH as usize, &options)))
+        .collect();
+    if slots[active as usize].is_none() {
+        return Err(format!("active slot {} has no logo", active));
+    }
+    //  Build the image and write it
+    let data = encoder::encode_logo_area(&slots, logo::WIDTH, logo::HEIGHT, active)
+        .map_err(|err| format!("unable to build image: {:?}", err)) ? ;
+    let mut out = create_file(&out_path) ? ;
+    convert::write_bin(&data, &mut out)
+        .map_err(|err| format!("{}: {}", out_path, err)) ? ;
+    eprintln!("{}: {} bytes, to be flashed at External SPI Flash offset 0x{:x}",
+        out_path, data.len(), slots::LOGO_AREA_OFFSET);
+    Ok(())
+}
+
+/// Upload a PNG file to a simulated PineTime over the Logo Transfer protocol:
+/// `upload <input.png> --slot <n> [--activate] [--chunk <bytes>] [--drop <n>] [--out <output.bin>] [<quantise>]`
+fn upload_command(args: &[String]) -> Result<(), String> {
+    let mut input    = None;
+    let mut slot     = None;
+    let mut activate = false;
+    let mut chunk    = MAX_DATA_SIZE;
+    let mut drop     = None;
+    let mut out_path = None;
+    let mut options  = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--slot"     => slot = Some(parse_slot(&next_value(&mut args, "--slot") ?) ?),
+            "--activate" => activate = true,
+            "--chunk"    => chunk = parse_number(&next_value(&mut args, "--chunk") ?, "--chunk") ?,
+            "--drop"     => drop = Some(parse_number(&next_value(&mut args, "--drop") ?, "--drop") ?),
+            "--out"      => out_path = Some(next_value(&mut args, "--out") ?),
+            _ if parse_quantise_option(arg, &mut args, &mut options) ? => {}
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}\n{}", arg, USAGE)),
+        }
+    }
+    let input = input.ok_or_else(|| USAGE.to_string()) ? ;
+    let slot  = slot.ok_or_else(|| USAGE.to_string()) ? ;
+    if drop == Some(0) { return Err("--drop must be at least 1".to_string()); }
+
+    //  Load the PNG, convert to RGB565 like `convert` and upload the big-endian pixels
+    let image = convert::load_png(Path::new(&input))
+        .map_err(|err| format!("{}: {}", input, err)) ? ;
+    let pixels = convert::to_rgb565(&quantise::quantise(&image.pixels, logo::WIDTH as usize, &options));
+    let mut transport = client::SimTransport::new(drop);
+    client::upload(&mut transport, &pixels, slot, activate, chunk, |received| {
+        eprint!("\r{}: {} of {} bytes", input, received, pixels.len());
+    }) ? ;
+    eprintln!("\n{}: uploaded to slot {}, {} chunks dropped", input, slot, transport.dropped);
+
+    //  Write the logo area of the simulated PineTime
+    if let Some(path) = &out_path {
+        let mut out = create_file(path) ? ;
+        convert::write_bin(transport.logo_area(), &mut out)
+            .map_err(|err| format!("{}: {}", path, err)) ? ;
+    }
+    Ok(())
+}
+
+/// Check a PNG file and write the RGB565 preview with the overlays:
+/// `check <input.png> [--preview <output.png>] [<quantise>] [<overlay>]...`
+fn check_command(args: &[String]) -> Result<(), String> {
+    let mut input        = None;
+    let mut preview_path = None;
+    let mut options      = Options::default();
+    let mut overlays     = Overlays::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--preview" => preview_path = Some(next_value(&mut args, "--preview") ?),
+            _ if parse_quantise_option(arg, &mut args, &mut options) ? => {}
+            _ if parse_overlay_option(arg, &mut args, &mut overlays) ? => {}
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}\n{}", arg, USAGE)),
+        }
+    }
+    let input = input.ok_or_else(|| USAGE.to_string()) ? ;
+
+    //  Report every problem, errors before warnings
+    let mut report = check::check_png(Path::new(&input), &options)
+        .map_err(|err| format!("{}: {}", input, err)) ? ;
+    for problem in &report.problems {
+        let severity = if problem.is_error() { "error" } else { "warning" };
+        eprintln!("{}: {}: {}", input, severity, problem);
+    }
+    if let Some(path) = &preview_path {
+        //  Overlays are only drawn on logos of the right size
+        if report.is_ok() { overlays.draw(&mut report.pixels); }
+        check::write_preview(&report, Path::new(path))
+            .map_err(|err| format!("{}: {}", path, err)) ? ;
+        eprintln!("{}: preview written to {}", input, path);
+    }
+    if !report.is_ok() { return Err(format!("{}: not a valid boot logo", input)); }
+    eprintln!("{}: OK", input);
+    Ok(())
+}
+
+/// Build an animated boot logo from a directory of PNG files:
+/// `animate <input_dir> [--delay <ms>] [--loops <n>] [--slot <n>] --out <output.bin> [<quantise>]`
+fn animate_command(args: &[String]) -> Result<(), String> {
+    let mut input    = None;
+    let mut delay    = 100;
+    let mut loops    = 1;
+    let mut slot     = slots::MAX_SLOTS - 1;
+    let mut out_path = None;
+    let mut options  = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--delay" => delay = parse_number(&next_value(&mut args, "--delay") ?, "--delay") ?,
+            "--loops" => loops = parse_number(&next_value(&mut args, "--loops") ?, "--loops") ?,
+            "--slot"  => slot = parse_slot(&next_value(&mut args, "--slot") ?) ?,
+            "--out"   => out_path = Some(next_value(&mut args, "--out") ?),
+            _ if parse_quantise_option(arg, &mut args, &mut options) ? => {}
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}\n{}", arg, USAGE)),
+        }
+    }
+    let input    = input.ok_or_else(|| USAGE.to_string()) ? ;
+    let out_path = out_path.ok_or_else(|| USAGE.to_string()) ? ;
+    if delay > u16::MAX as usize { return Err(format!("--delay must be at most {}", u16::MAX)); }
+    if loops == 0 || loops > u16::MAX as usize { return Err(format!("--loops must be 1 to {}", u16::MAX)); }
+
+    //  Encode the frames and check that they play back correctly
+    let frames = animate::load_frames(Path::new(&input), delay as u16, &options) ? ;
+    let image = encoder::encode_animation(&frames, logo::WIDTH, logo::HEIGHT, loops as u16)
+        .map_err(|err| format!("unable to encode animation: {:?}", err)) ? ;
+    let bands = animate::verify(&image, &frames, slot) ? ;
+    eprintln!("{}: {} frames, {} bands, {} bytes", input, frames.len(), bands, image.len());
+
+    //  Write the slot image
+    let mut out = create_file(&out_path) ? ;
+    convert::write_bin(&image, &mut out)
+        .map_err(|err| format!("{}: {}", out_path, err)) ? ;
+    let offset = slots::slot_offset(slot)
+        .map_err(|err| format!("bad slot: {:?}", err)) ? ;
+    eprintln!("{}: to be flashed at External SPI Flash offset 0x{:x} (slot {})", out_path, offset, slot);
+    Ok(())
+}
+
+/// Convert a BDF font to the font format of the `font` library, and preview the text rendered with it:
+/// `font <input.bdf> --out <output.font> [--aa <factor>] [--bpp <n>] [--replacement <char>] [--preview <output.png>] [--text <text>]`
+fn font_command(args: &[String]) -> Result<(), String> {
+    let mut input        = None;
+    let mut out_path     = None;
+    let mut factor       = 1;
+    let mut bpp          = None;
+    let mut replacement  = '?';
+    let mut preview_path = None;
+    let mut text         = fonts::PREVIEW_TEXT.to_string();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out"     => out_path = Some(next_value(&mut args, "--out") ?),
+            "--aa"      => factor = parse_number(&next_value(&mut args, "--aa") ?, "--aa") ?,
+            "--bpp"     => bpp = Some(parse_number(&next_value(&mut args, "--bpp") ?, "--bpp") ?),
+            "--preview" => preview_path = Some(next_value(&mut args, "--preview") ?),
+            "--text"    => text = next_value(&mut args, "--text") ?,
+            "--replacement" => {
+                let value = next_value(&mut args, "--replacement") ? ;
+                let mut chars = value.chars();
+                replacement = match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => return Err(format!("--replacement must be 1 character: {}", value)),
+                };
+            }
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}\n{}", arg, USAGE)),
+        }
+    }
+    let input    = input.ok_or_else(|| USAGE.to_string()) ? ;
+    let out_path = out_path.ok_or_else(|| USAGE.to_string()) ? ;
+    if factor == 0 || factor > 8 { return Err("--aa must be 1 to 8".to_string()); }
+
+    //  Downsampled glyphs are anti-aliased with 4 bits per pixel by default
+    let bpp = bpp.unwrap_or(if factor > 1 { 4 } else { 1 });
+    if ![1, 2, 4, 8].contains(&bpp) { return Err("--bpp must be 1, 2, 4 or 8".to_string()); }
+    let data = fonts::convert(Path::new(&input), factor as u32, bpp as u8, replacement) ? ;
+    let mut out = create_file(&out_path) ? ;
+    convert::write_bin(&data, &mut out)
+        .map_err(|err| format!("{}: {}", out_path, err)) ? ;
+    eprintln!("{}: {} bytes written to {}", input, data.len(), out_path);
+
+    //  Render the text with the converted font
+    if let Some(path) = &preview_path {
+        let lines = fonts::write_preview(&data, &text, Path::new(path)) ? ;
+        eprintln!("{}: preview of {} lines written to {}", input, lines, path);
+    }
+    Ok(())
+}
+
+/// Parse the quantise option into the options: `--dither <none|ordered|floyd>`, `--gamma` or `--colors <n>`.
+/// Returns false if the argument is not a quantise option.
+fn parse_quantise_option<'a>(arg: &str, args: &mut impl Iterator<Item = &'a String>, options: &mut Options) -> Result<bool, String> {
+    match arg {
+        "--dither" => {
+            let name = next_value(args, "--dither") ? ;
+            options.dither = Dither::parse(&name)
+                .ok_or_else(|| format!("unknown dither {}\n{}", name, USAGE)) ? ;
+        }
+        "--gamma"  => options.gamma = true,
+        "--colors" => {
+            let colors = parse_number(&next_value(args, "--colors") ?, "--colors") ? ;
+            if !(2..=quantise::MAX_COLORS).contains(&colors) {
+                return Err(format!("--colors must be 2 to {}", quantise::MAX_COLORS));
+            }
+            options.colors = Some(colors);
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Parse the overlay option into the overlays: `--text`, `--rect` or `--circle` adds an overlay, `--font`, `--color`
+/// and `--fill` set the style of the overlays that follow. Returns false if the argument is not an overlay option.
+fn parse_overlay_option<'a>(arg: &str, args: &mut impl Iterator<Item = &'a String>, overlays: &mut Overlays) -> Result<bool, String> {
+    let shape = match arg {
+        "--text"   => compose::parse_text(&next_value(args, arg) ?, overlays.font),
+        "--rect"   => compose::parse_rect(&next_value(args, arg) ?),
+        "--circle" => compose::parse_circle(&next_value(args, arg) ?),
+        "--font" => {
+            let name = next_value(args, arg) ? ;
+            overlays.font = TextFont::parse(&name)
+                .ok_or_else(|| format!("unknown font {}\n{}", name, USAGE)) ? ;
+            return Ok(true);
+        }
+        "--color" | "--fill" => {
+            let value = next_value(args, arg) ? ;
+            let color = compose::parse_color(&value)
+                .ok_or_else(|| format!("bad colour for {}: {}", arg, value)) ? ;
+            if arg == "--color" { overlays.color = color; } else { overlays.fill = color; }
+            return Ok(true);
+        }
+        _ => return Ok(false),
+    };
+    let shape = shape.ok_or_else(|| format!("bad value for {}\n{}", arg, USAGE)) ? ;
+    overlays.add(shape);
+    Ok(true)
+}
+
+/// Parse the number given for the option
+fn parse_number(value: &str, option: &str) -> Result<usize, String> {
+    value.parse::<usize>()
+        .map_err(|_| format!("bad number for {}: {}", option, value))
+}
+
+/// Parse the slot number. Fail if the slot doesn't exist.
+fn parse_slot(value: &str) -> Result<u8, String> {
+    match value.parse::<u8>() {
+        Ok(slot) if slot < slots::MAX_SLOTS => Ok(slot),
+        _ => Err(format!("slot must be 0 to {}", slots::MAX_SLOTS - 1)),
+    }
+}
+
+/// Encode the RGB565 pixels in the format given by `--format`, with header
+fn encode(pixels: &[u16], format: &str) -> Result<Vec<u8>, String> {
+    let (width, height) = (logo::WIDTH, logo::HEIGHT);
+    let result = match format {
+        "raw"     => encoder::encode(pixels, width, height, PixelFormat::Rgb565),
+        "rle"     => encoder::encode(pixels, width, height, PixelFormat::Rgb565Rle),
+        "palette" => encoder::encode(pixels, width, height, PixelFormat::Indexed8Rle),
+        "auto"    => encoder::encode_smallest(pixels, width, height),
+        _ => return Err(format!("unknown format {}\n{}", format, USAGE)),
+    };
+    result.map_err(|err| format!("unable to encode as {}: {:?}", format, err))
+}
+
+/// Return the value that follows the option, e.g. the path after `--inc`
+fn next_value<'a>(args: &mut impl Iterator<Item = &'a String>, option: &str) -> Result<String, String> {
+    args.next()
+        .cloned()
+        .ok_or_else(|| format!("missing value for {}\n{}", option, USAGE))
+}
+
+/// Create the output file
+fn create_file(path: &str) -> Result<File, String> {
+    File::create(path)
+        .map_err(|err| format!("{}: {}", path, err))
+}
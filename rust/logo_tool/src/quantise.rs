@@ -0,0 +1,264 @@
+//! Quantise 8-bit RGB pixels to RGB565, optionally with dithering to hide the banding in gradients.
+//! With the default options, the lower bits are truncated like `convert::rgb565()`, which is what PineTime shows today.
+//! Otherwise each pixel is mapped to the nearest RGB565 colour, optionally:
+//! - in linear light (`gamma`), so that dithered areas keep the brightness of the original, and
+//! - from a palette of at most `colors` colours picked by median cut, so that the logo may be stored as `Indexed8Rle`.
+use crate::convert;
+
+/// 4x4 Bayer matrix for ordered dithering, thresholds 0 to 15
+const BAYER: [[u8; 4]; 4] = [
+    [  0,  8,  2, 10 ],
+    [ 12,  4, 14,  6 ],
+    [  3, 11,  1,  9 ],
+    [ 15,  7, 13,  5 ],
+];
+
+/// Number of bits kept for red, green and blue in RGB565
+const CHANNEL_BITS: [u32; 3] = [5, 6, 5];
+
+/// Max number of colours for palette reduction, the size of the `Indexed8Rle` palette
+pub const MAX_COLORS: usize = 256;
+
+/// How the lower bits of each channel are dropped
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dither {
+    /// Truncate the lower bits, or map to the nearest colour
+    None,
+    /// Add the 4x4 Bayer threshold before mapping to the nearest colour
+    Ordered,
+    /// Map to the nearest colour and diffuse the error to the neighbouring pixels (Floyd-Steinberg)
+    FloydSteinberg,
+}
+
+/// Options for quantising
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Options {
+    /// Dithering
+    pub dither: Dither,
+    /// True if colours are compared and errors diffused in linear light, false for sRGB values
+    pub gamma:  bool,
+    /// Max number of colours in the logo, or `None` for all RGB565 colours
+    pub colors: Option<usize>,
+}
+
+impl Dither {
+    /// Parse the dithering given on the command line: `none`, `ordered` or `floyd`
+    pub fn parse(name: &str) -> Option<Dither> {
+        match name {
+            "none"    => Some(Dither::None),
+            "ordered" => Some(Dither::Ordered),
+            "floyd"   => Some(Dither::FloydSteinberg),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Options {
+    /// Truncate like `convert::rgb565()`
+    fn default() -> Options {
+        Options { dither: Dither::None, gamma: false, colors: None }
+    }
+}
+
+/// Colour in the working space: sRGB values or linear light, scaled to 0 to 255
+type Color = [f32; 3];
+
+/// Colours that the pixels may be mapped to
+enum Target {
+    /// Every RGB565 colour. Contains the values of the levels of each channel in the working space.
+    Levels([Vec<f32>; 3]),
+    /// Palette of RGB565 pixels with their colours in the working space, and the amplitude for ordered dithering
+    Palette(Vec<(u16, Color)>, Color),
+}
+
+/// Quantise the 8-bit RGB pixels (3 bytes per pixel, row by row) to RGB565 with the options
+pub fn quantise(rgb: &[u8], width: usize, options: &Options) -> Vec<u16> {
+    if *options == Options::default() {
+        return rgb.chunks(3)
+            .map(|p| convert::rgb565(p[0], p[1], p[2]))
+            .collect();
+    }
+    let gamma = options.gamma;
+    let colors: Vec<Color> = rgb.chunks(3)
+        .map(|p| [ to_space(p[0], gamma), to_space(p[1], gamma), to_space(p[2], gamma) ])
+        .collect();
+    let target = match options.colors {
+        None => Target::Levels(levels(gamma)),
+        Some(count) => palette(&colors, count, gamma),
+    };
+    match options.dither {
+        Dither::None => colors.iter()
+            .map(|color| nearest(&target, color).0)
+            .collect(),
+        Dither::Ordered => colors.iter()
+            .enumerate()
+            .map(|(i, color)| {
+                //  Spread the threshold over one step between colours, centred on 0
+                let threshold = (BAYER[(i / width) & 3][(i % width) & 3] as f32 + 0.5) / 16.0 - 0.5;
+                let step = step(&target, color);
+                let color = [ color[0] + threshold * step[0], color[1] + threshold * step[1], color[2] + threshold * step[2] ];
+                nearest(&target, &color).0
+            })
+            .collect(),
+        Dither::FloydSteinberg => error_diffusion(colors, width, &target),
+    }
+}
+
+/// Expand the RGB565 pixel to 8-bit red, green and blue, by replicating the upper bits into the lower bits
+pub fn to_rgb888(pixel: u16) -> [u8; 3] {
+    let levels = unpack(pixel);
+    [ expand(levels[0], 5), expand(levels[1], 6), expand(levels[2], 5) ]
+}
+
+/// Floyd-Steinberg error diffusion: 7/16 to the right, 3/16 below left, 5/16 below, 1/16 below right
+fn error_diffusion(mut colors: Vec<Color>, width: usize, target: &Target) -> Vec<u16> {
+    let height = colors.len() / width;
+    let mut pixels = Vec::with_capacity(colors.len());
+    for y in 0..height {
+        for x in 0..width {
+            let mut color = colors[y * width + x];
+            color.iter_mut().for_each(|c| *c = c.clamp(0.0, 255.0));
+            let (pixel, shown) = nearest(target, &color);
+            pixels.push(pixel);
+
+            //  Diffuse the error to the pixels that haven't been quantised
+            let error = [ color[0] - shown[0], color[1] - shown[1], color[2] - shown[2] ];
+            let mut diffuse = |dx: isize, dy: usize, weight: f32| {
+                let nx = x as isize + dx;
+                if nx < 0 || nx >= width as isize || y + dy >= height { return; }
+                let neighbour = &mut colors[(y + dy) * width + nx as usize];
+                for c in 0..3 { neighbour[c] += error[c] * weight / 16.0; }
+            };
+            diffuse( 1, 0, 7.0);
+            diffuse(-1, 1, 3.0);
+            diffuse( 0, 1, 5.0);
+            diffuse( 1, 1, 1.0);
+        }
+    }
+    pixels
+}
+
+/// Return the RGB565 pixel nearest to the colour, and its colour in the working space
+fn nearest(target: &Target, color: &Color) -> (u16, Color) {
+    match target {
+        Target::Levels(levels) => {
+            let mut indexes = [0; 3];
+            let mut shown = [0.0; 3];
+            for c in 0..3 {
+                let index = nearest_level(&levels[c], color[c]);
+                indexes[c] = index as u32;
+                shown[c] = levels[c][index];
+            }
+            (pack(indexes), shown)
+        }
+        Target::Palette(palette, _) => *palette.iter()
+            .min_by(|a, b| distance(&a.1, color).partial_cmp(&distance(&b.1, color)).unwrap())
+            .expect("empty palette"),
+    }
+}
+
+/// Return the distance between the colours that are nearest to the colour, for ordered dithering
+fn step(target: &Target, color: &Color) -> Color {
+    match target {
+        Target::Levels(levels) => {
+            let mut step = [0.0; 3];
+            for c in 0..3 {
+                let values = &levels[c];
+                let above = values.iter().position(|v| *v > color[c]).unwrap_or(values.len() - 1).max(1);
+                step[c] = values[above] - values[above - 1];
+            }
+            step
+        }
+        Target::Palette(_, amplitude) => *amplitude,
+    }
+}
+
+/// Return the index of the level nearest to the value
+fn nearest_level(values: &[f32], value: f32) -> usize {
+    let mut best = 0;
+    for (index, level) in values.iter().enumerate() {
+        if (level - value).abs() < (values[best] - value).abs() { best = index; }
+    }
+    best
+}
+
+/// Return the values of every level of red, green and blue in the working space
+fn levels(gamma: bool) -> [Vec<f32>; 3] {
+    let channel = |bits: u32| (0..1 << bits)
+        .map(|level| to_space(expand(level, bits), gamma))
+        .collect::<Vec<f32>>();
+    [ channel(CHANNEL_BITS[0]), channel(CHANNEL_BITS[1]), channel(CHANNEL_BITS[2]) ]
+}
+
+/// Pick a palette of at most `count` RGB565 colours for the colours by median cut: the box of colours with the
+/// widest range is split at the median until there are `count` boxes, and each box contributes its average colour
+fn palette(colors: &[Color], count: usize, gamma: bool) -> Target {
+    let mut boxes = vec![colors.to_vec()];
+    while boxes.len() < count {
+        let (index, channel, range) = boxes.iter()
+            .enumerate()
+            .flat_map(|(index, colors)| (0..3).map(move |c| (index, c, range(colors, c))))
+            .fold((0, 0, 0.0), |best, next| if next.2 > best.2 { next } else { best });
+        if range <= 0.0 { break; }  //  Every box has a single colour
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_by(|a, b| a[channel].partial_cmp(&b[channel]).unwrap());
+        let upper = colors.split_off(colors.len() / 2);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+    //  Map the average of each box to RGB565, without duplicates
+    let levels = Target::Levels(levels(gamma));
+    let mut palette: Vec<(u16, Color)> = Vec::new();
+    for colors in &boxes {
+        let mut sum = [0.0; 3];
+        for color in colors {
+            for c in 0..3 { sum[c] += color[c]; }
+        }
+        let average = [ sum[0] / colors.len() as f32, sum[1] / colors.len() as f32, sum[2] / colors.len() as f32 ];
+        let entry = nearest(&levels, &average);
+        if !palette.iter().any(|(pixel, _)| *pixel == entry.0) { palette.push(entry); }
+    }
+    //  Ordered dithering spreads each channel over the average spacing of the palette colours
+    let mut amplitude = [0.0; 3];
+    for (c, amplitude) in amplitude.iter_mut().enumerate() {
+        let values: Vec<Color> = palette.iter().map(|(_, color)| *color).collect();
+        *amplitude = range(&values, c) / (palette.len() as f32).cbrt();
+    }
+    Target::Palette(palette, amplitude)
+}
+
+/// Return the range of the channel in the colours
+fn range(colors: &[Color], channel: usize) -> f32 {
+    let (min, max) = colors.iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), color| (min.min(color[channel]), max.max(color[channel])));
+    if colors.is_empty() { 0.0 } else { max - min }
+}
+
+/// Return the squared distance between the colours
+fn distance(a: &Color, b: &Color) -> f32 {
+    (0..3).map(|c| (a[c] - b[c]) * (a[c] - b[c])).sum()
+}
+
+/// Convert the 8-bit sRGB value to the working space: unchanged, or linear light if `gamma` is true
+fn to_space(value: u8, gamma: bool) -> f32 {
+    if !gamma { return value as f32; }
+    let v = value as f64 / 255.0;
+    let linear = if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) };
+    (linear * 255.0) as f32
+}
+
+/// Pack the red, green and blue levels into a RGB565 pixel
+fn pack(levels: [u32; 3]) -> u16 {
+    ((levels[0] << 11) | (levels[1] << 5) | levels[2]) as u16
+}
+
+/// Unpack the RGB565 pixel into red, green and blue levels
+fn unpack(pixel: u16) -> [u32; 3] {
+    let pixel = pixel as u32;
+    [ pixel >> 11, (pixel >> 5) & 0x3f, pixel & 0x1f ]
+}
+
+/// Expand the level with the number of bits to 8 bits
+fn expand(level: u32, bits: u32) -> u8 {
+    ((level << (8 - bits)) | (level >> (2 * bits - 8))) as u8
+}
@@ -0,0 +1,85 @@
+//! Compare the logos converted by `logo_tool convert` with the golden binaries in `tests/golden`, for every PNG in
+//! `logos/` and every set of quantise and overlay options. Run on the host computer:
+//! `cargo test -p logo_tool --target x86_64-unknown-linux-gnu`
+//! After an intended change to the conversion, check the previews with `logo_tool check` and update the golden binaries:
+//! `UPDATE_GOLDEN=1 cargo test -p logo_tool --target x86_64-unknown-linux-gnu`
+use logo::decoder::Decoder;
+use std::{
+    collections::HashSet,
+    env,
+    fs,
+    path::{ Path, PathBuf },
+    process::Command,
+};
+
+/// Name of each set of quantise options, used in the golden file names, and the options
+const VARIANTS: &[(&str, &[&str])] = &[
+    ("truncate",       &[]),
+    ("ordered",        &["--dither", "ordered"]),
+    ("floyd-gamma-64", &["--dither", "floyd", "--gamma", "--colors", "64"]),
+    ("overlay",        &["--fill", "000000", "--rect", "0,196,239,239", "--font", "8x16", "--color", "ffff00",
+                         "--text", "center,204,If found, call 555-0100", "--fill", "none", "--circle", "200,40,20"]),
+];
+
+/// Return the PNG files in `logos/`, sorted by name
+fn logos() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../logos");
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("png"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Return the number of colours in the compressed logo. Fail if it can't be decoded.
+fn count_colors(logo: &[u8]) -> usize {
+    let mut decoder = Decoder::new(logo).unwrap();
+    let mut colors = HashSet::new();
+    let mut buf = [0; 4096];
+    loop {
+        let len = decoder.read(&mut buf).unwrap();
+        if len == 0 { break; }
+        colors.extend(buf[..len].chunks(2).map(|pixel| u16::from_be_bytes([pixel[0], pixel[1]])));
+    }
+    colors.len()
+}
+
+#[test]
+fn converted_logos_match_golden() {
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let out_dir = env::temp_dir().join(format!("logo_tool_golden_{}", std::process::id()));
+    fs::create_dir_all(&out_dir).unwrap();
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+
+    let logos = logos();
+    assert!(!logos.is_empty(), "no logos");
+    let mut mismatches = Vec::new();
+    for png in &logos {
+        let name = png.file_stem().unwrap().to_str().unwrap();
+        for (variant, options) in VARIANTS {
+            //  Convert the PNG to a compressed logo
+            let file = format!("{}.{}.bin", name, variant);
+            let out = out_dir.join(&file);
+            let status = Command::new(env!("CARGO_BIN_EXE_logo_tool"))
+                .arg("convert").arg(png).arg("--logo").arg(&out).args(*options)
+                .status().unwrap();
+            assert!(status.success(), "{}: convert failed", file);
+            let logo = fs::read(&out).unwrap();
+            if options.contains(&"--colors") {
+                assert!(count_colors(&logo) <= 64, "{}: too many colours", file);
+            } else {
+                count_colors(&logo);
+            }
+            //  Compare with the golden binary, or replace it
+            let golden = golden_dir.join(&file);
+            if update {
+                fs::write(&golden, &logo).unwrap();
+            } else if fs::read(&golden).ok().as_ref() != Some(&logo) {
+                mismatches.push(file);
+            }
+        }
+    }
+    fs::remove_dir_all(&out_dir).ok();
+    assert!(mismatches.is_empty(), "converted logos differ from tests/golden: {:?}", mismatches);
+}
@@ -8,6 +8,11 @@ impl I2C {
         I2C { i2c_num: 0, timeout: 1000 }
     }
 
+    /// Use an I2C port that has already been configured and enabled, e.g. by the BSP
+    pub const fn from_port(i2c_num: u8, operation_timeout_in_ticks: u32) -> Self {
+        I2C { i2c_num, timeout: operation_timeout_in_ticks }
+    }
+
     /// Initiaise the I2C port
     pub fn init(
         &mut self,
@@ -19,6 +19,106 @@ pub const HAL_SPI_MSB_FIRST: u32 = 0;
 pub const HAL_SPI_LSB_FIRST: u32 = 1;
 pub const HAL_SPI_WORD_SIZE_8BIT: u32 = 0;
 pub const HAL_SPI_WORD_SIZE_9BIT: u32 = 1;
+#[mynewt_macros::safe_wrap(attr)] extern "C" {
+    pub fn hal_flash_ioctl(flash_id: u8, cmd: u32, args: *mut ::cty::c_void) -> ::cty::c_int;
+}
+#[mynewt_macros::safe_wrap(attr)] extern "C" {
+    #[doc = " Reads a block of data from flash."]
+    #[doc = ""]
+    #[doc = " - __`flash_id`__:              The ID of the flash device to read from."]
+    #[doc = " - __`address`__:               The address to read from."]
+    #[doc = " - __`dst`__:                   A buffer to fill with data read from flash."]
+    #[doc = " - __`num_bytes`__:             The number of bytes to read."]
+    #[doc = ""]
+    #[doc = " Return:                      0 on success;"]
+    #[doc = "                              SYS_EINVAL on bad argument error;"]
+    #[doc = "                              SYS_EIO on flash driver error."]
+    pub fn hal_flash_read(
+        flash_id: u8,
+        address: u32,
+        dst: *mut ::cty::c_void,
+        num_bytes: u32,
+    ) -> ::cty::c_int;
+}
+#[mynewt_macros::safe_wrap(attr)] extern "C" {
+    #[doc = " Writes a block of data to flash."]
+    #[doc = ""]
+    #[doc = " - __`flash_id`__:              The ID of the flash device to write to."]
+    #[doc = " - __`address`__:               The address to write to."]
+    #[doc = " - __`src`__:                   A buffer containing the data to be written."]
+    #[doc = " - __`num_bytes`__:             The number of bytes to write."]
+    #[doc = ""]
+    #[doc = " Return:                      0 on success;"]
+    #[doc = "                              SYS_EINVAL on bad argument error;"]
+    #[doc = "                              SYS_EACCES if flash region is write protected;"]
+    #[doc = "                              SYS_EIO on flash driver error."]
+    pub fn hal_flash_write(
+        flash_id: u8,
+        address: u32,
+        src: *const ::cty::c_void,
+        num_bytes: u32,
+    ) -> ::cty::c_int;
+}
+#[mynewt_macros::safe_wrap(attr)] extern "C" {
+    #[doc = " Erases a single flash sector."]
+    #[doc = ""]
+    #[doc = " - __`flash_id`__:              The ID of the flash device to erase."]
+    #[doc = " - __`sector_address`__:        An address within the sector to erase."]
+    #[doc = ""]
+    #[doc = " Return:                      0 on success;"]
+    #[doc = "                              SYS_EINVAL on bad argument error;"]
+    #[doc = "                              SYS_EACCES if flash region is write protected;"]
+    #[doc = "                              SYS_EIO on flash driver error."]
+    pub fn hal_flash_erase_sector(flash_id: u8, sector_address: u32) -> ::cty::c_int;
+}
+#[mynewt_macros::safe_wrap(attr)] extern "C" {
+    #[doc = " Erases a contiguous sequence of flash sectors.  If the specified range does"]
+    #[doc = " not start and end on sector boundaries, the contents of the partial sectors"]
+    #[doc = " are also erased."]
+    #[doc = ""]
+    #[doc = " - __`flash_id`__:              The ID of the flash device to erase."]
+    #[doc = " - __`address`__:               An address within the first sector to erase."]
+    #[doc = " - __`num_bytes`__:             The number of bytes to erase."]
+    #[doc = ""]
+    #[doc = " Return:                      0 on success;"]
+    #[doc = "                              SYS_EINVAL on bad argument error;"]
+    #[doc = "                              SYS_EACCES if flash region is write protected;"]
+    #[doc = "                              SYS_EIO on flash driver error."]
+    pub fn hal_flash_erase(flash_id: u8, address: u32, num_bytes: u32) -> ::cty::c_int;
+}
+#[mynewt_macros::safe_wrap(attr)] extern "C" {
+    #[doc = " Determines if the specified region of flash is completely erased."]
+    #[doc = ""]
+    #[doc = " - __`flash_id`__:              The ID of the flash device to check."]
+    #[doc = " - __`address`__:               The start of the range to check."]
+    #[doc = " - __`dst`__:                   A buffer to hold the flash contents that are read."]
+    #[doc = " - __`num_bytes`__:             The size of the range to check, in bytes."]
+    #[doc = ""]
+    #[doc = " Return:                      1 if the specified region is completely erased;"]
+    #[doc = "                              0 if any non-erased bytes were found;"]
+    #[doc = "                              SYS_EINVAL on bad argument error;"]
+    #[doc = "                              SYS_EIO on flash driver error."]
+    pub fn hal_flash_isempty(
+        flash_id: u8,
+        address: u32,
+        dst: *mut ::cty::c_void,
+        num_bytes: u32,
+    ) -> ::cty::c_int;
+}
+#[mynewt_macros::safe_wrap(attr)] extern "C" {
+    #[doc = " Returns the required alignment of writes to the flash device, in bytes."]
+    pub fn hal_flash_align(flash_id: u8) -> u8;
+}
+#[mynewt_macros::safe_wrap(attr)] extern "C" {
+    #[doc = " Returns the value of an erased byte of the flash device, typically 0xff."]
+    pub fn hal_flash_erased_val(flash_id: u8) -> u8;
+}
+#[mynewt_macros::safe_wrap(attr)] extern "C" {
+    #[doc = " Initializes all flash devices in the system."]
+    #[doc = ""]
+    #[doc = " Return: 0 on success; nonzero on failure."]
+    pub fn hal_flash_init() -> ::cty::c_int;
+}
 #[doc = " Not connected"]
 pub const hal_gpio_mode_e_HAL_GPIO_MODE_NC: hal_gpio_mode_e = -1;
 #[doc = " Input"]
@@ -1,5 +1,6 @@
 //! Experimental Non-Blocking SPI Transfer API. Uses a background task to send SPI requests sequentially.
 //! Request data is copied into Mbuf Queues before transmitting. 
+//! The SPI port is shared with External SPI Flash, so tasks that access the flash lock the SPI port with `spi_lock()`.
 use crate::{
     self as mynewt,
     result::*,
@@ -40,9 +41,16 @@ static mut PENDING_CMD: heapless::Vec<u8, PendingCmdSize> = heapless::Vec(heaple
 /// Pending SPI Data Bytes to be written
 static mut PENDING_DATA: heapless::Vec<u8, PendingDataSize> = heapless::Vec(heapless::i::Vec::new());
 
+/// Mutex that gives one task at a time the use of the SPI port, which is shared by the display and External SPI Flash.
+/// Held while queueing a request, and by `spi_lock()` while accessing another device on the SPI port.
+static mut SPI_MUTEX: os::os_mutex = fill_zero!(os::os_mutex);
+
 /// Semaphore that is signalled for every completed SPI request
 static mut SPI_SEM: os::os_sem = fill_zero!(os::os_sem);
 
+/// Max number of queued SPI requests
+const SPI_THROTTLE_COUNT: u16 = 2;
+
 /// Semaphore that throttles the number of queued SPI requests
 static mut SPI_THROTTLE_SEM: os::os_sem = fill_zero!(os::os_sem);
 
@@ -100,12 +108,16 @@ pub fn spi_noblock_init() -> MynewtResult<()> {
     ) };
     assert_eq!(rc, 0, "mqueue fail");  //  TODO: Map to MynewtResult
 
+    //  Create the Mutex that will give one task at a time the use of the SPI port
+    let rc = unsafe { os::os_mutex_init(&mut SPI_MUTEX) };
+    assert_eq!(rc, 0, "mutex fail");  //  TODO: Map to MynewtResult
+
     //  Create the Semaphore that will signal whether the SPI request has completed
     let rc = unsafe { os::os_sem_init(&mut SPI_SEM, 0) };  //  Init to 0 tokens, so caller will block until SPI request is completed.
     assert_eq!(rc, 0, "sem fail");  //  TODO: Map to MynewtResult
 
     //  Create the Semaphore that will throttle the number of queued SPI requests
-    let rc = unsafe { os::os_sem_init(&mut SPI_THROTTLE_SEM, 2) };  //  Only max 2 requests queued, the next request will block
+    let rc = unsafe { os::os_sem_init(&mut SPI_THROTTLE_SEM, SPI_THROTTLE_COUNT) };  //  Only max 2 requests queued, the next request will block
     assert_eq!(rc, 0, "sem fail");  //  TODO: Map to MynewtResult
     
     //  Create a task to send SPI requests sequentially from the SPI Event Queue and Mbuf Queue
@@ -163,11 +175,16 @@ pub fn spi_noblock_write_flush() -> MynewtResult<()> {
         unsafe { PENDING_DATA.len() } == 0 {
         return Ok(());
     }
-    //  Enqueue the pending SPI request into the Mbuf Queue
-    if let Err(e) = spi_noblock_write(
-        unsafe { PENDING_CMD[0] },  //  Command Byte
-        unsafe { &PENDING_DATA }    //  Data Bytes
-    ) {  //  In case of error, clear the pending request and return error.        
+    //  Enqueue the pending SPI request into the Mbuf Queue, unless another task is using the SPI port
+    let result = spi_lock().and_then(|_| {
+        let result = spi_noblock_write(
+            unsafe { PENDING_CMD[0] },  //  Command Byte
+            unsafe { &PENDING_DATA }    //  Data Bytes
+        );
+        spi_unlock();
+        result
+    });
+    if let Err(e) = result {  //  In case of error, clear the pending request and return error.
         unsafe { PENDING_CMD.clear() };
         unsafe { PENDING_DATA.clear() };    
         return Err(e);
@@ -178,6 +195,39 @@ pub fn spi_noblock_write_flush() -> MynewtResult<()> {
     Ok(())
 }
 
+/// Enqueue any pending request and wait for all queued SPI requests to be written. Call this before using the
+/// SPI port for another device, like External SPI Flash, which shares the SPI port with the display.
+pub fn spi_noblock_wait() -> MynewtResult<()> {
+    spi_noblock_write_flush() ? ;
+    //  Every queued request holds a throttle token until it's written, so take all tokens and give them back.
+    let timeout = 30_000;
+    let mut taken = 0;
+    while taken < SPI_THROTTLE_COUNT {
+        let rc = unsafe { os::os_sem_pend(&mut SPI_THROTTLE_SEM, timeout * OS_TICKS_PER_SEC / 1000) };
+        if rc != 0 { break; }
+        taken += 1;
+    }
+    for _ in 0..taken {
+        unsafe { os::os_sem_release(&mut SPI_THROTTLE_SEM) };
+    }
+    if taken < SPI_THROTTLE_COUNT { return Err(MynewtError::SYS_ETIMEOUT); }
+    Ok(())
+}
+
+/// Lock the SPI port for the current task. Blocks while another task is using the SPI port, and may be locked again
+/// by the same task. Requests from other tasks are not queued until `spi_unlock()`. To access another device on
+/// the SPI port, like External SPI Flash, call `spi_lock()`, then `spi_noblock_wait()`, then access the device.
+pub fn spi_lock() -> MynewtResult<()> {
+    let rc = unsafe { os::os_mutex_pend(&mut SPI_MUTEX, os::OS_TIMEOUT_NEVER) };
+    if rc != 0 { return Err(MynewtError::from(rc as i32)); }
+    Ok(())
+}
+
+/// Unlock the SPI port after `spi_lock()`, so that other tasks may queue their requests
+pub fn spi_unlock() {
+    unsafe { os::os_mutex_release(&mut SPI_MUTEX) };
+}
+
 /// Enqueue request for non-blocking SPI write. Returns without waiting for write to complete.
 /// Request must have a Command Byte, followed by optional Data Bytes.
 fn spi_noblock_write(cmd: u8, data: &[u8]) -> MynewtResult<()> {
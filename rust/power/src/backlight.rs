@@ -0,0 +1,174 @@
+//! Backlight of the PineTime display, driven by three GPIO pins (active when low): LCD_BACKLIGHT_LOW (P0.14),
+//! LCD_BACKLIGHT_MID (P0.22) and LCD_BACKLIGHT_HIGH (P0.23). Combinations of the pins give brightness levels 0 (off)
+//! to 7 (brightest): bit 0 of the level switches on the Low pin, bit 1 the Mid pin and bit 2 the High pin.
+//! Fades and the auto-dim timeout are stepped by `tick()`, which returns the number of milliseconds until it should
+//! be called again, e.g. by a Mynewt callout. The driver doesn't call Mynewt, so it may be tested with mock pins.
+use embedded_hal::digital::v2::OutputPin;
+
+/// Brightest level
+pub const MAX_LEVEL: u8 = 7;
+
+/// Duration of the fade when dimming after the auto-dim timeout, and when brightening again, in milliseconds
+pub const DIM_FADE_MS: u32 = 300;
+
+/// Backlight driver over the Low, Mid and High pins
+pub struct Backlight<P: OutputPin> {
+    /// Low, Mid and High pins
+    pins:       [P; 3],
+    /// Level shown now
+    level:      u8,
+    /// Level set by the app, restored after dimming
+    brightness: u8,
+    /// Fade in progress, if any
+    fade:       Option<Fade>,
+    /// Auto-dim timeout, if enabled
+    auto_dim:   Option<AutoDim>,
+    /// Milliseconds since the last activity
+    idle_ms:    u32,
+    /// True if dimmed by the auto-dim timeout
+    dimmed:     bool,
+}
+
+/// Fade that steps one level at a time towards the target
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Fade {
+    /// Level at the end of the fade
+    target:  u8,
+    /// Milliseconds between steps
+    step_ms: u32,
+}
+
+/// Dim the backlight after a period without activity
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct AutoDim {
+    /// Milliseconds without activity before dimming
+    timeout_ms: u32,
+    /// Level when dimmed
+    level:      u8,
+}
+
+impl<P: OutputPin> Backlight<P> {
+    /// Return the backlight driver for the Low, Mid and High pins. The backlight is switched off.
+    pub fn new(pins: [P; 3]) -> Result<Backlight<P>, P::Error> {
+        let mut backlight = Backlight {
+            pins, level: 0, brightness: 0, fade: None, auto_dim: None, idle_ms: 0, dimmed: false,
+        };
+        backlight.show(0) ? ;
+        Ok(backlight)
+    }
+
+    /// Return the level shown now
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// Return the level set by the app, which is shown when not dimmed or fading
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// Return true if dimmed by the auto-dim timeout
+    pub fn is_dimmed(&self) -> bool {
+        self.dimmed
+    }
+
+    /// Set the brightness to the level (0 to 7) immediately, stopping any fade. Returns the milliseconds until the
+    /// next `tick()`, if any.
+    pub fn set_level(&mut self, level: u8) -> Result<Option<u32>, P::Error> {
+        let level = level.min(MAX_LEVEL);
+        self.brightness = level;
+        self.fade       = None;
+        self.dimmed     = false;
+        self.idle_ms    = 0;
+        self.show(level) ? ;
+        Ok(self.next_tick())
+    }
+
+    /// Fade from the current level to the level (0 to 7) over the duration, one level at a time. Returns the
+    /// milliseconds until the next `tick()`, if any.
+    pub fn fade_to(&mut self, level: u8, duration_ms: u32) -> Option<u32> {
+        let level = level.min(MAX_LEVEL);
+        self.brightness = level;
+        self.dimmed     = false;
+        self.idle_ms    = 0;
+        self.start_fade(level, duration_ms);
+        self.next_tick()
+    }
+
+    /// Fade in to the brightest level over the duration
+    pub fn fade_in(&mut self, duration_ms: u32) -> Option<u32> {
+        self.fade_to(MAX_LEVEL, duration_ms)
+    }
+
+    /// Fade out until switched off over the duration
+    pub fn fade_out(&mut self, duration_ms: u32) -> Option<u32> {
+        self.fade_to(0, duration_ms)
+    }
+
+    /// Dim the backlight to the level after `timeout_ms` milliseconds without activity, or `None` to disable
+    /// auto-dim. Returns the milliseconds until the next `tick()`, if any.
+    pub fn set_auto_dim(&mut self, timeout_ms: Option<u32>, level: u8) -> Option<u32> {
+        self.auto_dim = timeout_ms.map(|timeout_ms| AutoDim { timeout_ms, level: level.min(MAX_LEVEL) });
+        self.idle_ms  = 0;
+        self.next_tick()
+    }
+
+    /// Restart the auto-dim timeout after activity, like a touch. If dimmed, fade back to the brightness set by the
+    /// app. Returns the milliseconds until the next `tick()`, if any.
+    pub fn activity(&mut self) -> Option<u32> {
+        self.idle_ms = 0;
+        if self.dimmed {
+            self.dimmed = false;
+            self.start_fade(self.brightness, DIM_FADE_MS);
+        }
+        self.next_tick()
+    }
+
+    /// Step the fade and the auto-dim timeout after `elapsed_ms` milliseconds. Returns the milliseconds until the
+    /// next `tick()`, or `None` if nothing is pending.
+    pub fn tick(&mut self, elapsed_ms: u32) -> Result<Option<u32>, P::Error> {
+        self.idle_ms = self.idle_ms.saturating_add(elapsed_ms);
+        //  Step one level towards the target of the fade
+        if let Some(fade) = self.fade {
+            let level = if fade.target > self.level { self.level + 1 }
+                else if fade.target < self.level { self.level - 1 }
+                else { self.level };
+            self.show(level) ? ;
+            if level == fade.target { self.fade = None; }
+        }
+        //  Dim after the timeout, unless already darker
+        if let Some(auto_dim) = self.auto_dim {
+            if !self.dimmed && self.idle_ms >= auto_dim.timeout_ms {
+                self.dimmed = true;
+                if auto_dim.level < self.brightness { self.start_fade(auto_dim.level, DIM_FADE_MS); }
+            }
+        }
+        Ok(self.next_tick())
+    }
+
+    /// Return the milliseconds until the next `tick()`: the next step of the fade or the auto-dim timeout
+    pub fn next_tick(&self) -> Option<u32> {
+        if let Some(fade) = self.fade { return Some(fade.step_ms); }
+        match self.auto_dim {
+            Some(auto_dim) if !self.dimmed => Some(auto_dim.timeout_ms.saturating_sub(self.idle_ms).max(1)),
+            _ => None,
+        }
+    }
+
+    /// Start fading to the level, stepping evenly over the duration
+    fn start_fade(&mut self, target: u8, duration_ms: u32) {
+        let steps = (target as i32 - self.level as i32).unsigned_abs();
+        self.fade = duration_ms.checked_div(steps)
+            .map(|step_ms| Fade { target, step_ms: step_ms.max(1) });
+    }
+
+    /// Switch the pins for the level. The pins are active when low.
+    fn show(&mut self, level: u8) -> Result<(), P::Error> {
+        for (bit, pin) in self.pins.iter_mut().enumerate() {
+            if level & (1 << bit) != 0 { pin.set_low() ? ; }
+            else { pin.set_high() ? ; }
+        }
+        self.level = level;
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+//! Power state of the PineTime display. After a period without activity, the backlight is switched off and the
+//! ST7789 display controller is put to sleep (DISPOFF, SLPIN). The next activity, like a touch, wakes the display
+//! (SLPOUT, DISPON) and restores the brightness set before sleeping, not the auto-dimmed level. The inactivity
+//! timeout is stepped by `tick()` like `backlight::Backlight`, and the commands are sent through the `Panel` trait,
+//! so the power state doesn't call Mynewt and may be tested with mock pins.
+use embedded_hal::{
+    blocking::delay::DelayMs,
+    digital::v2::OutputPin,
+};
+use crate::backlight::Backlight;
+
+/// ST7789 Sleep In
+pub const SLPIN: u8 = 0x10;
+/// ST7789 Sleep Out
+pub const SLPOUT: u8 = 0x11;
+/// ST7789 Display Off
+pub const DISPOFF: u8 = 0x28;
+/// ST7789 Display On
+pub const DISPON: u8 = 0x29;
+
+/// Milliseconds to wait after Sleep Out before the next command, from the ST7789 datasheet
+pub const SLPOUT_DELAY_MS: u8 = 5;
+
+/// Display controller that receives the sleep and wake commands, e.g. the ST7789 over SPI
+pub trait Panel {
+    /// Error returned when a command can't be sent
+    type Error;
+    /// Send the command without parameters. Returns after the command has been sent.
+    fn command(&mut self, command: u8) -> Result<(), Self::Error>;
+}
+
+/// Puts the display to sleep after the inactivity timeout and wakes it on activity
+pub struct Power {
+    /// Milliseconds without activity before the display sleeps
+    timeout_ms:  u32,
+    /// Milliseconds since the last activity
+    idle_ms:     u32,
+    /// True if the display is asleep
+    asleep:      bool,
+    /// Brightness set by the app before sleeping, restored on wakeup
+    awake_level: u8,
+}
+
+impl Power {
+    /// Return the power state for an awake display that sleeps after `timeout_ms` milliseconds without activity
+    pub const fn new(timeout_ms: u32) -> Power {
+        Power { timeout_ms, idle_ms: 0, asleep: false, awake_level: 0 }
+    }
+
+    /// Return true if the display is asleep
+    pub fn is_asleep(&self) -> bool {
+        self.asleep
+    }
+
+    /// Restart the inactivity timeout after activity, like a touch, and the auto-dim timeout of the backlight. If the
+    /// display is asleep, wake the display and restore the brightness set before sleeping. Returns true if the
+    /// display was asleep, so that the activity that woke the display is not passed to the app.
+    pub fn wake<P, S, D, E>(&mut self, backlight: &mut Backlight<P>, panel: &mut S, delay: &mut D) -> Result<bool, E>
+    where
+        P: OutputPin<Error = E>,
+        S: Panel<Error = E>,
+        D: DelayMs<u8>,
+    {
+        let asleep = self.asleep;
+        if asleep {
+            panel.command(SLPOUT) ? ;
+            delay.delay_ms(SLPOUT_DELAY_MS);
+            panel.command(DISPON) ? ;
+            backlight.set_level(self.awake_level) ? ;
+            self.asleep = false;
+        }
+        backlight.activity();
+        self.idle_ms = 0;
+        Ok(asleep)
+    }
+
+    /// Switch off the backlight and put the display to sleep. The brightness set by the app is kept for wakeup,
+    /// even if the backlight has been dimmed.
+    pub fn sleep<P, S, E>(&mut self, backlight: &mut Backlight<P>, panel: &mut S) -> Result<(), E>
+    where
+        P: OutputPin<Error = E>,
+        S: Panel<Error = E>,
+    {
+        if self.asleep { return Ok(()); }
+        self.awake_level = backlight.brightness();
+        backlight.set_level(0) ? ;
+        panel.command(DISPOFF) ? ;
+        panel.command(SLPIN) ? ;
+        self.asleep = true;
+        Ok(())
+    }
+
+    /// Step the inactivity timeout after `elapsed_ms` milliseconds, and sleep when it expires. Returns the
+    /// milliseconds until the next `tick()`, or `None` while asleep.
+    pub fn tick<P, S, E>(&mut self, elapsed_ms: u32, backlight: &mut Backlight<P>, panel: &mut S)
+        -> Result<Option<u32>, E>
+    where
+        P: OutputPin<Error = E>,
+        S: Panel<Error = E>,
+    {
+        self.idle_ms = self.idle_ms.saturating_add(elapsed_ms);
+        if !self.asleep && self.idle_ms >= self.timeout_ms {
+            self.sleep(backlight, panel) ? ;
+        }
+        Ok(self.next_tick())
+    }
+
+    /// Return the milliseconds until the next `tick()`: the rest of the inactivity timeout, or `None` while asleep
+    pub fn next_tick(&self) -> Option<u32> {
+        if self.asleep { return None; }
+        Some(self.timeout_ms.saturating_sub(self.idle_ms).max(1))
+    }
+}
@@ -0,0 +1,26 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *  http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+//!  Power management for the PineTime display. The backlight driver sets brightness levels 0 to 7 from combinations
+//!  of the three backlight GPIO pins, and steps fades and the auto-dim timeout (see `backlight.rs`). The display
+//!  power state switches off the backlight and puts the ST7789 to sleep after inactivity (see `display.rs`).
+//!  Doesn't call Mynewt and doesn't read the clock, so it may be tested on the host computer with mock pins.
+#![no_std]  //  Don't link with standard Rust library, which is not compatible with embedded systems
+
+pub mod backlight;  //  Export `backlight.rs` as Rust module `power::backlight`
+pub mod display;    //  Export `display.rs` as Rust module `power::display`
@@ -0,0 +1,133 @@
+//! Check the backlight levels, fades and auto-dim timeout with mock GPIO pins. Run on the host computer:
+//! `cargo test -p power --target x86_64-unknown-linux-gnu`
+use embedded_hal::digital::v2::OutputPin;
+use power::backlight::{ Backlight, DIM_FADE_MS, MAX_LEVEL };
+use std::{
+    cell::RefCell,
+    rc::Rc,
+};
+
+/// Mock GPIO pin that records its state in the shared pin states. Low is true, like the backlight pins.
+struct MockPin {
+    /// Index of the pin: 0 for Low, 1 for Mid, 2 for High
+    index:  usize,
+    /// True for each pin that is low
+    states: Rc<RefCell<[bool; 3]>>,
+}
+
+impl OutputPin for MockPin {
+    type Error = ();
+
+    fn set_low(&mut self) -> Result<(), ()> {
+        self.states.borrow_mut()[self.index] = true;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), ()> {
+        self.states.borrow_mut()[self.index] = false;
+        Ok(())
+    }
+}
+
+/// Return the backlight driver over mock pins, and the shared pin states
+fn mock_backlight() -> (Backlight<MockPin>, Rc<RefCell<[bool; 3]>>) {
+    let states = Rc::new(RefCell::new([true; 3]));
+    let pin = |index| MockPin { index, states: states.clone() };
+    let backlight = Backlight::new([ pin(0), pin(1), pin(2) ]).unwrap();
+    (backlight, states)
+}
+
+/// Return the level shown by the mock pins
+fn pin_level(states: &Rc<RefCell<[bool; 3]>>) -> u8 {
+    states.borrow().iter()
+        .enumerate()
+        .map(|(bit, low)| if *low { 1 << bit } else { 0 })
+        .sum()
+}
+
+/// Call `tick()` until nothing is pending, and return the levels shown by the pins after each tick with the delay
+/// before the tick. Stops after `max_ticks`.
+fn run(backlight: &mut Backlight<MockPin>, states: &Rc<RefCell<[bool; 3]>>, mut next: Option<u32>, max_ticks: usize)
+    -> Vec<(u32, u8)> {
+    let mut levels = Vec::new();
+    while let Some(delay) = next {
+        if levels.len() == max_ticks { break; }
+        next = backlight.tick(delay).unwrap();
+        levels.push((delay, pin_level(states)));
+    }
+    levels
+}
+
+#[test]
+fn levels_switch_pin_combinations() {
+    let (mut backlight, states) = mock_backlight();
+    assert_eq!(*states.borrow(), [false; 3], "must start switched off");
+    for level in 0..=MAX_LEVEL {
+        assert_eq!(backlight.set_level(level).unwrap(), None);
+        assert_eq!(pin_level(&states), level);
+        assert_eq!(backlight.level(), level);
+    }
+    backlight.set_level(5).unwrap();
+    assert_eq!(*states.borrow(), [true, false, true]);
+    backlight.set_level(100).unwrap();
+    assert_eq!(backlight.level(), MAX_LEVEL);
+}
+
+#[test]
+fn fade_steps_one_level_at_a_time() {
+    let (mut backlight, states) = mock_backlight();
+    let next = backlight.fade_in(700);
+    assert_eq!(next, Some(100));
+    let levels = run(&mut backlight, &states, next, 100);
+    assert_eq!(levels, (1..=7).map(|level| (100, level)).collect::<Vec<_>>());
+
+    let next = backlight.fade_to(4, 30);
+    let levels = run(&mut backlight, &states, next, 100);
+    assert_eq!(levels, vec![ (10, 6), (10, 5), (10, 4) ]);
+
+    let next = backlight.fade_out(0);
+    let levels = run(&mut backlight, &states, next, 100);
+    assert_eq!(levels, vec![ (1, 3), (1, 2), (1, 1), (1, 0) ]);
+}
+
+#[test]
+fn auto_dim_after_timeout_and_wake_on_activity() {
+    let (mut backlight, states) = mock_backlight();
+    backlight.set_level(MAX_LEVEL).unwrap();
+    let next = backlight.set_auto_dim(Some(5000), 2);
+    assert_eq!(next, Some(5000));
+
+    //  Activity restarts the timeout
+    assert_eq!(backlight.tick(3000).unwrap(), Some(2000));
+    assert_eq!(backlight.activity(), Some(5000));
+    assert!(!backlight.is_dimmed());
+
+    //  Dims after the timeout, fading to level 2
+    let levels = run(&mut backlight, &states, Some(5000), 100);
+    let step = DIM_FADE_MS / 5;
+    assert_eq!(levels, vec![ (5000, 7), (step, 6), (step, 5), (step, 4), (step, 3), (step, 2) ]);
+    assert!(backlight.is_dimmed());
+    assert_eq!(backlight.brightness(), MAX_LEVEL);
+
+    //  Activity fades back to the brightness set by the app, then waits for the timeout again
+    let next = backlight.activity();
+    assert!(!backlight.is_dimmed());
+    let levels = run(&mut backlight, &states, next, 5);
+    assert_eq!(levels, vec![ (step, 3), (step, 4), (step, 5), (step, 6), (step, 7) ]);
+    assert_eq!(backlight.tick(0).unwrap(), Some(5000 - DIM_FADE_MS));
+
+    //  No more ticks when auto-dim is disabled
+    assert_eq!(backlight.set_auto_dim(None, 0), None);
+}
+
+#[test]
+fn auto_dim_keeps_darker_level() {
+    let (mut backlight, states) = mock_backlight();
+    backlight.set_level(1).unwrap();
+    let next = backlight.set_auto_dim(Some(1000), 3);
+    let levels = run(&mut backlight, &states, next, 100);
+    assert_eq!(levels, vec![ (1000, 1) ]);
+    assert!(backlight.is_dimmed());
+    assert_eq!(backlight.activity(), Some(1000));
+    assert_eq!(pin_level(&states), 1);
+}
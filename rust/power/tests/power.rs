@@ -0,0 +1,177 @@
+//! Check the display sleep and wakeup with mock GPIO pins and a mock display controller. Run on the host computer:
+//! `cargo test -p power --target x86_64-unknown-linux-gnu`
+use embedded_hal::{
+    blocking::delay::DelayMs,
+    digital::v2::OutputPin,
+};
+use power::{
+    backlight::{ Backlight, MAX_LEVEL },
+    display::{ Panel, Power, DISPOFF, DISPON, SLPIN, SLPOUT, SLPOUT_DELAY_MS },
+};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+};
+
+/// Mock GPIO pin that records its state in the shared pin states. Low is true, like the backlight pins.
+struct MockPin {
+    /// Index of the pin: 0 for Low, 1 for Mid, 2 for High
+    index:  usize,
+    /// True for each pin that is low
+    states: Rc<RefCell<[bool; 3]>>,
+}
+
+impl OutputPin for MockPin {
+    type Error = ();
+
+    fn set_low(&mut self) -> Result<(), ()> {
+        self.states.borrow_mut()[self.index] = true;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), ()> {
+        self.states.borrow_mut()[self.index] = false;
+        Ok(())
+    }
+}
+
+/// Command or delay sent to the mock display controller
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Sent {
+    /// Command without parameters
+    Command(u8),
+    /// Delay in milliseconds
+    Delay(u8),
+}
+
+/// Mock display controller that records the commands in the shared log
+struct MockPanel {
+    log: Rc<RefCell<Vec<Sent>>>,
+}
+
+impl Panel for MockPanel {
+    type Error = ();
+
+    fn command(&mut self, command: u8) -> Result<(), ()> {
+        self.log.borrow_mut().push(Sent::Command(command));
+        Ok(())
+    }
+}
+
+/// Mock delay that records the delays in the shared log, between the commands
+struct MockDelay {
+    log: Rc<RefCell<Vec<Sent>>>,
+}
+
+impl DelayMs<u8> for MockDelay {
+    fn delay_ms(&mut self, ms: u8) {
+        self.log.borrow_mut().push(Sent::Delay(ms));
+    }
+}
+
+/// Return the mock display controller and delay, and the shared log
+fn mock_panel() -> (MockPanel, MockDelay, Rc<RefCell<Vec<Sent>>>) {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    (MockPanel { log: log.clone() }, MockDelay { log: log.clone() }, log)
+}
+
+/// Return the backlight driver over mock pins, and the shared pin states
+fn mock_backlight() -> (Backlight<MockPin>, Rc<RefCell<[bool; 3]>>) {
+    let states = Rc::new(RefCell::new([true; 3]));
+    let pin = |index| MockPin { index, states: states.clone() };
+    let backlight = Backlight::new([ pin(0), pin(1), pin(2) ]).unwrap();
+    (backlight, states)
+}
+
+/// Return the level shown by the mock pins
+fn pin_level(states: &Rc<RefCell<[bool; 3]>>) -> u8 {
+    states.borrow().iter()
+        .enumerate()
+        .map(|(bit, low)| if *low { 1 << bit } else { 0 })
+        .sum()
+}
+
+/// Call `tick()` until nothing is pending, and return the levels shown by the pins after each tick with the delay
+/// before the tick. Stops after `max_ticks`.
+fn run(backlight: &mut Backlight<MockPin>, states: &Rc<RefCell<[bool; 3]>>, mut next: Option<u32>, max_ticks: usize)
+    -> Vec<(u32, u8)> {
+    let mut levels = Vec::new();
+    while let Some(delay) = next {
+        if levels.len() == max_ticks { break; }
+        next = backlight.tick(delay).unwrap();
+        levels.push((delay, pin_level(states)));
+    }
+    levels
+}
+
+#[test]
+fn display_sleeps_after_timeout() {
+    let (mut backlight, states) = mock_backlight();
+    let (mut panel, _, log) = mock_panel();
+    backlight.set_level(MAX_LEVEL).unwrap();
+    let mut power = Power::new(30_000);
+    assert_eq!(power.next_tick(), Some(30_000));
+
+    //  Stays awake until the timeout
+    assert_eq!(power.tick(20_000, &mut backlight, &mut panel).unwrap(), Some(10_000));
+    assert!(!power.is_asleep());
+    assert!(log.borrow().is_empty());
+
+    //  Switches off the backlight, then the display
+    assert_eq!(power.tick(10_000, &mut backlight, &mut panel).unwrap(), None);
+    assert!(power.is_asleep());
+    assert_eq!(*log.borrow(), vec![ Sent::Command(DISPOFF), Sent::Command(SLPIN) ]);
+    assert_eq!(pin_level(&states), 0);
+    assert_eq!(power.next_tick(), None);
+
+    //  Sleeping again sends nothing
+    power.sleep(&mut backlight, &mut panel).unwrap();
+    assert_eq!(log.borrow().len(), 2);
+}
+
+#[test]
+fn touch_that_wakes_display_is_swallowed() {
+    let (mut backlight, states) = mock_backlight();
+    let (mut panel, mut delay, log) = mock_panel();
+    backlight.set_level(MAX_LEVEL).unwrap();
+    let mut power = Power::new(30_000);
+    power.tick(30_000, &mut backlight, &mut panel).unwrap();
+    log.borrow_mut().clear();
+
+    //  The first touch wakes the display and is not passed to the app
+    assert!(power.wake(&mut backlight, &mut panel, &mut delay).unwrap());
+    assert!(!power.is_asleep());
+    assert_eq!(*log.borrow(), vec![ Sent::Command(SLPOUT), Sent::Delay(SLPOUT_DELAY_MS), Sent::Command(DISPON) ]);
+    assert_eq!(pin_level(&states), MAX_LEVEL);
+    assert_eq!(power.next_tick(), Some(30_000));
+
+    //  The next touch is passed to the app and restarts the timeout
+    log.borrow_mut().clear();
+    assert_eq!(power.tick(20_000, &mut backlight, &mut panel).unwrap(), Some(10_000));
+    assert!(!power.wake(&mut backlight, &mut panel, &mut delay).unwrap());
+    assert!(log.borrow().is_empty());
+    assert_eq!(power.next_tick(), Some(30_000));
+}
+
+#[test]
+fn wake_restores_brightness_before_dimming() {
+    let (mut backlight, states) = mock_backlight();
+    let (mut panel, mut delay, _) = mock_panel();
+    backlight.set_level(5).unwrap();
+    let next = backlight.set_auto_dim(Some(10_000), 1);
+    let mut power = Power::new(30_000);
+
+    //  Dims to level 1, then sleeps
+    run(&mut backlight, &states, next, 100);
+    assert!(backlight.is_dimmed());
+    assert_eq!(pin_level(&states), 1);
+    power.tick(30_000, &mut backlight, &mut panel).unwrap();
+    assert_eq!(pin_level(&states), 0);
+
+    //  Wakes at the brightness set by the app, not the dimmed level
+    power.wake(&mut backlight, &mut panel, &mut delay).unwrap();
+    assert_eq!(pin_level(&states), 5);
+    assert_eq!(backlight.brightness(), 5);
+    assert!(!backlight.is_dimmed());
+    assert_eq!(backlight.next_tick(), Some(10_000));
+}
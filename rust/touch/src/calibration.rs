@@ -0,0 +1,220 @@
+//! Touch calibration: an affine transform from the coordinates reported by the touch controller to the pixels of
+//! the display panel, before any rotation of the display. The transform is fitted to the touches on 3 or 5 targets
+//! shown by the calibration screen, by least squares, and is stored as 32 bytes in flash. `Session` collects the
+//! touches on each target from the touch events, so the calibration screen only needs to draw the targets.
+use crate::{ event::TouchEvent, Error, TouchResult };
+
+/// Largest X or Y coordinate of the display panel, 240x240 pixels
+pub const MAX_COORD: u16 = 239;
+
+/// Targets for 3-point calibration, on the display panel
+pub const TARGETS_3: [(u16, u16); 3] = [(30, 30), (210, 120), (60, 210)];
+
+/// Targets for 5-point calibration: the 4 corners and the centre of the display panel
+pub const TARGETS_5: [(u16, u16); 5] = [(30, 30), (210, 30), (210, 210), (30, 210), (120, 120)];
+
+/// Maximum number of targets
+pub const MAX_TARGETS: usize = 5;
+
+/// Bytes in the stored calibration
+pub const CALIBRATION_LEN: usize = 32;
+
+/// Magic number at the start of the stored calibration
+const MAGIC: [u8; 4] = *b"TCAL";
+
+/// Maximum distance in pixels between a target and its touch after calibration, along the X or Y axis. If a touch
+/// is further away, it was not on the target and the calibration fails.
+const MAX_ERROR: u16 = 12;
+
+/// Bits after the binary point of the coefficients
+const FRACTION_BITS: u32 = 16;
+
+/// Affine transform from touch coordinates (x, y) to panel coordinates:
+/// `panel_x = a * x + b * y + c`, `panel_y = d * x + e * y + f`, with the coefficients in 16.16 fixed point
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+    pub a: i32,
+    pub b: i32,
+    pub c: i32,
+    pub d: i32,
+    pub e: i32,
+    pub f: i32,
+}
+
+impl Calibration {
+    /// Calibration that passes the touch coordinates through unchanged
+    pub const IDENTITY: Calibration = Calibration {
+        a: 1 << FRACTION_BITS, b: 0, c: 0,
+        d: 0, e: 1 << FRACTION_BITS, f: 0,
+    };
+
+    /// Fit the calibration to the touches on the targets, by least squares. Fails if there are fewer than 3 targets,
+    /// if the touches are in a line, or if a touch is too far from its target after calibration.
+    pub fn fit(targets: &[(u16, u16)], touches: &[(u16, u16)]) -> TouchResult<Calibration> {
+        if targets.len() < 3 || targets.len() != touches.len() { return Err(Error::BadCalibration); }
+
+        //  Normal equations: the sums of the products of the touch coordinates
+        let (mut xx, mut xy, mut yy, mut x, mut y) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        let (mut xt, mut yt, mut t) = ([0.0; 2], [0.0; 2], [0.0; 2]);
+        for (&(target_x, target_y), &(touch_x, touch_y)) in targets.iter().zip(touches.iter()) {
+            let (tx, ty) = (touch_x as f64, touch_y as f64);
+            xx += tx * tx; xy += tx * ty; yy += ty * ty; x += tx; y += ty;
+            for (i, target) in [target_x as f64, target_y as f64].iter().enumerate() {
+                xt[i] += tx * target; yt[i] += ty * target; t[i] += target;
+            }
+        }
+        let n = targets.len() as f64;
+        let matrix = [[xx, xy, x], [xy, yy, y], [x, y, n]];
+        let det = determinant(&matrix);
+        if det.abs() < 1.0 { return Err(Error::BadCalibration); }  //  Touches are in a line
+
+        //  Solve for each panel coordinate with Cramer's rule
+        let mut coefficients = [[0; 3]; 2];
+        for i in 0..2 {
+            let rhs = [xt[i], yt[i], t[i]];
+            for (column, coefficient) in coefficients[i].iter_mut().enumerate() {
+                let mut replaced = matrix;
+                for row in 0..3 { replaced[row][column] = rhs[row]; }
+                *coefficient = to_fixed(determinant(&replaced) / det) ? ;
+            }
+        }
+        let [[a, b, c], [d, e, f]] = coefficients;
+        let calibration = Calibration { a, b, c, d, e, f };
+
+        //  Every touch must land near its target
+        for (&(target_x, target_y), &(touch_x, touch_y)) in targets.iter().zip(touches.iter()) {
+            let (x, y) = calibration.apply(touch_x, touch_y);
+            let error = distance(x, target_x).max(distance(y, target_y));
+            if error > MAX_ERROR { return Err(Error::BadCalibration); }
+        }
+        Ok(calibration)
+    }
+
+    /// Map the touch coordinates to the display panel, clamped to the panel
+    pub fn apply(&self, x: u16, y: u16) -> (u16, u16) {
+        let map = |p: i32, q: i32, r: i32| {
+            let value = (p as i64 * x as i64 + q as i64 * y as i64 + r as i64 + (1 << (FRACTION_BITS - 1)))
+                >> FRACTION_BITS;
+            value.max(0).min(MAX_COORD as i64) as u16
+        };
+        (map(self.a, self.b, self.c), map(self.d, self.e, self.f))
+    }
+
+    /// Return the calibration to be stored: magic number, the 6 coefficients and the checksum, little endian
+    pub fn to_bytes(&self) -> [u8; CALIBRATION_LEN] {
+        let mut bytes = [0; CALIBRATION_LEN];
+        bytes[..4].copy_from_slice(&MAGIC);
+        let coefficients = [self.a, self.b, self.c, self.d, self.e, self.f];
+        for (i, coefficient) in coefficients.iter().enumerate() {
+            bytes[4 + i * 4..8 + i * 4].copy_from_slice(&coefficient.to_le_bytes());
+        }
+        let checksum = checksum(&bytes[..28]);
+        bytes[28..].copy_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    /// Load the stored calibration. Fails if there is no calibration, e.g. erased flash, or it's corrupted.
+    pub fn from_bytes(bytes: &[u8]) -> TouchResult<Calibration> {
+        if bytes.len() < CALIBRATION_LEN || bytes[..4] != MAGIC { return Err(Error::NoCalibration); }
+        let word = |i: usize| {
+            let mut word = [0; 4];
+            word.copy_from_slice(&bytes[i * 4..i * 4 + 4]);
+            word
+        };
+        if u32::from_le_bytes(word(7)) != checksum(&bytes[..28]) { return Err(Error::NoCalibration); }
+        let coefficient = |i: usize| i32::from_le_bytes(word(1 + i));
+        Ok(Calibration {
+            a: coefficient(0), b: coefficient(1), c: coefficient(2),
+            d: coefficient(3), e: coefficient(4), f: coefficient(5),
+        })
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Calibration {
+        Calibration::IDENTITY
+    }
+}
+
+/// Calibration session: collects the touch on each target in turn. The touch events must have the coordinates
+/// reported by the touch controller, without calibration or rotation.
+pub struct Session {
+    /// Targets to be touched
+    targets: &'static [(u16, u16)],
+    /// Average touch on each target touched so far
+    touches: [(u16, u16); MAX_TARGETS],
+    /// Number of targets touched so far
+    count:   usize,
+    /// Sums of the X and Y coordinates, and the number of touch points, for the current target
+    sum:     (u32, u32, u32),
+}
+
+impl Session {
+    /// Start the calibration with the targets, `TARGETS_3` or `TARGETS_5`
+    pub fn new(targets: &'static [(u16, u16)]) -> Session {
+        assert!(targets.len() <= MAX_TARGETS, "too many targets");
+        Session { targets, touches: [(0, 0); MAX_TARGETS], count: 0, sum: (0, 0, 0) }
+    }
+
+    /// Start again from the first target, e.g. after the calibration failed
+    pub fn restart(&mut self) {
+        self.count = 0;
+        self.sum = (0, 0, 0);
+    }
+
+    /// Return the target to be touched now, or `None` if all targets have been touched
+    pub fn target(&self) -> Option<(u16, u16)> {
+        self.targets.get(self.count).copied()
+    }
+
+    /// Collect the touch event for the current target. Returns true when the finger has left the target, so the
+    /// next target should be shown.
+    pub fn event(&mut self, event: &TouchEvent) -> bool {
+        if self.target().is_none() { return false; }
+        match event {
+            TouchEvent::Down(point) | TouchEvent::Move(point) => {
+                self.sum = (self.sum.0 + point.x as u32, self.sum.1 + point.y as u32, self.sum.2 + 1);
+                false
+            }
+            TouchEvent::Up(_) => {
+                let (sum_x, sum_y, n) = self.sum;
+                if n == 0 { return false; }
+                self.touches[self.count] = ((sum_x / n) as u16, (sum_y / n) as u16);
+                self.count += 1;
+                self.sum = (0, 0, 0);
+                true
+            }
+        }
+    }
+
+    /// Return the calibration fitted to the touches, after all targets have been touched
+    pub fn finish(&self) -> TouchResult<Calibration> {
+        if self.target().is_some() { return Err(Error::BadCalibration); }
+        Calibration::fit(self.targets, &self.touches[..self.count])
+    }
+}
+
+/// Return the determinant of the 3x3 matrix
+fn determinant(m: &[[f64; 3]; 3]) -> f64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+/// Convert the coefficient to 16.16 fixed point, rounded. Fails if it's out of range.
+fn to_fixed(value: f64) -> TouchResult<i32> {
+    let scaled = value * (1u32 << FRACTION_BITS) as f64;
+    let rounded = if scaled >= 0.0 { scaled + 0.5 } else { scaled - 0.5 };
+    if !(i32::MIN as f64..=i32::MAX as f64).contains(&rounded) { return Err(Error::BadCalibration); }
+    Ok(rounded as i32)
+}
+
+/// Return the checksum of the stored calibration
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x5a5a_5a5a_u32, |sum, byte| sum.rotate_left(5) ^ *byte as u32)
+}
+
+/// Return the distance between the coordinates
+fn distance(a: u16, b: u16) -> u16 {
+    (a as i32 - b as i32).unsigned_abs() as u16
+}
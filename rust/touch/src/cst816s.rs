@@ -0,0 +1,265 @@
+//! Driver for the CST816S touch controller by Hynitron in PineTime, at I2C address 0x15. Generic over the
+//! `embedded-hal` I2C traits: `mynewt::I2C` on PineTime, or a mock I2C bus for testing on the host computer.
+//! Reads the touch report (the gesture and up to 5 touch points), the chip ID and firmware version, and configures
+//! the interrupt, the gestures detected by the controller and auto-sleep. The controller only responds on I2C while
+//! the screen is touched, or shortly after, because it sleeps otherwise. Register layout based on
+//! https://github.com/lupyuen/hynitron_i2c_cst0xxse/blob/master/cst0xx_core.h
+use embedded_hal::blocking::i2c::{ Write, WriteRead };
+use crate::Action;
+
+/// I2C address of the touch controller
+pub const ADDRESS: u8 = 0x15;
+
+/// Max touch channels of the touch controller, for the size of the report
+pub const MAX_POINTS: usize = 10;
+
+/// Max touch points read from the report
+pub const MAX_REPORT_POINTS: usize = 5;
+
+/// Bytes per touch point in the report
+const POINT_STEP: usize = 6;
+
+/// Bytes in the touch report, starting from register 0
+pub const REPORT_LEN: usize = 3 + POINT_STEP * MAX_POINTS;
+
+/// Touch point ID that marks the end of the touch points
+const NO_POINT_ID: u8 = 0x0F;
+
+//  Offsets in the report. The touch point offsets are for the first point, followed by the other points.
+const GESTURE_POS: usize   = 1;  //  Gesture detected by the controller
+const POINT_NUM_POS: usize = 2;  //  Number of touch points (low 4 bits)
+const EVENT_POS: usize     = 3;  //  Touch action (high 2 bits)
+const X_H_POS: usize       = 3;  //  X coordinate (low 4 bits)
+const X_L_POS: usize       = 4;
+const ID_POS: usize        = 5;  //  Touch point ID (high 4 bits)
+const Y_H_POS: usize       = 5;  //  Y coordinate (low 4 bits)
+const Y_L_POS: usize       = 6;
+const PRESSURE_POS: usize  = 7;  //  Pressure of touch
+const AREA_POS: usize      = 8;  //  Area touched (high 4 bits)
+
+//  Registers for identification and configuration
+const REG_SLEEP_MODE: u8      = 0xE5;  //  Write `SLEEP_DEEP` to enter deep sleep
+const REG_CHIP_ID: u8         = 0xA7;
+const REG_PROJECT_ID: u8      = 0xA8;
+const REG_FW_VERSION: u8      = 0xA9;
+const REG_MOTION_MASK: u8     = 0xEC;  //  Gestures detected by the controller: `MOTION_*`
+const REG_AUTO_SLEEP_TIME: u8 = 0xF9;  //  Seconds without touches before auto-sleep
+const REG_IRQ_CTL: u8         = 0xFA;  //  When to pulse the interrupt: `IRQ_*`
+const REG_DIS_AUTO_SLEEP: u8  = 0xFE;  //  Non-zero to disable auto-sleep
+
+/// Deep sleep mode for `REG_SLEEP_MODE`. Only a reset wakes the controller.
+const SLEEP_DEEP: u8 = 0x03;
+
+/// Interrupt on periodic test pulses
+pub const IRQ_TEST: u8 = 0x80;
+/// Interrupt periodically while touched
+pub const IRQ_TOUCH: u8 = 0x40;
+/// Interrupt when the touch changes
+pub const IRQ_CHANGE: u8 = 0x20;
+/// Interrupt when a gesture is detected
+pub const IRQ_MOTION: u8 = 0x10;
+/// Interrupt once on long press
+pub const IRQ_ONCE_LONG_PRESS: u8 = 0x01;
+
+/// Detect double clicks
+pub const MOTION_DOUBLE_CLICK: u8 = 0x01;
+/// Repeat slide up and down gestures while the finger moves
+pub const MOTION_CONTINUOUS_UP_DOWN: u8 = 0x02;
+/// Repeat slide left and right gestures while the finger moves
+pub const MOTION_CONTINUOUS_LEFT_RIGHT: u8 = 0x04;
+
+/// Gesture detected by the touch controller
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControllerGesture {
+    SlideUp,
+    SlideDown,
+    SlideLeft,
+    SlideRight,
+    SingleClick,
+    DoubleClick,
+    LongPress,
+}
+
+impl ControllerGesture {
+    /// Return the gesture for the gesture register, or `None` for no gesture
+    pub fn from_raw(raw: u8) -> Option<ControllerGesture> {
+        match raw {
+            0x01 => Some(ControllerGesture::SlideUp),
+            0x02 => Some(ControllerGesture::SlideDown),
+            0x03 => Some(ControllerGesture::SlideLeft),
+            0x04 => Some(ControllerGesture::SlideRight),
+            0x05 => Some(ControllerGesture::SingleClick),
+            0x0B => Some(ControllerGesture::DoubleClick),
+            0x0C => Some(ControllerGesture::LongPress),
+            _    => None,
+        }
+    }
+}
+
+/// Touch point in the report
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReportPoint {
+    /// X coordinate
+    pub x:        u16,
+    /// Y coordinate
+    pub y:        u16,
+    /// Touch action: 0 = down, 1 = up, 2 = contact, see `Action::from_raw()`
+    pub action:   u8,
+    /// Touch point ID of the finger
+    pub id:       u8,
+    /// Pressure of touch
+    pub pressure: u8,
+    /// Area touched
+    pub area:     u8,
+}
+
+impl ReportPoint {
+    /// Return the touch action, or `None` if reserved
+    pub fn action(&self) -> Option<Action> {
+        Action::from_raw(self.action)
+    }
+}
+
+/// Touch report read from the controller
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Report {
+    /// Raw value of the gesture register, see `gesture()`
+    pub gesture:   u8,
+    /// Number of touch points reported by the controller
+    pub point_num: u8,
+    /// Touch points parsed
+    pub count:     u8,
+    /// Touch points, `count` of them valid
+    pub points:    [ReportPoint; MAX_REPORT_POINTS],
+}
+
+impl Report {
+    /// Parse the report read from register 0. Ported from
+    /// https://github.com/lupyuen/hynitron_i2c_cst0xxse/blob/master/cst0xx_core.c#L407-L466
+    pub fn parse(buf: &[u8; REPORT_LEN]) -> Report {
+        let mut report = Report {
+            gesture:   buf[GESTURE_POS],
+            point_num: buf[POINT_NUM_POS] & 0x0F,
+            ..Report::default()
+        };
+        for i in 0..MAX_REPORT_POINTS {
+            let offset = POINT_STEP * i;
+            let id = buf[ID_POS + offset] >> 4;
+            if id >= NO_POINT_ID { break; }
+            let point = ReportPoint {
+                x:        ((buf[X_H_POS + offset] & 0x0F) as u16) << 8 | buf[X_L_POS + offset] as u16,
+                y:        ((buf[Y_H_POS + offset] & 0x0F) as u16) << 8 | buf[Y_L_POS + offset] as u16,
+                action:   buf[EVENT_POS + offset] >> 6,
+                id,
+                pressure: buf[PRESSURE_POS + offset],
+                area:     buf[AREA_POS + offset] >> 4,
+            };
+            report.points[i] = point;
+            report.count += 1;
+            //  If no more touch points, stop
+            if (point.action == 0 || point.action == 2) && report.point_num == 0 { break; }
+        }
+        report
+    }
+
+    /// Return the valid touch points
+    pub fn points(&self) -> &[ReportPoint] {
+        &self.points[..self.count as usize]
+    }
+
+    /// Return the gesture detected by the controller, if any
+    pub fn gesture(&self) -> Option<ControllerGesture> {
+        ControllerGesture::from_raw(self.gesture)
+    }
+}
+
+/// CST816S touch controller on an I2C bus
+pub struct Cst816s<I2C> {
+    /// I2C bus of the controller
+    i2c:     I2C,
+    /// I2C address of the controller
+    address: u8,
+}
+
+impl<I2C> Cst816s<I2C> {
+    /// Return the driver for the controller at the default address on the I2C bus
+    pub const fn new(i2c: I2C) -> Cst816s<I2C> {
+        Cst816s { i2c, address: ADDRESS }
+    }
+
+    /// Return the I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+}
+
+impl<I2C, E> Cst816s<I2C>
+where
+    I2C: WriteRead<Error = E> + Write<Error = E>,
+{
+    /// Read the touch report into `buf`, e.g. for recording, and parse it
+    pub fn read_report(&mut self, buf: &mut [u8; REPORT_LEN]) -> Result<Report, E> {
+        self.i2c.write_read(self.address, &[0], buf) ? ;
+        Ok(Report::parse(buf))
+    }
+
+    /// Read the gesture detected by the controller, if any
+    pub fn gesture(&mut self) -> Result<Option<ControllerGesture>, E> {
+        let raw = self.read_register(GESTURE_POS as u8) ? ;
+        Ok(ControllerGesture::from_raw(raw))
+    }
+
+    /// Read the chip ID: 0xB4 for CST816S
+    pub fn chip_id(&mut self) -> Result<u8, E> {
+        self.read_register(REG_CHIP_ID)
+    }
+
+    /// Read the project ID
+    pub fn project_id(&mut self) -> Result<u8, E> {
+        self.read_register(REG_PROJECT_ID)
+    }
+
+    /// Read the firmware version
+    pub fn firmware_version(&mut self) -> Result<u8, E> {
+        self.read_register(REG_FW_VERSION)
+    }
+
+    /// Set when the controller pulses the interrupt pin, as `IRQ_*` flags
+    pub fn set_interrupt_mode(&mut self, flags: u8) -> Result<(), E> {
+        self.write_register(REG_IRQ_CTL, flags)
+    }
+
+    /// Set the gestures detected by the controller, as `MOTION_*` flags. Single clicks, long presses and slides are
+    /// always detected.
+    pub fn set_motion_mask(&mut self, flags: u8) -> Result<(), E> {
+        self.write_register(REG_MOTION_MASK, flags)
+    }
+
+    /// Sleep after the seconds without touches, or never sleep if `None`
+    pub fn set_auto_sleep(&mut self, seconds: Option<u8>) -> Result<(), E> {
+        match seconds {
+            Some(seconds) => {
+                self.write_register(REG_AUTO_SLEEP_TIME, seconds) ? ;
+                self.write_register(REG_DIS_AUTO_SLEEP, 0)
+            }
+            None => self.write_register(REG_DIS_AUTO_SLEEP, 1),
+        }
+    }
+
+    /// Enter deep sleep. Only a reset on the reset pin wakes the controller.
+    pub fn sleep(&mut self) -> Result<(), E> {
+        self.write_register(REG_SLEEP_MODE, SLEEP_DEEP)
+    }
+
+    /// Read the register
+    fn read_register(&mut self, register: u8) -> Result<u8, E> {
+        let mut value = [0];
+        self.i2c.write_read(self.address, &[register], &mut value) ? ;
+        Ok(value[0])
+    }
+
+    /// Write the value to the register
+    fn write_register(&mut self, register: u8, value: u8) -> Result<(), E> {
+        self.i2c.write(self.address, &[register, value])
+    }
+}
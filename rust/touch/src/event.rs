@@ -0,0 +1,167 @@
+//! Typed touch events for each finger: Down, Move and Up with the point ID, position, pressure and time. `Tracker`
+//! turns the touch reports from the touch controller into events, and `Subscribers` passes each event to all
+//! the components that have subscribed, e.g. the app and the gesture recogniser.
+use crate::{ cst816s::Report, Action, Error, TouchResult };
+
+/// Number of point IDs that the touch controller may report: 0 to 15
+pub const MAX_POINT_IDS: usize = 16;
+
+/// Maximum number of components that may subscribe to touch events
+pub const MAX_SUBSCRIBERS: usize = 8;
+
+/// Touch point of a finger
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TouchPoint {
+    /// Point ID reported by the touch controller, the same from Down to Up for each finger
+    pub id:       u8,
+    /// X coordinate
+    pub x:        u16,
+    /// Y coordinate
+    pub y:        u16,
+    /// Pressure reported by the touch controller
+    pub pressure: u8,
+    /// Milliseconds since startup, wrapping around at 2^32
+    pub time:     u32,
+}
+
+/// Touch event for a finger
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TouchEvent {
+    /// Finger touched the screen
+    Down(TouchPoint),
+    /// Finger is still on the screen, maybe at another position
+    Move(TouchPoint),
+    /// Finger left the screen. The position is the last one reported.
+    Up(TouchPoint),
+}
+
+impl TouchEvent {
+    /// Return the touch point of the event
+    pub fn point(&self) -> &TouchPoint {
+        match self {
+            TouchEvent::Down(point) | TouchEvent::Move(point) | TouchEvent::Up(point) => point,
+        }
+    }
+}
+
+/// Tracks which fingers are on the screen, to turn the touch points reported into Down, Move and Up events. The
+/// controller may report Down repeatedly for the same finger, or Contact without Down.
+pub struct Tracker {
+    /// Last touch point of each finger on the screen, by point ID
+    points: [Option<TouchPoint>; MAX_POINT_IDS],
+}
+
+impl Tracker {
+    /// Return a tracker with no fingers on the screen
+    pub const fn new() -> Tracker {
+        Tracker { points: [None; MAX_POINT_IDS] }
+    }
+
+    /// Return the event for the touch point reported, or `None` if the finger is not on the screen
+    pub fn update(&mut self, action: Action, point: TouchPoint) -> Option<TouchEvent> {
+        let slot = self.points.get_mut(point.id as usize) ? ;
+        let down = slot.is_some();
+        match action {
+            Action::Up => {
+                //  Up is reported at the position in the report, ignored if the finger is not on the screen
+                slot.take() ? ;
+                Some(TouchEvent::Up(point))
+            }
+            Action::Down | Action::Contact => {
+                *slot = Some(point);
+                if down { Some(TouchEvent::Move(point)) } else { Some(TouchEvent::Down(point)) }
+            }
+        }
+    }
+
+    /// Turn the touch report into events: the touch points in the report, then Up for the fingers that are missing
+    /// from it. `map` maps the coordinates of each point, e.g. with the calibration and the display rotation. Points
+    /// at (0, 0) are skipped, because the controller may pad the report with them: "count: 5, pt: 1".
+    pub fn report<M, F>(&mut self, report: &Report, time: u32, map: M, emit: &mut F)
+    where
+        M: Fn(u16, u16) -> (u16, u16),
+        F: FnMut(TouchEvent),
+    {
+        let mut reported: u16 = 0;
+        for point in report.points() {
+            if point.x == 0 && point.y == 0 { continue; }
+            let action = match point.action() { Some(action) => action, None => continue };
+            reported |= 1 << point.id;
+            let (x, y) = map(point.x, point.y);
+            let point = TouchPoint { id: point.id, x, y, pressure: point.pressure, time };
+            if let Some(event) = self.update(action, point) { emit(event); }
+        }
+        self.release_missing(reported, time, emit);
+    }
+
+    /// Release the fingers that are on the screen but were not in the last report, given as a bit mask of the point
+    /// IDs reported. Passes an Up event at the last position to `emit`.
+    pub fn release_missing<F: FnMut(TouchEvent)>(&mut self, reported: u16, time: u32, emit: &mut F) {
+        for (id, slot) in self.points.iter_mut().enumerate() {
+            if reported & (1 << id) != 0 { continue; }
+            if let Some(point) = slot.take() {
+                emit(TouchEvent::Up(TouchPoint { time, ..point }));
+            }
+        }
+    }
+
+    /// Return true if any finger is on the screen
+    pub fn is_down(&self) -> bool {
+        self.points.iter().any(Option::is_some)
+    }
+}
+
+impl Default for Tracker {
+    fn default() -> Tracker {
+        Tracker::new()
+    }
+}
+
+/// Function that is called for each touch event
+pub type Subscriber = fn(&TouchEvent);
+
+/// Handle for unsubscribing from touch events
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SubscriberId(usize);
+
+/// Components that have subscribed to touch events
+pub struct Subscribers {
+    /// Subscribed functions, `None` for free slots
+    subscribers: [Option<Subscriber>; MAX_SUBSCRIBERS],
+}
+
+impl Subscribers {
+    /// Return an empty list of subscribers
+    pub const fn new() -> Subscribers {
+        Subscribers { subscribers: [None; MAX_SUBSCRIBERS] }
+    }
+
+    /// Call the function for every touch event from now on. Returns the handle for `unsubscribe()`.
+    pub fn subscribe(&mut self, subscriber: Subscriber) -> TouchResult<SubscriberId> {
+        let index = self.subscribers.iter().position(Option::is_none)
+            .ok_or(Error::TooManySubscribers) ? ;
+        self.subscribers[index] = Some(subscriber);
+        Ok(SubscriberId(index))
+    }
+
+    /// Stop calling the function subscribed with the handle
+    pub fn unsubscribe(&mut self, id: SubscriberId) {
+        if let Some(slot) = self.subscribers.get_mut(id.0) { *slot = None; }
+    }
+
+    /// Pass the event to every subscriber. Returns the number of subscribers called.
+    pub fn publish(&self, event: &TouchEvent) -> usize {
+        let mut count = 0;
+        for subscriber in self.subscribers.iter().flatten() {
+            subscriber(event);
+            count += 1;
+        }
+        count
+    }
+}
+
+impl Default for Subscribers {
+    fn default() -> Subscribers {
+        Subscribers::new()
+    }
+}
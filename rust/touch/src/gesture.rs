@@ -0,0 +1,293 @@
+//! Gesture recogniser for one finger: a state machine that turns the touches reported by the touch controller, or the
+//! touch events of the first finger, into taps, double taps, long presses and swipes in four directions with
+//! velocity. Times are in milliseconds from any clock that wraps around at 2^32, so the recogniser may be driven by
+//! recorded touches on the host computer. Call `touch()` for each touch point reported (or `event()` for each touch
+//! event), and `tick()` at the time returned by `deadline()`: a long press is recognised while the finger is still
+//! down, and a tap only after the time for a double tap has passed. The touch controller doesn't always report the
+//! finger leaving the screen, so a finger that has not been reported for `Config::release_ms` is treated as
+//! released at the last position reported.
+use crate::{
+    event::TouchEvent,
+    Action,
+};
+
+/// Thresholds for recognising gestures. Distances are in pixels along the X or Y axis, whichever is larger.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    /// Maximum distance that the finger may move during a tap or long press
+    pub tap_distance:        u16,
+    /// Maximum distance between the two taps of a double tap
+    pub double_tap_distance: u16,
+    /// Maximum milliseconds from the end of the first tap to the start of the second tap. 0 to recognise taps
+    /// immediately without double taps.
+    pub double_tap_ms:       u32,
+    /// Milliseconds that the finger must stay down for a long press
+    pub long_press_ms:       u32,
+    /// Minimum distance that the finger must move for a swipe
+    pub swipe_distance:      u16,
+    /// Minimum velocity of a swipe in pixels per second. Slower moves are not recognised as gestures.
+    pub swipe_velocity:      u32,
+    /// Milliseconds without reports after which the finger is treated as released
+    pub release_ms:          u32,
+}
+
+impl Config {
+    /// Thresholds for the 240x240 PineTime display
+    pub const DEFAULT: Config = Config {
+        tap_distance:        12,
+        double_tap_distance: 30,
+        double_tap_ms:       300,
+        long_press_ms:       600,
+        swipe_distance:      40,
+        swipe_velocity:      150,
+        release_ms:          250,
+    };
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::DEFAULT
+    }
+}
+
+/// Direction of a swipe on the screen. Up is towards row 0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Gesture recognised from the touches. `x` and `y` are where the finger first touched the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gesture {
+    /// Finger touched and left the screen without moving, and didn't tap again
+    Tap { x: u16, y: u16 },
+    /// Finger tapped twice at the same place. `x` and `y` are the position of the first tap.
+    DoubleTap { x: u16, y: u16 },
+    /// Finger stayed down without moving for `Config::long_press_ms`. Reported before the finger leaves the screen.
+    LongPress { x: u16, y: u16 },
+    /// Finger moved quickly and left the screen. `distance` is in pixels along the direction, `velocity` in pixels
+    /// per second.
+    Swipe { direction: Direction, x: u16, y: u16, distance: u16, velocity: u32 },
+}
+
+/// Recogniser that tracks a finger from touch down to release
+pub struct Recognizer {
+    /// Thresholds for the gestures
+    config: Config,
+    /// What the finger has done so far
+    state:  State,
+    /// Point ID of the finger tracked by `event()`
+    finger: u8,
+}
+
+/// State of the recogniser
+#[derive(Clone, Copy, Debug)]
+enum State {
+    /// Finger is not on the screen
+    Idle,
+    /// Finger is on the screen, may become a tap, double tap, long press or swipe
+    Pressed(Press),
+    /// Long press has been reported, waiting for the finger to leave the screen
+    Held { time: u32 },
+    /// Tap has ended at `time`, waiting for a second tap
+    TapPending { x: u16, y: u16, time: u32 },
+}
+
+/// Finger on the screen
+#[derive(Clone, Copy, Debug)]
+struct Press {
+    /// Where and when the finger touched the screen
+    start_x:    u16,
+    start_y:    u16,
+    start_time: u32,
+    /// Where and when the finger was last reported
+    x:          u16,
+    y:          u16,
+    time:       u32,
+    /// True if the finger has moved further than a tap
+    moved:      bool,
+    /// Position of the first tap, if this may be the second tap of a double tap
+    first_tap:  Option<(u16, u16)>,
+}
+
+impl Recognizer {
+    /// Return a recogniser with the thresholds and no finger on the screen
+    pub const fn new(config: Config) -> Recognizer {
+        Recognizer { config, state: State::Idle, finger: 0 }
+    }
+
+    /// Forget the finger and any pending tap, e.g. when the app changes
+    pub fn reset(&mut self) {
+        self.state = State::Idle;
+    }
+
+    /// Return true if the finger is on the screen
+    pub fn is_pressed(&self) -> bool {
+        matches!(self.state, State::Pressed(_) | State::Held { .. })
+    }
+
+    /// Track the touch point reported at `time`, and pass any gestures recognised to `emit`
+    pub fn touch<F: FnMut(Gesture)>(&mut self, action: Action, x: u16, y: u16, time: u32, emit: &mut F) {
+        //  Complete the gestures that timed out before this report
+        self.tick(time, emit);
+        let config = self.config;
+        match (action, self.state) {
+            (Action::Up, State::Pressed(mut press)) => {
+                press.update(x, y, time, config.tap_distance);
+                self.release(press, emit);
+            }
+            (Action::Up, State::Held { .. }) => self.state = State::Idle,
+            (Action::Up, _) => {}  //  Finger is not on the screen
+            //  Down and Contact are handled alike, because the controller may report Down repeatedly
+            (_, State::Idle) => self.state = State::Pressed(Press::new(x, y, time, None)),
+            (_, State::TapPending { x: tap_x, y: tap_y, .. }) => {
+                if distance(tap_x, tap_y, x, y) <= config.double_tap_distance {
+                    self.state = State::Pressed(Press::new(x, y, time, Some((tap_x, tap_y))));
+                } else {
+                    emit(Gesture::Tap { x: tap_x, y: tap_y });
+                    self.state = State::Pressed(Press::new(x, y, time, None));
+                }
+            }
+            (_, State::Pressed(mut press)) => {
+                press.update(x, y, time, config.tap_distance);
+                if press.moved {
+                    //  Second touch is not a tap, so the first tap was a single tap
+                    if let Some((tap_x, tap_y)) = press.first_tap.take() {
+                        emit(Gesture::Tap { x: tap_x, y: tap_y });
+                    }
+                }
+                self.state = State::Pressed(press);
+            }
+            (_, State::Held { .. }) => self.state = State::Held { time },
+        }
+    }
+
+    /// Track the touch event if it's for the finger being tracked, or if no finger is on the screen. Events for the
+    /// other fingers are ignored. Passes any gestures recognised to `emit`.
+    pub fn event<F: FnMut(Gesture)>(&mut self, event: &TouchEvent, emit: &mut F) {
+        let point = event.point();
+        self.tick(point.time, emit);
+        if self.is_pressed() && point.id != self.finger { return; }
+        self.finger = point.id;
+        let action = match event {
+            TouchEvent::Down(_) => Action::Down,
+            TouchEvent::Move(_) => Action::Contact,
+            TouchEvent::Up(_)   => Action::Up,
+        };
+        self.touch(action, point.x, point.y, point.time, emit);
+    }
+
+    /// Complete the gestures that have timed out at `time`: long press, release without an Up report and tap
+    pub fn tick<F: FnMut(Gesture)>(&mut self, time: u32, emit: &mut F) {
+        let config = self.config;
+        if let State::Pressed(press) = self.state {
+            //  If the finger is no longer reported, it was last seen down at the last report
+            let released = elapsed(press.time, time) >= config.release_ms;
+            let down_until = if released { press.time } else { time };
+            if !press.moved && elapsed(press.start_time, down_until) >= config.long_press_ms {
+                if let Some((tap_x, tap_y)) = press.first_tap {
+                    emit(Gesture::Tap { x: tap_x, y: tap_y });
+                }
+                emit(Gesture::LongPress { x: press.start_x, y: press.start_y });
+                self.state = State::Held { time: press.time };
+            } else if released {
+                self.release(press, emit);
+            }
+        }
+        match self.state {
+            State::Held { time: last } if elapsed(last, time) >= config.release_ms => {
+                self.state = State::Idle;
+            }
+            State::TapPending { x, y, time: end } if elapsed(end, time) >= config.double_tap_ms => {
+                emit(Gesture::Tap { x, y });
+                self.state = State::Idle;
+            }
+            _ => {}
+        }
+    }
+
+    /// Return the time at which `tick()` should be called next, or `None` if no gesture is waiting for a timeout
+    pub fn deadline(&self) -> Option<u32> {
+        let config = self.config;
+        match self.state {
+            State::Idle => None,
+            State::Pressed(press) => {
+                let release = press.time.wrapping_add(config.release_ms);
+                if press.moved { return Some(release); }
+                let long_press = press.start_time.wrapping_add(config.long_press_ms);
+                //  Whichever comes first, comparing the times relative to the last report
+                if elapsed(press.time, long_press) < config.release_ms { Some(long_press) } else { Some(release) }
+            }
+            State::Held { time } => Some(time.wrapping_add(config.release_ms)),
+            State::TapPending { time, .. } => Some(time.wrapping_add(config.double_tap_ms)),
+        }
+    }
+
+    /// The finger has left the screen. Recognise a tap, double tap or swipe.
+    fn release<F: FnMut(Gesture)>(&mut self, press: Press, emit: &mut F) {
+        let config = self.config;
+        self.state = State::Idle;
+        if press.moved {
+            if let Some(swipe) = press.swipe(config) { emit(swipe); }
+        } else if let Some((tap_x, tap_y)) = press.first_tap {
+            emit(Gesture::DoubleTap { x: tap_x, y: tap_y });
+        } else if config.double_tap_ms == 0 {
+            emit(Gesture::Tap { x: press.start_x, y: press.start_y });
+        } else {
+            self.state = State::TapPending { x: press.start_x, y: press.start_y, time: press.time };
+        }
+    }
+}
+
+impl Default for Recognizer {
+    fn default() -> Recognizer {
+        Recognizer::new(Config::DEFAULT)
+    }
+}
+
+impl Press {
+    /// Return a finger that touched the screen at the position and time
+    fn new(x: u16, y: u16, time: u32, first_tap: Option<(u16, u16)>) -> Press {
+        Press { start_x: x, start_y: y, start_time: time, x, y, time, moved: false, first_tap }
+    }
+
+    /// Move the finger to the position reported at the time
+    fn update(&mut self, x: u16, y: u16, time: u32, tap_distance: u16) {
+        self.x = x;
+        self.y = y;
+        self.time = time;
+        if distance(self.start_x, self.start_y, x, y) > tap_distance { self.moved = true; }
+    }
+
+    /// Return the swipe from the start to the last position, if far and fast enough
+    fn swipe(&self, config: Config) -> Option<Gesture> {
+        let dx = self.x as i32 - self.start_x as i32;
+        let dy = self.y as i32 - self.start_y as i32;
+        let (direction, distance) =
+            if dx.abs() >= dy.abs() {
+                (if dx < 0 { Direction::Left } else { Direction::Right }, dx.unsigned_abs())
+            } else {
+                (if dy < 0 { Direction::Up } else { Direction::Down }, dy.unsigned_abs())
+            };
+        if distance < config.swipe_distance as u32 { return None; }
+        let duration = elapsed(self.start_time, self.time).max(1);
+        let velocity = distance * 1000 / duration;
+        if velocity < config.swipe_velocity { return None; }
+        Some(Gesture::Swipe { direction, x: self.start_x, y: self.start_y, distance: distance as u16, velocity })
+    }
+}
+
+/// Return the distance between the positions along the X or Y axis, whichever is larger
+fn distance(x1: u16, y1: u16, x2: u16, y2: u16) -> u16 {
+    let dx = (x1 as i32 - x2 as i32).unsigned_abs();
+    let dy = (y1 as i32 - y2 as i32).unsigned_abs();
+    dx.max(dy) as u16
+}
+
+/// Return the milliseconds from `start` to `end`, allowing for the clock wrapping around
+fn elapsed(start: u32, end: u32) -> u32 {
+    end.wrapping_sub(start)
+}
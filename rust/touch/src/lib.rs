@@ -0,0 +1,71 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *  http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing,
+ * software distributed under the License is distributed on an
+ * "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+ * KIND, either express or implied.  See the License for the
+ * specific language governing permissions and limitations
+ * under the License.
+ */
+//!  Touch input for PineTime. Touches are read from the CST816S touch controller over I2C (see `cst816s.rs`), turned
+//!  into typed events for each finger, passed to all subscribers (see `event.rs`), and into gestures: tap, double
+//!  tap, long press and swipes with velocity (see `gesture.rs`). Touches are calibrated to the display panel with an
+//!  affine transform fitted to touches on targets (see `calibration.rs`). Doesn't call Mynewt and doesn't depend on
+//!  the timer, so the driver may be tested with a mock I2C bus, and touch reports recorded on PineTime (see
+//!  `record.rs`) may be replayed on the host computer with `cargo test`.
+#![no_std]  //  Don't link with standard Rust library, which is not compatible with embedded systems
+
+pub mod calibration;  //  Export `calibration.rs` as Rust module `touch::calibration`
+pub mod cst816s;      //  Export `cst816s.rs` as Rust module `touch::cst816s`
+pub mod event;        //  Export `event.rs` as Rust module `touch::event`
+pub mod gesture;      //  Export `gesture.rs` as Rust module `touch::gesture`
+pub mod record;       //  Export `record.rs` as Rust module `touch::record`
+
+/// Action of a touch point, as reported by the touch controller
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    /// Finger touched the screen
+    Down,
+    /// Finger left the screen
+    Up,
+    /// Finger is still touching the screen, maybe at another position
+    Contact,
+}
+
+impl Action {
+    /// Return the action for the 2-bit event flag of the touch controller: 0 = down, 1 = up, 2 = contact.
+    /// Returns `None` for the reserved value 3.
+    pub fn from_raw(raw: u8) -> Option<Action> {
+        match raw {
+            0 => Some(Action::Down),
+            1 => Some(Action::Up),
+            2 => Some(Action::Contact),
+            _ => None,
+        }
+    }
+}
+
+/// Errors while handling touch input
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// No more subscribers may be added for touch events
+    TooManySubscribers,
+    /// Touch calibration failed: too few targets, touches in a line, or touches too far from their targets
+    BadCalibration,
+    /// No touch calibration stored, or the stored calibration is corrupted
+    NoCalibration,
+    /// Touch recording is truncated or corrupted
+    BadRecording,
+}
+
+/// Common return type for the Touch API
+pub type TouchResult<T> = ::core::result::Result<T, Error>;
@@ -0,0 +1,159 @@
+//! Touch recorder: the raw touch reports read from the touch controller, with their times, in a compact format for
+//! storing in flash and dumping to the console. The recording is replayed on the host computer with `Replay`, into
+//! the report parser, the touch events and the gesture recogniser, to reproduce touch bugs under `cargo test`.
+//!
+//! Each record is a length byte, the milliseconds since the previous record as a LEB128 varint, then the first
+//! `RECORDED_LEN` bytes of the report, without the trailing zeros. The length byte is `FAILED` if the controller
+//! didn't respond, and erased flash (`END`) ends the recording. A record for a single touch point is about 12 bytes.
+use crate::{
+    cst816s::{ MAX_REPORT_POINTS, REPORT_LEN },
+    Error, TouchResult,
+};
+
+/// Bytes of the report that are recorded: the header and the touch points read by the parser
+pub const RECORDED_LEN: usize = 3 + 6 * MAX_REPORT_POINTS;
+
+/// Maximum bytes in a record: length byte, time and report
+pub const MAX_RECORD_LEN: usize = 1 + MAX_VARINT_LEN + RECORDED_LEN;
+
+/// Length byte for a report that couldn't be read
+pub const FAILED: u8 = 0xFE;
+
+/// Length byte at the end of the recording, same as erased flash
+pub const END: u8 = 0xFF;
+
+/// Maximum bytes in a LEB128 varint for `u32`
+const MAX_VARINT_LEN: usize = 5;
+
+/// Touch report replayed from the recording
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Recorded {
+    /// Milliseconds since startup, like the `time` of `TouchPoint`
+    pub time:   u32,
+    /// Report read from register 0, or `None` if the controller didn't respond
+    pub report: Option<[u8; REPORT_LEN]>,
+}
+
+/// Encodes the touch reports into records
+pub struct Recorder {
+    /// Time of the last record
+    last_time: u32,
+}
+
+impl Recorder {
+    /// Return a recorder that starts at time 0
+    pub const fn new() -> Recorder {
+        Recorder { last_time: 0 }
+    }
+
+    /// Encode the report read at the time, or `None` if the controller didn't respond, into `buf`. Returns the
+    /// bytes of the record.
+    pub fn record<'a>(&mut self, time: u32, report: Option<&[u8; REPORT_LEN]>, buf: &'a mut [u8; MAX_RECORD_LEN])
+        -> &'a [u8] {
+        let report = report.map(|report| {
+            let len = report[..RECORDED_LEN].iter().rposition(|byte| *byte != 0).map_or(0, |i| i + 1);
+            &report[..len]
+        });
+        buf[0] = match report { Some(report) => report.len() as u8, None => FAILED };
+        let mut len = 1 + write_varint(time.wrapping_sub(self.last_time), &mut buf[1..]);
+        self.last_time = time;
+        if let Some(report) = report {
+            buf[len..len + report.len()].copy_from_slice(report);
+            len += report.len();
+        }
+        &buf[..len]
+    }
+}
+
+impl Default for Recorder {
+    fn default() -> Recorder {
+        Recorder::new()
+    }
+}
+
+/// Return the number of bytes in the record at the start of `bytes`, or `None` at the end of the recording. Fails
+/// if the record is truncated or corrupted.
+pub fn record_len(bytes: &[u8]) -> TouchResult<Option<usize>> {
+    let report_len = match bytes.first() {
+        None | Some(&END) => return Ok(None),
+        Some(&FAILED) => 0,
+        Some(&len) if len as usize <= RECORDED_LEN => len as usize,
+        Some(_) => return Err(Error::BadRecording),
+    };
+    let (_, varint_len) = read_varint(&bytes[1..]) ? ;
+    let len = 1 + varint_len + report_len;
+    if len > bytes.len() { return Err(Error::BadRecording); }
+    Ok(Some(len))
+}
+
+/// Replays the touch reports from a recording, in the order recorded
+pub struct Replay<'a> {
+    /// Records not replayed yet
+    bytes: &'a [u8],
+    /// Time of the last record
+    time:  u32,
+}
+
+impl<'a> Replay<'a> {
+    /// Replay the recording, e.g. a dump of the flash, until the end or the first corrupted record
+    pub fn new(bytes: &'a [u8]) -> Replay<'a> {
+        Replay { bytes, time: 0 }
+    }
+}
+
+impl<'a> Iterator for Replay<'a> {
+    type Item = TouchResult<Recorded>;
+
+    fn next(&mut self) -> Option<TouchResult<Recorded>> {
+        let len = match record_len(self.bytes) {
+            Ok(Some(len)) => len,
+            Ok(None) => return None,
+            Err(err) => { self.bytes = &[]; return Some(Err(err)); }
+        };
+        let (record, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        let (delta, varint_len) = read_varint(&record[1..]).ok() ? ;
+        self.time = self.time.wrapping_add(delta);
+        let report = if record[0] == FAILED { None } else {
+            //  Trailing zeros were not recorded
+            let mut report = [0; REPORT_LEN];
+            let bytes = &record[1 + varint_len..];
+            report[..bytes.len()].copy_from_slice(bytes);
+            Some(report)
+        };
+        Some(Ok(Recorded { time: self.time, report }))
+    }
+}
+
+/// Decode the hex digits in `text`, e.g. a record dumped to the console, into `buf`. Returns the number of bytes.
+pub fn decode_hex(text: &str, buf: &mut [u8]) -> TouchResult<usize> {
+    let text = text.trim().as_bytes();
+    if text.len() & 1 != 0 || text.len() / 2 > buf.len() { return Err(Error::BadRecording); }
+    let digit = |c: u8| (c as char).to_digit(16).ok_or(Error::BadRecording);
+    for (byte, pair) in buf.iter_mut().zip(text.chunks(2)) {
+        *byte = (digit(pair[0]) ? << 4 | digit(pair[1]) ? ) as u8;
+    }
+    Ok(text.len() / 2)
+}
+
+/// Write the value as a LEB128 varint. Returns the number of bytes written.
+fn write_varint(mut value: u32, buf: &mut [u8]) -> usize {
+    let mut len = 0;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 { buf[len] = byte; return len + 1; }
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+}
+
+/// Read a LEB128 varint. Returns the value and the number of bytes read.
+fn read_varint(bytes: &[u8]) -> TouchResult<(u32, usize)> {
+    let mut value: u32 = 0;
+    for (i, byte) in bytes.iter().take(MAX_VARINT_LEN).enumerate() {
+        value |= ((byte & 0x7f) as u32) << (7 * i);
+        if byte & 0x80 == 0 { return Ok((value, i + 1)); }
+    }
+    Err(Error::BadRecording)
+}
@@ -0,0 +1,91 @@
+//! Check the touch calibration: fitting to touches on the targets, rejection of bad touches, the stored calibration,
+//! and the calibration session. Run on the host computer: `cargo test -p touch --target x86_64-unknown-linux-gnu`
+use touch::{
+    calibration::{ Calibration, Session, CALIBRATION_LEN, TARGETS_3, TARGETS_5 },
+    event::{ TouchEvent, TouchPoint },
+    Error,
+};
+
+/// Return the touches on the targets for a touch panel that is offset, scaled and slightly rotated from the display
+fn skewed_touches(targets: &[(u16, u16)]) -> Vec<(u16, u16)> {
+    targets.iter().map(|&(x, y)| {
+        let (x, y) = (x as f64, y as f64);
+        ((0.9 * x + 0.05 * y + 8.0).round() as u16, (-0.04 * x + 1.1 * y - 5.0).round() as u16)
+    }).collect()
+}
+
+#[test]
+fn identity_passes_touches_through() {
+    assert_eq!(Calibration::default(), Calibration::IDENTITY);
+    assert_eq!(Calibration::IDENTITY.apply(0, 0), (0, 0));
+    assert_eq!(Calibration::IDENTITY.apply(123, 45), (123, 45));
+    assert_eq!(Calibration::IDENTITY.apply(300, 239), (239, 239));  //  Clamped to the panel
+}
+
+#[test]
+fn fit_maps_touches_to_targets() {
+    for targets in [&TARGETS_3[..], &TARGETS_5[..]].iter() {
+        let touches = skewed_touches(targets);
+        let calibration = Calibration::fit(targets, &touches).unwrap();
+        for (&target, &(x, y)) in targets.iter().zip(touches.iter()) {
+            let (cal_x, cal_y) = calibration.apply(x, y);
+            assert!((cal_x as i32 - target.0 as i32).abs() <= 1, "{:?} {:?}", target, (cal_x, cal_y));
+            assert!((cal_y as i32 - target.1 as i32).abs() <= 1, "{:?} {:?}", target, (cal_x, cal_y));
+        }
+    }
+
+    //  Touches on the targets give the identity
+    let calibration = Calibration::fit(&TARGETS_5, &TARGETS_5).unwrap();
+    assert_eq!(calibration.apply(100, 200), (100, 200));
+}
+
+#[test]
+fn bad_touches_are_rejected() {
+    //  Too few targets, or touches in a line
+    assert_eq!(Calibration::fit(&TARGETS_3[..2], &TARGETS_3[..2]), Err(Error::BadCalibration));
+    assert_eq!(Calibration::fit(&TARGETS_3, &[(10, 10), (20, 20), (30, 30)]), Err(Error::BadCalibration));
+
+    //  With 5 targets, a touch far from its target is detected
+    let mut touches = skewed_touches(&TARGETS_5);
+    touches[4] = (200, 40);
+    assert_eq!(Calibration::fit(&TARGETS_5, &touches), Err(Error::BadCalibration));
+}
+
+#[test]
+fn stored_calibration_is_checked() {
+    let calibration = Calibration::fit(&TARGETS_5, &skewed_touches(&TARGETS_5)).unwrap();
+    let bytes = calibration.to_bytes();
+    assert_eq!(&bytes[..4], b"TCAL");
+    assert_eq!(Calibration::from_bytes(&bytes), Ok(calibration));
+
+    //  Erased flash, corrupted or short calibration
+    assert_eq!(Calibration::from_bytes(&[0xff; CALIBRATION_LEN]), Err(Error::NoCalibration));
+    let mut corrupted = bytes;
+    corrupted[10] ^= 0x01;
+    assert_eq!(Calibration::from_bytes(&corrupted), Err(Error::NoCalibration));
+    assert_eq!(Calibration::from_bytes(&bytes[..20]), Err(Error::NoCalibration));
+}
+
+#[test]
+fn session_collects_touch_on_each_target() {
+    let mut session = Session::new(&TARGETS_3);
+    let touches = skewed_touches(&TARGETS_3);
+    let point = |x, y, time| TouchPoint { id: 0, x, y, pressure: 40, time };
+    assert_eq!(session.finish(), Err(Error::BadCalibration));  //  Not finished
+
+    //  Up without touches is ignored
+    assert!(!session.event(&TouchEvent::Up(point(0, 0, 0))));
+    for (i, &(x, y)) in touches.iter().enumerate() {
+        assert_eq!(session.target(), Some(TARGETS_3[i]));
+        let time = i as u32 * 1000;
+        assert!(!session.event(&TouchEvent::Down(point(x - 2, y + 1, time))));
+        assert!(!session.event(&TouchEvent::Move(point(x + 2, y - 1, time + 50))));
+        assert!(session.event(&TouchEvent::Up(point(x + 2, y - 1, time + 100))));
+    }
+    assert_eq!(session.target(), None);
+    assert!(!session.event(&TouchEvent::Down(point(10, 10, 5000))));
+    assert_eq!(session.finish(), Calibration::fit(&TARGETS_3, &touches));
+
+    session.restart();
+    assert_eq!(session.target(), Some(TARGETS_3[0]));
+}
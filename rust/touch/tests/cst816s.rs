@@ -0,0 +1,145 @@
+//! Check the CST816S touch controller driver with a mock I2C bus: parsing of touch reports, identification and
+//! configuration registers, and I2C errors. Run on the host computer:
+//! `cargo test -p touch --target x86_64-unknown-linux-gnu`
+use embedded_hal::blocking::i2c::{ Write, WriteRead };
+use touch::{
+    cst816s::{
+        ControllerGesture, Cst816s, Report, ReportPoint, ADDRESS, IRQ_CHANGE, IRQ_TOUCH, MOTION_DOUBLE_CLICK,
+        REPORT_LEN,
+    },
+    Action,
+};
+
+/// Mock I2C bus with the registers of the touch controller
+struct MockI2c {
+    /// Register values, read with auto-increment
+    registers: [u8; 256],
+    /// Register writes as (address, register, value)
+    writes:    Vec<(u8, u8, u8)>,
+    /// True if the controller is asleep and doesn't acknowledge
+    asleep:    bool,
+}
+
+/// I2C error of the mock bus
+#[derive(Debug, PartialEq)]
+struct Nack;
+
+impl MockI2c {
+    fn new() -> MockI2c {
+        MockI2c { registers: [0xff; 256], writes: Vec::new(), asleep: false }
+    }
+}
+
+impl WriteRead for MockI2c {
+    type Error = Nack;
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Nack> {
+        if self.asleep || address != ADDRESS { return Err(Nack); }
+        let start = bytes[0] as usize;
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.registers[start + i];
+        }
+        Ok(())
+    }
+}
+
+impl Write for MockI2c {
+    type Error = Nack;
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Nack> {
+        if self.asleep || address != ADDRESS { return Err(Nack); }
+        self.registers[bytes[0] as usize] = bytes[1];
+        self.writes.push((address, bytes[0], bytes[1]));
+        Ok(())
+    }
+}
+
+/// Return the report for the touch points as (action, id, x, y), with the gesture and number of points
+fn report_bytes(gesture: u8, point_num: u8, points: &[(u8, u8, u16, u16)]) -> [u8; REPORT_LEN] {
+    let mut buf = [0xff; REPORT_LEN];
+    buf[0] = 0;
+    buf[1] = gesture;
+    buf[2] = point_num;
+    for (i, &(action, id, x, y)) in points.iter().enumerate() {
+        let offset = 3 + 6 * i;
+        buf[offset]     = action << 6 | (x >> 8) as u8;
+        buf[offset + 1] = x as u8;
+        buf[offset + 2] = id << 4 | (y >> 8) as u8;
+        buf[offset + 3] = y as u8;
+        buf[offset + 4] = 0x20 + i as u8;  //  Pressure
+        buf[offset + 5] = 0x30;            //  Area
+    }
+    buf
+}
+
+#[test]
+fn report_is_parsed_into_points() {
+    let mut i2c = MockI2c::new();
+    i2c.registers[..REPORT_LEN].copy_from_slice(&report_bytes(0x03, 1, &[(0, 0, 124, 190), (1, 1, 300, 2)]));
+    let mut controller = Cst816s::new(i2c);
+    let mut buf = [0; REPORT_LEN];
+    let report = controller.read_report(&mut buf).unwrap();
+    assert_eq!(buf[2], 1);
+    assert_eq!(report.point_num, 1);
+    assert_eq!(report.gesture(), Some(ControllerGesture::SlideLeft));
+    assert_eq!(report.points(), [
+        ReportPoint { x: 124, y: 190, action: 0, id: 0, pressure: 0x20, area: 3 },
+        ReportPoint { x: 300, y: 2,   action: 1, id: 1, pressure: 0x21, area: 3 },
+    ]);
+    assert_eq!(report.points()[1].action(), Some(Action::Up));
+}
+
+#[test]
+fn report_stops_at_end_of_points() {
+    //  Point ID 15 marks the end of the points
+    let report = Report::parse(&report_bytes(0, 2, &[(2, 0, 10, 20)]));
+    assert_eq!(report.count, 1);
+
+    //  "count: 5, pt: 1": zeros after the first point are read as 4 empty points
+    let mut buf = [0; REPORT_LEN];
+    buf[2] = 1;
+    buf[3..9].copy_from_slice(&[0x80, 166, 0x00, 62, 0x40, 0x10]);
+    let report = Report::parse(&buf);
+    assert_eq!(report.count, 5);
+    assert_eq!(report.points()[0], ReportPoint { x: 166, y: 62, action: 2, id: 0, pressure: 0x40, area: 1 });
+    assert!(report.points()[1..].iter().all(|point| point.x == 0 && point.y == 0));
+
+    //  Touch down without more points ends the report
+    let report = Report::parse(&report_bytes(0, 0, &[(0, 0, 10, 20), (0, 1, 30, 40)]));
+    assert_eq!(report.count, 1);
+    assert_eq!(report.gesture(), None);
+}
+
+#[test]
+fn identification_and_configuration_registers() {
+    let mut i2c = MockI2c::new();
+    i2c.registers[0xA7] = 0xB4;
+    i2c.registers[0xA8] = 0x00;
+    i2c.registers[0xA9] = 0x01;
+    i2c.registers[0x01] = 0x0B;
+    let mut controller = Cst816s::new(i2c);
+    assert_eq!(controller.chip_id(), Ok(0xB4));
+    assert_eq!(controller.project_id(), Ok(0x00));
+    assert_eq!(controller.firmware_version(), Ok(0x01));
+    assert_eq!(controller.gesture(), Ok(Some(ControllerGesture::DoubleClick)));
+
+    controller.set_interrupt_mode(IRQ_TOUCH | IRQ_CHANGE).unwrap();
+    controller.set_motion_mask(MOTION_DOUBLE_CLICK).unwrap();
+    controller.set_auto_sleep(Some(5)).unwrap();
+    controller.set_auto_sleep(None).unwrap();
+    controller.sleep().unwrap();
+    let i2c = controller.release();
+    assert_eq!(i2c.writes, [
+        (ADDRESS, 0xFA, 0x60), (ADDRESS, 0xEC, 0x01), (ADDRESS, 0xF9, 5), (ADDRESS, 0xFE, 0), (ADDRESS, 0xFE, 1),
+        (ADDRESS, 0xE5, 0x03),
+    ]);
+}
+
+#[test]
+fn i2c_errors_are_returned() {
+    let mut i2c = MockI2c::new();
+    i2c.asleep = true;
+    let mut controller = Cst816s::new(i2c);
+    let mut buf = [0; REPORT_LEN];
+    assert_eq!(controller.read_report(&mut buf), Err(Nack));
+    assert_eq!(controller.chip_id(), Err(Nack));
+    assert_eq!(controller.set_auto_sleep(Some(5)), Err(Nack));
+}
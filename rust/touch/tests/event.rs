@@ -0,0 +1,92 @@
+//! Check the Down, Move and Up events for each finger, and the passing of events to the subscribers. Run on the host
+//! computer: `cargo test -p touch --target x86_64-unknown-linux-gnu`
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use touch::{
+    event::{ Subscribers, TouchEvent, TouchPoint, Tracker, MAX_SUBSCRIBERS },
+    gesture::{ Gesture, Recognizer },
+    Action, Error,
+};
+
+/// Return a touch point
+fn point(id: u8, x: u16, y: u16, time: u32) -> TouchPoint {
+    TouchPoint { id, x, y, pressure: 40, time }
+}
+
+#[test]
+fn tracker_reports_down_move_up() {
+    let mut tracker = Tracker::new();
+    assert_eq!(tracker.update(Action::Down, point(0, 10, 20, 0)), Some(TouchEvent::Down(point(0, 10, 20, 0))));
+    assert!(tracker.is_down());
+
+    //  Repeated Down is a Move of the same finger
+    assert_eq!(tracker.update(Action::Down, point(0, 12, 20, 10)), Some(TouchEvent::Move(point(0, 12, 20, 10))));
+    assert_eq!(tracker.update(Action::Contact, point(0, 14, 22, 20)), Some(TouchEvent::Move(point(0, 14, 22, 20))));
+    assert_eq!(tracker.update(Action::Up, point(0, 14, 22, 30)), Some(TouchEvent::Up(point(0, 14, 22, 30))));
+    assert!(!tracker.is_down());
+
+    //  Up without Down is ignored, Contact without Down is a Down
+    assert_eq!(tracker.update(Action::Up, point(0, 14, 22, 40)), None);
+    assert_eq!(tracker.update(Action::Contact, point(3, 1, 2, 50)), Some(TouchEvent::Down(point(3, 1, 2, 50))));
+    assert_eq!(tracker.update(Action::Down, point(16, 1, 2, 50)), None);  //  Invalid point ID
+}
+
+#[test]
+fn fingers_missing_from_report_are_released() {
+    let mut tracker = Tracker::new();
+    tracker.update(Action::Down, point(0, 10, 20, 0));
+    tracker.update(Action::Down, point(1, 100, 120, 0));
+    let mut events = Vec::new();
+    tracker.release_missing(1 << 1, 30, &mut |event| events.push(event));
+    assert_eq!(events, [TouchEvent::Up(point(0, 10, 20, 30))]);
+    assert!(tracker.is_down());
+    tracker.release_missing(0, 40, &mut |event| events.push(event));
+    assert_eq!(events[1], TouchEvent::Up(point(1, 100, 120, 40)));
+    assert!(!tracker.is_down());
+}
+
+/// Number of events received by each subscriber
+static FIRST:  AtomicUsize = AtomicUsize::new(0);
+static SECOND: AtomicUsize = AtomicUsize::new(0);
+
+fn first(_event: &TouchEvent)  { FIRST.fetch_add(1, Ordering::SeqCst); }
+fn second(_event: &TouchEvent) { SECOND.fetch_add(1, Ordering::SeqCst); }
+fn ignore(_event: &TouchEvent) {}
+
+#[test]
+fn events_are_passed_to_all_subscribers() {
+    let mut subscribers = Subscribers::new();
+    let event = TouchEvent::Down(point(0, 10, 20, 0));
+    assert_eq!(subscribers.publish(&event), 0);
+
+    let first_id = subscribers.subscribe(first).unwrap();
+    subscribers.subscribe(second).unwrap();
+    assert_eq!(subscribers.publish(&event), 2);
+    subscribers.unsubscribe(first_id);
+    assert_eq!(subscribers.publish(&event), 1);
+    assert_eq!((FIRST.load(Ordering::SeqCst), SECOND.load(Ordering::SeqCst)), (1, 2));
+
+    //  Free slots are reused until the list is full
+    for _ in 1..MAX_SUBSCRIBERS {
+        subscribers.subscribe(ignore).unwrap();
+    }
+    assert_eq!(subscribers.subscribe(ignore).unwrap_err(), Error::TooManySubscribers);
+}
+
+#[test]
+fn recogniser_follows_first_finger() {
+    let mut recognizer = Recognizer::default();
+    let mut gestures = Vec::new();
+    let events = [
+        TouchEvent::Down(point(0, 20, 120, 0)),
+        TouchEvent::Down(point(1, 200, 20, 10)),  //  Second finger is ignored
+        TouchEvent::Move(point(0, 80, 120, 20)),
+        TouchEvent::Move(point(1, 200, 200, 30)),
+        TouchEvent::Up(point(0, 140, 120, 40)),
+        TouchEvent::Up(point(1, 200, 220, 50)),
+    ];
+    for event in events.iter() {
+        recognizer.event(event, &mut |gesture| gestures.push(gesture));
+    }
+    assert!(recognizer.deadline().is_none());
+    assert!(matches!(gestures[..], [Gesture::Swipe { x: 20, y: 120, distance: 120, velocity: 3000, .. }]));
+}
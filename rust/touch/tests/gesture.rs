@@ -0,0 +1,136 @@
+//! Replay touches into the gesture recogniser and check the gestures recognised. Run on the host computer:
+//! `cargo test -p touch --target x86_64-unknown-linux-gnu`
+use touch::{
+    gesture::{ Config, Direction, Gesture, Recognizer },
+    Action,
+};
+
+/// Touch reported by the controller: raw action (0 = down, 1 = up, 2 = contact), X, Y and milliseconds
+type Report = (u8, u16, u16, u32);
+
+/// Replay the touches into a recogniser with the thresholds, then wait for the pending timeouts. Returns the
+/// gestures with the time at which each was recognised.
+fn replay(config: Config, reports: &[Report]) -> Vec<(u32, Gesture)> {
+    let mut recognizer = Recognizer::new(config);
+    let mut gestures = Vec::new();
+    for &(action, x, y, time) in reports {
+        let action = Action::from_raw(action).unwrap();
+        recognizer.touch(action, x, y, time, &mut |gesture| gestures.push((time, gesture)));
+    }
+    while let Some(deadline) = recognizer.deadline() {
+        recognizer.tick(deadline, &mut |gesture| gestures.push((deadline, gesture)));
+    }
+    gestures
+}
+
+/// Return the gestures without the times
+fn gestures(config: Config, reports: &[Report]) -> Vec<Gesture> {
+    replay(config, reports).into_iter().map(|(_, gesture)| gesture).collect()
+}
+
+#[test]
+fn tap_is_reported_after_double_tap_time() {
+    let reports = [(0, 120, 100, 1000), (2, 122, 101, 1030), (1, 122, 101, 1080)];
+    let gestures = replay(Config::DEFAULT, &reports);
+    assert_eq!(gestures, [(1380, Gesture::Tap { x: 120, y: 100 })]);
+
+    //  Without double taps, the tap is reported on release
+    let config = Config { double_tap_ms: 0, ..Config::DEFAULT };
+    assert_eq!(replay(config, &reports), [(1080, Gesture::Tap { x: 120, y: 100 })]);
+}
+
+#[test]
+fn two_quick_taps_are_a_double_tap() {
+    let reports = [
+        (0, 120, 100, 1000), (1, 120, 100, 1060),
+        (0, 125, 104, 1200), (1, 125, 104, 1250),
+    ];
+    assert_eq!(gestures(Config::DEFAULT, &reports), [Gesture::DoubleTap { x: 120, y: 100 }]);
+
+    //  Too slow or too far apart for a double tap
+    let slow = [(0, 120, 100, 1000), (1, 120, 100, 1060), (0, 120, 100, 1400), (1, 120, 100, 1450)];
+    assert_eq!(gestures(Config::DEFAULT, &slow), [Gesture::Tap { x: 120, y: 100 }; 2]);
+    let far = [(0, 20, 100, 1000), (1, 20, 100, 1060), (0, 200, 100, 1200), (1, 200, 100, 1250)];
+    assert_eq!(gestures(Config::DEFAULT, &far), [Gesture::Tap { x: 20, y: 100 }, Gesture::Tap { x: 200, y: 100 }]);
+}
+
+#[test]
+fn long_press_is_reported_while_down() {
+    let mut reports = vec![(0, 60, 60, 0)];
+    reports.extend((1..=10).map(|i| (2, 61, 60, i * 100)));
+    reports.push((1, 61, 60, 1050));
+    let gestures = replay(Config::DEFAULT, &reports);
+    assert_eq!(gestures, [(600, Gesture::LongPress { x: 60, y: 60 })]);
+
+    //  The time is checked by `tick()` even without reports
+    let mut recognizer = Recognizer::default();
+    let mut gestures = Vec::new();
+    recognizer.touch(Action::Down, 60, 60, 0, &mut |gesture| gestures.push(gesture));
+    recognizer.touch(Action::Contact, 60, 60, 200, &mut |gesture| gestures.push(gesture));
+    recognizer.tick(400, &mut |gesture| gestures.push(gesture));
+    assert!(gestures.is_empty());
+    assert!(recognizer.is_pressed());
+    recognizer.tick(recognizer.deadline().unwrap(), &mut |gesture| gestures.push(gesture));
+    assert!(gestures.is_empty());  //  Released at 450 without reports, before the long press
+    assert!(!recognizer.is_pressed());
+}
+
+#[test]
+fn swipes_have_direction_and_velocity() {
+    let swipe = |dx: i32, dy: i32| {
+        let reports: Vec<Report> = (0..=5)
+            .map(|i| {
+                let action = if i == 0 { 0 } else if i == 5 { 1 } else { 2 };
+                (action, (120 + dx * i / 5) as u16, (120 + dy * i / 5) as u16, 5000 + i as u32 * 20)
+            })
+            .collect();
+        gestures(Config::DEFAULT, &reports)
+    };
+    let expect = |direction, distance| {
+        vec![Gesture::Swipe { direction, x: 120, y: 120, distance, velocity: distance as u32 * 10 }]
+    };
+    assert_eq!(swipe(100, 5), expect(Direction::Right, 100));
+    assert_eq!(swipe(-80, 20), expect(Direction::Left, 80));
+    assert_eq!(swipe(10, -100), expect(Direction::Up, 100));
+    assert_eq!(swipe(-30, 60), expect(Direction::Down, 60));
+
+    //  Too short, or too slow
+    assert!(swipe(30, 0).is_empty());
+    let slow = [(0, 20, 120, 0), (2, 60, 120, 1000), (2, 100, 120, 2000), (1, 100, 120, 2100)];
+    let config = Config { long_press_ms: 5000, release_ms: 1500, ..Config::DEFAULT };
+    assert!(gestures(config, &slow).is_empty());
+}
+
+#[test]
+fn missing_up_report_releases_after_timeout() {
+    //  Recorded from PineTime: the controller reported Down and Contact but no Up
+    let reports = [(0, 166, 62, 0), (2, 166, 62, 20), (2, 167, 63, 40)];
+    assert_eq!(replay(Config::DEFAULT, &reports), [(40 + 300, Gesture::Tap { x: 166, y: 62 })]);
+
+    //  Repeated Down reports are contacts of the same finger
+    let reports = [(0, 50, 50, 0), (0, 70, 50, 20), (0, 100, 50, 40), (0, 140, 50, 60)];
+    let expected = Gesture::Swipe { direction: Direction::Right, x: 50, y: 50, distance: 90, velocity: 1500 };
+    assert_eq!(gestures(Config::DEFAULT, &reports), [expected]);
+}
+
+#[test]
+fn second_touch_that_moves_completes_first_tap() {
+    let reports = [
+        (0, 100, 100, 0), (1, 100, 100, 50),
+        (0, 100, 100, 150), (2, 150, 100, 170), (1, 200, 100, 190),
+    ];
+    let gestures = replay(Config::DEFAULT, &reports);
+    assert_eq!(gestures, [
+        (170, Gesture::Tap { x: 100, y: 100 }),
+        (190, Gesture::Swipe { direction: Direction::Right, x: 100, y: 100, distance: 100, velocity: 2500 }),
+    ]);
+}
+
+#[test]
+fn clock_may_wrap_around() {
+    let start = u32::MAX - 100;
+    let reports = [(0, 120, 120, start), (2, 120, 160, start.wrapping_add(40)), (1, 120, 200, start.wrapping_add(80))];
+    let expected = Gesture::Swipe { direction: Direction::Down, x: 120, y: 120, distance: 80, velocity: 1000 };
+    assert_eq!(gestures(Config::DEFAULT, &reports), [expected]);
+    assert_eq!(Action::from_raw(3), None);
+}
@@ -0,0 +1,143 @@
+//! Replay touch recordings into the report parser, the touch events, the subscribers and the gesture recogniser, the
+//! same way as `touch_event_callback()` in `rust/app/src/touch_sensor.rs`. The recordings in `tests/recordings` are
+//! synthetic fixtures, written by hand in the console format dumped by `rust/app/src/touch_recorder.rs`, not
+//! recorded on PineTime. Run on the host computer:
+//! `cargo test -p touch --target x86_64-unknown-linux-gnu`
+use std::{ cell::RefCell, fs, path::Path };
+use touch::{
+    calibration::Calibration,
+    cst816s::{ Report, REPORT_LEN },
+    event::{ Subscribers, TouchEvent, TouchPoint, Tracker },
+    gesture::{ Direction, Gesture, Recognizer },
+    record::{ decode_hex, Recorded, Recorder, Replay, FAILED, MAX_RECORD_LEN, RECORDED_LEN },
+    Error,
+};
+
+thread_local! {
+    /// Touch events received by the subscriber in this test
+    static EVENTS: RefCell<Vec<TouchEvent>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Subscriber that stands in for the touch handler of the app
+fn app_touch(event: &TouchEvent) {
+    EVENTS.with(|events| events.borrow_mut().push(*event));
+}
+
+/// Return the recording in the console log: the records on the lines starting with `rec `
+fn load(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/recordings").join(name);
+    let log = fs::read_to_string(&path).unwrap();
+    let mut bytes = Vec::new();
+    for line in log.lines().filter_map(|line| line.strip_prefix("rec ")) {
+        let mut buf = [0; MAX_RECORD_LEN];
+        let len = decode_hex(line, &mut buf).unwrap();
+        bytes.extend_from_slice(&buf[..len]);
+    }
+    bytes
+}
+
+/// Replay the recording with the calibration. Returns the events passed to the subscriber, and the gestures with
+/// the time at which each was recognised.
+fn replay(recording: &[u8], calibration: Calibration) -> (Vec<TouchEvent>, Vec<(u32, Gesture)>) {
+    let mut tracker = Tracker::new();
+    let mut subscribers = Subscribers::new();
+    let mut recognizer = Recognizer::default();
+    let mut gestures = Vec::new();
+    subscribers.subscribe(app_touch).unwrap();
+    EVENTS.with(|events| events.borrow_mut().clear());
+    for recorded in Replay::new(recording) {
+        let Recorded { time, report } = recorded.unwrap();
+        //  Fire the gesture callout for the deadlines that have passed
+        while let Some(deadline) = recognizer.deadline().filter(|deadline| *deadline <= time) {
+            recognizer.tick(deadline, &mut |gesture| gestures.push((deadline, gesture)));
+        }
+        //  Reports that couldn't be read are skipped
+        let report = match report { Some(buf) => Report::parse(&buf), None => continue };
+        tracker.report(&report, time, |x, y| calibration.apply(x, y), &mut |event| {
+            subscribers.publish(&event);
+            recognizer.event(&event, &mut |gesture| gestures.push((time, gesture)));
+        });
+    }
+    while let Some(deadline) = recognizer.deadline() {
+        recognizer.tick(deadline, &mut |gesture| gestures.push((deadline, gesture)));
+    }
+    (EVENTS.with(|events| events.borrow().clone()), gestures)
+}
+
+/// Return a report with one touch point. Only the bytes read by the parser are recorded, the rest are zero.
+fn report(action: u8, x: u16, y: u16) -> [u8; REPORT_LEN] {
+    let mut buf = [0; REPORT_LEN];
+    buf[2] = 1;
+    buf[3..9].copy_from_slice(&[action << 6 | (x >> 8) as u8, x as u8, (y >> 8) as u8, y as u8, 0x40, 0x10]);
+    buf[9..RECORDED_LEN].iter_mut().for_each(|byte| *byte = 0xff);
+    buf
+}
+
+#[test]
+fn recorder_round_trip() {
+    let mut recorder = Recorder::new();
+    let reports = [
+        (100, Some(report(0, 120, 100))), (70_000, None), (70_020, Some(report(1, 121, 100))), (70_020, None),
+    ];
+    let mut recording = Vec::new();
+    for (time, report) in reports.iter() {
+        let mut buf = [0; MAX_RECORD_LEN];
+        recording.extend_from_slice(recorder.record(*time, report.as_ref(), &mut buf));
+    }
+    assert_eq!(recording[..3], [33, 100, 0]);   //  Length, time and first byte of the report
+    assert_eq!(recording[35..39], [FAILED, 0x8c, 0xa2, 0x04]);  //  69,900 milliseconds later
+    let replayed: Vec<Recorded> = Replay::new(&recording).map(Result::unwrap).collect();
+    let expected: Vec<Recorded> = reports.iter().map(|&(time, report)| Recorded { time, report }).collect();
+    assert_eq!(replayed, expected);
+
+    //  Trailing zeros are not recorded. Erased flash ends the recording.
+    let mut buf = [0; MAX_RECORD_LEN];
+    assert_eq!(Recorder::new().record(5, Some(&[0; REPORT_LEN]), &mut buf), [0, 5]);
+    recording.extend_from_slice(&[0xff; 16]);
+    assert_eq!(Replay::new(&recording).count(), reports.len());
+}
+
+#[test]
+fn corrupted_recording_is_detected() {
+    let mut buf = [0; MAX_RECORD_LEN];
+    let record = Recorder::new().record(1000, Some(&report(0, 10, 20)), &mut buf).to_vec();
+    let mut replay = Replay::new(&record[..record.len() - 1]);
+    assert_eq!(replay.next(), Some(Err(Error::BadRecording)));
+    assert_eq!(replay.next(), None);
+    assert_eq!(Replay::new(&[40, 0]).next(), Some(Err(Error::BadRecording)));  //  Longer than a report
+
+    assert_eq!(decode_hex("0a1B", &mut buf), Ok(2));
+    assert_eq!(buf[..2], [0x0a, 0x1b]);
+    assert_eq!(decode_hex("0a1", &mut buf), Err(Error::BadRecording));
+    assert_eq!(decode_hex("0x1b", &mut buf), Err(Error::BadRecording));
+}
+
+#[test]
+fn swipe_is_replayed() {
+    let (events, gestures) = replay(&load("swipe_right.txt"), Calibration::IDENTITY);
+    assert_eq!(events.len(), 10);
+    assert_eq!(events[0], TouchEvent::Down(TouchPoint { id: 0, x: 30, y: 120, pressure: 0x40, time: 5000 }));
+    assert_eq!(events[9], TouchEvent::Up(TouchPoint { id: 0, x: 200, y: 123, pressure: 0x40, time: 5180 }));
+    assert_eq!(gestures, [(5180, Gesture::Swipe {
+        direction: Direction::Right, x: 30, y: 120, distance: 170, velocity: 944,
+    })]);
+}
+
+#[test]
+fn count_5_pt_1_report_is_replayed() {
+    //  The empty touch points at (0, 0) are skipped. The failed read is skipped, and the missing touch up is taken
+    //  from the report without touch points.
+    let (events, gestures) = replay(&load("count5_pt1.txt"), Calibration::IDENTITY);
+    let points: Vec<(u16, u16, u32)> = events.iter().map(|event| {
+        let point = event.point();
+        (point.x, point.y, point.time)
+    }).collect();
+    assert_eq!(points, [(166, 60, 12000), (166, 62, 12020), (167, 62, 12060), (167, 62, 12080)]);
+    assert!(matches!(events[..], [TouchEvent::Down(_), TouchEvent::Move(_), TouchEvent::Move(_), TouchEvent::Up(_)]));
+    assert_eq!(gestures, [(12380, Gesture::Tap { x: 166, y: 60 })]);
+
+    //  The calibration is applied to every touch point
+    let offset = Calibration { c: 10 << 16, f: -(20 << 16), ..Calibration::IDENTITY };
+    let (events, _) = replay(&load("count5_pt1.txt"), offset);
+    assert_eq!((events[0].point().x, events[0].point().y), (176, 40));
+}
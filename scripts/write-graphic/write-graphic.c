@@ -1,7 +1,7 @@
 #include <stdio.h>
 #include <stdlib.h>
 
-/// Boot Graphic to be written. Converted from PNG file by https://github.com/lupyuen/pinetime-graphic
+/// Boot Graphic to be written. Converted from PNG file by rust/logo_tool
 static const uint8_t image_data[] = {  //  Should be 115,200 bytes
 #include "graphic.inc"
 };